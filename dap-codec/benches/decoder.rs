@@ -0,0 +1,109 @@
+use bytes::BytesMut;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dap_codec::DapDecoder;
+use tokio_util::codec::Decoder;
+
+fn message(body_size: usize) -> Vec<u8> {
+    let body = serde_json::json!({
+        "seq": 1,
+        "type": "event",
+        "event": "output",
+        "body": {
+            "category": "stdout",
+            "output": "x".repeat(body_size),
+        },
+    })
+    .to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+}
+
+/// Decode a message that arrives in small chunks, as happens for large messages read from a
+/// socket
+pub fn chunked_decode_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunked decode");
+    for body_size in [16 * 1024, 256 * 1024, 1024 * 1024] {
+        let input = message(body_size);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(body_size),
+            &input,
+            |b, input| {
+                b.iter(|| {
+                    let mut decoder = DapDecoder::new();
+                    let mut buf = BytesMut::new();
+                    for chunk in input.chunks(4096) {
+                        buf.extend_from_slice(chunk);
+                        if let Some(message) = decoder.decode(&mut buf).unwrap() {
+                            return message;
+                        }
+                    }
+                    panic!("message not decoded");
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Decode a message whose header arrives one byte at a time
+pub fn slow_header_benchmark(c: &mut Criterion) {
+    let input = [vec![b' '; 16 * 1024], message(16)].concat();
+    c.bench_function("slow header", |b| {
+        b.iter(|| {
+            let mut decoder = DapDecoder::new();
+            let mut buf = BytesMut::new();
+            for byte in &input {
+                buf.extend_from_slice(&[*byte]);
+                if let Some(message) = decoder.decode(&mut buf).unwrap() {
+                    return message;
+                }
+            }
+            panic!("message not decoded");
+        })
+    });
+}
+
+/// Decode a `variables` response for a scope with many variables
+pub fn variables_payload_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("variables payload");
+    for count in [100, 1_000, 10_000] {
+        let variables: Vec<_> = (0..count)
+            .map(|i| {
+                serde_json::json!({
+                    "name": format!("variable_{i}"),
+                    "value": format!("{{'key': {i}, 'items': [1, 2, 3]}}"),
+                    "type": "dict",
+                    "variablesReference": i + 1,
+                })
+            })
+            .collect();
+        let body = serde_json::json!({
+            "seq": 1,
+            "type": "response",
+            "request_seq": 1,
+            "success": true,
+            "command": "variables",
+            "body": { "variables": variables },
+        })
+        .to_string();
+        let input = format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes();
+
+        group.bench_with_input(BenchmarkId::from_parameter(count), &input, |b, input| {
+            b.iter(|| {
+                let mut buf = BytesMut::from(&input[..]);
+                DapDecoder::new()
+                    .decode(&mut buf)
+                    .unwrap()
+                    .expect("message decoded")
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    chunked_decode_benchmark,
+    slow_header_benchmark,
+    variables_payload_benchmark
+);
+criterion_main!(benches);
@@ -1,77 +1,406 @@
+//! [`tokio_util::codec`] support for the Debug Adapter Protocol wire format
+//!
+//! Messages are framed with a `Content-Length` header followed by a JSON body:
+//!
+//! ```text
+//! Content-Length: 119\r\n
+//! \r\n
+//! {"seq": 153, "type": "request", ...}
+//! ```
 use bytes::Buf;
 use dap::base_message::{BaseMessage, Sendable};
 use tokio_util::codec::Decoder;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/// Name of the header giving the length of each message body
+const CONTENT_LENGTH: &[u8] = b"Content-Length";
+
+/// Default value of [`DapDecoderBuilder::max_message_size`]
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Errors produced while decoding DAP messages
 #[derive(thiserror::Error, Debug)]
-enum CodecError {
+pub enum CodecError {
     #[error("invalid utf8")]
     InvalidUtf8(#[from] std::str::Utf8Error),
     #[error("invalid integer")]
     InvalidInteger(#[from] std::num::ParseIntError),
-    #[allow(dead_code)]
     #[error("missing content-length header")]
     MissingContentLengthHeader,
+    #[error("invalid header {0:?}")]
+    InvalidHeader(String),
+    #[error("message of {length} bytes exceeds the maximum size of {max} bytes")]
+    MessageTooLarge { length: usize, max: usize },
     #[error("deserializing message content")]
     Deserializing(#[from] serde_json::Error),
+    #[error("reading input")]
+    Io(#[from] std::io::Error),
 }
 
-#[allow(dead_code)]
-struct DapDecoder {}
+/// Decoder for DAP messages, for use with e.g. [`tokio_util::codec::FramedRead`]
+///
+/// By default any bytes before a `Content-Length` header are skipped, unknown headers are
+/// ignored and messages may be up to [`DEFAULT_MAX_MESSAGE_SIZE`] bytes. Use
+/// [`DapDecoder::builder`] to change this.
+///
+/// When decoding fails, the malformed input has already been dropped, so decoding can carry on
+/// from the next `Content-Length` header.
+#[derive(Debug, Clone)]
+pub struct DapDecoder {
+    max_message_size: usize,
+    strict_headers: bool,
+    /// Header of the message currently being received, once it has been parsed
+    header: Option<Header>,
+    /// Number of bytes at the start of the buffer already searched for the end of the header
+    scanned: usize,
+}
 
-impl Decoder for DapDecoder {
-    type Item = Sendable;
+impl Default for DapDecoder {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
 
-    type Error = Box<dyn std::error::Error>;
+impl DapDecoder {
+    /// Create a decoder with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // skip to the start of the first header
-        // TODO: we assume Content-Length for now
-        let Some(start_pos) = src
-            .windows("Content-Length".len())
-            .position(|s| s == b"Content-Length")
-        else {
-            return Ok(None);
+    pub fn builder() -> DapDecoderBuilder {
+        DapDecoderBuilder {
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            strict_headers: false,
+        }
+    }
+}
+
+/// Builder for [`DapDecoder`]
+#[derive(Debug, Clone)]
+pub struct DapDecoderBuilder {
+    max_message_size: usize,
+    strict_headers: bool,
+}
+
+impl DapDecoderBuilder {
+    /// Reject messages whose header section or body is larger than `size` bytes
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Error on malformed input instead of skipping it
+    ///
+    /// In strict mode the input must start with a header, and every header must be a
+    /// `Content-Length` header.
+    pub fn strict_headers(mut self, strict: bool) -> Self {
+        self.strict_headers = strict;
+        self
+    }
+
+    pub fn build(self) -> DapDecoder {
+        DapDecoder {
+            max_message_size: self.max_message_size,
+            strict_headers: self.strict_headers,
+            header: None,
+            scanned: 0,
+        }
+    }
+}
+
+/// Message decoded by [`DapDecoder`]
+// messages are the common case, so avoid boxing them
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum DapMessage {
+    Known(Sendable),
+    /// A message the `dap` crate does not understand, e.g. a vendor specific event such as
+    /// debugpy's `debugpySockets`
+    Unknown {
+        /// The `type` field of the message, e.g. `"event"`
+        r#type: String,
+        raw: serde_json::Value,
+    },
+}
+
+impl DapMessage {
+    /// Deserialize a message body, falling back to [`DapMessage::Unknown`] for any JSON
+    /// object with a `type` field that does not match the protocol
+    fn from_slice(body: &[u8]) -> Result<Self, serde_json::Error> {
+        let error = match serde_json::from_slice::<BaseMessage>(body) {
+            Ok(base_message) => return Ok(Self::Known(base_message.message)),
+            Err(e) => e,
         };
 
-        src.advance(start_pos);
+        let raw: serde_json::Value = serde_json::from_slice(body)?;
+        match raw.get("type").and_then(|t| t.as_str()) {
+            Some(r#type) => Ok(Self::Unknown {
+                r#type: r#type.to_string(),
+                raw,
+            }),
+            None => Err(error),
+        }
+    }
+}
+
+/// Location of a complete message header at the start of a buffer
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    /// Length of the header section, excluding the `\r\n\r\n` separator
+    len: usize,
+    content_length: usize,
+}
+
+impl Header {
+    /// Length of the whole message including the header section
+    fn message_len(&self) -> usize {
+        self.len + 4 + self.content_length
+    }
+}
+
+impl DapDecoder {
+    /// Convert into a decoder that reports malformed messages instead of failing the stream
+    pub fn lenient(self) -> LenientDapDecoder {
+        LenientDapDecoder { inner: self }
+    }
+
+    /// Header of the next message in `src`, parsed at most once per message
+    ///
+    /// Parse progress is kept between calls so that bytes are not rescanned as more input
+    /// arrives.
+    fn next_header(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Header>, CodecError> {
+        if let Some(header) = self.header {
+            return Ok(Some(header));
+        }
+
+        match self.parse_header(src) {
+            Ok(header) => {
+                self.header = header;
+                if header.is_some() {
+                    self.scanned = 0;
+                }
+                Ok(header)
+            }
+            Err(e) => {
+                self.scanned = 0;
+                Err(e)
+            }
+        }
+    }
 
-        let Some(split_point) = src.windows(4).position(|s| s == b"\r\n\r\n") else {
+    /// Parse the header of the next message, skipping leading garbage unless in strict mode
+    fn parse_header(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Header>, CodecError> {
+        if self.strict_headers {
+            let prefix = &CONTENT_LENGTH[..src.len().min(CONTENT_LENGTH.len())];
+            if !src.starts_with(prefix) {
+                let end = src.len().min(32);
+                return Err(CodecError::InvalidHeader(
+                    String::from_utf8_lossy(&src[..end]).into_owned(),
+                ));
+            }
+        } else {
+            // skip to the `Content-Length` header, along with any other headers before it
+            let Some(start_pos) = find(src, CONTENT_LENGTH) else {
+                // keep only the bytes that could be the start of a header
+                src.advance(src.len() - partial_header_len(src));
+                return Ok(None);
+            };
+
+            src.advance(start_pos);
+        }
+
+        // the separator may straddle the end of the previously scanned bytes
+        let scan_from = self.scanned.saturating_sub(3);
+        let Some(split_point) = find(&src[scan_from..], b"\r\n\r\n").map(|pos| pos + scan_from)
+        else {
+            self.scanned = src.len();
+            if src.len() > self.max_message_size {
+                return Err(CodecError::MessageTooLarge {
+                    length: src.len(),
+                    max: self.max_message_size,
+                });
+            }
             // TODO: is this always lack of input?
             return Ok(None);
         };
 
-        let headers = &src[..split_point];
-        let header_len = headers.len();
-        // TOOD: parse other headers when they are added
-        let content_length = 'cl: {
-            let headers_str = std::str::from_utf8(headers).map_err(CodecError::InvalidUtf8)?;
-            for header_str in headers_str.split("\r\n") {
-                let (key, value) = {
-                    let mut raw_key_and_value = header_str.split(':').take(2);
-                    let key = raw_key_and_value.next().unwrap().trim();
-                    let value = raw_key_and_value.next().unwrap().trim();
-                    (key, value)
-                };
-                if key == "Content-Length" {
-                    break 'cl value.parse::<usize>().map_err(CodecError::InvalidInteger)?;
-                };
+        let content_length = self.content_length(&src[..split_point])?;
+
+        if content_length > self.max_message_size {
+            return Err(CodecError::MessageTooLarge {
+                length: content_length,
+                max: self.max_message_size,
+            });
+        }
+
+        Ok(Some(Header {
+            len: split_point,
+            content_length,
+        }))
+    }
+}
+
+impl DapDecoder {
+    /// Content length given by the header section of a message
+    ///
+    /// Unknown headers are skipped unless in strict mode, but lines that are not headers or
+    /// conflicting content lengths are always errors.
+    fn content_length(&self, headers: &[u8]) -> Result<usize, CodecError> {
+        let invalid = |line: &[u8]| CodecError::InvalidHeader(String::from_utf8_lossy(line).into());
+
+        let mut content_length = None;
+        for line in headers.split(|&b| b == b'\n') {
+            let line = line.trim_ascii();
+            let Some(colon) = line.iter().position(|&b| b == b':') else {
+                return Err(invalid(line));
+            };
+            let (key, value) = (line[..colon].trim_ascii(), line[colon + 1..].trim_ascii());
+            if key != CONTENT_LENGTH {
+                if self.strict_headers {
+                    return Err(invalid(line));
+                }
+                continue;
+            }
+
+            let length = std::str::from_utf8(value)?.parse::<usize>()?;
+            match content_length {
+                Some(previous) if previous != length => return Err(invalid(line)),
+                _ => content_length = Some(length),
+            }
+        }
+        content_length.ok_or(CodecError::MissingContentLengthHeader)
+    }
+}
+
+/// Number of bytes to skip after malformed input at the start of `src`, so that decoding
+/// resumes at the next `Content-Length` header within the first `limit` bytes
+///
+/// A header starting inside the malformed input but extending past it is still found, e.g.
+/// when a message torn off by the adapter claims more bytes than it sent.
+fn resync_len(src: &[u8], limit: usize) -> usize {
+    let end = src.len().min(limit + CONTENT_LENGTH.len() - 1);
+    match find(&src[1..end], CONTENT_LENGTH) {
+        Some(pos) => pos + 1,
+        None => limit - partial_header_len(&src[..limit]).min(limit - 1),
+    }
+}
+
+/// Length of the longest suffix of `src` that could be the start of a `Content-Length` header
+fn partial_header_len(src: &[u8]) -> usize {
+    (1..CONTENT_LENGTH.len())
+        .rev()
+        .find(|&n| src.ends_with(&CONTENT_LENGTH[..n]))
+        .unwrap_or(0)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|s| s == needle)
+}
+
+impl Decoder for DapDecoder {
+    type Item = DapMessage;
+
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let header = match self.next_header(src) {
+            Ok(Some(header)) => header,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                src.advance(resync_len(src, src.len()));
+                return Err(e);
             }
-            return Err(CodecError::MissingContentLengthHeader.into());
         };
 
         // check the buffer has enough bytes (including \r\n\r\n)
-        let message_len_bytes = header_len + 4 + content_length;
+        let message_len_bytes = header.message_len();
         if src.len() < message_len_bytes {
+            src.reserve(message_len_bytes - src.len());
             return Ok(None);
         }
 
         // parse the body
-        let base_message: BaseMessage =
-            serde_json::from_slice(&src[header_len + 4..message_len_bytes])
-                .map_err(CodecError::Deserializing)?;
+        self.header = None;
+        match DapMessage::from_slice(&src[header.len + 4..message_len_bytes]) {
+            Ok(message) => {
+                src.advance(message_len_bytes);
+                Ok(Some(message))
+            }
+            Err(e) => {
+                src.advance(resync_len(src, message_len_bytes));
+                Err(CodecError::Deserializing(e))
+            }
+        }
+    }
+}
+
+/// Item produced by [`LenientDapDecoder`]
+// messages are the common case, so avoid boxing them
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Frame {
+    Message {
+        message: DapMessage,
+        /// The JSON content of the message, without the header
+        body: bytes::Bytes,
+    },
+    /// Input that could not be decoded, after which the decoder resynchronises on the next
+    /// `Content-Length` header
+    Malformed {
+        /// The bytes that were skipped, including any header
+        raw: bytes::Bytes,
+        error: CodecError,
+    },
+}
+
+/// Decoder that yields [`Frame::Malformed`] for bad input rather than erroring the stream
+///
+/// Created with [`DapDecoder::lenient`]. Only I/O errors and messages larger than the maximum
+/// size are reported as errors.
+#[derive(Debug, Clone)]
+pub struct LenientDapDecoder {
+    inner: DapDecoder,
+}
+
+impl Decoder for LenientDapDecoder {
+    type Item = Frame;
 
-        src.advance(message_len_bytes);
-        Ok(Some(base_message.message))
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let header = match self.inner.next_header(src) {
+            Ok(Some(header)) => header,
+            Ok(None) => return Ok(None),
+            Err(error) => {
+                let raw = src.split_to(resync_len(src, src.len())).freeze();
+                return match error {
+                    CodecError::MessageTooLarge { .. } | CodecError::Io(_) => Err(error),
+                    error => Ok(Some(Frame::Malformed { raw, error })),
+                };
+            }
+        };
+
+        let message_len_bytes = header.message_len();
+        if src.len() < message_len_bytes {
+            src.reserve(message_len_bytes - src.len());
+            return Ok(None);
+        }
+
+        self.inner.header = None;
+        let body = &src[header.len + 4..message_len_bytes];
+        match DapMessage::from_slice(body) {
+            Ok(message) => {
+                let raw = src.split_to(message_len_bytes).freeze();
+                let body = raw.slice(header.len + 4..);
+                Ok(Some(Frame::Message { message, body }))
+            }
+            Err(e) => Ok(Some(Frame::Malformed {
+                raw: src.split_to(resync_len(src, message_len_bytes)).freeze(),
+                error: CodecError::Deserializing(e),
+            })),
+        }
     }
 }
 
@@ -101,7 +430,7 @@ mod tests {
 
                 messages.put(&$extra[..]);
 
-                let mut framed_read = FramedRead::new(&messages[..], DapDecoder {});
+                let mut framed_read = FramedRead::new(&messages[..], DapDecoder::new());
 
                 $(
                     let Some(msg) = framed_read.next().await else {
@@ -126,7 +455,7 @@ mod tests {
             "seq": 1,
             "type": "event",
             "event": "initialized",
-        }) => Sendable::Event(Event::Initialized)
+        }) => DapMessage::Known(Sendable::Event(Event::Initialized))
     );
 
     create_test!(
@@ -135,12 +464,12 @@ mod tests {
             "seq": 1,
             "type": "event",
             "event": "initialized",
-        }) => Sendable::Event(Event::Initialized),
+        }) => DapMessage::Known(Sendable::Event(Event::Initialized)),
         serde_json::json!({
             "seq": 1,
             "type": "event",
             "event": "initialized",
-        }) => Sendable::Event(Event::Initialized)
+        }) => DapMessage::Known(Sendable::Event(Event::Initialized))
     );
 
     create_test!(
@@ -150,6 +479,259 @@ mod tests {
             "seq": 1,
             "type": "event",
             "event": "initialized",
-        }) => Sendable::Event(Event::Initialized)
+        }) => DapMessage::Known(Sendable::Event(Event::Initialized))
+    );
+
+    #[tokio::test]
+    async fn message_too_large() {
+        let input = construct_message(&serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "initialized",
+        }));
+        let decoder = DapDecoder::builder().max_message_size(8).build();
+        let mut framed_read = FramedRead::new(&input[..], decoder);
+
+        let result = framed_read.next().await.unwrap();
+        assert!(matches!(
+            result,
+            Err(CodecError::MessageTooLarge { max: 8, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn strict_headers() {
+        let mut input = b"garbage".to_vec();
+        input.extend(construct_message(&serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "initialized",
+        })));
+        let decoder = DapDecoder::builder().strict_headers(true).build();
+        let mut framed_read = FramedRead::new(&input[..], decoder);
+
+        let result = framed_read.next().await.unwrap();
+        assert!(matches!(result, Err(CodecError::InvalidHeader(_))));
+    }
+
+    #[tokio::test]
+    async fn lenient_resynchronises() {
+        let message = construct_message(&serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "initialized",
+        }));
+        let bad = b"Content-Length: 5\r\n\r\n{bad}".to_vec();
+        let input = [message.clone(), bad.clone(), message].concat();
+        let mut framed_read = FramedRead::new(&input[..], DapDecoder::new().lenient());
+
+        let first = framed_read.next().await.unwrap().unwrap();
+        assert!(matches!(
+            first,
+            Frame::Message {
+                message: DapMessage::Known(Sendable::Event(Event::Initialized)),
+                ..
+            }
+        ));
+
+        let Frame::Malformed { raw, error } = framed_read.next().await.unwrap().unwrap() else {
+            panic!("expected malformed frame");
+        };
+        assert_eq!(&raw[..], &bad[..]);
+        assert!(matches!(error, CodecError::Deserializing(_)));
+
+        let third = framed_read.next().await.unwrap().unwrap();
+        assert!(matches!(
+            third,
+            Frame::Message {
+                message: DapMessage::Known(Sendable::Event(Event::Initialized)),
+                ..
+            }
+        ));
+    }
+
+    create_test!(
+        unknown_event,
+        serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "debugpySockets",
+            "body": {"sockets": []},
+        }) => DapMessage::Unknown { .. },
+        serde_json::json!({
+            "seq": 2,
+            "type": "event",
+            "event": "initialized",
+        }) => DapMessage::Known(Sendable::Event(Event::Initialized))
     );
+
+    #[test]
+    fn unknown_messages_keep_their_content() {
+        let vendor_event = serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "debugpySockets",
+            "body": {"sockets": [{"port": 5678}]},
+        });
+        let vendor_type = serde_json::json!({
+            "seq": 2,
+            "type": "telemetry",
+            "data": 1,
+        });
+        let untyped = serde_json::json!({"seq": 3});
+        let input = [&vendor_event, &vendor_type, &untyped]
+            .map(construct_message)
+            .concat();
+
+        let mut decoder = DapDecoder::new();
+        let mut buf = bytes::BytesMut::from(&input[..]);
+        let Ok(Some(DapMessage::Unknown { r#type, raw })) = decoder.decode(&mut buf) else {
+            panic!("expected unknown event");
+        };
+        assert_eq!(r#type, "event");
+        assert_eq!(raw, vendor_event);
+        let Ok(Some(DapMessage::Unknown { r#type, raw })) = decoder.decode(&mut buf) else {
+            panic!("expected unknown message type");
+        };
+        assert_eq!(r#type, "telemetry");
+        assert_eq!(raw, vendor_type);
+        // without a type there is nothing to go on, so this is still an error
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Err(CodecError::Deserializing(_))
+        ));
+        assert!(buf.is_empty());
+
+        let mut buf = bytes::BytesMut::from(&input[..]);
+        let Ok(Some(Frame::Message {
+            message: DapMessage::Unknown { raw, .. },
+            body,
+        })) = DapDecoder::new().lenient().decode(&mut buf)
+        else {
+            panic!("expected unknown message from lenient decoder");
+        };
+        assert_eq!(raw, vendor_event);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+            vendor_event
+        );
+    }
+
+    #[test]
+    fn byte_at_a_time() {
+        let message = construct_message(&serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "initialized",
+        }));
+        let input = [b"junk".to_vec(), message.clone(), message].concat();
+
+        let mut decoder = DapDecoder::new();
+        let mut buf = bytes::BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in input {
+            buf.put_u8(byte);
+            while let Some(message) = decoder.decode(&mut buf).unwrap() {
+                decoded.push(message);
+            }
+        }
+
+        assert_eq!(decoded.len(), 2);
+        assert!(decoded
+            .iter()
+            .all(|m| matches!(m, DapMessage::Known(Sendable::Event(Event::Initialized)))));
+    }
+
+    #[test]
+    fn skips_unknown_headers() {
+        let message = construct_message(&serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "initialized",
+        }));
+        let input = [b"Content-Type: application/json\r\n".to_vec(), message].concat();
+
+        let mut decoder = DapDecoder::builder().strict_headers(true).build();
+        let mut buf = bytes::BytesMut::from(&input[..]);
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Err(CodecError::InvalidHeader(_))
+        ));
+        // the strict decoder resynchronises on the `Content-Length` header
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Ok(Some(DapMessage::Known(Sendable::Event(Event::Initialized))))
+        ));
+
+        let mut buf = bytes::BytesMut::from(&input[..]);
+        assert!(matches!(
+            DapDecoder::new().decode(&mut buf),
+            Ok(Some(DapMessage::Known(Sendable::Event(Event::Initialized))))
+        ));
+    }
+
+    #[test]
+    fn recovers_from_malformed_header() {
+        let message = construct_message(&serde_json::json!({
+            "seq": 1,
+            "type": "event",
+            "event": "initialized",
+        }));
+        let input = [b"Content-Length\r\n\r\n".to_vec(), message].concat();
+
+        let mut decoder = DapDecoder::new();
+        let mut buf = bytes::BytesMut::from(&input[..]);
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Err(CodecError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            decoder.decode(&mut buf),
+            Ok(Some(DapMessage::Known(Sendable::Event(Event::Initialized))))
+        ));
+        assert!(buf.is_empty());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn arbitrary_input_does_not_panic(
+            input in testing::arb_noise(),
+            split_points in proptest::collection::vec(0..512usize, 0..16),
+        ) {
+            testing::decode_all(DapDecoder::new(), &input, &split_points);
+            testing::decode_all(DapDecoder::builder().strict_headers(true).build(), &input, &split_points);
+            testing::decode_all(DapDecoder::new().lenient(), &input, &split_points);
+        }
+
+        #[test]
+        fn resynchronises_after_corrupt_input((messages, input, split_points) in testing::arb_corrupted_messages()) {
+            let (decoded, _) = testing::decode_all(DapDecoder::new(), &input, &split_points);
+            proptest::prop_assert_eq!(decoded.len(), messages.len());
+            for (original, decoded) in messages.iter().zip(&decoded) {
+                proptest::prop_assert!(testing::same_message(original, decoded), "{original} decoded as {decoded:?}");
+            }
+
+            let (frames, _) = testing::decode_all(DapDecoder::new().lenient(), &input, &split_points);
+            let decoded: Vec<_> = frames
+                .iter()
+                .filter_map(|frame| match frame {
+                    Frame::Message { message, .. } => Some(message),
+                    Frame::Malformed { .. } => None,
+                })
+                .collect();
+            proptest::prop_assert_eq!(decoded.len(), messages.len());
+            for (original, decoded) in messages.iter().zip(decoded) {
+                proptest::prop_assert!(testing::same_message(original, decoded), "{original} decoded as {decoded:?}");
+            }
+        }
+
+        #[test]
+        fn chunked_round_trip((messages, split_points) in testing::arb_chunked_messages()) {
+            let decoded = testing::round_trip(&messages, &split_points).unwrap();
+            proptest::prop_assert_eq!(decoded.len(), messages.len());
+            for (original, decoded) in messages.iter().zip(&decoded) {
+                proptest::prop_assert!(testing::same_message(original, decoded), "{original} decoded as {decoded:?}");
+            }
+        }
+    }
 }
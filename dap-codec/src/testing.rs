@@ -0,0 +1,216 @@
+//! Helpers for property testing the codec against chunked network delivery
+//!
+//! Enabled with the `testing` feature.
+//!
+//! ```ignore
+//! use dap_codec::testing::{arb_chunked_messages, round_trip, same_message};
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     #[test]
+//!     fn chunked((messages, split_points) in arb_chunked_messages()) {
+//!         let decoded = round_trip(&messages, &split_points).unwrap();
+//!         prop_assert_eq!(decoded.len(), messages.len());
+//!     }
+//! }
+//! ```
+use bytes::BytesMut;
+use proptest::prelude::*;
+use serde_json::{json, Value};
+use tokio_util::codec::Decoder;
+
+use crate::{CodecError, DapDecoder, DapMessage};
+
+/// Frame a message body with a `Content-Length` header
+pub fn encode(message: &Value) -> Vec<u8> {
+    let body = serde_json::to_string(message).expect("serializing json value");
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+}
+
+/// Generate DAP events, including ones the `dap` crate does not know about
+pub fn arb_message() -> impl Strategy<Value = Value> {
+    let seq = 1..10_000u64;
+    prop_oneof![
+        seq.clone()
+            .prop_map(|seq| json!({"seq": seq, "type": "event", "event": "initialized"})),
+        (
+            seq.clone(),
+            any::<String>(),
+            prop::option::of("stdout|stderr|console")
+        )
+            .prop_map(|(seq, output, category)| {
+                json!({
+                    "seq": seq,
+                    "type": "event",
+                    "event": "output",
+                    "body": {"output": output, "category": category},
+                })
+            }),
+        (seq.clone(), 1..100i64).prop_map(|(seq, thread_id)| {
+            json!({
+                "seq": seq,
+                "type": "event",
+                "event": "stopped",
+                "body": {"reason": "breakpoint", "threadId": thread_id},
+            })
+        }),
+        (seq, "[a-z]{1,16}Vendor", any::<String>()).prop_map(|(seq, event, payload)| {
+            json!({
+                "seq": seq,
+                "type": "event",
+                "event": event,
+                "body": {"payload": payload},
+            })
+        }),
+    ]
+}
+
+/// Generate a sequence of messages along with byte offsets at which to split their encoding
+pub fn arb_chunked_messages() -> impl Strategy<Value = (Vec<Value>, Vec<usize>)> {
+    prop::collection::vec(arb_message(), 1..8).prop_flat_map(|messages| {
+        let len: usize = messages.iter().map(|m| encode(m).len()).sum();
+        let split_points = prop::collection::vec(0..=len, 0..32);
+        (Just(messages), split_points)
+    })
+}
+
+/// Generate input that precedes a message without being one, which a decoder must skip
+///
+/// This is noise that cannot contain a header, a message torn off part way through, or a
+/// malformed header.
+pub fn arb_corruption() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        prop::collection::vec(
+            any::<u8>().prop_filter("header start", |b| *b != b'C'),
+            0..32
+        ),
+        (arb_message(), any::<prop::sample::Index>()).prop_map(|(message, index)| {
+            let message = encode(&message);
+            message[..index.index(message.len())].to_vec()
+        }),
+        prop_oneof![
+            Just("Content-Length\r\n\r\n"),
+            Just("Content-Length: x\r\n\r\n{}"),
+            Just("Content-Length: 1\r\nContent-Length: 2\r\n\r\n"),
+            Just("Content-Length: 2\r\n\r\n{]"),
+            Just("Content-Type: application/json\r\n\r\n"),
+        ]
+        .prop_map(|header| header.as_bytes().to_vec()),
+    ]
+}
+
+/// Generate a sequence of messages, their encoding with corrupt input before each one, and
+/// byte offsets at which to split the encoding
+pub fn arb_corrupted_messages() -> impl Strategy<Value = (Vec<Value>, Vec<u8>, Vec<usize>)> {
+    prop::collection::vec((arb_corruption(), arb_message()), 1..8).prop_flat_map(|pairs| {
+        let mut input = Vec::new();
+        for (corruption, message) in &pairs {
+            input.extend(corruption);
+            input.extend(encode(message));
+        }
+        // complete any message torn off before the last one, so that it cannot hold back the
+        // messages it overlaps
+        input.extend(vec![b' '; input.len()]);
+
+        let messages = pairs.into_iter().map(|(_, message)| message).collect();
+        let split_points = prop::collection::vec(0..=input.len(), 0..32);
+        (Just(messages), Just(input), split_points)
+    })
+}
+
+/// Generate arbitrary bytes, biased towards fragments of message framing
+pub fn arb_noise() -> impl Strategy<Value = Vec<u8>> {
+    prop::collection::vec(
+        prop_oneof![
+            prop::collection::vec(any::<u8>(), 0..16),
+            prop_oneof![
+                Just("Content-Length"),
+                Just(": "),
+                Just("\r\n"),
+                Just("\r\n\r\n"),
+                Just("12"),
+                Just("{}"),
+            ]
+            .prop_map(|fragment| fragment.as_bytes().to_vec()),
+        ],
+        0..32,
+    )
+    .prop_map(|fragments| fragments.concat())
+}
+
+/// Split `input` into chunks at the given offsets, which may be unsorted or repeated
+pub fn split_at<'a>(input: &'a [u8], split_points: &[usize]) -> Vec<&'a [u8]> {
+    let mut split_points: Vec<usize> = split_points.iter().map(|&p| p.min(input.len())).collect();
+    split_points.sort_unstable();
+    split_points.dedup();
+
+    let mut chunks = Vec::with_capacity(split_points.len() + 1);
+    let mut start = 0;
+    for point in split_points {
+        chunks.push(&input[start..point]);
+        start = point;
+    }
+    chunks.push(&input[start..]);
+    chunks
+}
+
+/// Encode `messages`, deliver them to a [`DapDecoder`] in chunks split at `split_points` and
+/// return everything that was decoded
+pub fn round_trip(
+    messages: &[Value],
+    split_points: &[usize],
+) -> Result<Vec<DapMessage>, CodecError> {
+    let input: Vec<u8> = messages.iter().flat_map(encode).collect();
+
+    let mut decoder = DapDecoder::new();
+    let mut buf = BytesMut::new();
+    let mut decoded = Vec::with_capacity(messages.len());
+    for chunk in split_at(&input, split_points) {
+        buf.extend_from_slice(chunk);
+        while let Some(message) = decoder.decode(&mut buf)? {
+            decoded.push(message);
+        }
+    }
+    Ok(decoded)
+}
+
+/// Deliver `input` to `decoder` in chunks split at `split_points`, carrying on after errors,
+/// and return everything that was decoded along with the number of errors
+///
+/// Panics if the decoder stops making progress after an error.
+pub fn decode_all<D>(mut decoder: D, input: &[u8], split_points: &[usize]) -> (Vec<D::Item>, usize)
+where
+    D: Decoder,
+{
+    let mut buf = BytesMut::new();
+    let mut decoded = Vec::new();
+    let mut errors = 0;
+    for chunk in split_at(input, split_points) {
+        buf.extend_from_slice(chunk);
+        loop {
+            let len = buf.len();
+            match decoder.decode(&mut buf) {
+                Ok(Some(item)) => decoded.push(item),
+                Ok(None) => break,
+                Err(_) => {
+                    assert!(buf.len() < len, "malformed input was not skipped");
+                    errors += 1;
+                }
+            }
+        }
+    }
+    (decoded, errors)
+}
+
+/// Whether `decoded` has the same type and event or command name as the `original` message
+pub fn same_message(original: &Value, decoded: &DapMessage) -> bool {
+    let decoded = match decoded {
+        DapMessage::Known(sendable) => {
+            serde_json::to_value(sendable).expect("serializing decoded message")
+        }
+        DapMessage::Unknown { raw, .. } => return raw == original,
+    };
+    ["type", "event", "command"]
+        .iter()
+        .all(|key| original.get(key) == decoded.get(key))
+}
@@ -0,0 +1,170 @@
+//! Man in the middle proxy for inspecting the traffic between a DAP client and adapter
+//!
+//! Bytes are forwarded unchanged in both directions, and a copy is decoded with
+//! [`dap_codec`] so that every message can be reported.
+use std::{
+    net::SocketAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::BytesMut;
+use dap_codec::{DapDecoder, Frame};
+use eyre::WrapErr;
+pub use pcaplog::Direction;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::mpsc,
+};
+use tokio_util::codec::Decoder;
+
+/// Message passing through the proxy
+#[derive(Debug)]
+pub struct ProxiedMessage {
+    pub direction: Direction,
+    /// Time the message was received, since the unix epoch
+    pub timestamp: Duration,
+    /// Index of the client connection, in the order connections were accepted
+    pub stream_id: usize,
+    pub client: SocketAddr,
+    pub adapter: SocketAddr,
+    pub frame: Frame,
+}
+
+/// Details of a proxied connection shared by both directions
+#[derive(Debug, Clone, Copy)]
+struct Connection {
+    stream_id: usize,
+    client: SocketAddr,
+    adapter: SocketAddr,
+}
+
+pub struct Proxy {
+    listener: TcpListener,
+    adapter: SocketAddr,
+}
+
+impl Proxy {
+    /// Listen for DAP clients on `addr`, forwarding their connections to `adapter`
+    pub async fn bind(addr: impl ToSocketAddrs, adapter: SocketAddr) -> eyre::Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .context("binding listen socket")?;
+        Ok(Self { listener, adapter })
+    }
+
+    pub fn local_addr(&self) -> eyre::Result<SocketAddr> {
+        self.listener.local_addr().context("getting local address")
+    }
+
+    /// Accept connections until an error occurs, sending every decoded message to `tx`
+    pub async fn run(self, tx: mpsc::UnboundedSender<ProxiedMessage>) -> eyre::Result<()> {
+        for stream_id in 0.. {
+            let (client, client_addr) = self
+                .listener
+                .accept()
+                .await
+                .context("accepting connection")?;
+            tracing::info!(%client_addr, %stream_id, "accepted connection");
+
+            let connection = Connection {
+                stream_id,
+                client: client_addr,
+                adapter: self.adapter,
+            };
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(client, connection, tx).await {
+                    tracing::warn!(error = ?e, %stream_id, "proxying connection");
+                }
+                tracing::info!(%stream_id, "connection closed");
+            });
+        }
+        Ok(())
+    }
+}
+
+async fn handle_connection(
+    client: TcpStream,
+    connection: Connection,
+    tx: mpsc::UnboundedSender<ProxiedMessage>,
+) -> eyre::Result<()> {
+    let adapter = TcpStream::connect(connection.adapter)
+        .await
+        .context("connecting to adapter")?;
+
+    let (client_reader, client_writer) = client.into_split();
+    let (adapter_reader, adapter_writer) = adapter.into_split();
+
+    tokio::try_join!(
+        pump(
+            client_reader,
+            adapter_writer,
+            Direction::ClientToServer,
+            connection,
+            tx.clone()
+        ),
+        pump(
+            adapter_reader,
+            client_writer,
+            Direction::ServerToClient,
+            connection,
+            tx
+        ),
+    )?;
+    Ok(())
+}
+
+/// Copy bytes from `reader` to `writer` until end of file, reporting the messages they contain
+async fn pump(
+    mut reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    direction: Direction,
+    connection: Connection,
+    tx: mpsc::UnboundedSender<ProxiedMessage>,
+) -> eyre::Result<()> {
+    let mut decoder = Some(DapDecoder::new().lenient());
+    let mut buf = BytesMut::with_capacity(8 * 1024);
+    loop {
+        let start = buf.len();
+        let n = reader.read_buf(&mut buf).await.context("reading")?;
+        if n == 0 {
+            writer.shutdown().await.context("closing")?;
+            return Ok(());
+        }
+        writer
+            .write_all(&buf[start..])
+            .await
+            .context("forwarding")?;
+
+        let Some(inner) = decoder.as_mut() else {
+            buf.clear();
+            continue;
+        };
+        loop {
+            match inner.decode(&mut buf) {
+                Ok(Some(frame)) => {
+                    let timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default();
+                    // the receiver going away only stops reporting, not forwarding
+                    let _ = tx.send(ProxiedMessage {
+                        direction,
+                        timestamp,
+                        stream_id: connection.stream_id,
+                        client: connection.client,
+                        adapter: connection.adapter,
+                        frame,
+                    });
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::warn!(error = %e, ?direction, "cannot decode traffic, forwarding without inspection");
+                    decoder = None;
+                    buf.clear();
+                    break;
+                }
+            }
+        }
+    }
+}
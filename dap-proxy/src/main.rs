@@ -0,0 +1,102 @@
+use std::{io::Write, net::SocketAddr};
+
+use clap::Parser;
+use dap_codec::Frame;
+use dap_proxy::{Direction, ProxiedMessage, Proxy};
+use eyre::WrapErr;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Address of the debug adapter to forward to
+    adapter: SocketAddr,
+
+    /// Port to listen for DAP clients on
+    #[clap(short, long, default_value_t = 5679)]
+    port: u16,
+
+    /// Write messages in the pcaplog JSON lines format instead of plain text
+    #[clap(long)]
+    jsonl: bool,
+}
+
+/// Same shape as the records written by `pcaplog --jsonl`
+#[derive(Serialize)]
+struct Record {
+    direction: Direction,
+    timestamp: f64,
+    stream_id: usize,
+    payload: serde_json::Value,
+}
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let args = Args::parse();
+    tracing::debug!(?args, "parsed command line arguments");
+
+    let proxy = Proxy::bind(("127.0.0.1", args.port), args.adapter).await?;
+    tracing::info!(addr = %proxy.local_addr()?, adapter = %args.adapter, "listening");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let printer = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if let Err(e) = print_message(std::io::stdout().lock(), &message, args.jsonl) {
+                tracing::error!(error = ?e, "printing message");
+            }
+        }
+    });
+
+    proxy.run(tx).await?;
+    printer.await.context("waiting for output to be written")?;
+    Ok(())
+}
+
+fn print_message(
+    mut writer: impl Write,
+    message: &ProxiedMessage,
+    jsonl: bool,
+) -> eyre::Result<()> {
+    let arrow = match message.direction {
+        Direction::ClientToServer => "->",
+        Direction::ServerToClient => "<-",
+    };
+    match &message.frame {
+        Frame::Message { body, .. } if jsonl => {
+            let record = Record {
+                direction: message.direction,
+                timestamp: message.timestamp.as_secs_f64(),
+                stream_id: message.stream_id,
+                payload: serde_json::from_slice(body).context("parsing message body")?,
+            };
+            serde_json::to_writer(&mut writer, &record).context("serializing message")?;
+            writeln!(writer).context("writing message separator")?;
+        }
+        Frame::Message { body, .. } => {
+            writeln!(
+                writer,
+                "[{}] {arrow} {}",
+                message.stream_id,
+                String::from_utf8_lossy(body)
+            )
+            .context("writing message")?;
+        }
+        Frame::Malformed { raw, error } => {
+            tracing::warn!(
+                stream_id = message.stream_id,
+                ?message.direction,
+                %error,
+                raw = %String::from_utf8_lossy(raw),
+                "malformed message"
+            );
+        }
+    }
+    writer.flush().context("flushing output")?;
+    Ok(())
+}
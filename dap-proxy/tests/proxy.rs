@@ -0,0 +1,63 @@
+use dap_codec::{DapMessage, Frame};
+use dap_proxy::{Direction, Proxy};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+fn encode(body: &str) -> Vec<u8> {
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes()
+}
+
+#[tokio::test]
+async fn forwards_and_reports_messages() {
+    let request = encode(r#"{"seq":1,"type":"request","command":"threads"}"#);
+    let event = encode(r#"{"seq":1,"type":"event","event":"initialized"}"#);
+
+    // fake adapter that replies to the first request with an event
+    let adapter = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let adapter_addr = adapter.local_addr().unwrap();
+    let adapter_task = {
+        let (request, event) = (request.clone(), event.clone());
+        tokio::spawn(async move {
+            let (mut stream, _) = adapter.accept().await.unwrap();
+            let mut received = vec![0; request.len()];
+            stream.read_exact(&mut received).await.unwrap();
+            assert_eq!(received, request);
+            stream.write_all(&event).await.unwrap();
+        })
+    };
+
+    let proxy = Proxy::bind("127.0.0.1:0", adapter_addr).await.unwrap();
+    let proxy_addr = proxy.local_addr().unwrap();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(proxy.run(tx));
+
+    let mut client = TcpStream::connect(proxy_addr).await.unwrap();
+    // split the request to check messages are reassembled
+    let (first, second) = request.split_at(10);
+    client.write_all(first).await.unwrap();
+    client.flush().await.unwrap();
+    client.write_all(second).await.unwrap();
+
+    let mut received = vec![0; event.len()];
+    client.read_exact(&mut received).await.unwrap();
+    assert_eq!(received, event);
+    adapter_task.await.unwrap();
+
+    let to_adapter = rx.recv().await.unwrap();
+    assert_eq!(to_adapter.direction, Direction::ClientToServer);
+    assert_eq!(to_adapter.stream_id, 0);
+    assert!(matches!(to_adapter.frame, Frame::Message { .. }));
+
+    let to_client = rx.recv().await.unwrap();
+    assert_eq!(to_client.direction, Direction::ServerToClient);
+    assert!(matches!(
+        to_client.frame,
+        Frame::Message {
+            message: DapMessage::Known(_),
+            ..
+        }
+    ));
+}
@@ -0,0 +1,38 @@
+//! Helpers for attaching a [`Debugger`] to a [`FakeAdapter`]
+use std::{path::PathBuf, time::Duration};
+
+use debugger::{AttachArguments, Debugger, Event, Language};
+use eyre::WrapErr;
+
+use crate::FakeAdapter;
+
+/// How long to wait for the adapter to initialise the session
+const INITIALISE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Arguments for attaching to a Python debugee listening on `port`, with its code in `/project`
+pub fn attach_arguments(port: u16) -> AttachArguments {
+    AttachArguments {
+        working_directory: PathBuf::from("/project"),
+        port: Some(port),
+        host: None,
+        language: Language::DebugPy,
+        path_mappings: None,
+        just_my_code: None,
+    }
+}
+
+/// Attach to `adapter` with [`attach_arguments`], returning once the session is initialised and
+/// ready to be configured
+pub fn attach(adapter: &FakeAdapter) -> eyre::Result<Debugger> {
+    attach_with(adapter, attach_arguments(adapter.port()))
+}
+
+/// Like [`attach`] but with custom `arguments`
+pub fn attach_with(adapter: &FakeAdapter, arguments: AttachArguments) -> eyre::Result<Debugger> {
+    let debugger =
+        Debugger::on_port(adapter.port(), arguments).context("attaching to fake adapter")?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Initialised), INITIALISE_TIMEOUT)
+        .ok_or_else(|| eyre::eyre!("session was not initialised"))?;
+    Ok(debugger)
+}
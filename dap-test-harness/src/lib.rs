@@ -0,0 +1,447 @@
+//! Scriptable fake debug adapter, for testing DAP clients without debugpy or delve installed
+//!
+//! The adapter listens on a local TCP port and follows a [`Script`] for each connection:
+//!
+//! ```no_run
+//! use dap_test_harness::{FakeAdapter, Script};
+//! use serde_json::json;
+//!
+//! # fn main() -> eyre::Result<()> {
+//! let adapter = FakeAdapter::start(
+//!     Script::new()
+//!         .respond("initialize", json!({}))
+//!         .event("initialized", None)
+//!         .respond("configurationDone", None)
+//!         .event("stopped", json!({ "reason": "breakpoint", "threadId": 1 })),
+//! )?;
+//! // connect a client to `adapter.port()`
+//! let requests = adapter.join()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Requests that the script is not waiting for are answered with an empty successful
+//! response, so scripts only need to describe the parts of a session a test cares about.
+//!
+//! With the `debugger` feature, [`attach`] connects a `debugger::Debugger` to the adapter.
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use eyre::WrapErr;
+use serde_json::{json, Value};
+
+#[cfg(feature = "debugger")]
+mod attach;
+#[cfg(feature = "debugger")]
+pub use attach::{attach, attach_arguments, attach_with};
+
+/// One action taken by the fake adapter
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Wait for a request with this command and respond to it
+    Respond {
+        command: String,
+        success: bool,
+        /// Error message for failed requests
+        message: Option<String>,
+        body: Option<Value>,
+        /// How long to wait before sending the response
+        delay: Duration,
+    },
+    /// Send an event
+    Event { event: String, body: Option<Value> },
+    /// Wait before carrying on with the script
+    Sleep(Duration),
+    /// Close the connection
+    Disconnect,
+}
+
+/// Behaviour of the fake adapter for a single connection
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Respond to `initialize` with `capabilities` and send the `initialized` event, as at the
+    /// start of every session
+    pub fn initialised(capabilities: Value) -> Self {
+        Self::new()
+            .respond("initialize", capabilities)
+            .event("initialized", None)
+    }
+
+    pub fn step(mut self, step: Step) -> Self {
+        self.steps.push(step);
+        self
+    }
+
+    /// Wait for a `command` request and respond successfully with `body`
+    pub fn respond(self, command: impl Into<String>, body: impl Into<Option<Value>>) -> Self {
+        self.respond_after(command, body, Duration::ZERO)
+    }
+
+    /// Like [`Script::respond`] but wait for `delay` before responding
+    pub fn respond_after(
+        self,
+        command: impl Into<String>,
+        body: impl Into<Option<Value>>,
+        delay: Duration,
+    ) -> Self {
+        self.step(Step::Respond {
+            command: command.into(),
+            success: true,
+            message: None,
+            body: body.into(),
+            delay,
+        })
+    }
+
+    /// Wait for a `command` request and respond with an error
+    pub fn fail(self, command: impl Into<String>, message: impl Into<String>) -> Self {
+        self.step(Step::Respond {
+            command: command.into(),
+            success: false,
+            message: Some(message.into()),
+            body: None,
+            delay: Duration::ZERO,
+        })
+    }
+
+    /// Like [`Script::fail`] but with a structured `error` in the body of the response
+    pub fn fail_with_error(
+        self,
+        command: impl Into<String>,
+        message: impl Into<String>,
+        error: Value,
+    ) -> Self {
+        self.step(Step::Respond {
+            command: command.into(),
+            success: false,
+            message: Some(message.into()),
+            body: Some(json!({ "error": error })),
+            delay: Duration::ZERO,
+        })
+    }
+
+    pub fn event(self, event: impl Into<String>, body: impl Into<Option<Value>>) -> Self {
+        self.step(Step::Event {
+            event: event.into(),
+            body: body.into(),
+        })
+    }
+
+    pub fn sleep(self, duration: Duration) -> Self {
+        self.step(Step::Sleep(duration))
+    }
+
+    pub fn disconnect(self) -> Self {
+        self.step(Step::Disconnect)
+    }
+
+    /// Stop thread 1 for `reason` and answer the requests a client makes to show where it
+    /// stopped, at the top frame of [`stack_trace`] with no variables in scope
+    pub fn paused(self, reason: &str) -> Self {
+        self.stopped(reason)
+            .respond("scopes", json!({ "scopes": [] }))
+    }
+
+    /// Like [`Script::paused`] but with a single `Locals` scope holding the `locals` variables
+    pub fn paused_with_locals(self, reason: &str, locals: Value) -> Self {
+        self.stopped(reason)
+            .respond(
+                "scopes",
+                json!({ "scopes": [{ "name": "Locals", "variablesReference": 1, "expensive": false }] }),
+            )
+            .respond("variables", json!({ "variables": locals }))
+    }
+
+    /// Stop thread 1 for `reason` and send its stack
+    fn stopped(self, reason: &str) -> Self {
+        self.event("stopped", json!({ "reason": reason, "threadId": 1 }))
+            .respond("stackTrace", stack_trace())
+    }
+}
+
+/// Body of a `stackTrace` response with a single frame, with id 7, at line 3 of
+/// `/project/main.py`
+pub fn stack_trace() -> Value {
+    json!({
+        "stackFrames": [{
+            "id": 7,
+            "name": "main",
+            "source": { "path": "/project/main.py" },
+            "line": 3,
+            "column": 0,
+        }]
+    })
+}
+
+/// Requests received by the adapter, shared with the serving thread
+type Received = Arc<Mutex<Vec<Value>>>;
+
+/// Fake debug adapter serving [`Script`]s on a background thread
+pub struct FakeAdapter {
+    port: u16,
+    received: Received,
+    handle: JoinHandle<eyre::Result<()>>,
+}
+
+impl FakeAdapter {
+    /// Serve `script` to the first client that connects
+    pub fn start(script: Script) -> eyre::Result<Self> {
+        Self::serve([script])
+    }
+
+    /// Serve each script to a new connection in turn, e.g. to test reconnecting clients
+    pub fn serve(scripts: impl IntoIterator<Item = Script>) -> eyre::Result<Self> {
+        let scripts: Vec<Script> = scripts.into_iter().collect();
+        Self::spawn(move |listener, received| {
+            for (i, script) in scripts.into_iter().enumerate() {
+                let (stream, addr) = listener.accept().context("accepting connection")?;
+                tracing::debug!(%addr, connection = %i, "client connected");
+                Session::new(stream, Arc::clone(&received))?
+                    .run(script)
+                    .with_context(|| format!("serving connection {i}"))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Serve `before`, then close every connection made within `downtime` of it finishing, as
+    /// an adapter that is being restarted does, before serving `after`
+    pub fn restarting(before: Script, downtime: Duration, after: Script) -> eyre::Result<Self> {
+        Self::spawn(move |listener, received| {
+            let (stream, addr) = listener.accept().context("accepting connection")?;
+            tracing::debug!(%addr, "client connected");
+            Session::new(stream, Arc::clone(&received))?
+                .run(before)
+                .context("serving connection before restart")?;
+
+            let back = Instant::now() + downtime;
+            loop {
+                let (stream, addr) = listener.accept().context("accepting connection")?;
+                if Instant::now() >= back {
+                    tracing::debug!(%addr, "client connected after restart");
+                    return Session::new(stream, received)?
+                        .run(after)
+                        .context("serving connection after restart");
+                }
+                tracing::debug!(%addr, "closing connection while restarting");
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        })
+    }
+
+    /// Run `serve` with a listener on a free local port on a background thread
+    fn spawn(
+        serve: impl FnOnce(TcpListener, Received) -> eyre::Result<()> + Send + 'static,
+    ) -> eyre::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").context("binding listener")?;
+        let port = listener
+            .local_addr()
+            .context("getting local address")?
+            .port();
+        let received = Received::default();
+
+        let thread_received = Arc::clone(&received);
+        let handle = thread::spawn(move || serve(listener, thread_received));
+
+        Ok(Self {
+            port,
+            received,
+            handle,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Requests received so far, across all connections
+    pub fn requests(&self) -> Vec<Value> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Commands of the requests received so far, in order
+    pub fn commands(&self) -> Vec<String> {
+        self.requests()
+            .iter()
+            .filter_map(|r| r["command"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Wait for every script to finish and the clients to disconnect, returning the requests
+    /// received
+    pub fn join(self) -> eyre::Result<Vec<Value>> {
+        self.handle
+            .join()
+            .map_err(|_| eyre::eyre!("fake adapter thread panicked"))??;
+        let requests = self.received.lock().unwrap().clone();
+        Ok(requests)
+    }
+}
+
+/// A single client connection
+struct Session {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    seq: i64,
+    received: Received,
+}
+
+impl Session {
+    fn new(stream: TcpStream, received: Received) -> eyre::Result<Self> {
+        // responses are small writes, don't let Nagle's algorithm hold them back
+        stream
+            .set_nodelay(true)
+            .context("disabling Nagle's algorithm")?;
+        let reader = BufReader::new(stream.try_clone().context("cloning stream")?);
+        Ok(Self {
+            reader,
+            writer: stream,
+            seq: 0,
+            received,
+        })
+    }
+
+    fn run(mut self, script: Script) -> eyre::Result<()> {
+        for step in script.steps {
+            match step {
+                Step::Respond {
+                    command,
+                    success,
+                    message,
+                    body,
+                    delay,
+                } => loop {
+                    let Some(request) = self.next_request()? else {
+                        eyre::bail!("client disconnected while waiting for {command} request");
+                    };
+                    if request["command"] != command.as_str() {
+                        self.respond(&request, true, None, None)?;
+                        continue;
+                    }
+                    thread::sleep(delay);
+                    self.respond(&request, success, message, body)?;
+                    break;
+                },
+                Step::Event { event, body } => {
+                    let mut message = json!({ "type": "event", "event": event });
+                    if let Some(body) = body {
+                        message["body"] = body;
+                    }
+                    self.send(message)?;
+                }
+                Step::Sleep(duration) => thread::sleep(duration),
+                Step::Disconnect => {
+                    tracing::debug!("closing connection");
+                    let _ = self.writer.shutdown(Shutdown::Both);
+                    return Ok(());
+                }
+            }
+        }
+
+        // keep the client happy until it goes away, which may happen before it reads the
+        // response to its final request
+        while let Some(request) = self.next_request()? {
+            if self.respond(&request, true, None, None).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the next request, or `None` if the client has disconnected
+    fn next_request(&mut self) -> eyre::Result<Option<Value>> {
+        loop {
+            let Some(message) = read_message(&mut self.reader)? else {
+                return Ok(None);
+            };
+            tracing::debug!(%message, "received message");
+            if message["type"] == "request" {
+                self.received.lock().unwrap().push(message.clone());
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    fn respond(
+        &mut self,
+        request: &Value,
+        success: bool,
+        message: Option<String>,
+        body: Option<Value>,
+    ) -> eyre::Result<()> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": success,
+        });
+        if let Some(message) = message {
+            response["message"] = message.into();
+        }
+        if let Some(body) = body {
+            response["body"] = body;
+        }
+        self.send(response)
+    }
+
+    fn send(&mut self, mut message: Value) -> eyre::Result<()> {
+        self.seq += 1;
+        message["seq"] = self.seq.into();
+        let body = serde_json::to_string(&message).context("encoding message")?;
+        tracing::debug!(message = %body, "sending message");
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .context("writing message")?;
+        self.writer.flush().context("flushing message")?;
+        Ok(())
+    }
+}
+
+/// Read one `Content-Length` framed message, or `None` at the end of the stream
+fn read_message(reader: &mut impl BufRead) -> eyre::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) => return Ok(None),
+            Ok(_) => {}
+            // the client hanging up without a clean shutdown is not an error for a test
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset => return Ok(None),
+            Err(e) => return Err(e).context("reading header"),
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim() == "Content-Length" {
+                content_length = Some(value.trim().parse::<usize>().context("parsing length")?);
+            }
+        }
+    }
+
+    let content_length =
+        content_length.ok_or_else(|| eyre::eyre!("message without Content-Length header"))?;
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).context("reading body")?;
+    serde_json::from_slice(&body)
+        .context("decoding message")
+        .map(Some)
+}
@@ -0,0 +1,72 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dap_test_harness::{FakeAdapter, Script};
+use debugger::{AttachArguments, Debugger, Event, Language};
+use serde_json::json;
+
+const EVENTS: usize = 1_000;
+
+/// Deliver a burst of adapter events to several subscribers
+pub fn fan_out_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("event fan-out");
+    group.sample_size(20);
+    for subscribers in [1, 4, 16] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(subscribers),
+            &subscribers,
+            |b, &subscribers| {
+                b.iter_custom(|iters| {
+                    let mut total = Duration::ZERO;
+                    for _ in 0..iters {
+                        // the burst starts once the session is configured
+                        let mut script = Script::new()
+                            .respond("initialize", json!({}))
+                            .respond("configurationDone", None);
+                        for i in 0..EVENTS {
+                            script = script.event(
+                                "output",
+                                json!({ "category": "stdout", "output": format!("line {i}\n") }),
+                            );
+                        }
+                        let adapter = FakeAdapter::start(script).unwrap();
+                        let debugger = Debugger::on_port(
+                            adapter.port(),
+                            AttachArguments {
+                                working_directory: PathBuf::from("/"),
+                                port: Some(adapter.port()),
+                                host: None,
+                                language: Language::DebugPy,
+                                path_mappings: None,
+                                just_my_code: None,
+                            },
+                        )
+                        .unwrap();
+                        let receivers: Vec<_> =
+                            (0..subscribers).map(|_| debugger.subscribe()).collect();
+
+                        let start = Instant::now();
+                        debugger.start().unwrap();
+                        for rx in &receivers {
+                            let mut received = 0;
+                            while received < EVENTS {
+                                if let Event::Output { .. } = rx.recv().unwrap() {
+                                    received += 1;
+                                }
+                            }
+                        }
+                        total += start.elapsed();
+                    }
+                    total
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, fan_out_benchmark);
+criterion_main!(benches);
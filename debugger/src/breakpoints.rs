@@ -0,0 +1,265 @@
+//! Tracking of breakpoints and their synchronisation with the debug adapter
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{Error, Result},
+    persistence::PersistBreakpoints,
+    types::{Breakpoint, BreakpointId},
+};
+
+/// Single source of truth for the breakpoints of a debugging session
+///
+/// Mutations only change the store. The debugger then sends a `setBreakpoints` request for
+/// each file whose breakpoints differ from what the adapter was last told (see
+/// [`BreakpointStore::changes`]), so several changes to one file are sent as one request.
+#[derive(Default)]
+pub struct BreakpointStore {
+    breakpoints: BTreeMap<BreakpointId, Breakpoint>,
+    last_id: BreakpointId,
+    /// Whether the adapter could bind each breakpoint to executable code
+    verified: HashMap<BreakpointId, bool>,
+    /// Breakpoints most recently sent to the adapter for each file
+    synced: HashMap<PathBuf, Vec<(BreakpointId, Breakpoint)>>,
+    persistence: Option<Box<dyn PersistBreakpoints>>,
+}
+
+impl std::fmt::Debug for BreakpointStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BreakpointStore")
+            .field("breakpoints", &self.breakpoints)
+            .field("verified", &self.verified)
+            .field("persistent", &self.persistence.is_some())
+            .finish()
+    }
+}
+
+impl BreakpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Save the breakpoints with `persistence` whenever they change
+    pub fn set_persistence(&mut self, persistence: Box<dyn PersistBreakpoints>) {
+        self.persistence = Some(persistence);
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) -> BreakpointId {
+        self.last_id += 1;
+        self.breakpoints.insert(self.last_id, breakpoint);
+        self.last_id
+    }
+
+    pub fn remove(&mut self, id: BreakpointId) -> Option<Breakpoint> {
+        self.verified.remove(&id);
+        self.breakpoints.remove(&id)
+    }
+
+    /// Replace an existing breakpoint, returning the previous value
+    ///
+    /// Nothing is changed if there is no breakpoint with this id.
+    pub fn update(&mut self, id: BreakpointId, breakpoint: Breakpoint) -> Option<Breakpoint> {
+        let existing = self.breakpoints.get_mut(&id)?;
+        if existing.line != breakpoint.line || existing.path != breakpoint.path {
+            self.verified.remove(&id);
+        }
+        Some(std::mem::replace(existing, breakpoint))
+    }
+
+    pub fn get(&self, id: BreakpointId) -> Option<&Breakpoint> {
+        self.breakpoints.get(&id)
+    }
+
+    /// Breakpoints in the order they were added
+    pub fn iter(&self) -> impl Iterator<Item = (BreakpointId, &Breakpoint)> {
+        self.breakpoints.iter().map(|(id, b)| (*id, b))
+    }
+
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.breakpoints.values().cloned().collect()
+    }
+
+    pub fn breakpoints_with_ids(&self) -> Vec<(BreakpointId, Breakpoint)> {
+        self.iter().map(|(id, b)| (id, b.clone())).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    /// Whether the adapter has bound the breakpoint to executable code, if known
+    pub fn verified(&self, id: BreakpointId) -> Option<bool> {
+        self.verified.get(&id).copied()
+    }
+
+    /// Files whose breakpoints differ from what was last sent to the adapter, along with the
+    /// full set of breakpoints to send for each
+    ///
+    /// A file with no remaining breakpoints is included with an empty list, as that is the
+    /// only way for the adapter to learn they were removed.
+    pub(crate) fn changes(&self) -> Vec<(PathBuf, Vec<(BreakpointId, Breakpoint)>)> {
+        let mut by_file: BTreeMap<PathBuf, Vec<(BreakpointId, Breakpoint)>> = BTreeMap::new();
+        for (id, breakpoint) in &self.breakpoints {
+            by_file
+                .entry(breakpoint.path.clone())
+                .or_default()
+                .push((*id, breakpoint.clone()));
+        }
+
+        let paths: BTreeSet<PathBuf> = by_file.keys().chain(self.synced.keys()).cloned().collect();
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let current = by_file.remove(&path).unwrap_or_default();
+                let synced = self.synced.get(&path).map(Vec::as_slice).unwrap_or(&[]);
+                (current.as_slice() != synced).then_some((path, current))
+            })
+            .collect()
+    }
+
+    /// Record that the adapter accepted `breakpoints` for `path`, with `verified` giving the
+    /// status of each breakpoint in the same order
+    pub(crate) fn mark_synced(
+        &mut self,
+        path: PathBuf,
+        breakpoints: Vec<(BreakpointId, Breakpoint)>,
+        verified: impl IntoIterator<Item = bool>,
+    ) {
+        for ((id, _), verified) in breakpoints.iter().zip(verified) {
+            self.verified.insert(*id, verified);
+        }
+        if breakpoints.is_empty() {
+            self.synced.remove(&path);
+        } else {
+            self.synced.insert(path, breakpoints);
+        }
+    }
+
+    /// Files whose breakpoints were sent to the adapter but not verified, e.g. because the
+    /// adapter had not loaded the file yet
+    pub fn pending(&self) -> Vec<PathBuf> {
+        self.synced
+            .iter()
+            .filter(|(_, breakpoints)| {
+                breakpoints
+                    .iter()
+                    .any(|(id, _)| self.verified(*id) == Some(false))
+            })
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Send the breakpoints for `path` again on the next sync, so the adapter can verify
+    /// breakpoints it previously rejected
+    pub fn resync(&mut self, path: &Path) {
+        self.synced.remove(path);
+    }
+
+    /// Forget what the adapter has been told, e.g. after connecting to a new adapter, so that
+    /// every breakpoint is sent again
+    pub(crate) fn reset_sync(&mut self) {
+        self.synced.clear();
+        self.verified.clear();
+    }
+
+    /// Save the breakpoints, if persistence has been configured
+    pub fn persist(&mut self) -> Result<()> {
+        let breakpoints = self.breakpoints();
+        match &mut self.persistence {
+            Some(persistence) => persistence.save(&breakpoints).map_err(Error::from),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::BreakpointStore;
+    use crate::Breakpoint;
+
+    fn breakpoint(file: &str, line: usize) -> Breakpoint {
+        Breakpoint {
+            path: PathBuf::from(file),
+            line,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn changes_are_batched_per_file() {
+        let mut store = BreakpointStore::new();
+        store.add(breakpoint("a.py", 1));
+        store.add(breakpoint("a.py", 2));
+        store.add(breakpoint("b.py", 3));
+
+        let changes = store.changes();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].0, PathBuf::from("a.py"));
+        assert_eq!(changes[0].1.len(), 2);
+
+        for (path, breakpoints) in changes {
+            let verified = vec![true; breakpoints.len()];
+            store.mark_synced(path, breakpoints, verified);
+        }
+        assert!(store.changes().is_empty());
+        assert_eq!(store.verified(1), Some(true));
+    }
+
+    #[test]
+    fn removing_last_breakpoint_sends_empty_list() {
+        let mut store = BreakpointStore::new();
+        let id = store.add(breakpoint("a.py", 1));
+        for (path, breakpoints) in store.changes() {
+            store.mark_synced(path, breakpoints, [true]);
+        }
+
+        store.remove(id);
+        let changes = store.changes();
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].1.is_empty());
+        assert_eq!(store.verified(id), None);
+
+        for (path, breakpoints) in changes {
+            store.mark_synced(path, breakpoints, []);
+        }
+        assert!(store.changes().is_empty());
+    }
+
+    #[test]
+    fn moving_breakpoint_updates_both_files() {
+        let mut store = BreakpointStore::new();
+        let id = store.add(breakpoint("a.py", 1));
+        for (path, breakpoints) in store.changes() {
+            store.mark_synced(path, breakpoints, [true]);
+        }
+
+        store.update(id, breakpoint("b.py", 1)).unwrap();
+        let paths: Vec<_> = store.changes().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, vec![PathBuf::from("a.py"), PathBuf::from("b.py")]);
+    }
+
+    #[test]
+    fn unverified_breakpoints_are_pending_until_resent() {
+        let mut store = BreakpointStore::new();
+        store.add(breakpoint("a.py", 1));
+        store.add(breakpoint("b.py", 1));
+        for (path, breakpoints) in store.changes() {
+            let verified = path != Path::new("b.py");
+            store.mark_synced(path, breakpoints, [verified]);
+        }
+        assert_eq!(store.pending(), vec![PathBuf::from("b.py")]);
+
+        store.resync(Path::new("b.py"));
+        let changes = store.changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].0, PathBuf::from("b.py"));
+        for (path, breakpoints) in changes {
+            store.mark_synced(path, breakpoints, [true]);
+        }
+        assert!(store.pending().is_empty());
+    }
+}
@@ -1,26 +1,37 @@
 use std::{
-    io,
+    io::{self, BufRead, BufReader, Read},
     net::{TcpStream, ToSocketAddrs},
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{mpsc, Arc, Mutex, Weak},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use eyre::WrapErr;
 use retry::{delay::Exponential, retry};
-use server::Implementation;
+use server::{Implementation, OutputLine, OutputStream};
 use transport::{
-    requests::{self, Disconnect},
-    responses,
-    types::{BreakpointLocation, StackFrameId},
-    DEFAULT_DAP_PORT,
+    replay::{Recording, Timing},
+    requests, responses,
+    tap::MessageTap,
+    types::{
+        BreakpointLocation, CompletionItem, ExceptionBreakpointsFilter, Module, Source, StackFrame,
+        StackFrameId, Thread, ThreadId, Variable, VariablesReference,
+    },
+    StdioTransport, DEFAULT_DAP_PORT,
 };
 
 use crate::{
+    breakpoints::BreakpointStore,
+    error::{Error, Result},
     internals::DebuggerInternals,
+    liveness::{self, LivenessOptions},
+    metrics::Metrics,
+    persistence::PersistBreakpoints,
+    recovery::{self, RecoveryOptions},
     state::{self, DebuggerState},
-    types::{self, EvaluateResult},
+    types::{self, EvaluateContext, EvaluateResult, Memory, TerminateMode, WatchResult},
     Event,
 };
 
@@ -32,6 +43,24 @@ pub enum InitialiseArguments {
 
     /// Attach to a running process
     Attach(state::AttachArguments),
+
+    /// Launch or attach with a configuration passed to the adapter as is, e.g. the arguments
+    /// documented by adapters such as `lldb-dap`
+    Configuration(requests::StartDebugging),
+}
+
+impl InitialiseArguments {
+    /// Whether the session launches the debugee, rather than attaching to a running process
+    fn launches(&self) -> bool {
+        matches!(
+            self,
+            InitialiseArguments::Launch(_)
+                | InitialiseArguments::Configuration(requests::StartDebugging {
+                    request: requests::StartDebuggingRequest::Launch,
+                    ..
+                })
+        )
+    }
 }
 
 impl From<state::LaunchArguments> for InitialiseArguments {
@@ -72,7 +101,11 @@ where
 /// Represents a debugging session
 pub struct Debugger {
     internals: Arc<Mutex<DebuggerInternals>>,
+    connection: Connection,
     rx: crossbeam_channel::Receiver<Event>,
+    terminate_on_drop: bool,
+    /// Sessions started at the request of the adapter, e.g. for debugpy subprocesses
+    children: Arc<Mutex<Vec<Arc<Debugger>>>>,
 }
 
 impl Debugger {
@@ -83,71 +116,210 @@ impl Debugger {
     pub fn on_port(
         port: u16,
         initialise_arguments: impl Into<InitialiseArguments>,
-    ) -> eyre::Result<Self> {
+    ) -> Result<Self> {
         tracing::debug!("creating new client");
 
-        // notify our subscribers
-        let (tx, rx) = crossbeam_channel::unbounded();
-        let _ = tx.send(Event::Uninitialised);
+        let args: InitialiseArguments = initialise_arguments.into();
+        // we own the debugee when launching, but attached processes should outlive the session
+        let terminate_on_drop = args.launches();
+        let adapter = implementation_for(&args);
+        let adapter_executable = match &args {
+            InitialiseArguments::Launch(launch) => launch.adapter_executable.clone(),
+            InitialiseArguments::Attach(_) | InitialiseArguments::Configuration(_) => None,
+        };
+        let server = adapter
+            .map(|implementation| {
+                server::for_implementation_with_executable(
+                    implementation,
+                    port,
+                    adapter_executable.as_deref(),
+                )
+            })
+            .transpose()
+            .context("creating background server process")?;
+        let host = match &args {
+            InitialiseArguments::Attach(state::AttachArguments { host, .. }) => host.clone(),
+            InitialiseArguments::Launch(_) | InitialiseArguments::Configuration(_) => None,
+        }
+        .unwrap_or_else(|| "127.0.0.1".to_string());
 
+        let client = new_client(&host, port).context("connecting to server")?;
+        let debugger = Self::connect(
+            Endpoint::Tcp { host, port },
+            client,
+            server,
+            terminate_on_drop,
+            |internals| internals.initialise(args),
+        )?;
+        {
+            let mut internals = debugger.internals.lock().unwrap();
+            internals.adapter = adapter;
+            internals.adapter_executable = adapter_executable;
+        }
+        Ok(debugger)
+    }
+
+    /// Start a session with an adapter that speaks DAP over its stdin and stdout, e.g.
+    /// `lldb-dap`, `codelldb` or `js-debug`
+    ///
+    /// The adapter is started with `command` and stopped when the session ends. Anything it
+    /// writes to stderr is emitted as [`Event::AdapterLog`]. Unlike sessions over TCP, the
+    /// session cannot be recovered or restarted by reconnecting to the adapter, and child
+    /// sessions requested with `startDebugging` are rejected.
+    #[tracing::instrument(skip(initialise_arguments))]
+    pub fn over_stdio(
+        command: Command,
+        initialise_arguments: impl Into<InitialiseArguments>,
+    ) -> Result<Self> {
         let args: InitialiseArguments = initialise_arguments.into();
-        let internals_rx = rx.clone();
-        let (mut internals, events) = match &args {
-            InitialiseArguments::Launch(state::LaunchArguments { language, .. }) => {
-                // let implementation = language.into();
-                let implementation: Implementation = match language {
-                    crate::Language::DebugPy => Implementation::Debugpy,
-                    crate::Language::Delve => Implementation::Delve,
-                };
+        let terminate_on_drop = args.launches();
 
-                let s = server::for_implementation_on_port(implementation, port)
-                    .context("creating background server process")?;
-                let stream = reliable_tcp_stream(format!("127.0.0.1:{port}"))
-                    .context("connecting to server")?;
+        let (events_tx, events) = crossbeam_channel::unbounded();
+        let (reverse_tx, reverse_requests) = crossbeam_channel::unbounded();
+        let (mut transport, client) = StdioTransport::spawn(command, events_tx, Some(reverse_tx))
+            .context("starting adapter")?;
+        let stderr = transport.take_stderr();
+
+        let debugger = Self::connect(
+            Endpoint::Stdio,
+            (client, events, reverse_requests),
+            None,
+            terminate_on_drop,
+            |internals| internals.initialise(args),
+        )?;
+        debugger.internals.lock().unwrap().stdio = Some(transport);
+        if let Some(stderr) = stderr {
+            debugger
+                .connection
+                .forward_adapter_output(read_lines(stderr, OutputStream::Stderr));
+        }
+        Ok(debugger)
+    }
 
-                let (ttx, trx) = crossbeam_channel::unbounded();
-                let client =
-                    transport::Client::new(stream, ttx).context("creating transport client")?;
+    /// Replay a recorded session in place of a live adapter, e.g. one written by
+    /// [`Debugger::set_message_tap`] or `pcaplog --jsonl`
+    ///
+    /// The session is started with the recorded `launch` or `attach` request, or by attaching
+    /// if the recording started part way through a session, and adapter events are replayed
+    /// with the gaps given by `timing`. Recorded responses are sent as the
+    /// session repeats the recorded requests, see [`Recording::replay`], so the replay only
+    /// advances past e.g. a `continue` once [`Debugger::r#continue`] is called. Replayed sessions
+    /// cannot be recovered or restarted, and child sessions are rejected.
+    #[tracing::instrument(skip(recording))]
+    pub fn replay(recording: Recording, timing: Timing) -> Result<Self> {
+        let arguments = recording
+            .start_request()
+            .unwrap_or_else(|| requests::StartDebugging {
+                configuration: serde_json::json!({}),
+                request: requests::StartDebuggingRequest::Attach,
+            });
 
-                let internals = DebuggerInternals::new(client, tx, Some(s));
-                (internals, trx)
-            }
-            InitialiseArguments::Attach(_) => {
-                let stream = reliable_tcp_stream(format!("127.0.0.1:{port}"))
-                    .context("connecting to server")?;
+        let (events_tx, events) = crossbeam_channel::unbounded();
+        let (reverse_tx, reverse_requests) = crossbeam_channel::unbounded();
+        let client = recording
+            .replay(timing, events_tx, Some(reverse_tx))
+            .context("starting replay")?;
+        Self::connect(
+            Endpoint::Replay,
+            (client, events, reverse_requests),
+            None,
+            false,
+            |internals| internals.initialise(InitialiseArguments::Configuration(arguments)),
+        )
+        .map_err(Error::from)
+    }
 
-                let (ttx, trx) = crossbeam_channel::unbounded();
-                let client =
-                    transport::Client::new(stream, ttx).context("creating transport client")?;
+    /// Start handling the events and reverse requests received by `client`
+    pub(crate) fn connect(
+        endpoint: Endpoint,
+        client: ClientChannels,
+        server: Option<Box<dyn server::Server + Send>>,
+        terminate_on_drop: bool,
+        initialise: impl FnOnce(&mut DebuggerInternals) -> eyre::Result<()>,
+    ) -> eyre::Result<Self> {
+        // notify our subscribers
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let _ = tx.send(Event::Uninitialised);
 
-                let internals = DebuggerInternals::new(client, tx, None);
-                (internals, trx)
-            }
-        };
+        let (client, events, reverse_requests) = client;
 
-        internals.initialise(args).context("initialising")?;
+        let mut internals = DebuggerInternals::new(client, tx, server);
+        initialise(&mut internals).context("initialising")?;
 
+        let adapter_output = internals.server.as_mut().and_then(|s| s.take_output());
         let internals = Arc::new(Mutex::new(internals));
+        let children = Arc::new(Mutex::new(Vec::new()));
+        let connection = Connection {
+            endpoint,
+            terminate_on_drop,
+            generation: 0,
+            internals: Arc::downgrade(&internals),
+            children: Arc::downgrade(&children),
+        };
+        connection.handle_reverse_requests(reverse_requests);
+        if let Some(output) = adapter_output {
+            connection.forward_adapter_output(output);
+        }
 
         // background thread reading transport events, and handling the event with our internal state
-        let background_internals = Arc::clone(&internals);
-        let background_events = events.clone();
-        thread::spawn(move || loop {
-            let event = background_events.recv().unwrap();
-            background_internals.lock().unwrap().on_event(event);
-        });
+        let event_connection = connection.clone();
+        thread::spawn(move || event_connection.handle_events(events));
 
         Ok(Self {
             internals,
-            rx: internals_rx,
+            connection,
+            rx,
+            terminate_on_drop,
+            children,
         })
     }
 
+    /// Start a session requested by the adapter with a `startDebugging` request
+    ///
+    /// The new session gets a copy of the parent's breakpoints and is started immediately.
+    fn start_child(
+        host: String,
+        port: u16,
+        terminate_on_drop: bool,
+        arguments: requests::StartDebugging,
+        breakpoints: Vec<types::Breakpoint>,
+    ) -> eyre::Result<Self> {
+        let client = new_client(&host, port).context("connecting to server")?;
+        let child = Self::connect(
+            Endpoint::Tcp { host, port },
+            client,
+            None,
+            terminate_on_drop,
+            |internals| internals.initialise(InitialiseArguments::Configuration(arguments)),
+        )?;
+
+        child
+            .wait_for_event_timeout(
+                |e| matches!(e, Event::Initialised),
+                CHILD_INITIALISE_TIMEOUT,
+            )
+            .ok_or_else(|| eyre::eyre!("timed out waiting for child session to initialise"))?;
+        child
+            .with_breakpoints(|store| {
+                for breakpoint in breakpoints {
+                    store.add(breakpoint);
+                }
+            })
+            .context("adding breakpoints to child session")?;
+        child.start().context("starting child session")?;
+        Ok(child)
+    }
+
+    /// Sessions started automatically for child processes of the debugee
+    pub fn children(&self) -> Vec<Arc<Debugger>> {
+        self.children.lock().unwrap().clone()
+    }
+
     /// Create a new debugging session on the default DAP port (5678)
     ///
     /// Note: the debugging session does not start until [`Debugger::start`] is called
     #[tracing::instrument(skip(initialise_arguments))]
-    pub fn new(initialise_arguments: impl Into<InitialiseArguments>) -> eyre::Result<Self> {
+    pub fn new(initialise_arguments: impl Into<InitialiseArguments>) -> Result<Self> {
         Self::on_port(DEFAULT_DAP_PORT, initialise_arguments)
     }
 
@@ -156,19 +328,125 @@ impl Debugger {
         self.rx.clone()
     }
 
+    /// Return a new [`crossbeam_channel::Receiver<Event>`] that receives a copy of every
+    /// subsequent event
+    ///
+    /// Unlike [`Debugger::events`], which shares a single queue (so each event is delivered to
+    /// only one receiver), this does not take events away from [`Debugger::wait_for_event`].
+    pub fn subscribe(&self) -> crossbeam_channel::Receiver<Event> {
+        self.internals.lock().unwrap().publisher.subscribe()
+    }
+
+    /// Reconnect to the adapter if the connection is lost unexpectedly, see [`RecoveryOptions`]
+    ///
+    /// A [`Event::Recovered`] event is emitted once the session has been re-established.
+    pub fn enable_recovery(&self, options: RecoveryOptions) {
+        self.internals.lock().unwrap().recovery = Some(options);
+    }
+
+    /// Check that the adapter is still responding every [`LivenessOptions::interval`], see
+    /// [`LivenessOptions`]
+    ///
+    /// An [`Event::AdapterUnresponsive`] event is emitted when the adapter stops responding,
+    /// so that frontends can let the user decide what to do rather than appearing frozen. It
+    /// is emitted again if the adapter recovers and then stops responding again.
+    pub fn enable_liveness(&self, options: LivenessOptions) {
+        let mut internals = self.internals.lock().unwrap();
+        if internals.liveness.replace(options.clone()).is_none() {
+            let session = liveness::Session::new(&internals, options);
+            let internals = Arc::downgrade(&self.internals);
+            thread::spawn(move || liveness::monitor(internals, session));
+        }
+    }
+
+    /// Change how long to wait for the adapter to respond to each request, or wait forever if
+    /// `None`
+    ///
+    /// Requests that are not answered in time fail with [`Error::Timeout`], so a dead adapter
+    /// cannot hang the caller. Defaults to [`transport::DEFAULT_REQUEST_TIMEOUT`].
+    pub fn set_request_timeout(&self, timeout: Option<Duration>) {
+        self.internals.lock().unwrap().set_request_timeout(timeout);
+    }
+
+    /// Mirror every message exchanged with the adapter to `tap`, or stop if `None`
+    ///
+    /// For example [`transport::tap::JsonlTap`] writes a protocol log for post-mortem
+    /// debugging, and [`transport::tap::MessageLog`] keeps the latest messages in memory.
+    /// The tap is kept when the session is recovered or restarted.
+    pub fn set_message_tap(&self, tap: Option<Arc<dyn MessageTap>>) {
+        self.internals.lock().unwrap().set_tap(tap);
+    }
+
+    /// Check every message from the adapter against the protocol, e.g. that sequence numbers
+    /// increase and responses answer outstanding requests
+    ///
+    /// Violations are emitted as [`Event::ProtocolViolation`] rather than failing the session,
+    /// which helps to track down misbehaving adapters. The setting is kept when the session is
+    /// recovered or restarted.
+    pub fn set_strict_protocol(&self, enabled: bool) {
+        self.internals.lock().unwrap().set_strict_protocol(enabled);
+    }
+
+    /// Request and event metrics for this session
+    pub fn metrics(&self) -> Metrics {
+        self.internals.lock().unwrap().metrics.clone()
+    }
+
     /// Add a breakpoint for the current debugging session
-    pub fn add_breakpoint(
+    pub fn add_breakpoint(&self, breakpoint: &types::Breakpoint) -> Result<types::BreakpointId> {
+        let mut internals = self.internals.lock().unwrap();
+        internals.add_breakpoint(breakpoint).map_err(Error::from)
+    }
+
+    /// Remove a breakpoint from the current debugging session
+    pub fn remove_breakpoint(&self, id: types::BreakpointId) -> Result<()> {
+        let mut internals = self.internals.lock().unwrap();
+        internals.remove_breakpoint(id).map_err(Error::from)
+    }
+
+    /// Whether the adapter has bound the breakpoint to executable code
+    ///
+    /// Returns `None` if the adapter has not reported on this breakpoint yet.
+    pub fn breakpoint_verified(&self, id: types::BreakpointId) -> Option<bool> {
+        self.internals.lock().unwrap().breakpoints.verified(id)
+    }
+
+    /// Change several breakpoints at once
+    ///
+    /// The adapter is sent one `setBreakpoints` request per changed file once `f` returns,
+    /// rather than one per change.
+    pub fn with_breakpoints<R>(&self, f: impl FnOnce(&mut BreakpointStore) -> R) -> Result<R> {
+        let mut internals = self.internals.lock().unwrap();
+        let res = f(&mut internals.breakpoints);
+        internals.breakpoints_changed()?;
+        Ok(res)
+    }
+
+    /// Save the breakpoints with `persistence` whenever they change
+    pub fn set_breakpoint_persistence(&self, persistence: Box<dyn PersistBreakpoints>) {
+        self.internals
+            .lock()
+            .unwrap()
+            .breakpoints
+            .set_persistence(persistence);
+    }
+
+    /// Replace an existing breakpoint, e.g. to change its condition
+    pub fn update_breakpoint(
         &self,
+        id: types::BreakpointId,
         breakpoint: &types::Breakpoint,
-    ) -> eyre::Result<types::BreakpointId> {
+    ) -> Result<()> {
         let mut internals = self.internals.lock().unwrap();
-        internals.add_breakpoint(breakpoint)
+        internals
+            .update_breakpoint(id, breakpoint)
+            .map_err(Error::from)
     }
 
     pub fn get_breakpoint_locations(
         &self,
         path: impl Into<PathBuf>,
-    ) -> eyre::Result<Vec<BreakpointLocation>> {
+    ) -> Result<Vec<BreakpointLocation>> {
         let locations = self
             .internals
             .lock()
@@ -180,18 +458,53 @@ impl Debugger {
 
     /// Return the list of breakpoints configured
     pub fn breakpoints(&self) -> Vec<types::Breakpoint> {
+        self.internals.lock().unwrap().breakpoints.breakpoints()
+    }
+
+    /// Return the list of breakpoints configured, along with the id used to remove them
+    pub fn breakpoints_with_ids(&self) -> Vec<(types::BreakpointId, types::Breakpoint)> {
         self.internals
             .lock()
             .unwrap()
             .breakpoints
-            .clone()
-            .values()
-            .cloned()
-            .collect()
+            .breakpoints_with_ids()
+    }
+
+    /// Features the adapter reported in its `initialize` response
+    ///
+    /// All capabilities are unset until the adapter has been initialised. Methods that need a
+    /// capability the adapter lacks fail with [`Error::Unsupported`] without sending a request.
+    pub fn capabilities(&self) -> responses::Capabilities {
+        self.internals.lock().unwrap().capabilities.clone()
+    }
+
+    /// Kinds of exception the adapter can break on, e.g. to render a checkbox for each
+    ///
+    /// Empty until the adapter has been initialised.
+    pub fn exception_breakpoint_filters(&self) -> Vec<ExceptionBreakpointsFilter> {
+        self.internals.lock().unwrap().exception_filters().to_vec()
+    }
+
+    /// Filters of the exception breakpoints currently enabled
+    pub fn exception_breakpoints(&self) -> Vec<String> {
+        self.internals.lock().unwrap().exception_breakpoints.clone()
+    }
+
+    /// Break on the given kinds of exception, replacing any previously enabled
+    ///
+    /// Each filter must be one of the `filter` values from
+    /// [`Debugger::exception_breakpoint_filters`]. Pass an empty list to stop breaking on
+    /// exceptions.
+    pub fn set_exception_breakpoints(&self, filters: &[String]) -> Result<()> {
+        let mut internals = self.internals.lock().unwrap();
+        internals
+            .set_exception_breakpoints(filters.to_vec())
+            .context("setting exception breakpoints")
+            .map_err(Error::from)
     }
 
     /// Launch a debugging session
-    pub fn start(&self) -> eyre::Result<()> {
+    pub fn start(&self) -> Result<()> {
         let mut internals = self.internals.lock().unwrap();
         let _ = internals
             .client
@@ -201,51 +514,192 @@ impl Debugger {
         Ok(())
     }
 
-    /// Perform a code/variable evaluation within a debugging session
+    /// Evaluate an expression in the scope of a stack frame
+    ///
+    /// If the expression fails to evaluate, the error message is returned as the output with
+    /// [`EvaluateResult::error`] set. The [`EvaluateContext::Clipboard`] context falls back to
+    /// [`EvaluateContext::Repl`] for adapters that do not support it.
     pub fn evaluate(
         &self,
         input: &str,
         frame_id: StackFrameId,
-    ) -> eyre::Result<Option<EvaluateResult>> {
+        context: EvaluateContext,
+    ) -> Result<Option<EvaluateResult>> {
+        self.internals
+            .lock()
+            .unwrap()
+            .evaluate(input, frame_id, context)
+            .map_err(Error::from)
+    }
+
+    /// Assign `value` to an assignable expression such as `obj.attr` or `items[0]`
+    ///
+    /// The value is itself an expression, evaluated in the scope of `frame_id`. Returns the new
+    /// value as reported by the adapter, or [`Error::Unsupported`] if the adapter cannot set
+    /// expressions.
+    pub fn set_expression(
+        &self,
+        expression: &str,
+        value: &str,
+        frame_id: Option<StackFrameId>,
+    ) -> Result<EvaluateResult> {
+        self.internals
+            .lock()
+            .unwrap()
+            .set_expression(expression, value, frame_id)
+            .wrap_err("setting expression")
+            .map_err(Error::from)
+    }
+
+    /// Read `count` bytes of the debugee's memory, starting `offset` bytes from
+    /// `memory_reference`
+    ///
+    /// Memory references come from the adapter, e.g. on variables or stack frames. Fewer bytes
+    /// than requested are returned if some could not be read.
+    pub fn read_memory(&self, memory_reference: &str, offset: i64, count: usize) -> Result<Memory> {
+        self.internals
+            .lock()
+            .unwrap()
+            .read_memory(memory_reference, offset, count)
+            .wrap_err("reading memory")
+            .map_err(Error::from)
+    }
+
+    /// Overwrite the debugee's memory starting `offset` bytes from `memory_reference`,
+    /// returning the number of bytes written
+    pub fn write_memory(&self, memory_reference: &str, offset: i64, data: &[u8]) -> Result<usize> {
+        self.internals
+            .lock()
+            .unwrap()
+            .write_memory(memory_reference, offset, data)
+            .wrap_err("writing memory")
+            .map_err(Error::from)
+    }
+
+    /// Possible completions of partially typed REPL input, e.g. variable or attribute names
+    ///
+    /// `column` is the 1-indexed cursor position within `text`. Names are looked up in the
+    /// scope of `frame_id`, or the global scope if it is not given. Apply a completion with
+    /// [`crate::utils::apply_completion`].
+    pub fn completions(
+        &self,
+        text: &str,
+        column: usize,
+        frame_id: Option<StackFrameId>,
+    ) -> Result<Vec<CompletionItem>> {
+        self.internals
+            .lock()
+            .unwrap()
+            .completions(text, column, frame_id)
+            .wrap_err("fetching completions")
+            .map_err(Error::from)
+    }
+
+    /// The stack frame the debugee is paused in, or the frame selected with
+    /// [`Debugger::change_scope`]
+    pub fn current_frame_id(&self) -> Option<StackFrameId> {
+        self.internals.lock().unwrap().current_frame_id
+    }
+
+    /// Fetch the children of a variable, e.g. the fields of an object or items of a list
+    pub fn variables(&self, variables_reference: VariablesReference) -> Result<Vec<Variable>> {
+        self.internals
+            .lock()
+            .unwrap()
+            .variables(variables_reference, None, None)
+            .map_err(Error::from)
+    }
+
+    /// Fetch the value of a lazy variable, see [`Variable::is_lazy`]
+    ///
+    /// The adapter provides the value as the single child of the variable, which is returned
+    /// with the name of the original. Other variables are returned unchanged.
+    pub fn resolve_lazy(&self, variable: &Variable) -> Result<Variable> {
+        if !variable.is_lazy() {
+            return Ok(variable.clone());
+        }
+        let child = self
+            .variables(variable.variables_reference)
+            .wrap_err("fetching value of lazy variable")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre::eyre!("lazy variable {} has no value", variable.name))?;
+        Ok(Variable {
+            name: variable.name.clone(),
+            evaluate_name: variable
+                .evaluate_name
+                .clone()
+                .or_else(|| child.evaluate_name.clone()),
+            ..child
+        })
+    }
+
+    /// Fetch at most `count` children of a variable, starting from index `start`
+    ///
+    /// Use this rather than [`Debugger::variables`] for variables with many children, such as
+    /// large lists, using [`Variable::indexed_variables`] and [`Variable::named_variables`] as
+    /// the total number of children where the adapter reports them. Adapters that do not
+    /// support paging may return every child regardless.
+    pub fn variables_range(
+        &self,
+        variables_reference: VariablesReference,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<Variable>> {
+        self.internals
+            .lock()
+            .unwrap()
+            .variables(variables_reference, Some(start), Some(count))
+            .map_err(Error::from)
+    }
+
+    /// Add an expression to watch, see [`Debugger::evaluate_watches`]
+    pub fn add_watch(&self, expression: impl Into<String>) -> types::WatchId {
+        self.internals.lock().unwrap().add_watch(expression.into())
+    }
+
+    /// Stop watching an expression
+    pub fn remove_watch(&self, id: types::WatchId) -> Result<()> {
+        self.internals
+            .lock()
+            .unwrap()
+            .remove_watch(id)
+            .map_err(Error::from)
+    }
+
+    /// Return the list of watch expressions
+    pub fn watches(&self) -> Vec<types::Watch> {
+        self.internals
+            .lock()
+            .unwrap()
+            .watches
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Evaluate all watch expressions in the context of the given stack frame, in the order
+    /// they were added
+    ///
+    /// Watches are not evaluated automatically, so frontends call this on each
+    /// [`Event::Paused`] to keep them up to date.
+    pub fn evaluate_watches(&self, frame_id: StackFrameId) -> Result<Vec<WatchResult>> {
         let internals = self.internals.lock().unwrap();
-        let req = requests::RequestBody::Evaluate(requests::Evaluate {
-            expression: input.to_string(),
-            frame_id: Some(frame_id),
-            context: Some("repl".to_string()),
-        });
-        let res = internals
-            .client
-            .send(req)
-            .context("sending evaluate request")?;
-        match res {
-            responses::Response {
-                body:
-                    Some(responses::ResponseBody::Evaluate(responses::EvaluateResponse {
-                        result, ..
-                    })),
-                success: true,
-                ..
-            } => Ok(Some(EvaluateResult {
-                output: result,
-                error: false,
-            })),
-            responses::Response {
-                message: Some(msg),
-                success: false,
-                ..
-            } => Ok(Some(EvaluateResult {
-                output: msg,
-                error: true,
-            })),
-            other => {
-                tracing::warn!(response = ?other, "unhandled response");
-                Ok(None)
-            }
+        let mut results = Vec::with_capacity(internals.watches.len());
+        for watch in internals.watches.values() {
+            let result = internals
+                .evaluate(&watch.expression, frame_id, EvaluateContext::Watch)
+                .with_context(|| format!("evaluating watch expression {}", watch.expression))?;
+            results.push(WatchResult {
+                watch: watch.clone(),
+                result,
+            });
         }
+        Ok(results)
     }
 
     /// Resume execution of the debugee
-    pub fn r#continue(&self) -> eyre::Result<()> {
+    pub fn r#continue(&self) -> Result<()> {
         let internals = self.internals.lock().unwrap();
         match internals.current_thread_id {
             Some(thread_id) => {
@@ -253,32 +707,39 @@ impl Debugger {
                     .client
                     .execute(requests::RequestBody::Continue(requests::Continue {
                         thread_id,
-                        single_thread: false,
+                        single_thread: internals.single_thread.then_some(true),
                     }))
                     .context("sending continue request")?;
             }
-            None => eyre::bail!("logic error: no current thread id"),
+            None => {
+                return Err(eyre::eyre!("logic error: no current thread id").into());
+            }
         }
         Ok(())
     }
 
     /// Step over a statement
-    pub fn step_over(&self) -> eyre::Result<()> {
+    pub fn step_over(&self) -> Result<()> {
         let internals = self.internals.lock().unwrap();
         match internals.current_thread_id {
             Some(thread_id) => {
                 internals
                     .client
-                    .execute(requests::RequestBody::Next(requests::Next { thread_id }))
+                    .execute(requests::RequestBody::Next(requests::Next {
+                        thread_id,
+                        single_thread: internals.single_thread.then_some(true),
+                    }))
                     .context("sending step_over request")?;
             }
-            None => eyre::bail!("logic error: no current thread id"),
+            None => {
+                return Err(eyre::eyre!("logic error: no current thread id").into());
+            }
         }
         Ok(())
     }
 
     /// Step into a statement
-    pub fn step_in(&self) -> eyre::Result<()> {
+    pub fn step_in(&self) -> Result<()> {
         let internals = self.internals.lock().unwrap();
         match internals.current_thread_id {
             Some(thread_id) => {
@@ -286,16 +747,19 @@ impl Debugger {
                     .client
                     .execute(requests::RequestBody::StepIn(requests::StepIn {
                         thread_id,
+                        single_thread: internals.single_thread.then_some(true),
                     }))
                     .context("sending step_in` request")?;
             }
-            None => eyre::bail!("logic error: no current thread id"),
+            None => {
+                return Err(eyre::eyre!("logic error: no current thread id").into());
+            }
         }
         Ok(())
     }
 
     /// Step out of a statement
-    pub fn step_out(&self) -> eyre::Result<()> {
+    pub fn step_out(&self) -> Result<()> {
         let internals = self.internals.lock().unwrap();
         match internals.current_thread_id {
             Some(thread_id) => {
@@ -303,16 +767,44 @@ impl Debugger {
                     .client
                     .execute(requests::RequestBody::StepOut(requests::StepOut {
                         thread_id,
+                        single_thread: internals.single_thread.then_some(true),
                     }))
                     .context("sending `step_out` request")?;
             }
-            None => eyre::bail!("logic error: no current thread id"),
+            None => {
+                return Err(eyre::eyre!("logic error: no current thread id").into());
+            }
         }
         Ok(())
     }
 
-    fn execute(&self, body: requests::RequestBody) -> eyre::Result<()> {
-        self.internals.lock().unwrap().client.execute(body)
+    /// Pause execution of the debugee
+    ///
+    /// The debugee is paused on the current thread, or the thread that last stopped or was
+    /// selected with [`Debugger::select_thread`]. If that thread no longer exists, or none is
+    /// known, the first thread reported by the adapter is paused instead.
+    pub fn pause(&self) -> Result<()> {
+        let internals = self.internals.lock().unwrap();
+        let res = internals
+            .client
+            .send(requests::RequestBody::Threads)
+            .context("sending threads request")?;
+        let responses::ResponseBody::Threads(responses::ThreadsResponse { threads }) = res else {
+            return Err(eyre::eyre!("invalid response type: {:?}", res).into());
+        };
+        let selected = internals.current_thread_id.or(internals.selected_thread_id);
+        let thread = threads
+            .iter()
+            .find(|thread| Some(thread.id) == selected)
+            .or_else(|| threads.first())
+            .ok_or_else(|| eyre::eyre!("debugee has no threads"))?;
+        internals
+            .client
+            .execute(requests::RequestBody::Pause(requests::Pause {
+                thread_id: thread.id,
+            }))
+            .context("sending pause request")?;
+        Ok(())
     }
 
     /// Pause the debugging session waiting for a specific event, where the predicate returns true
@@ -333,12 +825,238 @@ impl Debugger {
             } else {
                 tracing::trace!(event = ?evt, "non-matching event");
             }
-            n += 1;
+            // chatty debugees should not exhaust the event budget
+            if !matches!(evt, Event::Output { .. } | Event::AdapterLog { .. }) {
+                n += 1;
+            }
+        }
+    }
+
+    /// Like [`Debugger::wait_for_event`] but gives up after `timeout`, returning `None`
+    pub fn wait_for_event_timeout<F>(&self, pred: F, timeout: Duration) -> Option<Event>
+    where
+        F: Fn(&Event) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let evt = self.rx.recv_timeout(remaining).ok()?;
+            if pred(&evt) {
+                tracing::debug!(event = ?evt, "received expected event");
+                return Some(evt);
+            } else {
+                tracing::trace!(event = ?evt, "non-matching event");
+            }
+        }
+    }
+
+    /// End the debugging session
+    ///
+    /// Disconnects from the debug adapter (terminating the debugee if we launched it) and stops
+    /// any adapter process started for this session. This is also performed when the debugger is
+    /// dropped, so calling it explicitly is only needed for deterministic cleanup.
+    pub fn shutdown(&self) -> Result<()> {
+        let mode = if self.terminate_on_drop {
+            TerminateMode::DisconnectAndTerminate
+        } else {
+            TerminateMode::Detach
+        };
+        self.stop(mode)
+            .wrap_err("shutting down debugger")
+            .map_err(Error::from)
+    }
+
+    /// End the debugging session, choosing what happens to the debugee
+    ///
+    /// Unlike [`Debugger::shutdown`], this can terminate attached debugees, e.g. when the user
+    /// quits the frontend. Any adapter process started for this session is stopped either way.
+    pub fn stop(&self, mode: TerminateMode) -> Result<()> {
+        self.internals
+            .lock()
+            .unwrap()
+            .stop(mode)
+            .wrap_err("stopping debugger")
+            .map_err(Error::from)
+    }
+
+    /// Threads of the debugee, as of when it last stopped
+    pub fn threads(&self) -> Vec<Thread> {
+        self.internals.lock().unwrap().threads.clone()
+    }
+
+    /// Modules loaded by the debugee, e.g. shared libraries when debugging native code
+    ///
+    /// Empty unless the adapter supports the `modules` request or sends `module` events.
+    pub fn modules(&self) -> Vec<Module> {
+        self.internals.lock().unwrap().modules.clone()
+    }
+
+    /// Sources loaded by the debugee, if the adapter supports the `loadedSources` request or
+    /// sends `loadedSource` events
+    pub fn loaded_sources(&self) -> Vec<Source> {
+        self.internals.lock().unwrap().loaded_sources.clone()
+    }
+
+    /// Thread whose stack is currently being inspected, if the debugee is paused
+    pub fn current_thread(&self) -> Option<ThreadId> {
+        self.internals.lock().unwrap().current_thread_id
+    }
+
+    /// Fetch the stack trace of any thread, without changing the current thread
+    ///
+    /// The full stack is returned, regardless of [`Debugger::set_frame_filter`].
+    pub fn stack_trace(&self, thread_id: ThreadId) -> Result<Vec<StackFrame>> {
+        self.internals
+            .lock()
+            .unwrap()
+            .stack_trace(thread_id)
+            .wrap_err("fetching stack trace")
+            .map_err(Error::from)
+    }
+
+    /// Hide stack frames outside of the user's code from the stacks in [`Event::Paused`] and
+    /// [`Event::ScopeChange`]
+    ///
+    /// Frames are hidden if the adapter de-emphasises their source, or if it is outside the
+    /// working directory of the session. If the debugee is paused, the current stack is sent
+    /// again with the filter applied.
+    pub fn set_frame_filter(&self, enabled: bool) -> Result<()> {
+        self.internals
+            .lock()
+            .unwrap()
+            .set_frame_filter(enabled)
+            .wrap_err("changing frame filter")
+            .map_err(Error::from)
+    }
+
+    /// Whether stack frames outside of the user's code are hidden
+    pub fn frame_filter(&self) -> bool {
+        self.internals.lock().unwrap().frame_filter
+    }
+
+    /// Make `thread_id` the current thread, emitting [`Event::ScopeChange`] for its top frame
+    pub fn select_thread(&self, thread_id: ThreadId) -> Result<()> {
+        self.internals
+            .lock()
+            .unwrap()
+            .select_thread(thread_id)
+            .wrap_err("selecting thread")
+            .map_err(Error::from)
+    }
+
+    /// Make the thread called `name` the current thread, as with [`Debugger::select_thread`]
+    ///
+    /// Returns the id of the selected thread. If several threads share the name the first
+    /// reported by the adapter is chosen.
+    pub fn select_thread_by_name(&self, name: &str) -> Result<ThreadId> {
+        let mut internals = self.internals.lock().unwrap();
+        let thread_id = internals.thread_named(name)?;
+        internals
+            .select_thread(thread_id)
+            .wrap_err("selecting thread")?;
+        Ok(thread_id)
+    }
+
+    /// Only resume the current thread when continuing or stepping, leaving the other threads
+    /// of the debugee suspended
+    ///
+    /// Fails with [`Error::Unsupported`] if the adapter cannot resume a single thread. The setting
+    /// applies for the rest of the session.
+    pub fn set_single_thread(&self, enabled: bool) -> Result<()> {
+        self.internals
+            .lock()
+            .unwrap()
+            .set_single_thread(enabled)
+            .wrap_err("changing single thread execution")
+            .map_err(Error::from)
+    }
+
+    /// Whether continuing and stepping only resume the current thread
+    pub fn single_thread(&self) -> bool {
+        self.internals.lock().unwrap().single_thread
+    }
+
+    /// Set the next statement to run to `line` of `path`, without running the code in between
+    ///
+    /// The adapter reports the new position by stopping again, so this is followed by an
+    /// [`Event::Paused`].
+    pub fn jump_to_line(&self, path: impl AsRef<Path>, line: usize) -> Result<()> {
+        self.internals
+            .lock()
+            .unwrap()
+            .jump_to_line(path.as_ref(), line)
+            .wrap_err("jumping to line")
+            .map_err(Error::from)
+    }
+
+    /// Re-run the debugee without tearing down the session
+    ///
+    /// Uses the adapter's `restart` request when it is supported. Otherwise the session is
+    /// disconnected and the original `launch` or `attach` request is replayed on a new
+    /// connection, re-applying all breakpoints as when recovering a lost connection.
+    pub fn restart(&self) -> Result<()> {
+        self.restart_with(None)
+    }
+
+    /// See [`Debugger::restart`], passing `arguments` to the adapter's `restart` request
+    fn restart_with(&self, arguments: Option<serde_json::Value>) -> Result<()> {
+        {
+            let mut internals = self.internals.lock().unwrap();
+            if internals.disconnected {
+                return Err(Error::Disconnected);
+            }
+            if internals.capabilities.supports_restart_request == Some(true) {
+                return internals
+                    .restart(arguments)
+                    .wrap_err("restarting debugee")
+                    .map_err(Error::from);
+            }
+        }
+        if let Endpoint::Stdio | Endpoint::Replay = self.connection.endpoint {
+            return Err(Error::Unsupported {
+                command: "restart".to_string(),
+            });
         }
+        recovery::restart(&self.connection)
+            .wrap_err("restarting session")
+            .map_err(Error::from)
+    }
+
+    /// Quickly re-run the debugee after editing it, if the adapter supports the `restart`
+    /// request
+    ///
+    /// Unlike [`Debugger::restart`] the adapter and connection are kept, so breakpoints and
+    /// watches carry over without being sent again. [`Event::Running`] is emitted straight
+    /// away, followed by [`Event::Paused`] when a breakpoint is hit.
+    pub fn reload(&self) -> Result<()> {
+        let arguments = {
+            let internals = self.internals.lock().unwrap();
+            if internals.disconnected {
+                return Err(Error::Disconnected);
+            }
+            internals
+                .reload_arguments()
+                .wrap_err("reloading debugee")
+                .map_err(Error::from)?
+        };
+        self.restart_with(arguments)
+    }
+
+    /// Re-run a stack frame from its start, if the adapter supports it
+    ///
+    /// The adapter stops again at the start of the frame, so this is followed by an
+    /// [`Event::Paused`].
+    pub fn restart_frame(&self, frame_id: StackFrameId) -> Result<()> {
+        self.internals
+            .lock()
+            .unwrap()
+            .restart_frame(frame_id)
+            .wrap_err("restarting frame")
+            .map_err(Error::from)
     }
 
     /// Change the current scope to a new stack frame
-    pub fn change_scope(&self, stack_frame_id: StackFrameId) -> eyre::Result<()> {
+    pub fn change_scope(&self, stack_frame_id: StackFrameId) -> Result<()> {
         self.internals
             .lock()
             .unwrap()
@@ -348,12 +1066,213 @@ impl Debugger {
     }
 }
 
+/// How long to wait for a child session to be initialised before giving up
+const CHILD_INITIALISE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Adapter implementation to start for a launched session
+fn implementation_for(arguments: &InitialiseArguments) -> Option<Implementation> {
+    match arguments {
+        InitialiseArguments::Launch(state::LaunchArguments { language, .. }) => {
+            Some(match language {
+                crate::Language::DebugPy => Implementation::Debugpy,
+                crate::Language::Delve => Implementation::Delve,
+            })
+        }
+        InitialiseArguments::Attach(_) | InitialiseArguments::Configuration(_) => None,
+    }
+}
+
+/// Transport events received from the adapter
+pub(crate) type TransportEvents = crossbeam_channel::Receiver<transport::events::Event>;
+
+/// Client connected to an adapter, along with the events and reverse requests it receives
+pub(crate) type ClientChannels = (
+    transport::Client,
+    TransportEvents,
+    crossbeam_channel::Receiver<requests::Request>,
+);
+
+/// Connect to the adapter at `host:port`
+pub(crate) fn new_client(host: &str, port: u16) -> eyre::Result<ClientChannels> {
+    // keep the last connection error in the chain so that it is reported as disconnected
+    let stream = reliable_tcp_stream(format!("{host}:{port}"))
+        .map_err(|e| e.error)
+        .context("connecting to adapter")?;
+    let (events_tx, events) = crossbeam_channel::unbounded();
+    let (reverse_tx, reverse_requests) = crossbeam_channel::unbounded();
+    let client = transport::Client::with_reverse_requests(stream, events_tx, Some(reverse_tx))
+        .context("creating transport client")?;
+    Ok((client, events, reverse_requests))
+}
+
+/// Send each line read from `reader` as output of the adapter process, until it is closed
+fn read_lines(
+    reader: impl Read + Send + 'static,
+    stream: OutputStream,
+) -> mpsc::Receiver<OutputLine> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if tx.send(OutputLine { stream, line }).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// How a session reaches its adapter
+#[derive(Debug, Clone)]
+pub(crate) enum Endpoint {
+    Tcp {
+        host: String,
+        port: u16,
+    },
+    /// Over the stdin and stdout of an adapter process started by [`Debugger::over_stdio`],
+    /// which cannot be reconnected to
+    Stdio,
+    /// To a recording replayed by [`Debugger::replay`]
+    Replay,
+}
+
+/// Adapter connection details shared by the background threads of a session
+///
+/// Only weak references are held, so the threads stop once the [`Debugger`] is dropped.
+#[derive(Clone)]
+pub(crate) struct Connection {
+    pub(crate) endpoint: Endpoint,
+    terminate_on_drop: bool,
+    /// Generation of the session this connection belongs to, see
+    /// [`DebuggerInternals::generation`]
+    pub(crate) generation: u64,
+    pub(crate) internals: Weak<Mutex<DebuggerInternals>>,
+    children: Weak<Mutex<Vec<Arc<Debugger>>>>,
+}
+
+impl Connection {
+    /// Handle events from the adapter until the session ends or is restarted
+    pub(crate) fn handle_events(&self, mut events: TransportEvents) {
+        loop {
+            match events.recv() {
+                Ok(event) => {
+                    let Some(internals) = self.internals.upgrade() else {
+                        return;
+                    };
+                    let mut internals = internals.lock().unwrap();
+                    if internals.generation != self.generation {
+                        tracing::debug!("session restarted, ignoring events from old connection");
+                        return;
+                    }
+                    internals.on_event(event);
+                }
+                Err(_) => {
+                    tracing::debug!("adapter connection closed");
+                    match recovery::recover(self) {
+                        Some(new_events) => events = new_events,
+                        None => return,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Emit [`Event::AdapterLog`] for each line written by the adapter process, until it exits
+    pub(crate) fn forward_adapter_output(&self, output: std::sync::mpsc::Receiver<OutputLine>) {
+        let internals = self.internals.clone();
+        thread::spawn(move || {
+            for OutputLine { stream, line } in output {
+                let Some(internals) = internals.upgrade() else {
+                    return;
+                };
+                internals
+                    .lock()
+                    .unwrap()
+                    .emit(Event::AdapterLog { stream, line });
+            }
+        });
+    }
+
+    pub(crate) fn handle_reverse_requests(
+        &self,
+        requests: crossbeam_channel::Receiver<requests::Request>,
+    ) {
+        let connection = self.clone();
+        thread::spawn(move || {
+            for request in requests {
+                handle_reverse_request(
+                    request,
+                    &connection.endpoint,
+                    connection.terminate_on_drop,
+                    &connection.internals,
+                    &connection.children,
+                );
+            }
+        });
+    }
+}
+
+/// Respond to a request sent by the adapter to the session with `parent` internals
+fn handle_reverse_request(
+    request: requests::Request,
+    endpoint: &Endpoint,
+    terminate_on_drop: bool,
+    parent: &Weak<Mutex<DebuggerInternals>>,
+    children: &Weak<Mutex<Vec<Arc<Debugger>>>>,
+) {
+    let Some(internals) = parent.upgrade() else {
+        return;
+    };
+    let client = internals.lock().unwrap().client.clone();
+    match (request.body, endpoint) {
+        // child sessions connect to the adapter again, which is only possible over TCP
+        (requests::RequestBody::StartDebugging(arguments), Endpoint::Tcp { host, port }) => {
+            tracing::debug!(?arguments, "starting child session");
+            if let Err(e) = client.respond(request.seq, "startDebugging", true, None) {
+                tracing::warn!(error = %e, "responding to startDebugging request");
+                return;
+            }
+
+            let breakpoints = internals.lock().unwrap().breakpoints.breakpoints();
+            // the child must not hold on to the parent, or dropping the parent leaks it
+            drop(internals);
+            match Debugger::start_child(
+                host.clone(),
+                *port,
+                terminate_on_drop,
+                arguments,
+                breakpoints,
+            ) {
+                Ok(child) => {
+                    if let Some(children) = children.upgrade() {
+                        children.lock().unwrap().push(Arc::new(child));
+                    }
+                }
+                Err(e) => tracing::warn!(error = ?e, "starting child session"),
+            }
+        }
+        (other, _) => {
+            tracing::warn!(request = ?other, "unsupported reverse request");
+            let command = other.command();
+            if let Err(e) = client.respond(
+                request.seq,
+                &command,
+                false,
+                Some(format!("{command} is not supported")),
+            ) {
+                tracing::warn!(error = %e, "rejecting reverse request");
+            }
+        }
+    }
+}
+
 impl Drop for Debugger {
     fn drop(&mut self) {
         tracing::debug!("dropping debugger");
-        self.execute(requests::RequestBody::Disconnect(Disconnect {
-            terminate_debugee: true,
-        }))
-        .unwrap();
+        if let Err(e) = self.shutdown() {
+            tracing::warn!(error = %e, "error shutting down debugger");
+        }
     }
 }
@@ -0,0 +1,163 @@
+//! Errors returned by [`crate::Debugger`]
+use std::io::ErrorKind;
+
+use transport::{types::ErrorMessage, ConnectionLost, RequestTimeout, SendError};
+
+use crate::types::Unsupported;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error returned by the operations of a debugging session
+///
+/// Frontends can match on the variant to decide how to report a failure, e.g. showing an
+/// expression error inline but offering to reconnect when the adapter has gone away.
+#[derive(Debug)]
+pub enum Error {
+    /// The adapter did not advertise support for `command` in its capabilities, so no request
+    /// was sent
+    Unsupported { command: String },
+    /// The adapter responded to `command` with `success: false`, e.g. because an expression
+    /// could not be evaluated
+    AdapterError {
+        command: String,
+        /// Error message reported by the adapter, if any
+        message: Option<String>,
+        /// Structured error from the response, e.g. whether to show it to the user
+        error: Option<Box<ErrorMessage>>,
+    },
+    /// The adapter could not be reached, the connection to it was lost, or the session has
+    /// already been shut down
+    Disconnected,
+    /// The adapter did not respond to `command` in time
+    Timeout { command: String },
+    /// Any other failure, e.g. a malformed or unexpected response from the adapter, or a
+    /// command that is not valid in the current state of the debugee
+    Protocol(eyre::Report),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Unsupported { command } => {
+                write!(f, "adapter does not support {command} requests")
+            }
+            Error::AdapterError {
+                command,
+                message: Some(message),
+                ..
+            } => write!(f, "{command} request failed: {message}"),
+            Error::AdapterError {
+                command,
+                message: None,
+                ..
+            } => write!(f, "{command} request failed"),
+            Error::Disconnected => write!(f, "not connected to the debug adapter"),
+            Error::Timeout { command } => write!(f, "timed out waiting for {command} response"),
+            Error::Protocol(report) => write!(f, "{report:#}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<eyre::Report> for Error {
+    /// Classify an error from the internals of the debugger by the first recognised cause in
+    /// its chain
+    fn from(report: eyre::Report) -> Self {
+        for cause in report.chain() {
+            if let Some(Unsupported { command }) = cause.downcast_ref() {
+                return Error::Unsupported {
+                    command: command.clone(),
+                };
+            }
+            match cause.downcast_ref::<SendError>() {
+                Some(SendError::Adapter(e)) => {
+                    return Error::AdapterError {
+                        command: e.command.clone(),
+                        message: e.reason(),
+                        error: e.error.clone().map(Box::new),
+                    }
+                }
+                Some(SendError::Timeout(RequestTimeout { command, .. })) => {
+                    return Error::Timeout {
+                        command: command.clone(),
+                    }
+                }
+                Some(SendError::ConnectionLost(_)) => return Error::Disconnected,
+                // the report behind any other failure is the next cause
+                Some(SendError::Other(_)) | None => {}
+            }
+            // requests that do not wait for a response fail without a `SendError`
+            if cause.is::<ConnectionLost>() {
+                return Error::Disconnected;
+            }
+            if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+                if matches!(
+                    e.kind(),
+                    ErrorKind::BrokenPipe
+                        | ErrorKind::ConnectionAborted
+                        | ErrorKind::ConnectionReset
+                        | ErrorKind::ConnectionRefused
+                        | ErrorKind::NotConnected
+                        | ErrorKind::TimedOut
+                        | ErrorKind::UnexpectedEof
+                ) {
+                    return Error::Disconnected;
+                }
+            }
+        }
+        Error::Protocol(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eyre::WrapErr;
+
+    use transport::{AdapterError, SendError};
+
+    use super::Error;
+    use crate::types::Unsupported;
+
+    #[test]
+    fn classifies_causes() {
+        let err: Error = Err::<(), _>(Unsupported {
+            command: "restartFrame".to_string(),
+        })
+        .wrap_err("restarting frame")
+        .unwrap_err()
+        .into();
+        assert!(matches!(err, Error::Unsupported { command } if command == "restartFrame"));
+
+        let err: Error = Err::<(), _>(SendError::from(AdapterError {
+            command: "evaluate".to_string(),
+            message: Some("name 'x' is not defined".to_string()),
+            error: None,
+        }))
+        .wrap_err("sending evaluate request")
+        .unwrap_err()
+        .into();
+        assert_eq!(
+            err.to_string(),
+            "evaluate request failed: name 'x' is not defined"
+        );
+
+        let err: Error = eyre::Report::new(std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+            .wrap_err("sending request")
+            .into();
+        assert!(matches!(err, Error::Disconnected));
+
+        // io errors behind a failed send are still found
+        let err: Error = Err::<(), _>(SendError::Other(
+            eyre::Report::new(std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+                .wrap_err("writing message to output buffer"),
+        ))
+        .wrap_err("sending request")
+        .unwrap_err()
+        .into();
+        assert!(matches!(err, Error::Disconnected));
+
+        let err: Error = eyre::eyre!("invalid response").into();
+        assert!(matches!(err, Error::Protocol(_)));
+    }
+}
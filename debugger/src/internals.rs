@@ -1,38 +1,139 @@
+use base64::{prelude::BASE64_STANDARD, Engine};
 use eyre::WrapErr;
-use server::Server;
-use std::{collections::HashMap, path::PathBuf};
+use server::{Implementation, Server};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use transport::{
+    events::{ChangeReason, InvalidatedArea},
     requests::{self, Initialize, PathFormat},
-    responses::{self, ResponseBody},
-    types::{BreakpointLocation, Source, SourceBreakpoint, StackFrame, StackFrameId, ThreadId},
-    Client,
+    responses::{self, Capabilities, ResponseBody},
+    tap::MessageTap,
+    types::{
+        BreakpointLocation, CompletionItem, ExceptionBreakpointsFilter, Module, Source,
+        SourceBreakpoint, StackFrame, StackFrameId, Thread, ThreadId, Variable, VariablesReference,
+    },
+    Client, SendError, StdioTransport, DEFAULT_REQUEST_TIMEOUT,
 };
 
 use crate::{
+    breakpoints::BreakpointStore,
     debugger::InitialiseArguments,
+    liveness::LivenessOptions,
+    metrics::{MeteredClient, Metrics},
+    recovery::RecoveryOptions,
     state::DebuggerState,
-    types::{Breakpoint, BreakpointId, PausedFrame},
+    types::{
+        Breakpoint, BreakpointId, EvaluateContext, EvaluateResult, ExceptionInfo, Memory,
+        PausedFrame, StopReason, TerminateMode, Unsupported, Watch, WatchId,
+    },
+    utils::is_user_frame,
     Event,
 };
 
+/// How long [`DebuggerInternals::stop`] waits for the debugee to terminate before disconnecting
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FileSource {
     pub line: usize,
     pub file_path: Option<PathBuf>,
 }
 
+/// Sends the events of a session to its receivers
+///
+/// Cloned by background threads so that they can report events without waiting for the
+/// session lock, which is held while waiting for the adapter.
+#[derive(Clone)]
+pub(crate) struct Publisher {
+    publisher: crossbeam_channel::Sender<Event>,
+    /// Receivers that get a copy of every event, see [`crate::Debugger::subscribe`]
+    subscribers: Arc<Mutex<Vec<crossbeam_channel::Sender<Event>>>>,
+}
+
+impl Publisher {
+    fn new(publisher: crossbeam_channel::Sender<Event>) -> Self {
+        Self {
+            publisher,
+            subscribers: Arc::default(),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> crossbeam_channel::Receiver<Event> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub(crate) fn emit(&self, event: Event) {
+        // drop subscribers whose receivers have gone away
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|tx| tx.send(event.clone()).is_ok());
+        let _ = self.publisher.send(event);
+    }
+}
+
 pub(crate) struct DebuggerInternals {
-    pub(crate) client: Client,
-    pub(crate) publisher: crossbeam_channel::Sender<Event>,
+    pub(crate) client: MeteredClient,
+    pub(crate) metrics: Metrics,
+    pub(crate) publisher: Publisher,
 
     // debugger specific details
     pub(crate) current_thread_id: Option<ThreadId>,
-    pub(crate) breakpoints: HashMap<BreakpointId, Breakpoint>,
-
-    current_breakpoint_id: BreakpointId,
+    /// Thread that last stopped or was selected, kept while the debugee runs so that pausing
+    /// targets it
+    pub(crate) selected_thread_id: Option<ThreadId>,
+    /// Threads of the debugee, fetched each time it stops
+    pub(crate) threads: Vec<Thread>,
+    /// Modules loaded by the debugee, kept up to date with `module` events
+    pub(crate) modules: Vec<Module>,
+    /// Sources loaded by the debugee, kept up to date with `loadedSource` events
+    pub(crate) loaded_sources: Vec<Source>,
+    pub(crate) breakpoints: BreakpointStore,
+    pub(crate) watches: BTreeMap<WatchId, Watch>,
+    current_watch_id: WatchId,
     pub(crate) current_source: Option<FileSource>,
+    /// Stack frame that expressions are evaluated in by default
+    pub(crate) current_frame_id: Option<StackFrameId>,
 
-    pub(crate) _server: Option<Box<dyn Server + Send>>,
+    pub(crate) server: Option<Box<dyn Server + Send>>,
+    /// Adapter process the session talks to over stdio, see [`crate::Debugger::over_stdio`]
+    pub(crate) stdio: Option<StdioTransport>,
+    /// Adapter to restart if the connection is lost, only set if we started the adapter
+    pub(crate) adapter: Option<Implementation>,
+    pub(crate) adapter_executable: Option<PathBuf>,
+    /// `launch` or `attach` request that started the session, replayed when recovering
+    pub(crate) initialise_request: Option<requests::RequestBody>,
+    pub(crate) recovery: Option<RecoveryOptions>,
+    /// How often to check that the adapter is responding, if at all
+    pub(crate) liveness: Option<LivenessOptions>,
+    /// Capabilities of the adapter from its initialize response
+    pub(crate) capabilities: Capabilities,
+    /// Exception breakpoint filters currently enabled
+    pub(crate) exception_breakpoints: Vec<String>,
+    /// Root of the user's code, taken from the `launch` or `attach` request
+    workspace: Option<PathBuf>,
+    /// Whether stacks sent to subscribers only include frames in the user's code
+    pub(crate) frame_filter: bool,
+    /// Whether continuing and stepping only resume the current thread
+    pub(crate) single_thread: bool,
+    /// How long to wait for the adapter to respond to each request, or forever if `None`
+    pub(crate) request_timeout: Option<Duration>,
+    /// Observer of the raw messages exchanged with the adapter
+    pub(crate) tap: Option<Arc<dyn MessageTap>>,
+    /// Whether messages from the adapter are checked against the protocol
+    pub(crate) strict_protocol: bool,
+    pub(crate) disconnected: bool,
+    /// Whether the debugee has exited, so a closed connection is expected
+    pub(crate) ended: bool,
+    /// Incremented each time the session is restarted on a new connection, so that events
+    /// from the previous connection are ignored
+    pub(crate) generation: u64,
 }
 
 impl DebuggerInternals {
@@ -41,7 +142,107 @@ impl DebuggerInternals {
         publisher: crossbeam_channel::Sender<Event>,
         server: Option<Box<dyn Server + Send>>,
     ) -> Self {
-        Self::with_breakpoints(client, publisher, HashMap::new(), server)
+        let metrics = Metrics::default();
+        Self {
+            client: MeteredClient::new(client, metrics.clone()),
+            metrics,
+            publisher: Publisher::new(publisher),
+            current_thread_id: None,
+            selected_thread_id: None,
+            threads: Vec::new(),
+            modules: Vec::new(),
+            loaded_sources: Vec::new(),
+            breakpoints: BreakpointStore::new(),
+            watches: BTreeMap::new(),
+            current_watch_id: 0,
+            current_source: None,
+            current_frame_id: None,
+            server,
+            stdio: None,
+            adapter: None,
+            adapter_executable: None,
+            initialise_request: None,
+            recovery: None,
+            liveness: None,
+            capabilities: Capabilities::default(),
+            exception_breakpoints: Vec::new(),
+            workspace: None,
+            frame_filter: false,
+            single_thread: false,
+            request_timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            tap: None,
+            strict_protocol: false,
+            disconnected: false,
+            ended: false,
+            generation: 0,
+        }
+    }
+
+    /// Change how long to wait for the adapter to respond to each request
+    pub(crate) fn set_request_timeout(&mut self, timeout: Option<Duration>) {
+        self.request_timeout = timeout;
+        self.client.set_timeout(timeout);
+    }
+
+    /// Mirror the messages exchanged with the adapter to `tap`
+    pub(crate) fn set_tap(&mut self, tap: Option<Arc<dyn MessageTap>>) {
+        self.client.set_tap(tap.clone());
+        self.tap = tap;
+    }
+
+    /// Check the messages from the adapter against the protocol
+    pub(crate) fn set_strict_protocol(&mut self, enabled: bool) {
+        self.client.set_strict(enabled);
+        self.strict_protocol = enabled;
+    }
+
+    /// Disconnect from the debug adapter and terminate any adapter process we spawned
+    ///
+    /// Subsequent calls are no-ops.
+    pub(crate) fn disconnect(&mut self, terminate_debuggee: bool) -> eyre::Result<()> {
+        if self.disconnected {
+            return Ok(());
+        }
+        self.disconnected = true;
+
+        let res = self
+            .client
+            .execute(requests::RequestBody::Disconnect(requests::Disconnect {
+                terminate_debuggee,
+            }))
+            .wrap_err("sending disconnect request");
+        // dropping the server kills the adapter process
+        self.server.take();
+        self.stdio.take();
+        res
+    }
+
+    /// End the session, terminating or detaching from the debugee according to `mode`
+    pub(crate) fn stop(&mut self, mode: TerminateMode) -> eyre::Result<()> {
+        if self.disconnected {
+            return Ok(());
+        }
+        if mode == TerminateMode::Terminate
+            && self.capabilities.supports_terminate_request == Some(true)
+        {
+            // don't hold up quitting for long if the adapter has stopped responding
+            match self.client.send_with_timeout(
+                requests::RequestBody::Terminate(requests::Terminate { restart: None }),
+                Some(TERMINATE_TIMEOUT),
+            ) {
+                Ok(_) => {}
+                Err(e @ SendError::Adapter(_)) => {
+                    tracing::warn!(error = %e, "adapter could not terminate debugee")
+                }
+                Err(e) => tracing::warn!(error = %e, "sending terminate request"),
+            }
+        }
+        self.disconnect(mode != TerminateMode::Detach)
+    }
+
+    /// Whether the session was closed deliberately, rather than the connection being lost
+    pub(crate) fn finished(&self) -> bool {
+        self.disconnected || self.ended
     }
 
     pub(crate) fn change_scope(&mut self, stack_frame_id: StackFrameId) -> eyre::Result<()> {
@@ -49,23 +250,7 @@ impl DebuggerInternals {
             .current_thread_id
             .ok_or_else(|| eyre::eyre!("no current thread id"))?;
 
-        let responses::Response {
-            body:
-                Some(responses::ResponseBody::StackTrace(responses::StackTraceResponse {
-                    stack_frames,
-                })),
-            success: true,
-            ..
-        } = self
-            .client
-            .send(requests::RequestBody::StackTrace(requests::StackTrace {
-                thread_id: current_thread_id,
-                ..Default::default()
-            }))
-            .unwrap()
-        else {
-            unreachable!()
-        };
+        let stack_frames = self.stack_trace(current_thread_id)?;
 
         let chosen_stack_frame = stack_frames
             .iter()
@@ -75,48 +260,273 @@ impl DebuggerInternals {
         let paused_frame = self
             .compute_paused_frame(chosen_stack_frame)
             .context("computing paused frame")?;
+        self.current_frame_id = Some(stack_frame_id);
         self.emit(Event::ScopeChange {
-            stack: stack_frames,
-            breakpoints: self.breakpoints.values().cloned().collect(),
+            stack: self.visible_frames(stack_frames),
+            breakpoints: self.breakpoints.breakpoints(),
             paused_frame,
+            thread_id: current_thread_id,
         });
 
         Ok(())
     }
 
+    /// Re-fetch state that the adapter reported as invalid, emitting [`Event::ScopeChange`]
+    /// with the new stack and variables
+    ///
+    /// An empty list of `areas` means everything is invalid. Nothing is fetched unless the
+    /// debugee is paused, as there is no state to show otherwise.
+    fn refresh(
+        &mut self,
+        areas: &[InvalidatedArea],
+        thread_id: Option<ThreadId>,
+    ) -> eyre::Result<()> {
+        let Some(current_thread_id) = self.current_thread_id else {
+            return Ok(());
+        };
+        let invalid = |area| {
+            areas.is_empty() || areas.contains(&area) || areas.contains(&InvalidatedArea::All)
+        };
+
+        if invalid(InvalidatedArea::Threads) {
+            self.fetch_threads()?;
+        }
+        if thread_id.is_some_and(|t| t != current_thread_id)
+            || !(invalid(InvalidatedArea::Stacks) || invalid(InvalidatedArea::Variables))
+        {
+            return Ok(());
+        }
+
+        let stack_frames = self.stack_trace(current_thread_id)?;
+        // stay in the frame the user selected if it still exists
+        let frame = stack_frames
+            .iter()
+            .find(|f| Some(f.id) == self.current_frame_id)
+            .or_else(|| stack_frames.first())
+            .ok_or_else(|| eyre::eyre!("no stack frames for thread {current_thread_id}"))?;
+        let paused_frame = self
+            .compute_paused_frame(frame)
+            .context("computing paused frame")?;
+        self.current_frame_id = Some(frame.id);
+        self.emit(Event::ScopeChange {
+            stack: self.visible_frames(stack_frames),
+            breakpoints: self.breakpoints.breakpoints(),
+            paused_frame,
+            thread_id: current_thread_id,
+        });
+        Ok(())
+    }
+
+    /// Make `thread_id` the current thread, changing scope to its top stack frame
+    pub(crate) fn select_thread(&mut self, thread_id: ThreadId) -> eyre::Result<()> {
+        let stack_frames = self.stack_trace(thread_id)?;
+        let top_frame = stack_frames
+            .first()
+            .ok_or_else(|| eyre::eyre!("no stack frames for thread {thread_id}"))?;
+        let paused_frame = self
+            .compute_paused_frame(top_frame)
+            .context("computing paused frame")?;
+        self.current_thread_id = Some(thread_id);
+        self.selected_thread_id = Some(thread_id);
+        self.current_frame_id = Some(top_frame.id);
+        self.emit(Event::ScopeChange {
+            stack: self.visible_frames(stack_frames),
+            breakpoints: self.breakpoints.breakpoints(),
+            paused_frame,
+            thread_id,
+        });
+        Ok(())
+    }
+
+    /// Move execution of the current thread to `line` of `path`, without running the code in
+    /// between
+    pub(crate) fn jump_to_line(&mut self, path: &Path, line: usize) -> eyre::Result<()> {
+        Self::require(
+            self.capabilities.supports_goto_targets_request,
+            "gotoTargets",
+        )?;
+        let thread_id = self
+            .current_thread_id
+            .ok_or_else(|| eyre::eyre!("no current thread id"))?;
+
+        let targets = match self
+            .client
+            .send(requests::RequestBody::GotoTargets(requests::GotoTargets {
+                source: Source {
+                    name: Some(path.display().to_string()),
+                    path: Some(path.to_path_buf()),
+                    ..Default::default()
+                },
+                line,
+                column: None,
+            }))
+            .context("sending gotoTargets request")?
+        {
+            ResponseBody::GotoTargets(responses::GotoTargetsResponse { targets }) => targets,
+            other => return Err(unexpected_response("gotoTargets", other)),
+        };
+        let target = targets
+            .first()
+            .ok_or_else(|| eyre::eyre!("cannot jump to line {line} of {}", path.display()))?;
+
+        self.client
+            .send(requests::RequestBody::Goto(requests::Goto {
+                thread_id,
+                target_id: target.id,
+            }))
+            .context("sending goto request")?;
+        Ok(())
+    }
+
+    /// Frames of `stack_frames` to show to the user, hiding library code if the frame filter
+    /// is enabled
+    ///
+    /// The top frame is always kept, as that is where the debugee is paused.
+    fn visible_frames(&self, stack_frames: Vec<StackFrame>) -> Vec<StackFrame> {
+        if !self.frame_filter {
+            return stack_frames;
+        }
+        stack_frames
+            .into_iter()
+            .enumerate()
+            .filter(|(i, frame)| *i == 0 || is_user_frame(frame, self.workspace.as_deref()))
+            .map(|(_, frame)| frame)
+            .collect()
+    }
+
+    /// Show only frames in the user's code, re-sending the current stack if paused
+    pub(crate) fn set_frame_filter(&mut self, enabled: bool) -> eyre::Result<()> {
+        self.frame_filter = enabled;
+        self.refresh(&[InvalidatedArea::Stacks], None)
+    }
+
+    /// Fetch the full stack trace of a thread
+    pub(crate) fn stack_trace(&self, thread_id: ThreadId) -> eyre::Result<Vec<StackFrame>> {
+        match self
+            .client
+            .send(requests::RequestBody::StackTrace(requests::StackTrace {
+                thread_id,
+                ..Default::default()
+            }))
+            .context("sending stackTrace request")?
+        {
+            responses::ResponseBody::StackTrace(responses::StackTraceResponse { stack_frames }) => {
+                Ok(stack_frames)
+            }
+            other => Err(unexpected_response("stackTrace", other)),
+        }
+    }
+
+    /// Fetch the threads of the debugee
+    pub(crate) fn fetch_threads(&mut self) -> eyre::Result<Vec<Thread>> {
+        match self
+            .client
+            .send(requests::RequestBody::Threads)
+            .context("sending threads request")?
+        {
+            responses::ResponseBody::Threads(responses::ThreadsResponse { threads }) => {
+                self.threads = threads.clone();
+                Ok(threads)
+            }
+            other => Err(unexpected_response("threads", other)),
+        }
+    }
+
+    /// Replace the known modules with those reported by the adapter, if it supports the
+    /// `modules` request
+    pub(crate) fn fetch_modules(&mut self) -> eyre::Result<()> {
+        if self.capabilities.supports_modules_request != Some(true) {
+            self.modules.clear();
+            return Ok(());
+        }
+        match self
+            .client
+            .send(requests::RequestBody::Modules(requests::Modules::default()))
+            .context("sending modules request")?
+        {
+            ResponseBody::Modules(responses::ModulesResponse { modules, .. }) => {
+                self.modules = modules;
+                Ok(())
+            }
+            other => Err(unexpected_response("modules", other)),
+        }
+    }
+
+    /// Replace the known sources with those reported by the adapter, if it supports the
+    /// `loadedSources` request
+    pub(crate) fn fetch_loaded_sources(&mut self) -> eyre::Result<()> {
+        if self.capabilities.supports_loaded_sources_request != Some(true) {
+            self.loaded_sources.clear();
+            return Ok(());
+        }
+        match self
+            .client
+            .send(requests::RequestBody::LoadedSources)
+            .context("sending loadedSources request")?
+        {
+            ResponseBody::LoadedSources(responses::LoadedSourcesResponse { sources }) => {
+                self.loaded_sources = sources;
+                Ok(())
+            }
+            other => Err(unexpected_response("loadedSources", other)),
+        }
+    }
+
+    /// Fetch the stack of `thread_id` after it stopped and move to the paused state
+    fn pause(
+        &mut self,
+        thread_id: ThreadId,
+        all_threads_stopped: bool,
+        reason: StopReason,
+        exception: Option<Box<ExceptionInfo>>,
+    ) -> eyre::Result<()> {
+        let stack_frames = self.stack_trace(thread_id)?;
+        let top_frame = stack_frames
+            .first()
+            .ok_or_else(|| eyre::eyre!("thread {thread_id} stopped without any stack frames"))?;
+        // determine where we are in the source code
+        self.current_source = Some(FileSource {
+            line: top_frame.line,
+            file_path: top_frame
+                .source
+                .as_ref()
+                .and_then(|source| source.path.clone()),
+        });
+        self.current_frame_id = Some(top_frame.id);
+        let paused_frame = self
+            .compute_paused_frame(top_frame)
+            .wrap_err("building paused frame")?;
+
+        self.set_state(DebuggerState::Paused {
+            stack: self.visible_frames(stack_frames),
+            paused_frame: Box::new(paused_frame),
+            breakpoints: self.breakpoints.breakpoints(),
+            thread_id,
+            all_threads_stopped,
+            reason,
+            exception,
+        });
+        Ok(())
+    }
+
     fn compute_paused_frame(&self, stack_frame: &StackFrame) -> eyre::Result<PausedFrame> {
-        let responses::Response {
-            body: Some(responses::ResponseBody::Scopes(responses::ScopesResponse { scopes })),
-            success: true,
-            ..
-        } = self
+        let scopes = match self
             .client
             .send(requests::RequestBody::Scopes(requests::Scopes {
                 frame_id: stack_frame.id,
             }))
-            .expect("requesting scopes")
-        else {
-            unreachable!()
+            .wrap_err("sending scopes request")?
+        {
+            responses::ResponseBody::Scopes(responses::ScopesResponse { scopes }) => scopes,
+            other => return Err(unexpected_response("scopes", other)),
         };
 
         let mut variables = Vec::new();
         for scope in scopes {
-            let req = requests::RequestBody::Variables(requests::Variables {
-                variables_reference: scope.variables_reference,
-            });
-            match self.client.send(req).expect("fetching variables") {
-                responses::Response {
-                    body:
-                        Some(responses::ResponseBody::Variables(responses::VariablesResponse {
-                            variables: scope_variables,
-                        })),
-                    success: true,
-                    ..
-                } => variables.extend(scope_variables.into_iter()),
-                r => {
-                    tracing::warn!(?r, "unhandled response from send variables request")
-                }
-            };
+            let scope_variables = self
+                .variables(scope.variables_reference, None, None)
+                .context("fetching variables")?;
+            variables.extend(scope_variables);
         }
         let paused_frame = PausedFrame {
             frame: stack_frame.clone(),
@@ -126,12 +536,56 @@ impl DebuggerInternals {
         Ok(paused_frame)
     }
 
+    /// Fetch the children of a variable, optionally only `count` of them from index `start`
+    pub(crate) fn variables(
+        &self,
+        variables_reference: VariablesReference,
+        start: Option<usize>,
+        count: Option<usize>,
+    ) -> eyre::Result<Vec<Variable>> {
+        let req = requests::RequestBody::Variables(requests::Variables {
+            variables_reference,
+            start,
+            count,
+        });
+        match self.client.send(req).context("sending variables request")? {
+            responses::ResponseBody::Variables(responses::VariablesResponse { variables }) => {
+                Ok(variables)
+            }
+            r => {
+                tracing::warn!(?r, "unhandled response from send variables request");
+                Ok(Vec::new())
+            }
+        }
+    }
+
     pub(crate) fn emit(&mut self, event: Event) {
-        let _ = self.publisher.send(event);
+        self.publisher.emit(event);
     }
 
     #[tracing::instrument(skip(self))]
     pub(crate) fn initialise(&mut self, arguments: InitialiseArguments) -> eyre::Result<()> {
+        let req = match arguments {
+            InitialiseArguments::Launch(launch_arguments) => launch_arguments.to_request(),
+            InitialiseArguments::Attach(attach_arguments) => attach_arguments.to_request(),
+            InitialiseArguments::Configuration(requests::StartDebugging {
+                configuration,
+                request: requests::StartDebuggingRequest::Launch,
+            }) => requests::RequestBody::LaunchConfiguration(configuration),
+            InitialiseArguments::Configuration(requests::StartDebugging {
+                configuration,
+                request: requests::StartDebuggingRequest::Attach,
+            }) => requests::RequestBody::AttachConfiguration(configuration),
+        };
+        self.initialise_with_request(req)
+    }
+
+    /// Send the initialize request followed by the given `launch` or `attach` request
+    #[tracing::instrument(skip(self))]
+    pub(crate) fn initialise_with_request(
+        &mut self,
+        request: requests::RequestBody,
+    ) -> eyre::Result<()> {
         tracing::debug!("initialising debugger internals");
         let req = requests::RequestBody::Initialize(Initialize {
             adapter_id: "dap gui".to_string(),
@@ -142,142 +596,141 @@ impl DebuggerInternals {
             supports_variable_paging: true,
             supports_progress_reporting: true,
             supports_memory_event: true,
+            supports_invalidated_event: true,
         });
 
-        // TODO: deal with capabilities from the response
         tracing::debug!(request = ?req, "sending initialize event");
-        let _ = self.client.send(req).context("sending initialize event")?;
-
-        match arguments {
-            InitialiseArguments::Launch(launch_arguments) => {
-                // send launch event
-                let req = launch_arguments.to_request();
-                self.client.execute(req).context("sending launch request")?;
-            }
-            InitialiseArguments::Attach(attach_arguments) => {
-                let req = attach_arguments.to_request();
-                self.client.execute(req).context("sending attach request")?;
-            }
+        let res = self.client.send(req).context("sending initialize event")?;
+        if let ResponseBody::Initialize(capabilities) = res {
+            self.capabilities = capabilities;
         }
 
+        self.workspace = match &request {
+            requests::RequestBody::Launch(requests::Launch {
+                launch_arguments: Some(requests::LaunchArguments::Debugpy(arguments)),
+                ..
+            }) => Some(arguments.cwd.clone()),
+            requests::RequestBody::Attach(requests::Attach {
+                workspace_folder, ..
+            }) => Some(workspace_folder.clone()),
+            _ => None,
+        };
+        self.initialise_request = Some(request.clone());
+        self.client
+            .execute(request)
+            .context("sending launch or attach request")?;
+
         tracing::debug!("initialised");
 
         Ok(())
     }
 
-    pub(crate) fn with_breakpoints(
-        client: Client,
-        publisher: crossbeam_channel::Sender<Event>,
-        existing_breakpoints: impl Into<HashMap<BreakpointId, Breakpoint>>,
-        server: Option<Box<dyn Server + Send>>,
-    ) -> Self {
-        let breakpoints = existing_breakpoints.into();
-        let current_breakpoint_id = *breakpoints.keys().max().unwrap_or(&0);
-
-        Self {
-            client,
-            publisher,
-            current_thread_id: None,
-            breakpoints,
-            current_breakpoint_id,
-            current_source: None,
-            _server: server,
-        }
-    }
-
-    fn get_stack_frames(&self) -> eyre::Result<Vec<StackFrame>> {
-        todo!()
-    }
-
     #[tracing::instrument(skip(self), level = "trace")]
     pub(crate) fn on_event(&mut self, event: transport::events::Event) {
         tracing::debug!("handling event");
+        self.metrics.record_event(&event);
 
         match event {
             transport::events::Event::Initialized => {
+                if let Err(e) = self.fetch_modules() {
+                    tracing::warn!(error = %e, "fetching modules");
+                }
+                if let Err(e) = self.fetch_loaded_sources() {
+                    tracing::warn!(error = %e, "fetching loaded sources");
+                }
                 // broadcast our internal state change
                 self.set_state(DebuggerState::Initialised);
             }
-            // transport::events::Event::Output(_) => todo!(),
+            transport::events::Event::Output(transport::events::OutputEventBody {
+                category,
+                output,
+                source,
+                line,
+                ..
+            }) => {
+                let source = source.and_then(|s| s.path);
+                let line = line.map(|line| line as usize);
+                let logpoint = source
+                    .as_deref()
+                    .zip(line)
+                    .and_then(|(path, line)| self.logpoint_at(path, line));
+                self.emit(Event::Output {
+                    category: category.into(),
+                    output,
+                    source,
+                    line,
+                    logpoint,
+                });
+            }
             // transport::events::Event::Process(_) => todo!(),
             transport::events::Event::Stopped(transport::events::StoppedEventBody {
+                reason,
                 thread_id,
+                all_threads_stopped,
+                description,
+                text,
                 ..
             }) => {
                 self.current_thread_id = Some(thread_id);
-                // determine where we are in the source code
-                let responses::Response {
-                    body:
-                        Some(responses::ResponseBody::StackTrace(responses::StackTraceResponse {
-                            stack_frames,
-                        })),
-                    success: true,
-                    ..
-                } = self
-                    .client
-                    .send(requests::RequestBody::StackTrace(requests::StackTrace {
-                        thread_id,
-                        levels: Some(1),
-                        ..Default::default()
-                    }))
-                    .unwrap()
-                else {
-                    unreachable!()
-                };
-
-                if stack_frames.len() != 1 {
-                    panic!("unexpected number of stack frames: {}", stack_frames.len());
+                self.selected_thread_id = Some(thread_id);
+                let reason = StopReason::from(reason);
+                let exception = (reason == StopReason::Exception)
+                    .then(|| Box::new(self.exception_info(thread_id, description, text)));
+                if let Err(e) = self.fetch_threads() {
+                    tracing::warn!(error = %e, "fetching threads");
                 }
-
-                let source = stack_frames[0].source.as_ref().unwrap();
-                let line = stack_frames[0].line;
-
-                let current_source = FileSource {
-                    line,
-                    file_path: source.path.clone(),
-                };
-                self.current_source = Some(current_source.clone());
-
-                let responses::Response {
-                    body:
-                        Some(responses::ResponseBody::StackTrace(responses::StackTraceResponse {
-                            stack_frames,
-                        })),
-                    success: true,
-                    ..
-                } = self
-                    .client
-                    .send(requests::RequestBody::StackTrace(requests::StackTrace {
+                let all_threads_stopped = all_threads_stopped.unwrap_or(false);
+                if let Err(e) = self.pause(thread_id, all_threads_stopped, reason, exception) {
+                    tracing::warn!(error = ?e, "fetching paused stack");
+                    self.emit(Event::PauseFailed {
                         thread_id,
-                        ..Default::default()
-                    }))
-                    .unwrap()
-                else {
-                    unreachable!()
-                };
-
-                let top_frame = stack_frames.first().expect("no frames found");
-                let paused_frame = self
-                    .compute_paused_frame(top_frame)
-                    .expect("building paused frame construct");
-
-                self.set_state(DebuggerState::Paused {
-                    stack: stack_frames,
-                    paused_frame: Box::new(paused_frame),
-                    breakpoints: self.breakpoints.values().cloned().collect(),
-                });
-            }
-            transport::events::Event::Continued(_) => {
-                self.current_thread_id = None;
-                self.current_source = None;
-                self.set_state(DebuggerState::Running);
+                        error: format!("{e:#}"),
+                    });
+                }
             }
+            transport::events::Event::Continued(_) => self.running(),
             // transport::events::Event::Thread(_) => todo!(),
             transport::events::Event::Exited(_) | transport::events::Event::Terminated => {
+                self.ended = true;
                 self.set_state(DebuggerState::Ended);
             }
+            transport::events::Event::Invalidated(transport::events::InvalidatedEventBody {
+                areas,
+                thread_id,
+                ..
+            }) => {
+                if let Err(e) = self.refresh(&areas.unwrap_or_default(), thread_id) {
+                    tracing::warn!(error = %e, "refreshing invalidated state");
+                }
+            }
             // transport::events::Event::DebugpyWaitingForServer { host, port } => todo!(),
-            // transport::events::Event::Module(_) => todo!(),
+            transport::events::Event::Module(transport::events::ModuleEventBody {
+                reason,
+                module,
+            }) => {
+                self.modules.retain(|m| m.id != module.id);
+                if reason != ChangeReason::Removed {
+                    if let Some(path) = &module.path {
+                        self.retry_pending_breakpoints(path);
+                    }
+                    self.modules.push(module);
+                }
+            }
+            transport::events::Event::LoadedSource(transport::events::LoadedSourceEventBody {
+                reason,
+                source,
+            }) => {
+                self.loaded_sources.retain(|s| !same_source(s, &source));
+                if reason != ChangeReason::Removed {
+                    if let Some(path) = &source.path {
+                        self.retry_pending_breakpoints(path);
+                    }
+                    self.loaded_sources.push(source);
+                }
+            }
+            transport::events::Event::ProtocolViolation(violation) => {
+                self.emit(Event::ProtocolViolation { violation });
+            }
             _ => {
                 tracing::debug!(?event, "unknown event");
             }
@@ -287,65 +740,244 @@ impl DebuggerInternals {
     #[tracing::instrument(skip(self), level = "trace")]
     pub(crate) fn add_breakpoint(&mut self, breakpoint: &Breakpoint) -> eyre::Result<BreakpointId> {
         tracing::debug!("adding breakpoint");
-        let id = self.next_id();
-        self.breakpoints.insert(id, breakpoint.clone());
-        self.broadcast_breakpoints()
-            .context("updating breakpoints with debugee")?;
+        let id = self.breakpoints.add(breakpoint.clone());
+        self.breakpoints_changed()?;
         Ok(id)
     }
 
     #[tracing::instrument(skip(self), level = "debug")]
-    pub(crate) fn remove_breakpoint(&mut self, id: BreakpointId) {
+    pub(crate) fn remove_breakpoint(&mut self, id: BreakpointId) -> eyre::Result<()> {
         tracing::debug!("removing breakpoint");
-        self.breakpoints.remove(&id);
-        self.broadcast_breakpoints()
-            .expect("updating breakpoints with debugee");
+        if self.breakpoints.remove(id).is_none() {
+            eyre::bail!("no breakpoint with id {id}");
+        }
+        self.breakpoints_changed()
     }
 
-    fn broadcast_breakpoints(&mut self) -> eyre::Result<()> {
-        // TODO: don't assume the breakpoints are for the same file
-        if self.breakpoints.is_empty() {
-            return Ok(());
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub(crate) fn update_breakpoint(
+        &mut self,
+        id: BreakpointId,
+        breakpoint: &Breakpoint,
+    ) -> eyre::Result<()> {
+        tracing::debug!("updating breakpoint");
+        if self.breakpoints.update(id, breakpoint.clone()).is_none() {
+            eyre::bail!("no breakpoint with id {id}");
         }
+        self.breakpoints_changed()
+    }
 
-        // group breakpoints by source file and send in multiple batches
-        let breakpoints_by_source = self.breakpoints_by_source();
+    /// Send any breakpoint changes to the adapter, then notify subscribers and persist them
+    pub(crate) fn breakpoints_changed(&mut self) -> eyre::Result<()> {
+        let res = self
+            .sync_breakpoints()
+            .context("updating breakpoints with debugee");
+        // the store is the source of truth even if the adapter rejected the change
+        if let Err(e) = self.breakpoints.persist() {
+            tracing::warn!(error = ?e, "persisting breakpoints");
+        }
+        self.emit(Event::BreakpointsChanged {
+            breakpoints: self.breakpoints.breakpoints_with_ids(),
+        });
+        res
+    }
 
-        for (source, breakpoints) in &breakpoints_by_source {
-            let req = requests::RequestBody::SetBreakpoints(requests::SetBreakpoints {
-                source: Source {
-                    name: Some(source.display().to_string()),
-                    path: Some(source.clone()),
-                    ..Default::default()
-                },
-                lines: Some(breakpoints.iter().map(|b| b.line).collect()),
-                breakpoints: Some(
-                    breakpoints
-                        .iter()
-                        .map(|b| SourceBreakpoint {
-                            line: b.line,
-                            ..Default::default()
-                        })
-                        .collect(),
-                ),
-                ..Default::default()
-            });
+    /// Only resume the current thread when continuing or stepping, if the adapter supports it
+    pub(crate) fn set_single_thread(&mut self, enabled: bool) -> Result<(), Unsupported> {
+        if enabled {
+            Self::require(
+                self.capabilities.supports_single_thread_execution_requests,
+                "singleThread",
+            )?;
+        }
+        self.single_thread = enabled;
+        Ok(())
+    }
+
+    /// Id of the first thread called `name`, as of when the debugee last stopped
+    pub(crate) fn thread_named(&self, name: &str) -> eyre::Result<ThreadId> {
+        self.threads
+            .iter()
+            .find(|thread| thread.name == name)
+            .map(|thread| thread.id)
+            .ok_or_else(|| eyre::eyre!("no thread named {name:?}"))
+    }
+
+    /// Fail with [`Unsupported`] unless the adapter advertised the capability for `command`
+    fn require(supported: Option<bool>, command: &str) -> Result<(), Unsupported> {
+        if supported == Some(true) {
+            Ok(())
+        } else {
+            Err(Unsupported {
+                command: command.to_string(),
+            })
+        }
+    }
+
+    /// Exception breakpoint filters offered by the adapter
+    pub(crate) fn exception_filters(&self) -> &[ExceptionBreakpointsFilter] {
+        self.capabilities
+            .exception_breakpoint_filters
+            .as_deref()
+            .unwrap_or_default()
+    }
+
+    /// Forget where the debugee stopped, as it is running again
+    pub(crate) fn running(&mut self) {
+        self.current_thread_id = None;
+        self.threads.clear();
+        self.current_source = None;
+        self.current_frame_id = None;
+        self.set_state(DebuggerState::Running);
+    }
+
+    /// Ask the adapter to restart the debugee with a `restart` request, optionally with new
+    /// `launch` or `attach` arguments
+    pub(crate) fn restart(&mut self, arguments: Option<serde_json::Value>) -> eyre::Result<()> {
+        self.client
+            .send(requests::RequestBody::Restart(requests::Restart {
+                arguments,
+            }))
+            .context("sending restart request")?;
+        // the adapter does not always send a `continued` event when restarting
+        self.running();
+        Ok(())
+    }
+
+    /// Arguments of the `launch` or `attach` request that started the session, sent again
+    /// when reloading so that the adapter picks up any changes to the program
+    pub(crate) fn reload_arguments(&self) -> eyre::Result<Option<serde_json::Value>> {
+        Self::require(self.capabilities.supports_restart_request, "restart")?;
+        let arguments = match &self.initialise_request {
+            Some(requests::RequestBody::Launch(launch)) => {
+                Some(serde_json::to_value(launch).context("serializing launch arguments")?)
+            }
+            Some(requests::RequestBody::Attach(attach)) => {
+                Some(serde_json::to_value(attach).context("serializing attach arguments")?)
+            }
+            Some(
+                requests::RequestBody::LaunchConfiguration(configuration)
+                | requests::RequestBody::AttachConfiguration(configuration),
+            ) => Some(configuration.clone()),
+            _ => None,
+        };
+        Ok(arguments)
+    }
+
+    /// Restart execution of a stack frame, if the adapter supports it
+    pub(crate) fn restart_frame(&mut self, frame_id: StackFrameId) -> eyre::Result<()> {
+        Self::require(self.capabilities.supports_restart_frame, "restartFrame")?;
+        self.client
+            .send(requests::RequestBody::RestartFrame(
+                requests::RestartFrame { frame_id },
+            ))
+            .context("sending restartFrame request")?;
+        Ok(())
+    }
+
+    /// Enable breaking on the given kinds of exception, replacing any previously enabled
+    pub(crate) fn set_exception_breakpoints(&mut self, filters: Vec<String>) -> eyre::Result<()> {
+        if let Some(unknown) = filters
+            .iter()
+            .find(|f| !self.exception_filters().iter().any(|e| &e.filter == *f))
+        {
+            eyre::bail!("adapter does not support exception filter {unknown:?}");
+        }
+        self.send_exception_breakpoints(filters.clone())?;
+        self.exception_breakpoints = filters;
+        Ok(())
+    }
+
+    /// Send the enabled exception filters to the adapter, e.g. after reconnecting
+    pub(crate) fn send_exception_breakpoints(&mut self, filters: Vec<String>) -> eyre::Result<()> {
+        self.client
+            .send(requests::RequestBody::SetExceptionBreakpoints(
+                requests::SetExceptionBreakpoints { filters },
+            ))
+            .context("sending exception breakpoints")?;
+        Ok(())
+    }
+
+    /// Logpoint at this source location, used to attribute output events to the logpoint
+    /// that produced them
+    fn logpoint_at(&self, path: &Path, line: usize) -> Option<BreakpointId> {
+        let path = crate::utils::normalise_path(path);
+        self.breakpoints.iter().find_map(|(id, b)| {
+            (b.log_message.is_some() && b.line == line && b.normalised_path() == path).then_some(id)
+        })
+    }
 
-            let _ = self
-                .client
-                .send(req)
-                .context("broadcasting breakpoints to debugee")?;
+    /// Send the breakpoints for `path` again if the adapter could not verify them before, as
+    /// some adapters reject breakpoints in files they have not loaded yet
+    fn retry_pending_breakpoints(&mut self, path: &Path) {
+        let path = crate::utils::normalise_path(path);
+        let pending: Vec<_> = self
+            .breakpoints
+            .pending()
+            .into_iter()
+            .filter(|p| crate::utils::normalise_path(p) == path)
+            .collect();
+        if pending.is_empty() {
+            return;
+        }
+        for p in &pending {
+            self.breakpoints.resync(p);
+        }
+        tracing::debug!(path = %path.display(), "resending unverified breakpoints");
+        if let Err(e) = self.breakpoints_changed() {
+            tracing::warn!(error = %e, "resending breakpoints");
+        }
+    }
+
+    /// Send a `setBreakpoints` request for each file whose breakpoints have changed
+    fn sync_breakpoints(&mut self) -> eyre::Result<()> {
+        for (source, breakpoints) in self.breakpoints.changes() {
+            let verified = self.send_breakpoints_for_source(&source, &breakpoints)?;
+            self.breakpoints.mark_synced(source, breakpoints, verified);
         }
         Ok(())
     }
 
-    fn breakpoints_by_source(&self) -> HashMap<PathBuf, Vec<Breakpoint>> {
-        let mut out = HashMap::new();
-        for breakpoint in self.breakpoints.values() {
-            let file_breakpoints = out.entry(breakpoint.path.clone()).or_insert(Vec::new());
-            file_breakpoints.push(breakpoint.clone());
+    /// Replace the breakpoints for one source file, returning whether each was verified
+    fn send_breakpoints_for_source(
+        &mut self,
+        source: &Path,
+        breakpoints: &[(BreakpointId, Breakpoint)],
+    ) -> eyre::Result<Vec<bool>> {
+        let req = requests::RequestBody::SetBreakpoints(requests::SetBreakpoints {
+            source: Source {
+                name: Some(source.display().to_string()),
+                path: Some(source.to_path_buf()),
+                ..Default::default()
+            },
+            lines: Some(breakpoints.iter().map(|(_, b)| b.line).collect()),
+            breakpoints: Some(
+                breakpoints
+                    .iter()
+                    .map(|(_, b)| SourceBreakpoint {
+                        line: b.line,
+                        condition: b.condition.clone(),
+                        hit_condition: b.hit_condition.clone(),
+                        log_message: b.log_message.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        });
+
+        let res = self
+            .client
+            .send(req)
+            .context("broadcasting breakpoints to debugee")?;
+
+        // the adapter reports breakpoints in the same order they were requested
+        match res {
+            ResponseBody::SetBreakpoints(responses::SetBreakpoints {
+                breakpoints: set_breakpoints,
+            }) => Ok(set_breakpoints.iter().map(|b| b.verified).collect()),
+            _ => Ok(Vec::new()),
         }
-        out
     }
 
     pub(crate) fn get_breakpoint_locations(
@@ -365,16 +997,258 @@ impl DebuggerInternals {
             .send(req)
             .context("sending BreakpointLocations request")?;
 
-        let Some(ResponseBody::BreakpointLocations(locations)) = res.body else {
-            eyre::bail!("invalid response type: {:?}", res);
+        let ResponseBody::BreakpointLocations(locations) = res else {
+            return Err(unexpected_response("breakpointLocations", res));
         };
 
         Ok(locations.breakpoints)
     }
 
-    fn next_id(&mut self) -> BreakpointId {
-        self.current_breakpoint_id += 1;
-        self.current_breakpoint_id
+    /// Details of the exception `thread_id` stopped on
+    ///
+    /// Falls back to the `description` and `text` of the stopped event if the adapter does
+    /// not support the `exceptionInfo` request, or it fails.
+    fn exception_info(
+        &self,
+        thread_id: ThreadId,
+        description: Option<String>,
+        text: Option<String>,
+    ) -> ExceptionInfo {
+        let fallback = ExceptionInfo {
+            exception_id: text.unwrap_or_else(|| "exception".to_string()),
+            description,
+            details: None,
+        };
+        if self.capabilities.supports_exception_info_request != Some(true) {
+            return fallback;
+        }
+        match self.client.send(requests::RequestBody::ExceptionInfo(
+            requests::ExceptionInfo { thread_id },
+        )) {
+            Ok(ResponseBody::ExceptionInfo(responses::ExceptionInfoResponse {
+                exception_id,
+                description,
+                details,
+                ..
+            })) => ExceptionInfo {
+                exception_id,
+                description,
+                details,
+            },
+            Ok(res) => {
+                tracing::warn!(response = ?res, "unexpected exceptionInfo response");
+                fallback
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "sending exceptionInfo request");
+                fallback
+            }
+        }
+    }
+
+    /// Read `count` bytes starting `offset` bytes from `memory_reference`
+    pub(crate) fn read_memory(
+        &self,
+        memory_reference: &str,
+        offset: i64,
+        count: usize,
+    ) -> eyre::Result<Memory> {
+        Self::require(self.capabilities.supports_read_memory_request, "readMemory")?;
+        let res = self
+            .client
+            .send(requests::RequestBody::ReadMemory(requests::ReadMemory {
+                memory_reference: memory_reference.to_string(),
+                offset: Some(offset),
+                count,
+            }))
+            .context("sending readMemory request")?;
+        match res {
+            ResponseBody::ReadMemory(responses::ReadMemoryResponse {
+                address,
+                unreadable_bytes,
+                data,
+            }) => {
+                let data = match data {
+                    Some(data) => BASE64_STANDARD
+                        .decode(data)
+                        .context("decoding memory contents")?,
+                    None => Vec::new(),
+                };
+                Ok(Memory {
+                    address,
+                    data,
+                    unreadable_bytes: unreadable_bytes.unwrap_or_default(),
+                })
+            }
+            other => Err(unexpected_response("readMemory", other)),
+        }
+    }
+
+    /// Write `data` starting `offset` bytes from `memory_reference`, returning the number of
+    /// bytes written
+    pub(crate) fn write_memory(
+        &self,
+        memory_reference: &str,
+        offset: i64,
+        data: &[u8],
+    ) -> eyre::Result<usize> {
+        Self::require(
+            self.capabilities.supports_write_memory_request,
+            "writeMemory",
+        )?;
+        let res = self
+            .client
+            .send(requests::RequestBody::WriteMemory(requests::WriteMemory {
+                memory_reference: memory_reference.to_string(),
+                offset: Some(offset),
+                allow_partial: Some(false),
+                data: BASE64_STANDARD.encode(data),
+            }))
+            .context("sending writeMemory request")?;
+        // adapters may leave out the body if everything was written
+        let written = match res {
+            ResponseBody::WriteMemory(Some(responses::WriteMemoryResponse {
+                bytes_written: Some(written),
+                ..
+            })) => written,
+            _ => data.len(),
+        };
+        Ok(written)
+    }
+
+    /// Ask the adapter how `text` could be completed, with the cursor at character offset
+    /// `column` (1-indexed)
+    pub(crate) fn completions(
+        &self,
+        text: &str,
+        column: usize,
+        frame_id: Option<StackFrameId>,
+    ) -> eyre::Result<Vec<CompletionItem>> {
+        Self::require(
+            self.capabilities.supports_completions_request,
+            "completions",
+        )?;
+        let res = self
+            .client
+            .send(requests::RequestBody::Completions(requests::Completions {
+                frame_id,
+                text: text.to_string(),
+                column,
+                line: None,
+            }))
+            .context("sending completions request")?;
+        match res {
+            ResponseBody::Completions(responses::CompletionsResponse { targets }) => Ok(targets),
+            other => Err(unexpected_response("completions", other)),
+        }
+    }
+
+    /// Assign `value` to `expression`, e.g. `obj.attr`, if the adapter supports it
+    pub(crate) fn set_expression(
+        &self,
+        expression: &str,
+        value: &str,
+        frame_id: Option<StackFrameId>,
+    ) -> eyre::Result<EvaluateResult> {
+        Self::require(self.capabilities.supports_set_expression, "setExpression")?;
+        let res = self
+            .client
+            .send(requests::RequestBody::SetExpression(
+                requests::SetExpression {
+                    expression: expression.to_string(),
+                    value: value.to_string(),
+                    frame_id,
+                },
+            ))
+            .context("sending setExpression request")?;
+        match res {
+            ResponseBody::SetExpression(responses::SetExpressionResponse {
+                value,
+                r#type,
+                variables_reference,
+                ..
+            }) => Ok(EvaluateResult {
+                output: value,
+                error: false,
+                r#type,
+                variables_reference: variables_reference.unwrap_or_default(),
+            }),
+            other => Err(unexpected_response("setExpression", other)),
+        }
+    }
+
+    pub(crate) fn evaluate(
+        &self,
+        input: &str,
+        frame_id: StackFrameId,
+        context: EvaluateContext,
+    ) -> eyre::Result<Option<EvaluateResult>> {
+        // the clipboard context is a later addition to the protocol that not all adapters know
+        let context = match context {
+            EvaluateContext::Clipboard
+                if self.capabilities.supports_clipboard_context != Some(true) =>
+            {
+                EvaluateContext::Repl
+            }
+            context => context,
+        };
+        let req = requests::RequestBody::Evaluate(requests::Evaluate {
+            expression: input.to_string(),
+            frame_id: Some(frame_id),
+            context: Some(context.as_str().to_string()),
+        });
+        let res = match self.client.send(req) {
+            Ok(res) => res,
+            // show why the expression could not be evaluated in place of its value
+            Err(e) => {
+                let reason = match &e {
+                    SendError::Adapter(error) => error.reason(),
+                    _ => None,
+                };
+                match reason {
+                    Some(reason) => {
+                        return Ok(Some(EvaluateResult {
+                            output: reason,
+                            error: true,
+                            r#type: None,
+                            variables_reference: 0,
+                        }))
+                    }
+                    None => return Err(e).context("sending evaluate request"),
+                }
+            }
+        };
+        match res {
+            responses::ResponseBody::Evaluate(responses::EvaluateResponse {
+                result,
+                r#type,
+                variables_reference,
+                ..
+            }) => Ok(Some(EvaluateResult {
+                output: result,
+                error: false,
+                r#type,
+                variables_reference,
+            })),
+            other => {
+                tracing::warn!(response = ?other, "unhandled response");
+                Ok(None)
+            }
+        }
+    }
+
+    pub(crate) fn add_watch(&mut self, expression: String) -> WatchId {
+        self.current_watch_id += 1;
+        let id = self.current_watch_id;
+        self.watches.insert(id, Watch { id, expression });
+        id
+    }
+
+    pub(crate) fn remove_watch(&mut self, id: WatchId) -> eyre::Result<()> {
+        if self.watches.remove(&id).is_none() {
+            eyre::bail!("no watch expression with id {id}");
+        }
+        Ok(())
     }
 
     #[tracing::instrument(skip(self), level = "trace")]
@@ -384,3 +1258,17 @@ impl DebuggerInternals {
         self.emit(event);
     }
 }
+
+/// Error for a successful response without the body expected for `command`
+fn unexpected_response(command: &str, body: ResponseBody) -> eyre::Report {
+    eyre::eyre!("unexpected {command} response: {body:?}")
+}
+
+/// Whether two sources refer to the same file or adapter provided content
+fn same_source(a: &Source, b: &Source) -> bool {
+    match (&a.path, &b.path, a.source_reference, b.source_reference) {
+        (Some(a), Some(b), _, _) => a == b,
+        (_, _, Some(a), Some(b)) if a > 0 => a == b,
+        _ => a.name.is_some() && a.name == b.name,
+    }
+}
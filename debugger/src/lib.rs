@@ -1,12 +1,31 @@
 //! High level Debugger implementation
+mod breakpoints;
 mod debugger;
+mod error;
 mod internals;
+mod liveness;
+mod metrics;
 mod persistence;
+mod recovery;
+pub mod remote;
+mod sessions;
 pub(crate) mod state;
 mod types;
 pub mod utils;
 
+pub use breakpoints::BreakpointStore;
 pub use debugger::{Debugger, InitialiseArguments};
+pub use error::{Error, Result};
 pub use internals::FileSource;
+pub use liveness::{LivenessOptions, Unresponsive};
+pub use metrics::{CommandMetrics, LatencyHistogram, Metrics, MetricsSnapshot, LATENCY_BUCKETS};
+pub use persistence::PersistBreakpoints;
+pub use recovery::RecoveryOptions;
+pub use server::OutputStream;
+pub use sessions::{SessionEvent, SessionId, SessionRegistry};
 pub use state::{AttachArguments, Event, Language, LaunchArguments};
-pub use types::{Breakpoint, EvaluateResult, PausedFrame};
+pub use transport::{validation::Violation, ReconnectPolicy};
+pub use types::{
+    Breakpoint, BreakpointId, EvaluateContext, EvaluateResult, ExceptionInfo, Memory,
+    OutputCategory, PausedFrame, StopReason, TerminateMode, Watch, WatchId, WatchResult,
+};
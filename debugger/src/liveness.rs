@@ -0,0 +1,141 @@
+//! Noticing when the debug adapter stops responding without closing the connection
+use std::{
+    fmt,
+    sync::{Mutex, TryLockError, Weak},
+    thread,
+    time::Duration,
+};
+
+use transport::{SendError, WeakClient};
+
+use crate::{
+    internals::{DebuggerInternals, Publisher},
+    Event,
+};
+
+/// How often a session checks that the debug adapter is still responding
+#[derive(Debug, Clone)]
+pub struct LivenessOptions {
+    /// Time between checks
+    pub interval: Duration,
+    /// How long the adapter has to respond to each check
+    pub timeout: Duration,
+}
+
+impl Default for LivenessOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Way in which the adapter failed a liveness check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unresponsive {
+    /// No response to a `threads` request within `timeout`
+    NoResponse { timeout: Duration },
+    /// The request could not be sent, e.g. because the adapter closed its end of the
+    /// connection
+    SendFailed { error: String },
+}
+
+impl fmt::Display for Unresponsive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unresponsive::NoResponse { timeout } => {
+                write!(f, "adapter did not respond within {timeout:?}")
+            }
+            Unresponsive::SendFailed { error } => {
+                write!(f, "could not send to the adapter: {error}")
+            }
+        }
+    }
+}
+
+/// Check the adapter with a `threads` request every interval, emitting
+/// [`Event::AdapterUnresponsive`] when it fails a check after passing the previous one
+///
+/// Runs until the session is dropped or finishes. The session lock is held while the
+/// session waits for the adapter, so checks never wait for it: they use the client and
+/// options from the last time the lock was free, and go straight to the adapter.
+pub(crate) fn monitor(internals: Weak<Mutex<DebuggerInternals>>, mut session: Session) {
+    let mut responsive = true;
+    loop {
+        thread::sleep(session.options.interval);
+        if !session.refresh(&internals) {
+            return;
+        }
+
+        let problem = match session.client.probe(session.options.timeout) {
+            Ok(()) => None,
+            Err(SendError::Timeout(_)) => Some(Unresponsive::NoResponse {
+                timeout: session.options.timeout,
+            }),
+            // recovery deals with the connection closing
+            Err(SendError::ConnectionLost(_)) => None,
+            Err(e) => Some(Unresponsive::SendFailed {
+                error: format!("{e:#}"),
+            }),
+        };
+
+        match problem {
+            Some(reason) if responsive => {
+                if !session.refresh(&internals) {
+                    return;
+                }
+                tracing::warn!(%reason, "debug adapter is unresponsive");
+                session
+                    .publisher
+                    .emit(Event::AdapterUnresponsive { reason });
+                responsive = false;
+            }
+            Some(_) => {}
+            None => responsive = true,
+        }
+    }
+}
+
+/// What the monitor needs from the session, kept so that it can carry on while the session
+/// is locked
+pub(crate) struct Session {
+    /// Weak so that the monitor does not keep the connection open once the session is over
+    client: WeakClient,
+    options: LivenessOptions,
+    publisher: Publisher,
+}
+
+impl Session {
+    pub(crate) fn new(internals: &DebuggerInternals, options: LivenessOptions) -> Self {
+        Self {
+            client: internals.client.downgrade(),
+            options,
+            publisher: internals.publisher.clone(),
+        }
+    }
+
+    /// Pick up a reconnected client or changed options, returning `false` once the session
+    /// has gone away or finished
+    ///
+    /// A locked session is still in use, so the current values are kept.
+    fn refresh(&mut self, internals: &Weak<Mutex<DebuggerInternals>>) -> bool {
+        let Some(internals) = internals.upgrade() else {
+            return false;
+        };
+        let internals = match internals.try_lock() {
+            Ok(internals) => internals,
+            Err(TryLockError::WouldBlock) => return true,
+            Err(TryLockError::Poisoned(_)) => return false,
+        };
+        if internals.finished() {
+            return false;
+        }
+        let Some(options) = internals.liveness.clone() else {
+            return false;
+        };
+        self.client = internals.client.downgrade();
+        self.options = options;
+        true
+    }
+}
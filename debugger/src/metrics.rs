@@ -0,0 +1,317 @@
+//! Counters and timings for the traffic between the debugger and the adapter
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use transport::{
+    requests, responses::ResponseBody, tap::MessageTap, Client, SendError, WeakClient,
+};
+
+/// Upper bounds of the latency histogram buckets, slower requests go in a final overflow
+/// bucket
+pub const LATENCY_BUCKETS: [Duration; 12] = [
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(20),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(200),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+    Duration::from_secs(5),
+];
+
+/// Distribution of request latencies
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKETS.len() + 1],
+    total: Duration,
+    max: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        let count = self.count();
+        (count > 0).then(|| self.total / count as u32)
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        (self.count() > 0).then_some(self.max)
+    }
+
+    /// Upper bound of the bucket containing the `quantile` (between 0 and 1) latency
+    ///
+    /// Latencies beyond the last bucket are reported as the maximum latency seen.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let target = ((quantile.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, bucket_count) in self.counts.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= target {
+                return Some(LATENCY_BUCKETS.get(bucket).copied().unwrap_or(self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// Number of requests in each bucket, along with the bucket's upper bound (`None` for the
+    /// overflow bucket)
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| (LATENCY_BUCKETS.get(i).copied(), *count))
+    }
+}
+
+/// Metrics for a single request command, e.g. `stackTrace`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandMetrics {
+    pub requests: u64,
+    /// Requests that the adapter rejected or that could not be sent
+    pub failures: u64,
+    /// Time from sending the request to receiving the response, only for requests that wait
+    /// for a response
+    pub latency: LatencyHistogram,
+}
+
+/// Point in time copy of the metrics for a session
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    pub commands: BTreeMap<String, CommandMetrics>,
+    /// Number of events received from the adapter, by event name
+    pub events: BTreeMap<String, u64>,
+    /// Number of times the session recovered from losing the adapter connection
+    pub reconnects: u64,
+    /// Latency of the most recent request that waited for a response
+    pub last_latency: Option<Duration>,
+    /// Time since the session was created
+    pub elapsed: Duration,
+}
+
+impl MetricsSnapshot {
+    pub fn total_requests(&self) -> u64 {
+        self.commands.values().map(|c| c.requests).sum()
+    }
+
+    pub fn total_events(&self) -> u64 {
+        self.events.values().sum()
+    }
+
+    /// Average number of `event` events received per second over the session
+    pub fn event_rate(&self, event: &str) -> f64 {
+        let count = self.events.get(event).copied().unwrap_or_default();
+        count as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+#[derive(Debug)]
+struct MetricsInner {
+    started: Instant,
+    commands: BTreeMap<String, CommandMetrics>,
+    events: BTreeMap<String, u64>,
+    reconnects: u64,
+    last_latency: Option<Duration>,
+}
+
+/// Handle to the metrics of a debugging session, see [`crate::Debugger::metrics`]
+///
+/// Cloning the handle gives access to the same metrics.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    inner: Arc<Mutex<MetricsInner>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MetricsInner {
+                started: Instant::now(),
+                commands: BTreeMap::new(),
+                events: BTreeMap::new(),
+                reconnects: 0,
+                last_latency: None,
+            })),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let inner = self.inner.lock().unwrap();
+        MetricsSnapshot {
+            commands: inner.commands.clone(),
+            events: inner.events.clone(),
+            reconnects: inner.reconnects,
+            last_latency: inner.last_latency,
+            elapsed: inner.started.elapsed(),
+        }
+    }
+
+    /// Call `callback` with a snapshot every `interval` from a background thread, until the
+    /// session is dropped
+    pub fn export_every(
+        &self,
+        interval: Duration,
+        callback: impl Fn(&MetricsSnapshot) + Send + 'static,
+    ) {
+        let inner = Arc::downgrade(&self.inner);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            callback(&Metrics { inner }.snapshot());
+        });
+    }
+
+    fn record_request(&self, command: String, latency: Option<Duration>, success: bool) {
+        let mut inner = self.inner.lock().unwrap();
+        let metrics = inner.commands.entry(command).or_default();
+        metrics.requests += 1;
+        if !success {
+            metrics.failures += 1;
+        }
+        if let Some(latency) = latency {
+            metrics.latency.record(latency);
+            inner.last_latency = Some(latency);
+        }
+    }
+
+    pub(crate) fn record_event(&self, event: &transport::events::Event) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.events.entry(event.name().to_string()).or_default() += 1;
+    }
+
+    pub(crate) fn record_reconnect(&self) {
+        self.inner.lock().unwrap().reconnects += 1;
+    }
+}
+
+/// [`Client`] wrapper that records every request in [`Metrics`]
+#[derive(Clone)]
+pub(crate) struct MeteredClient {
+    client: Client,
+    metrics: Metrics,
+}
+
+impl MeteredClient {
+    pub(crate) fn new(client: Client, metrics: Metrics) -> Self {
+        Self { client, metrics }
+    }
+
+    pub(crate) fn send(&self, body: requests::RequestBody) -> Result<ResponseBody, SendError> {
+        self.record(body, |client, body| client.send(body))
+    }
+
+    /// Like [`MeteredClient::send`] but wait at most `timeout` for the response
+    pub(crate) fn send_with_timeout(
+        &self,
+        body: requests::RequestBody,
+        timeout: Option<Duration>,
+    ) -> Result<ResponseBody, SendError> {
+        self.record(body, |client, body| client.send_with_timeout(body, timeout))
+    }
+
+    fn record(
+        &self,
+        body: requests::RequestBody,
+        send: impl FnOnce(&Client, requests::RequestBody) -> Result<ResponseBody, SendError>,
+    ) -> Result<ResponseBody, SendError> {
+        let command = body.command();
+        let start = Instant::now();
+        let res = send(&self.client, body);
+        let latency = start.elapsed();
+        match &res {
+            Ok(_) => self.metrics.record_request(command, Some(latency), true),
+            // the adapter responded, so the latency still counts
+            Err(SendError::Adapter(_)) => {
+                self.metrics.record_request(command, Some(latency), false)
+            }
+            Err(_) => self.metrics.record_request(command, None, false),
+        }
+        res
+    }
+
+    /// Reference to the client that does not keep it alive, for checks that are not recorded
+    pub(crate) fn downgrade(&self) -> WeakClient {
+        self.client.downgrade()
+    }
+
+    pub(crate) fn set_timeout(&self, timeout: Option<Duration>) {
+        self.client.set_timeout(timeout);
+    }
+
+    pub(crate) fn set_tap(&self, tap: Option<Arc<dyn MessageTap>>) {
+        self.client.set_tap(tap);
+    }
+
+    pub(crate) fn set_strict(&self, enabled: bool) {
+        self.client.set_strict(enabled);
+    }
+
+    pub(crate) fn execute(&self, body: requests::RequestBody) -> eyre::Result<()> {
+        let command = body.command();
+        let res = self.client.execute(body);
+        self.metrics.record_request(command, None, res.is_ok());
+        res
+    }
+
+    pub(crate) fn respond(
+        &self,
+        request_seq: transport::types::Seq,
+        command: &str,
+        success: bool,
+        message: Option<String>,
+    ) -> eyre::Result<()> {
+        self.client.respond(request_seq, command, success, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::LatencyHistogram;
+
+    #[test]
+    fn histogram_quantiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.mean(), None);
+
+        for ms in [1, 3, 3, 8, 40] {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.mean(), Some(Duration::from_millis(11)));
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_millis(5)));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::from_millis(50)));
+
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::from_secs(60)));
+    }
+}
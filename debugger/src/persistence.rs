@@ -1 +1,8 @@
+//! Saving debugger state between sessions
+use crate::types::Breakpoint;
 
+/// Storage for breakpoints that outlives a debugging session, e.g. the `state` crate
+pub trait PersistBreakpoints: Send {
+    /// Replace the saved breakpoints with `breakpoints`
+    fn save(&mut self, breakpoints: &[Breakpoint]) -> eyre::Result<()>;
+}
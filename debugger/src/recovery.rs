@@ -0,0 +1,207 @@
+//! Re-establishing a session after the connection to the debug adapter is lost
+use std::{sync::Mutex, thread, time::Duration};
+
+use eyre::WrapErr;
+use transport::{events, requests, ReconnectPolicy};
+
+use crate::{
+    debugger::{new_client, Connection, Endpoint, TransportEvents},
+    internals::DebuggerInternals,
+    metrics::MeteredClient,
+    state::DebuggerState,
+    Event,
+};
+
+/// How long to wait for a new adapter connection to be initialised
+const INITIALISE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a session reacts to losing its connection to the debug adapter
+#[derive(Debug, Clone)]
+pub struct RecoveryOptions {
+    /// Start a new adapter process, for sessions that launched their own adapter
+    pub restart_adapter: bool,
+    /// How many times to try to reconnect before ending the session, and how long to wait
+    /// before each attempt, e.g. for an attached adapter to come back
+    pub reconnect: ReconnectPolicy,
+}
+
+impl Default for RecoveryOptions {
+    fn default() -> Self {
+        Self {
+            restart_adapter: true,
+            reconnect: ReconnectPolicy {
+                max_attempts: 3,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Try to re-establish the session after the adapter connection closed, returning the events
+/// for the new connection
+///
+/// Returns `None` if the session ended deliberately, recovery is not enabled or every attempt
+/// failed, in which case the session is marked as ended.
+pub(crate) fn recover(connection: &Connection) -> Option<TransportEvents> {
+    let internals = connection.internals.upgrade()?;
+    let options = {
+        let mut internals = internals.lock().unwrap();
+        if internals.finished() || internals.generation != connection.generation {
+            return None;
+        }
+        match internals.recovery.clone() {
+            Some(options) => options,
+            None => {
+                tracing::warn!("lost connection to debug adapter");
+                internals.set_state(DebuggerState::Ended);
+                return None;
+            }
+        }
+    };
+
+    for attempt in 1..=options.reconnect.max_attempts {
+        thread::sleep(options.reconnect.backoff(attempt));
+        {
+            let internals = internals.lock().unwrap();
+            if internals.finished() || internals.generation != connection.generation {
+                return None;
+            }
+        }
+
+        tracing::info!(%attempt, "recovering debugging session");
+        match reconnect(connection, &internals, &options) {
+            Ok((events, restarted_adapter)) => {
+                let mut internals = internals.lock().unwrap();
+                internals.metrics.record_reconnect();
+                internals.emit(Event::Recovered {
+                    attempts: attempt,
+                    restarted_adapter,
+                });
+                return Some(events);
+            }
+            Err(e) => tracing::warn!(error = ?e, %attempt, "recovering debugging session"),
+        }
+    }
+
+    internals.lock().unwrap().set_state(DebuggerState::Ended);
+    None
+}
+
+/// Restart the session by disconnecting from the adapter and replaying the `launch` or
+/// `attach` request on a new connection
+///
+/// Launched debugees are terminated first, while attached ones are left running.
+pub(crate) fn restart(connection: &Connection) -> eyre::Result<()> {
+    let internals = connection
+        .internals
+        .upgrade()
+        .ok_or_else(|| eyre::eyre!("session has been dropped"))?;
+    let connection = {
+        let mut internals = internals.lock().unwrap();
+        let terminate_debuggee = matches!(
+            internals.initialise_request,
+            Some(requests::RequestBody::Launch(_) | requests::RequestBody::LaunchConfiguration(_))
+        );
+        // ignore anything else the old connection sends, e.g. `terminated` events
+        internals.generation += 1;
+        internals.ended = false;
+        if let Err(e) =
+            internals
+                .client
+                .send(requests::RequestBody::Disconnect(requests::Disconnect {
+                    terminate_debuggee,
+                }))
+        {
+            tracing::warn!(error = %e, "disconnecting before restart");
+        }
+        let mut connection = connection.clone();
+        connection.generation = internals.generation;
+        connection
+    };
+
+    let options = RecoveryOptions {
+        restart_adapter: true,
+        ..Default::default()
+    };
+    let (events, _) = reconnect(&connection, &internals, &options)?;
+    thread::spawn(move || connection.handle_events(events));
+    Ok(())
+}
+
+/// Connect to a (possibly new) adapter, replay the `launch` or `attach` request and re-apply
+/// the breakpoints, returning the events for the new connection and whether the adapter was
+/// restarted
+fn reconnect(
+    connection: &Connection,
+    internals: &Mutex<DebuggerInternals>,
+    options: &RecoveryOptions,
+) -> eyre::Result<(TransportEvents, bool)> {
+    let Endpoint::Tcp { host, port } = &connection.endpoint else {
+        eyre::bail!("can only reconnect to adapters over TCP");
+    };
+    let (events, restarted_adapter) = {
+        let mut internals = internals.lock().unwrap();
+        let request = internals
+            .initialise_request
+            .clone()
+            .ok_or_else(|| eyre::eyre!("session was never initialised"))?;
+
+        let restarted_adapter = match internals.adapter {
+            Some(implementation) if options.restart_adapter => {
+                // make sure the old adapter has released the port first
+                internals.server.take();
+                let mut server = server::for_implementation_with_executable(
+                    implementation,
+                    *port,
+                    internals.adapter_executable.as_deref(),
+                )
+                .context("restarting adapter")?;
+                if let Some(output) = server.take_output() {
+                    connection.forward_adapter_output(output);
+                }
+                internals.server = Some(server);
+                true
+            }
+            _ => false,
+        };
+
+        let (client, events, reverse_requests) =
+            new_client(host, *port).context("reconnecting to adapter")?;
+        internals.client = MeteredClient::new(client, internals.metrics.clone());
+        internals.client.set_timeout(internals.request_timeout);
+        internals.client.set_tap(internals.tap.clone());
+        internals.client.set_strict(internals.strict_protocol);
+        connection.handle_reverse_requests(reverse_requests);
+        internals
+            .initialise_with_request(request)
+            .context("re-initialising session")?;
+        (events, restarted_adapter)
+    };
+
+    // the adapter only accepts breakpoints once it has been initialised
+    loop {
+        match events.recv_timeout(INITIALISE_TIMEOUT) {
+            Ok(events::Event::Initialized) => break,
+            Ok(event) => internals.lock().unwrap().on_event(event),
+            Err(e) => return Err(e).context("waiting for adapter to initialise"),
+        }
+    }
+
+    let mut internals = internals.lock().unwrap();
+    internals.breakpoints.reset_sync();
+    internals
+        .breakpoints_changed()
+        .context("re-applying breakpoints")?;
+    if !internals.exception_breakpoints.is_empty() {
+        let filters = internals.exception_breakpoints.clone();
+        internals
+            .send_exception_breakpoints(filters)
+            .context("re-applying exception breakpoints")?;
+    }
+    internals
+        .client
+        .send(requests::RequestBody::ConfigurationDone)
+        .context("completing configuration")?;
+    internals.running();
+    Ok((events, restarted_adapter))
+}
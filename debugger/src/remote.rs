@@ -0,0 +1,162 @@
+//! Debugging programs running on another machine over SSH
+use std::{net::TcpListener, path::PathBuf};
+
+use eyre::WrapErr;
+use server::ssh::SshServer;
+use transport::requests;
+
+use crate::{
+    debugger::{new_client, Endpoint},
+    error::{Error, Result},
+    Debugger, Language,
+};
+
+/// Arguments for [`attach`]
+#[derive(Debug)]
+pub struct RemoteArguments {
+    /// SSH destination, e.g. `user@host`
+    pub destination: String,
+
+    /// Directory on the remote machine containing the code being debugged
+    pub remote_working_directory: PathBuf,
+
+    /// Local checkout of the same code, used for mapping source paths
+    pub local_working_directory: PathBuf,
+
+    /// Programming language of the debugee
+    pub language: Language,
+
+    /// Port the debugee is listening on, on the remote machine
+    ///
+    /// For Delve this is a headless `dlv` server started with `--accept-multiclient`, e.g.
+    /// `dlv exec --headless --listen 127.0.0.1:2345 --accept-multiclient ./app`, which the
+    /// session connects to directly.
+    pub debugee_port: u16,
+
+    /// Port for the debugpy adapter on the remote machine, which must be free there
+    ///
+    /// Not used for Delve.
+    pub adapter_port: u16,
+}
+
+/// Attach to a process on a remote machine
+///
+/// Starts the adapter on the remote machine over SSH with a port forward to it, or for Delve
+/// forwards a port to the `dlv` server, and maps the remote working directory to the local one
+/// so breakpoints and stack frames refer to local files. The SSH connection is closed when the
+/// returned debugger is dropped.
+pub fn attach(arguments: RemoteArguments) -> Result<Debugger> {
+    let local_port = free_port().context("finding free local port")?;
+    let server = match arguments.language {
+        Language::DebugPy => SshServer::start(
+            &arguments.destination,
+            &arguments.remote_working_directory,
+            local_port,
+            arguments.adapter_port,
+        )
+        .context("starting remote adapter")?,
+        Language::Delve => {
+            SshServer::forward(&arguments.destination, local_port, arguments.debugee_port)
+                .context("forwarding port to remote dlv server")?
+        }
+    };
+
+    let request = attach_request(arguments);
+    let host = "127.0.0.1".to_string();
+    let client = new_client(&host, local_port).context("connecting to server")?;
+    Debugger::connect(
+        Endpoint::Tcp {
+            host,
+            port: local_port,
+        },
+        client,
+        Some(Box::new(server)),
+        false,
+        |internals| internals.initialise_with_request(request),
+    )
+    .map_err(Error::from)
+}
+
+fn attach_request(arguments: RemoteArguments) -> requests::RequestBody {
+    match arguments.language {
+        Language::DebugPy => requests::RequestBody::Attach(requests::Attach {
+            // the adapter connects to the debugee from the remote machine
+            connect: requests::ConnectInfo {
+                host: "127.0.0.1".to_string(),
+                port: arguments.debugee_port,
+            },
+            path_mappings: path_mappings(&arguments),
+            just_my_code: false,
+            workspace_folder: arguments.local_working_directory,
+        }),
+        Language::Delve => requests::RequestBody::AttachConfiguration(serde_json::json!({
+            "mode": "remote",
+            "substitutePath": [{
+                "from": arguments.local_working_directory,
+                "to": arguments.remote_working_directory,
+            }],
+        })),
+    }
+}
+
+fn path_mappings(arguments: &RemoteArguments) -> Vec<requests::PathMapping> {
+    vec![requests::PathMapping {
+        local_root: arguments
+            .local_working_directory
+            .to_string_lossy()
+            .into_owned(),
+        remote_root: arguments
+            .remote_working_directory
+            .to_string_lossy()
+            .into_owned(),
+    }]
+}
+
+fn free_port() -> std::io::Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    Ok(listener.local_addr()?.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+
+    use super::{attach_request, RemoteArguments};
+    use crate::Language;
+
+    fn arguments(language: Language) -> RemoteArguments {
+        RemoteArguments {
+            destination: "user@host".to_string(),
+            remote_working_directory: PathBuf::from("/srv/app"),
+            local_working_directory: PathBuf::from("/home/user/app"),
+            language,
+            debugee_port: 2345,
+            adapter_port: 5678,
+        }
+    }
+
+    #[test]
+    fn attach_requests_map_the_working_directory() {
+        let request = serde_json::to_value(attach_request(arguments(Language::DebugPy))).unwrap();
+        assert_eq!(request["command"], "attach");
+        assert_eq!(request["arguments"]["connect"]["port"], 2345);
+        assert_eq!(
+            request["arguments"]["pathMappings"],
+            json!([{ "localRoot": "/home/user/app", "remoteRoot": "/srv/app" }])
+        );
+
+        let request = serde_json::to_value(attach_request(arguments(Language::Delve))).unwrap();
+        assert_eq!(
+            request,
+            json!({
+                "command": "attach",
+                "arguments": {
+                    "mode": "remote",
+                    "substitutePath": [{ "from": "/home/user/app", "to": "/srv/app" }],
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Running several debugging sessions side by side
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::{error::Result, Debugger, Event};
+
+pub type SessionId = u64;
+
+/// [`Event`] from one of the sessions in a [`SessionRegistry`]
+#[derive(Debug, Clone)]
+pub struct SessionEvent {
+    pub session: SessionId,
+    pub event: Event,
+}
+
+/// Collection of independent [`Debugger`]s, e.g. a Python service and a Go service
+///
+/// Events from every session are merged into a single stream, tagged with the id of the
+/// session that produced them. One session is active at a time, which frontends use to decide
+/// which session to show and send commands to.
+#[derive(Default)]
+pub struct SessionRegistry {
+    inner: Mutex<Sessions>,
+    subscribers: Arc<Mutex<Vec<Sender<SessionEvent>>>>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    sessions: BTreeMap<SessionId, Session>,
+    next_id: SessionId,
+    active: Option<SessionId>,
+}
+
+struct Session {
+    name: String,
+    debugger: Arc<Debugger>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a debugger under a human readable `name`, returning the id its events will be
+    /// tagged with
+    ///
+    /// Only events emitted after the debugger is added are forwarded. The first session added
+    /// becomes the active session.
+    pub fn add(&self, name: impl Into<String>, debugger: Debugger) -> SessionId {
+        let events = debugger.subscribe();
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+
+        let subscribers = Arc::downgrade(&self.subscribers);
+        thread::spawn(move || {
+            // finishes when the debugger is dropped or the registry goes away
+            for event in events {
+                let Some(subscribers) = subscribers.upgrade() else {
+                    break;
+                };
+                let event = SessionEvent { session: id, event };
+                // drop subscribers whose receivers have gone away
+                subscribers
+                    .lock()
+                    .unwrap()
+                    .retain(|tx| tx.send(event.clone()).is_ok());
+            }
+            tracing::debug!(session = %id, "session event forwarding finished");
+        });
+
+        inner.sessions.insert(
+            id,
+            Session {
+                name: name.into(),
+                debugger: Arc::new(debugger),
+            },
+        );
+        inner.active.get_or_insert(id);
+        id
+    }
+
+    /// Get the debugger for a session
+    pub fn get(&self, id: SessionId) -> Option<Arc<Debugger>> {
+        self.inner
+            .lock()
+            .unwrap()
+            .sessions
+            .get(&id)
+            .map(|s| Arc::clone(&s.debugger))
+    }
+
+    /// Name the session was registered with
+    pub fn name(&self, id: SessionId) -> Option<String> {
+        self.inner
+            .lock()
+            .unwrap()
+            .sessions
+            .get(&id)
+            .map(|s| s.name.clone())
+    }
+
+    /// Remove a session from the registry
+    ///
+    /// If it was the active session, the first remaining session becomes active. The session
+    /// is shut down once the last reference to the returned debugger is dropped.
+    pub fn remove(&self, id: SessionId) -> Option<Arc<Debugger>> {
+        let mut inner = self.inner.lock().unwrap();
+        let session = inner.sessions.remove(&id)?;
+        if inner.active == Some(id) {
+            inner.active = inner.sessions.keys().next().copied();
+        }
+        Some(session.debugger)
+    }
+
+    /// Ids of all registered sessions, in the order they were added
+    pub fn sessions(&self) -> Vec<SessionId> {
+        self.inner
+            .lock()
+            .unwrap()
+            .sessions
+            .keys()
+            .copied()
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().sessions.is_empty()
+    }
+
+    /// Session that frontends should currently show, if any are registered
+    pub fn active(&self) -> Option<SessionId> {
+        self.inner.lock().unwrap().active
+    }
+
+    /// Debugger of the active session
+    pub fn active_debugger(&self) -> Option<Arc<Debugger>> {
+        let inner = self.inner.lock().unwrap();
+        let id = inner.active?;
+        inner.sessions.get(&id).map(|s| Arc::clone(&s.debugger))
+    }
+
+    /// Make `id` the active session
+    pub fn set_active(&self, id: SessionId) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.sessions.contains_key(&id) {
+            return Err(eyre::eyre!("no session with id {id}").into());
+        }
+        inner.active = Some(id);
+        Ok(())
+    }
+
+    /// Return a [`crossbeam_channel::Receiver<SessionEvent>`] of events from all sessions
+    ///
+    /// Every receiver gets a copy of each event emitted after it was created, so e.g. a
+    /// session switcher and a logger can both follow the sessions.
+    pub fn events(&self) -> Receiver<SessionEvent> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+}
@@ -2,6 +2,7 @@ use std::{path::PathBuf, str::FromStr};
 
 use transport::{
     requests::{self, DebugpyLaunchArguments},
+    types::ThreadId,
     DEFAULT_DAP_PORT,
 };
 
@@ -14,6 +15,10 @@ pub(crate) enum DebuggerState {
         stack: Vec<types::StackFrame>,
         paused_frame: Box<PausedFrame>,
         breakpoints: Vec<types::Breakpoint>,
+        thread_id: ThreadId,
+        all_threads_stopped: bool,
+        reason: types::StopReason,
+        exception: Option<Box<types::ExceptionInfo>>,
     },
     Running,
     Ended,
@@ -27,14 +32,68 @@ pub enum Event {
         stack: Vec<types::StackFrame>,
         breakpoints: Vec<types::Breakpoint>,
         paused_frame: types::PausedFrame,
+        /// Thread that stopped, whose stack is in `stack`
+        thread_id: ThreadId,
+        /// Whether every thread stopped, rather than just `thread_id`
+        all_threads_stopped: bool,
+        /// Why the debugee stopped, e.g. to distinguish stopping on entry from a breakpoint
+        reason: types::StopReason,
+        /// Exception the debugee stopped on, if it stopped because of one
+        exception: Option<Box<types::ExceptionInfo>>,
     },
     ScopeChange {
         stack: Vec<types::StackFrame>,
         breakpoints: Vec<types::Breakpoint>,
         paused_frame: types::PausedFrame,
+        /// Thread whose stack is in `stack`
+        thread_id: ThreadId,
     },
     Running,
     Ended,
+    /// Output produced by the debugee or the debug adapter
+    Output {
+        category: types::OutputCategory,
+        output: String,
+        /// File the output was produced from, e.g. the location of a `print` call
+        source: Option<PathBuf>,
+        line: Option<usize>,
+        /// Logpoint that produced the output, if any
+        logpoint: Option<types::BreakpointId>,
+    },
+    /// Line written by the debug adapter process to its stdout or stderr, e.g. its own logs or
+    /// the reason it crashed
+    ///
+    /// Only emitted for adapters started by the debugger.
+    AdapterLog {
+        stream: server::OutputStream,
+        line: String,
+    },
+    /// The session was re-established after the connection to the adapter was lost
+    Recovered {
+        /// Number of reconnection attempts that were needed
+        attempts: u32,
+        /// Whether a new adapter process was started
+        restarted_adapter: bool,
+    },
+    /// The set of breakpoints changed, after the changes were sent to the adapter
+    BreakpointsChanged {
+        breakpoints: Vec<(types::BreakpointId, types::Breakpoint)>,
+    },
+    /// A message from the adapter broke the protocol, see [`crate::Debugger::set_strict_protocol`]
+    ProtocolViolation {
+        violation: transport::validation::Violation,
+    },
+    /// The debugee stopped but its stack could not be fetched, e.g. because the adapter did
+    /// not respond in time
+    PauseFailed {
+        thread_id: ThreadId,
+        error: String,
+    },
+    /// The adapter stopped responding without closing the connection, see
+    /// [`crate::Debugger::enable_liveness`]
+    AdapterUnresponsive {
+        reason: crate::Unresponsive,
+    },
 }
 
 impl<'a> From<&'a DebuggerState> for Event {
@@ -45,11 +104,18 @@ impl<'a> From<&'a DebuggerState> for Event {
                 stack,
                 paused_frame,
                 breakpoints,
-                ..
+                thread_id,
+                all_threads_stopped,
+                reason,
+                exception,
             } => Event::Paused {
                 stack: stack.clone(),
                 paused_frame: *paused_frame.clone(),
                 breakpoints: breakpoints.clone(),
+                thread_id: *thread_id,
+                all_threads_stopped: *all_threads_stopped,
+                reason: reason.clone(),
+                exception: exception.clone(),
             },
             DebuggerState::Running => Event::Running,
             DebuggerState::Ended => Event::Ended,
@@ -82,6 +148,9 @@ pub struct AttachArguments {
     /// Working directory for the debugging session
     pub working_directory: PathBuf,
 
+    /// Debugger host to connect to (defaults to localhost)
+    pub host: Option<String>,
+
     /// Debugger port to connect to (defaults to 5678)
     pub port: Option<u16>,
 
@@ -90,17 +159,20 @@ pub struct AttachArguments {
 
     /// Custom mappings from the running code (e.g. in a Docker container) to local source checkout
     pub path_mappings: Option<Vec<requests::PathMapping>>,
+
+    /// Only step through and break in user code (defaults to false)
+    pub just_my_code: Option<bool>,
 }
 
 impl AttachArguments {
     pub fn to_request(self) -> requests::RequestBody {
         requests::RequestBody::Attach(requests::Attach {
             connect: requests::ConnectInfo {
-                host: "localhost".to_string(),
+                host: self.host.unwrap_or_else(|| "localhost".to_string()),
                 port: self.port.unwrap_or(DEFAULT_DAP_PORT),
             },
             path_mappings: self.path_mappings.unwrap_or_default(),
-            just_my_code: false,
+            just_my_code: self.just_my_code.unwrap_or(false),
             workspace_folder: self.working_directory,
         })
     }
@@ -117,6 +189,21 @@ pub struct LaunchArguments {
 
     /// Language used to create the process
     pub language: Language,
+
+    /// Whether to debug child processes started by the program, using the adapter default if
+    /// not given
+    pub sub_process: Option<bool>,
+
+    /// Executable used to start the adapter (the Python interpreter for debugpy, or `dlv`),
+    /// found on `PATH` if not given
+    pub adapter_executable: Option<PathBuf>,
+
+    /// Only step through and break in user code (defaults to true)
+    pub just_my_code: Option<bool>,
+
+    /// Whether to pause before running any of the program, reported with
+    /// [`types::StopReason::Entry`]
+    pub stop_on_entry: bool,
 }
 
 impl LaunchArguments {
@@ -128,6 +215,10 @@ impl LaunchArguments {
             program,
             working_directory: Some(working_directory),
             language,
+            sub_process: None,
+            adapter_executable: None,
+            just_my_code: None,
+            stop_on_entry: false,
         }
     }
 }
@@ -147,15 +238,16 @@ impl LaunchArguments {
                 program,
                 launch_arguments: Some(transport::requests::LaunchArguments::Debugpy(
                     DebugpyLaunchArguments {
-                        just_my_code: true,
+                        just_my_code: self.just_my_code.unwrap_or(true),
                         cwd,
                         show_return_value: true,
                         debug_options: vec![
                             "DebugStdLib".to_string(),
                             "ShowReturnValue".to_string(),
                         ],
-                        stop_on_entry: false,
+                        stop_on_entry: self.stop_on_entry,
                         is_output_redirected: false,
+                        sub_process: self.sub_process,
                     },
                 )),
             }),
@@ -12,6 +12,14 @@ pub struct Breakpoint {
     pub name: Option<String>,
     pub path: PathBuf,
     pub line: usize,
+    /// Only break when this expression evaluates to true
+    pub condition: Option<String>,
+    /// Controls how many hits of the breakpoint are ignored, interpreted by the adapter
+    pub hit_condition: Option<String>,
+    /// Log this message instead of pausing, making the breakpoint a logpoint
+    ///
+    /// Expressions within `{}` are interpolated by the adapter.
+    pub log_message: Option<String>,
 }
 
 impl Breakpoint {
@@ -28,9 +36,208 @@ pub struct PausedFrame {
 
 pub(crate) use transport::types::StackFrame;
 
+/// Where an expression passed to [`crate::Debugger::evaluate`] comes from, which adapters use
+/// to decide e.g. whether side effects are allowed or how long the result may be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluateContext {
+    /// Typed by the user into a REPL
+    Repl,
+    /// Re-evaluated every time the debugee pauses
+    Watch,
+    /// Shown when hovering over a name in the source, should be free of side effects
+    Hover,
+    /// Copied to the clipboard, so the full value should be returned rather than a summary
+    Clipboard,
+}
+
+impl EvaluateContext {
+    /// Value of the `context` argument of the `evaluate` request
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EvaluateContext::Repl => "repl",
+            EvaluateContext::Watch => "watch",
+            EvaluateContext::Hover => "hover",
+            EvaluateContext::Clipboard => "clipboard",
+        }
+    }
+}
+
+impl std::str::FromStr for EvaluateContext {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "repl" => Ok(EvaluateContext::Repl),
+            "watch" => Ok(EvaluateContext::Watch),
+            "hover" => Ok(EvaluateContext::Hover),
+            "clipboard" => Ok(EvaluateContext::Clipboard),
+            other => Err(eyre::eyre!("invalid evaluate context {other:?}")),
+        }
+    }
+}
+
+/// Stream an `output` event was written to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputCategory {
+    /// Messages from the debug adapter or debugger, the default if the adapter does not say
+    Console,
+    /// Messages the user should see, e.g. in a popup
+    Important,
+    Stdout,
+    Stderr,
+    /// Usage data sent by the adapter, not intended to be shown
+    Telemetry,
+    Other(String),
+}
+
+impl OutputCategory {
+    pub fn as_str(&self) -> &str {
+        match self {
+            OutputCategory::Console => "console",
+            OutputCategory::Important => "important",
+            OutputCategory::Stdout => "stdout",
+            OutputCategory::Stderr => "stderr",
+            OutputCategory::Telemetry => "telemetry",
+            OutputCategory::Other(other) => other,
+        }
+    }
+}
+
+impl From<Option<String>> for OutputCategory {
+    fn from(category: Option<String>) -> Self {
+        match category.as_deref() {
+            None | Some("console") => OutputCategory::Console,
+            Some("important") => OutputCategory::Important,
+            Some("stdout") => OutputCategory::Stdout,
+            Some("stderr") => OutputCategory::Stderr,
+            Some("telemetry") => OutputCategory::Telemetry,
+            Some(other) => OutputCategory::Other(other.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct EvaluateResult {
     pub output: String,
     pub error: bool,
+    pub r#type: Option<String>,
+    /// Reference to fetch the children of the result with, or 0 if it has none
+    pub variables_reference: transport::types::VariablesReference,
+}
+
+/// Why the debugee paused, included in [`crate::Event::Paused`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// Stopped before running any user code, because `stop_on_entry` was requested
+    Entry,
+    /// Hit a breakpoint, including function breakpoints
+    Breakpoint,
+    /// Finished a step
+    Step,
+    Exception,
+    /// Paused at the request of the user
+    Pause,
+    Other(String),
+}
+
+impl StopReason {
+    /// Value of the `reason` field of the `stopped` event
+    pub fn as_str(&self) -> &str {
+        match self {
+            StopReason::Entry => "entry",
+            StopReason::Breakpoint => "breakpoint",
+            StopReason::Step => "step",
+            StopReason::Exception => "exception",
+            StopReason::Pause => "pause",
+            StopReason::Other(other) => other,
+        }
+    }
+}
+
+impl From<transport::events::StoppedReason> for StopReason {
+    fn from(reason: transport::events::StoppedReason) -> Self {
+        use transport::events::StoppedReason;
+
+        match reason {
+            StoppedReason::Step => StopReason::Step,
+            StoppedReason::FunctionBreakpoint => StopReason::Breakpoint,
+            StoppedReason::Other(other) => match other.as_str() {
+                "entry" => StopReason::Entry,
+                "breakpoint"
+                | "function breakpoint"
+                | "data breakpoint"
+                | "instruction breakpoint" => StopReason::Breakpoint,
+                "step" => StopReason::Step,
+                "exception" => StopReason::Exception,
+                "pause" => StopReason::Pause,
+                _ => StopReason::Other(other),
+            },
+        }
+    }
+}
+
+/// Why the debugee stopped on an exception, included in [`crate::Event::Paused`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionInfo {
+    /// Identifier of the exception, e.g. its type name
+    pub exception_id: String,
+    pub description: Option<String>,
+    /// Message, type and stack trace of the exception, if the adapter supports the
+    /// `exceptionInfo` request
+    pub details: Option<transport::types::ExceptionDetails>,
+}
+
+/// Bytes read from the debugee by [`crate::Debugger::read_memory`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Memory {
+    /// Address of the first byte in `data`, formatted by the adapter, e.g. `0x7ffe0000`
+    pub address: String,
+    pub data: Vec<u8>,
+    /// Number of bytes after `data` that could not be read
+    pub unreadable_bytes: usize,
+}
+
+/// How [`crate::Debugger::stop`] ends the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminateMode {
+    /// Ask the debugee to exit gracefully with a `terminate` request, if the adapter
+    /// supports it, then disconnect and terminate anything still running
+    Terminate,
+    /// Disconnect and have the adapter terminate the debugee immediately
+    DisconnectAndTerminate,
+    /// Disconnect, leaving the debugee running
+    Detach,
+}
+
+/// Error returned instead of sending a request that the adapter did not advertise support for
+/// in its capabilities
+#[derive(Debug, Clone)]
+pub(crate) struct Unsupported {
+    pub command: String,
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "adapter does not support {} requests", self.command)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+pub type WatchId = u64;
+
+/// Expression for frontends to evaluate whenever the debugee pauses, with
+/// [`crate::Debugger::evaluate_watches`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    pub id: WatchId,
+    pub expression: String,
+}
+
+/// The outcome of evaluating a [`Watch`] in a specific stack frame
+pub struct WatchResult {
+    pub watch: Watch,
+    pub result: Option<EvaluateResult>,
 }
 
 #[cfg(test)]
@@ -45,6 +252,7 @@ mod tests {
             name: None,
             path: PathBuf::from("~/test"),
             line: 0,
+            ..Default::default()
         };
 
         let path = b.normalised_path();
@@ -52,4 +260,30 @@ mod tests {
         let home_dir = dirs::home_dir().unwrap();
         assert_eq!(path, home_dir.join("test"));
     }
+
+    #[test]
+    fn persists_conditions() {
+        let b = Breakpoint {
+            path: PathBuf::from("/project/main.py"),
+            line: 3,
+            condition: Some("a > 1".to_string()),
+            hit_condition: Some(">= 5".to_string()),
+            ..Default::default()
+        };
+
+        let saved = serde_json::to_value(&b).unwrap();
+        assert_eq!(saved["condition"], "a > 1");
+        assert_eq!(saved["hit_condition"], ">= 5");
+        assert_eq!(serde_json::from_value::<Breakpoint>(saved).unwrap(), b);
+
+        // state saved before conditions were added
+        let old: Breakpoint = serde_json::from_value(serde_json::json!({
+            "name": null,
+            "path": "/project/main.py",
+            "line": 3,
+        }))
+        .unwrap();
+        assert_eq!(old.condition, None);
+        assert_eq!(old.hit_condition, None);
+    }
 }
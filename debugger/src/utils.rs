@@ -1,5 +1,7 @@
 use std::{borrow::Cow, path::Path};
 
+use transport::types::{CompletionItem, StackFrame};
+
 pub fn normalise_path(path: &Path) -> Cow<'_, Path> {
     if path.starts_with("~") {
         let stub: String = path.display().to_string().chars().skip(2).collect();
@@ -8,3 +10,119 @@ pub fn normalise_path(path: &Path) -> Cow<'_, Path> {
         Cow::Borrowed(path)
     }
 }
+
+/// Whether a stack frame is in the user's own code, i.e. its source is not de-emphasised by
+/// the adapter and lives inside `workspace`, if given
+///
+/// Frames without a path on disk are treated as user code, as there is no way to tell.
+pub fn is_user_frame(frame: &StackFrame, workspace: Option<&Path>) -> bool {
+    let Some(source) = &frame.source else {
+        return true;
+    };
+    if source.presentation_hint.as_deref() == Some("deemphasize") {
+        return false;
+    }
+    match (&source.path, workspace) {
+        (Some(path), Some(workspace)) => path.starts_with(workspace),
+        _ => true,
+    }
+}
+
+/// Apply a completion to `text` with the cursor at character offset `cursor`, returning the
+/// new text and cursor position
+///
+/// Without an explicit `start`, the `length` characters before the cursor are replaced, which
+/// is how adapters such as debugpy report the partial name being completed.
+pub fn apply_completion(text: &str, cursor: usize, item: &CompletionItem) -> (String, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let length = item.length.unwrap_or(0);
+    let (start, end) = match item.start {
+        Some(start) => {
+            let start = start.saturating_sub(1).min(chars.len());
+            (start, (start + length).min(chars.len()))
+        }
+        None => (cursor.saturating_sub(length), cursor),
+    };
+
+    let insert = item.text.as_deref().unwrap_or(&item.label);
+    let mut out: String = chars[..start].iter().collect();
+    out.push_str(insert);
+    let new_cursor = out.chars().count();
+    out.extend(&chars[end..]);
+    (out, new_cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+    use transport::types::{CompletionItem, StackFrame};
+
+    use super::{apply_completion, is_user_frame};
+
+    #[test]
+    fn replaces_partial_name_before_cursor() {
+        let item = CompletionItem {
+            label: "value".to_string(),
+            length: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            apply_completion("a.va", 4, &item),
+            ("a.value".to_string(), 7)
+        );
+    }
+
+    #[test]
+    fn replaces_from_explicit_start() {
+        let item = CompletionItem {
+            label: "print".to_string(),
+            text: Some("print(".to_string()),
+            start: Some(1),
+            length: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            apply_completion("pr x", 2, &item),
+            ("print( x".to_string(), 6)
+        );
+    }
+
+    #[test]
+    fn user_frames_are_in_the_workspace() {
+        let frame = |source: serde_json::Value| -> StackFrame {
+            serde_json::from_value(json!({
+                "id": 1,
+                "name": "f",
+                "source": source,
+                "line": 1,
+                "column": 0,
+            }))
+            .unwrap()
+        };
+        let workspace = Some(Path::new("/project"));
+
+        assert!(is_user_frame(
+            &frame(json!({ "path": "/project/main.py" })),
+            workspace
+        ));
+        assert!(!is_user_frame(
+            &frame(json!({ "path": "/usr/lib/threading.py" })),
+            workspace
+        ));
+        assert!(is_user_frame(
+            &frame(json!({ "path": "/usr/lib/threading.py" })),
+            None
+        ));
+        assert!(!is_user_frame(
+            &frame(json!({ "path": "/project/vendored.py", "presentationHint": "deemphasize" })),
+            workspace
+        ));
+        assert!(is_user_frame(
+            &frame(json!({ "sourceReference": 3 })),
+            workspace
+        ));
+    }
+}
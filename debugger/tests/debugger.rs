@@ -53,9 +53,11 @@ fn test_remote_attach() -> eyre::Result<()> {
 
     let launch_args = debugger::AttachArguments {
         working_directory: cwd.clone(),
+        host: None,
         port: Some(port),
         language: debugger::Language::DebugPy,
         path_mappings: None,
+        just_my_code: None,
     };
 
     let debugger = Debugger::on_port(port, launch_args).context("creating debugger")?;
@@ -82,7 +84,7 @@ fn test_remote_attach() -> eyre::Result<()> {
     debugger.start().context("launching debugee")?;
 
     wait_for_event("running event", &drx, |e| {
-        matches!(e, debugger::Event::Running { .. })
+        matches!(e, debugger::Event::Running)
     });
 
     let debugger::Event::Paused { paused_frame, .. } = wait_for_event("paused event", &drx, |e| {
@@ -135,6 +137,10 @@ fn test_debugger() -> eyre::Result<()> {
         program: file_path.clone(),
         working_directory: None,
         language: debugger::Language::DebugPy,
+        sub_process: None,
+        adapter_executable: None,
+        just_my_code: None,
+        stop_on_entry: false,
     };
     let debugger = Debugger::on_port(port, launch_args).context("creating debugger")?;
     let drx = debugger.events();
@@ -154,7 +160,7 @@ fn test_debugger() -> eyre::Result<()> {
     debugger.start().context("launching debugee")?;
 
     wait_for_event("running event", &drx, |e| {
-        matches!(e, debugger::Event::Running { .. })
+        matches!(e, debugger::Event::Running)
     });
 
     let debugger::Event::Paused { paused_frame, .. } = wait_for_event("paused event", &drx, |e| {
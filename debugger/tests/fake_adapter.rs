@@ -0,0 +1,1783 @@
+//! Tests against a scripted fake adapter, which run without debugpy or delve installed
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dap_test_harness::{attach, attach_arguments, attach_with, stack_trace, FakeAdapter, Script};
+use debugger::{
+    AttachArguments, Breakpoint, Debugger, Error, EvaluateContext, Event, LivenessOptions,
+    OutputCategory, ReconnectPolicy, RecoveryOptions, SessionEvent, SessionRegistry, StopReason,
+    TerminateMode, Unresponsive,
+};
+use serde_json::{json, Value};
+use tracing_subscriber::EnvFilter;
+use transport::tap::MessageLog;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[ctor::ctor]
+fn init() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+}
+
+fn attach_to(port: u16) -> Result<Debugger, Error> {
+    Debugger::on_port(port, attach_arguments(port))
+}
+
+/// Initialise the session with `capabilities`, accepting a single breakpoint
+fn configure(capabilities: Value) -> Script {
+    Script::initialised(capabilities)
+        .respond(
+            "setBreakpoints",
+            json!({ "breakpoints": [{ "verified": true, "line": 3 }] }),
+        )
+        .respond("configurationDone", None)
+}
+
+fn breakpoint() -> Breakpoint {
+    Breakpoint {
+        path: PathBuf::from("/project/main.py"),
+        line: 3,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn pauses_at_breakpoint() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(configure(json!({})).paused_with_locals(
+        "breakpoint",
+        json!([{ "name": "a", "value": "1", "variablesReference": 0 }]),
+    ))?;
+
+    let debugger = attach(&adapter)?;
+    let id = debugger.add_breakpoint(&breakpoint())?;
+    assert_eq!(debugger.breakpoint_verified(id), Some(true));
+    debugger.start()?;
+
+    let Some(Event::Paused {
+        paused_frame,
+        reason,
+        ..
+    }) = debugger.wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+    else {
+        panic!("no paused event");
+    };
+    assert_eq!(reason, StopReason::Breakpoint);
+    assert_eq!(paused_frame.frame.id, 7);
+    assert_eq!(paused_frame.variables[0].name, "a");
+
+    let metrics = debugger.metrics().snapshot();
+    assert_eq!(metrics.commands["setBreakpoints"].requests, 1);
+    assert_eq!(metrics.commands["stackTrace"].latency.count(), 1);
+    assert_eq!(metrics.events["stopped"], 1);
+
+    let set_breakpoints = adapter
+        .requests()
+        .into_iter()
+        .find(|r| r["command"] == "setBreakpoints")
+        .expect("setBreakpoints request");
+    assert_eq!(
+        set_breakpoints["arguments"]["source"]["path"],
+        "/project/main.py"
+    );
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn updates_breakpoint() -> eyre::Result<()> {
+    let verified = json!({ "breakpoints": [{ "verified": true, "line": 3 }] });
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .respond("setBreakpoints", verified.clone())
+            .respond("setBreakpoints", verified.clone())
+            .respond("setBreakpoints", json!({ "breakpoints": [] }))
+            .respond("setBreakpoints", verified),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let id = debugger.add_breakpoint(&breakpoint())?;
+    let conditional = Breakpoint {
+        condition: Some("a > 1".to_string()),
+        ..breakpoint()
+    };
+    debugger.update_breakpoint(id, &conditional)?;
+    let moved = Breakpoint {
+        path: PathBuf::from("/project/other.py"),
+        ..conditional.clone()
+    };
+    debugger.update_breakpoint(id, &moved)?;
+    assert_eq!(debugger.breakpoints_with_ids(), vec![(id, moved)]);
+    assert!(debugger.update_breakpoint(id + 1, &breakpoint()).is_err());
+    drop(debugger);
+
+    let sent: Vec<_> = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "setBreakpoints")
+        .map(|r| {
+            let breakpoints: Vec<_> = r["arguments"]["breakpoints"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|b| (b["line"].clone(), b["condition"].clone()))
+                .collect();
+            (r["arguments"]["source"]["path"].clone(), breakpoints)
+        })
+        .collect();
+    assert_eq!(sent.len(), 4, "{sent:?}");
+    let main = json!("/project/main.py");
+    let other = json!("/project/other.py");
+    let condition = json!("a > 1");
+    assert_eq!(sent[0], (main.clone(), vec![(json!(3), Value::Null)]));
+    assert_eq!(sent[1], (main.clone(), vec![(json!(3), condition.clone())]));
+    // moving the breakpoint clears it from the old file
+    assert!(sent[2..].contains(&(main, Vec::new())));
+    assert!(sent[2..].contains(&(other, vec![(json!(3), condition)])));
+    Ok(())
+}
+
+#[test]
+fn removes_breakpoints() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .respond(
+                "setBreakpoints",
+                json!({ "breakpoints": [{ "verified": true, "line": 3 }] }),
+            )
+            .respond(
+                "setBreakpoints",
+                json!({ "breakpoints": [{ "verified": true }, { "verified": true }] }),
+            )
+            .respond(
+                "setBreakpoints",
+                json!({ "breakpoints": [{ "verified": true, "line": 3 }] }),
+            )
+            .respond("setBreakpoints", json!({ "breakpoints": [] })),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let first = debugger.add_breakpoint(&breakpoint())?;
+    let second = debugger.add_breakpoint(&Breakpoint {
+        line: 8,
+        ..breakpoint()
+    })?;
+    debugger.remove_breakpoint(second)?;
+    assert_eq!(debugger.breakpoints_with_ids(), vec![(first, breakpoint())]);
+    debugger.remove_breakpoint(first)?;
+    assert!(debugger.breakpoints_with_ids().is_empty());
+    assert!(debugger.remove_breakpoint(first).is_err());
+    drop(debugger);
+
+    let sent: Vec<_> = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "setBreakpoints")
+        .map(|r| r["arguments"].clone())
+        .collect();
+    assert_eq!(sent.len(), 4, "{sent:?}");
+    for arguments in &sent {
+        assert_eq!(arguments["source"]["path"], "/project/main.py");
+    }
+    // the remaining breakpoint is sent again, then an empty list clears the file
+    assert_eq!(sent[2]["breakpoints"].as_array().unwrap().len(), 1);
+    assert_eq!(sent[2]["breakpoints"][0]["line"], 3);
+    assert_eq!(sent[2]["lines"], json!([3]));
+    assert_eq!(sent[3]["breakpoints"], json!([]));
+    Ok(())
+}
+
+#[test]
+fn sends_hit_conditions() -> eyre::Result<()> {
+    let verified = json!({ "breakpoints": [{ "verified": true, "line": 3 }] });
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .respond("setBreakpoints", verified.clone())
+            .respond("setBreakpoints", verified),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let counted = Breakpoint {
+        hit_condition: Some(">= 5".to_string()),
+        ..breakpoint()
+    };
+    let id = debugger.add_breakpoint(&counted)?;
+    assert_eq!(debugger.breakpoints_with_ids(), vec![(id, counted)]);
+    debugger.update_breakpoint(id, &breakpoint())?;
+    drop(debugger);
+
+    let hit_conditions: Vec<_> = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "setBreakpoints")
+        .map(|r| r["arguments"]["breakpoints"][0]["hitCondition"].clone())
+        .collect();
+    assert_eq!(hit_conditions, vec![json!(">= 5"), Value::Null]);
+    Ok(())
+}
+
+#[test]
+fn reports_stack_that_cannot_be_fetched() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        configure(json!({}))
+            .event("stopped", json!({ "reason": "breakpoint", "threadId": 1 }))
+            .respond_after("stackTrace", stack_trace(), Duration::from_millis(500))
+            .paused_with_locals("step", json!([])),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.set_request_timeout(Some(Duration::from_millis(100)));
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let Some(Event::PauseFailed { thread_id, error }) =
+        debugger.wait_for_event_timeout(|e| matches!(e, Event::PauseFailed { .. }), TIMEOUT)
+    else {
+        panic!("no pause failed event");
+    };
+    assert_eq!(thread_id, 1);
+    assert!(error.contains("stackTrace"), "{error}");
+
+    // the session carries on handling events
+    let Some(Event::Paused { reason, .. }) =
+        debugger.wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+    else {
+        panic!("no paused event");
+    };
+    assert_eq!(reason, StopReason::Step);
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn logpoint_output_is_attributed() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(configure(json!({})).event(
+        "output",
+        json!({
+            "category": "console",
+            "output": "a = 1\n",
+            "source": { "path": "/project/main.py" },
+            "line": 3,
+        }),
+    ))?;
+
+    let debugger = attach(&adapter)?;
+    let id = debugger.add_breakpoint(&Breakpoint {
+        log_message: Some("a = {a}".to_string()),
+        ..breakpoint()
+    })?;
+    debugger.start()?;
+
+    let Some(Event::Output {
+        output, logpoint, ..
+    }) = debugger.wait_for_event_timeout(|e| matches!(e, Event::Output { .. }), TIMEOUT)
+    else {
+        panic!("no output event");
+    };
+    assert_eq!(output, "a = 1\n");
+    assert_eq!(logpoint, Some(id));
+
+    drop(debugger);
+    let set_breakpoints = adapter
+        .join()?
+        .into_iter()
+        .find(|r| r["command"] == "setBreakpoints")
+        .expect("setBreakpoints request");
+    assert_eq!(
+        set_breakpoints["arguments"]["breakpoints"][0]["logMessage"],
+        "a = {a}"
+    );
+    Ok(())
+}
+
+#[test]
+fn sets_exception_breakpoints() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({
+            "exceptionBreakpointFilters": [
+                { "filter": "raised", "label": "Raised Exceptions", "default": false },
+                { "filter": "uncaught", "label": "Uncaught Exceptions", "default": true },
+            ]
+        }))
+        .respond("setExceptionBreakpoints", None),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let filters: Vec<_> = debugger
+        .exception_breakpoint_filters()
+        .into_iter()
+        .map(|f| f.filter)
+        .collect();
+    assert_eq!(filters, vec!["raised", "uncaught"]);
+
+    assert!(debugger
+        .set_exception_breakpoints(&["unknown".to_string()])
+        .is_err());
+    debugger.set_exception_breakpoints(&["uncaught".to_string()])?;
+    assert_eq!(debugger.exception_breakpoints(), vec!["uncaught"]);
+
+    drop(debugger);
+    let requests: Vec<_> = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "setExceptionBreakpoints")
+        .collect();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["arguments"]["filters"], json!(["uncaught"]));
+    Ok(())
+}
+
+#[test]
+fn selects_another_thread() -> eyre::Result<()> {
+    let worker_stack = json!({
+        "stackFrames": [{
+            "id": 9,
+            "name": "worker",
+            "source": { "path": "/project/worker.py" },
+            "line": 12,
+            "column": 0,
+        }]
+    });
+    let adapter = FakeAdapter::start(
+        configure(json!({}))
+            .event(
+                "stopped",
+                json!({ "reason": "breakpoint", "threadId": 1, "allThreadsStopped": true }),
+            )
+            .respond(
+                "threads",
+                json!({ "threads": [{ "id": 1, "name": "MainThread" }, { "id": 2, "name": "worker" }] }),
+            )
+            .respond("stackTrace", stack_trace())
+            .respond("scopes", json!({ "scopes": [] }))
+            .respond("stackTrace", worker_stack)
+            .respond("scopes", json!({ "scopes": [] })),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let Some(Event::Paused {
+        thread_id,
+        all_threads_stopped,
+        ..
+    }) = debugger.wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+    else {
+        panic!("no paused event");
+    };
+    assert_eq!(thread_id, 1);
+    assert!(all_threads_stopped);
+    let threads: Vec<_> = debugger.threads().into_iter().map(|t| t.name).collect();
+    assert_eq!(threads, vec!["MainThread", "worker"]);
+
+    let rx = debugger.subscribe();
+    debugger.select_thread(2)?;
+    let event = rx.recv_timeout(TIMEOUT)?;
+    let Event::ScopeChange {
+        stack, thread_id, ..
+    } = event
+    else {
+        panic!("unexpected event {event:?}");
+    };
+    assert_eq!(thread_id, 2);
+    assert_eq!(stack[0].name, "worker");
+    assert_eq!(debugger.current_thread(), Some(2));
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn steps_single_thread_by_name() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        configure(json!({ "supportsSingleThreadExecutionRequests": true }))
+            .event("stopped", json!({ "reason": "breakpoint", "threadId": 1 }))
+            .respond(
+                "threads",
+                json!({ "threads": [{ "id": 1, "name": "MainThread" }, { "id": 2, "name": "worker" }] }),
+            )
+            .respond("stackTrace", stack_trace())
+            .respond("scopes", json!({ "scopes": [] }))
+            .respond("stackTrace", stack_trace())
+            .respond("scopes", json!({ "scopes": [] }))
+            .respond("next", None)
+            .respond("continue", json!({ "allThreadsContinued": false }))
+            .event("continued", json!({ "threadId": 2 })),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.set_single_thread(true)?;
+    assert!(debugger.single_thread());
+    debugger.start()?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+        .expect("paused event");
+
+    assert!(debugger.select_thread_by_name("missing").is_err());
+    assert_eq!(debugger.select_thread_by_name("worker")?, 2);
+    debugger.step_over()?;
+    debugger.r#continue()?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Running), TIMEOUT)
+        .expect("running event");
+    drop(debugger);
+
+    let requests = adapter.join()?;
+    let next = requests
+        .iter()
+        .find(|r| r["command"] == "next")
+        .expect("next request");
+    assert_eq!(next["arguments"]["threadId"], 2);
+    assert_eq!(next["arguments"]["singleThread"], true);
+    let r#continue = requests
+        .iter()
+        .find(|r| r["command"] == "continue")
+        .expect("continue request");
+    assert_eq!(r#continue["arguments"]["singleThread"], true);
+    Ok(())
+}
+
+#[test]
+fn pauses_selected_thread() -> eyre::Result<()> {
+    let threads =
+        json!({ "threads": [{ "id": 1, "name": "MainThread" }, { "id": 2, "name": "worker" }] });
+    let adapter = FakeAdapter::start(
+        configure(json!({}))
+            .respond("threads", threads.clone())
+            .respond("pause", None)
+            .event("stopped", json!({ "reason": "pause", "threadId": 1 }))
+            .respond("threads", threads.clone())
+            .respond("stackTrace", stack_trace())
+            .respond("scopes", json!({ "scopes": [] }))
+            .respond("stackTrace", stack_trace())
+            .respond("scopes", json!({ "scopes": [] }))
+            .respond("continue", json!({ "allThreadsContinued": true }))
+            .event("continued", json!({ "threadId": 2 }))
+            .respond("threads", threads)
+            .respond("pause", None)
+            .event("stopped", json!({ "reason": "pause", "threadId": 2 }))
+            .respond("stackTrace", stack_trace())
+            .respond("scopes", json!({ "scopes": [] })),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    // no thread is known yet, so the first is paused
+    debugger.pause()?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+        .expect("paused event");
+    debugger.select_thread(2)?;
+    debugger.r#continue()?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Running), TIMEOUT)
+        .expect("running event");
+    debugger.pause()?;
+    // the adapter has answered the pause request once it stops
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+        .expect("paused event");
+    drop(debugger);
+
+    let requests = adapter.join()?;
+    let paused: Vec<_> = requests
+        .iter()
+        .filter(|r| r["command"] == "pause")
+        .map(|r| r["arguments"]["threadId"].clone())
+        .collect();
+    assert_eq!(paused, vec![json!(1), json!(2)]);
+    // every thread is resumed, so the flag is left out
+    let r#continue = requests
+        .iter()
+        .find(|r| r["command"] == "continue")
+        .expect("continue request");
+    assert_eq!(r#continue["arguments"], json!({ "threadId": 2 }));
+    Ok(())
+}
+
+#[test]
+fn single_thread_requires_capability() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(Script::initialised(json!({})))?;
+
+    let debugger = attach(&adapter)?;
+    let err = debugger.set_single_thread(true).unwrap_err();
+    assert!(matches!(err, Error::Unsupported { .. }));
+    assert!(!debugger.single_thread());
+    debugger.set_single_thread(false)?;
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn jumps_to_line() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        configure(json!({ "supportsGotoTargetsRequest": true }))
+            .paused("breakpoint")
+            .respond(
+                "gotoTargets",
+                json!({ "targets": [{ "id": 42, "label": "main.py:10", "line": 10 }] }),
+            )
+            .respond("goto", None),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+        .expect("paused event");
+
+    debugger.jump_to_line("/project/main.py", 10)?;
+
+    drop(debugger);
+    let requests = adapter.join()?;
+    let goto_targets = requests
+        .iter()
+        .find(|r| r["command"] == "gotoTargets")
+        .expect("gotoTargets request");
+    assert_eq!(goto_targets["arguments"]["line"], 10);
+    let goto = requests
+        .iter()
+        .find(|r| r["command"] == "goto")
+        .expect("goto request");
+    assert_eq!(goto["arguments"], json!({ "threadId": 1, "targetId": 42 }));
+    Ok(())
+}
+
+#[test]
+fn completes_repl_input() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({ "supportsCompletionsRequest": true })).respond(
+            "completions",
+            json!({ "targets": [{ "label": "value", "type": "property", "length": 2 }] }),
+        ),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let items = debugger.completions("a.va", 5, Some(7))?;
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        debugger::utils::apply_completion("a.va", 4, &items[0]).0,
+        "a.value"
+    );
+    drop(debugger);
+
+    let requests = adapter.join()?;
+    let request = requests
+        .iter()
+        .find(|r| r["command"] == "completions")
+        .expect("completions request");
+    assert_eq!(request["arguments"]["text"], "a.va");
+    assert_eq!(request["arguments"]["column"], 5);
+    assert_eq!(request["arguments"]["frameId"], 7);
+    Ok(())
+}
+
+#[test]
+fn clipboard_evaluation_falls_back_to_repl() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(Script::initialised(json!({})).respond(
+        "evaluate",
+        json!({ "result": "'abc'", "type": "str", "variablesReference": 0 }),
+    ))?;
+
+    let debugger = attach(&adapter)?;
+    let result = debugger
+        .evaluate("a", 7, EvaluateContext::Clipboard)?
+        .expect("evaluate result");
+    assert_eq!(result.output, "'abc'");
+    assert_eq!(result.r#type.as_deref(), Some("str"));
+    drop(debugger);
+
+    let requests = adapter.join()?;
+    let request = requests
+        .iter()
+        .find(|r| r["command"] == "evaluate")
+        .expect("evaluate request");
+    assert_eq!(request["arguments"]["context"], "repl");
+    assert_eq!(request["arguments"]["frameId"], 7);
+    Ok(())
+}
+
+#[test]
+fn evaluation_errors_are_shown_as_results() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .fail_with_error(
+                "evaluate",
+                "evaluationFailed",
+                json!({
+                    "id": 1,
+                    "format": "name {name} is not defined",
+                    "variables": { "name": "'b'" },
+                    "showUser": true,
+                }),
+            )
+            .fail("evaluate", "notStopped"),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let result = debugger
+        .evaluate("b", 7, EvaluateContext::Repl)?
+        .expect("evaluate result");
+    assert!(result.error);
+    assert_eq!(result.output, "name 'b' is not defined");
+
+    // without a structured error the message is used instead
+    let result = debugger
+        .evaluate("b", 7, EvaluateContext::Repl)?
+        .expect("evaluate result");
+    assert_eq!(result.output, "notStopped");
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn evaluates_watches_in_frame() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .respond(
+                "evaluate",
+                json!({ "result": "1", "type": "int", "variablesReference": 0 }),
+            )
+            .fail("evaluate", "name 'b' is not defined"),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let a = debugger.add_watch("a");
+    let b = debugger.add_watch("b");
+
+    let results = debugger.evaluate_watches(7)?;
+    let results: Vec<_> = results
+        .into_iter()
+        .map(|r| {
+            let result = r.result.expect("evaluate result");
+            (r.watch.id, result.output, result.error)
+        })
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            (a, "1".to_string(), false),
+            (b, "name 'b' is not defined".to_string(), true),
+        ]
+    );
+
+    drop(debugger);
+    let requests = adapter.join()?;
+    let evaluated: Vec<_> = requests
+        .iter()
+        .filter(|r| r["command"] == "evaluate")
+        .map(|r| r["arguments"].clone())
+        .collect();
+    assert_eq!(
+        evaluated,
+        vec![
+            json!({ "expression": "a", "frameId": 7, "context": "watch" }),
+            json!({ "expression": "b", "frameId": 7, "context": "watch" }),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn sets_expression() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({ "supportsSetExpression": true }))
+            .respond("setExpression", json!({ "value": "5", "type": "int" })),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let result = debugger.set_expression("obj.attr", "5", Some(7))?;
+    assert_eq!(result.output, "5");
+    drop(debugger);
+
+    let requests = adapter.join()?;
+    let request = requests
+        .iter()
+        .find(|r| r["command"] == "setExpression")
+        .expect("setExpression request");
+    assert_eq!(request["arguments"]["expression"], "obj.attr");
+    assert_eq!(request["arguments"]["value"], "5");
+    assert_eq!(request["arguments"]["frameId"], 7);
+    Ok(())
+}
+
+#[test]
+fn exception_info_is_included_when_paused() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        configure(json!({ "supportsExceptionInfoRequest": true }))
+            .event("stopped", json!({ "reason": "exception", "threadId": 1 }))
+            .respond(
+                "exceptionInfo",
+                json!({
+                    "exceptionId": "ValueError",
+                    "description": "bad value",
+                    "breakMode": "unhandled",
+                    "details": { "typeName": "ValueError", "stackTrace": "main.py:3" },
+                }),
+            )
+            .respond("stackTrace", stack_trace())
+            .respond("scopes", json!({ "scopes": [] })),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let Some(Event::Paused {
+        exception: Some(exception),
+        ..
+    }) = debugger.wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+    else {
+        panic!("expected paused event with exception");
+    };
+    assert_eq!(exception.exception_id, "ValueError");
+    assert_eq!(exception.description.as_deref(), Some("bad value"));
+    assert_eq!(
+        exception.details.and_then(|d| d.stack_trace).as_deref(),
+        Some("main.py:3")
+    );
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn tracks_modules_and_loaded_sources() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(
+            json!({ "supportsModulesRequest": true, "supportsLoadedSourcesRequest": true }),
+        )
+        .respond(
+            "modules",
+            json!({ "modules": [{ "id": 1, "name": "libc.so" }, { "id": "a", "name": "app" }] }),
+        )
+        .respond(
+            "loadedSources",
+            json!({ "sources": [{ "path": "/project/main.py" }] }),
+        )
+        .event(
+            "module",
+            json!({ "reason": "removed", "module": { "id": 1, "name": "libc.so" } }),
+        )
+        .event(
+            "loadedSource",
+            json!({ "reason": "new", "source": { "path": "/project/lib.py" } }),
+        )
+        .event(
+            "output",
+            json!({ "category": "console", "output": "done\n" }),
+        ),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Output { .. }), TIMEOUT)
+        .expect("output event");
+
+    let modules: Vec<_> = debugger.modules().into_iter().map(|m| m.name).collect();
+    assert_eq!(modules, vec!["app"]);
+    let sources: Vec<_> = debugger
+        .loaded_sources()
+        .into_iter()
+        .filter_map(|s| s.path)
+        .collect();
+    assert_eq!(
+        sources,
+        vec![
+            PathBuf::from("/project/main.py"),
+            PathBuf::from("/project/lib.py")
+        ]
+    );
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn reads_and_writes_memory() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(
+            json!({ "supportsReadMemoryRequest": true, "supportsWriteMemoryRequest": true }),
+        )
+        .respond(
+            "readMemory",
+            json!({ "address": "0x1000", "data": "3q2+7w==", "unreadableBytes": 4 }),
+        )
+        .respond("writeMemory", None),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let memory = debugger.read_memory("0x1000", 0, 8)?;
+    assert_eq!(memory.address, "0x1000");
+    assert_eq!(memory.data, vec![0xde, 0xad, 0xbe, 0xef]);
+    assert_eq!(memory.unreadable_bytes, 4);
+    assert_eq!(debugger.write_memory("0x1000", 2, &[1, 2])?, 2);
+    drop(debugger);
+
+    let requests = adapter.join()?;
+    let write = requests
+        .iter()
+        .find(|r| r["command"] == "writeMemory")
+        .expect("writeMemory request");
+    assert_eq!(write["arguments"]["offset"], 2);
+    assert_eq!(write["arguments"]["data"], "AQI=");
+    Ok(())
+}
+
+#[test]
+fn pages_through_variables() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(Script::initialised(json!({})).respond(
+        "variables",
+        json!({ "variables": [{ "name": "100", "value": "1", "variablesReference": 0 }] }),
+    ))?;
+
+    let debugger = attach(&adapter)?;
+    let variables = debugger.variables_range(5, 100, 50)?;
+    assert_eq!(variables.len(), 1);
+    drop(debugger);
+
+    let requests = adapter.join()?;
+    let request = requests
+        .iter()
+        .find(|r| r["command"] == "variables")
+        .expect("variables request");
+    assert_eq!(request["arguments"]["variablesReference"], 5);
+    assert_eq!(request["arguments"]["start"], 100);
+    assert_eq!(request["arguments"]["count"], 50);
+    Ok(())
+}
+
+#[test]
+fn expands_child_variables() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .respond(
+                "variables",
+                json!({ "variables": [{ "name": "obj", "value": "Obj()", "variablesReference": 3 }] }),
+            )
+            .respond(
+                "variables",
+                json!({ "variables": [{ "name": "field", "value": "1", "variablesReference": 0 }] }),
+            )
+            .fail("variables", "invalid variablesReference"),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let variables = debugger.variables(2)?;
+    assert_eq!(variables[0].name, "obj");
+    let children = debugger.variables(variables[0].variables_reference)?;
+    let children: Vec<_> = children
+        .iter()
+        .map(|v| (v.name.as_str(), v.value.as_str()))
+        .collect();
+    assert_eq!(children, vec![("field", "1")]);
+
+    let err = debugger.variables(99).unwrap_err();
+    assert!(
+        matches!(&err, Error::AdapterError { command, .. } if command == "variables"),
+        "{err:?}"
+    );
+    drop(debugger);
+
+    let references: Vec<_> = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "variables")
+        .map(|r| r["arguments"]["variablesReference"].clone())
+        .collect();
+    assert_eq!(references, vec![json!(2), json!(3), json!(99)]);
+    Ok(())
+}
+
+#[test]
+fn resolves_lazy_variables() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .respond(
+                "variables",
+                json!({ "variables": [{
+                    "name": "prop",
+                    "value": "",
+                    "variablesReference": 3,
+                    "presentationHint": { "lazy": true, "attributes": ["readOnly"], "kind": "property" },
+                }] }),
+            )
+            .respond(
+                "variables",
+                json!({ "variables": [{ "name": "", "value": "42", "type": "int", "variablesReference": 0 }] }),
+            ),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let variables = debugger.variables(2)?;
+    let prop = &variables[0];
+    assert!(prop.is_lazy());
+    assert!(prop.is_read_only());
+    assert_eq!(prop.kind(), Some("property"));
+
+    let resolved = debugger.resolve_lazy(prop)?;
+    assert_eq!(resolved.name, "prop");
+    assert_eq!(resolved.value, "42");
+    assert!(!resolved.is_lazy());
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn refreshes_invalidated_variables() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        configure(json!({}))
+            .paused("breakpoint")
+            .respond("evaluate", json!({ "result": "None", "variablesReference": 0 }))
+            .event("invalidated", json!({ "areas": ["variables"], "threadId": 1 }))
+            .respond("stackTrace", stack_trace())
+            .respond(
+                "scopes",
+                json!({ "scopes": [{ "name": "Locals", "variablesReference": 1, "expensive": false }] }),
+            )
+            .respond(
+                "variables",
+                json!({ "variables": [{ "name": "a", "value": "2", "variablesReference": 0 }] }),
+            ),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+        .expect("paused event");
+
+    let rx = debugger.subscribe();
+    debugger.evaluate("a = 2", 7, EvaluateContext::Repl)?;
+    let event = rx.recv_timeout(TIMEOUT)?;
+    let Event::ScopeChange { paused_frame, .. } = event else {
+        panic!("unexpected event {event:?}");
+    };
+    assert_eq!(paused_frame.frame.id, 7);
+    assert_eq!(paused_frame.variables[0].value, "2");
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn output_has_category_and_location() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        configure(json!({}))
+            .event("output", json!({ "output": "starting\n" }))
+            .event(
+                "output",
+                json!({
+                    "category": "stderr",
+                    "output": "Traceback\n",
+                    "source": { "path": "/project/main.py" },
+                    "line": 5,
+                }),
+            ),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    let rx = debugger.subscribe();
+    debugger.start()?;
+
+    let mut outputs = Vec::new();
+    while outputs.len() < 2 {
+        if let Event::Output {
+            category,
+            output,
+            source,
+            line,
+            ..
+        } = rx.recv_timeout(TIMEOUT)?
+        {
+            outputs.push((category, output, source, line));
+        }
+    }
+    assert_eq!(
+        outputs,
+        vec![
+            (
+                OutputCategory::Console,
+                "starting\n".to_string(),
+                None,
+                None
+            ),
+            (
+                OutputCategory::Stderr,
+                "Traceback\n".to_string(),
+                Some(PathBuf::from("/project/main.py")),
+                Some(5)
+            ),
+        ]
+    );
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn reports_stop_on_entry() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(configure(json!({})).paused("entry"))?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let Some(Event::Paused {
+        reason, exception, ..
+    }) = debugger.wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+    else {
+        panic!("no paused event");
+    };
+    assert_eq!(reason, StopReason::Entry);
+    assert!(exception.is_none());
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn filters_frames_outside_user_code() -> eyre::Result<()> {
+    let stack = json!({
+        "stackFrames": [
+            { "id": 7, "name": "main", "source": { "path": "/project/main.py" }, "line": 3, "column": 0 },
+            { "id": 8, "name": "run", "source": { "path": "/usr/lib/python3/threading.py" }, "line": 10, "column": 0 },
+            {
+                "id": 9,
+                "name": "helper",
+                "source": { "path": "/project/vendor/lib.py", "presentationHint": "deemphasize" },
+                "line": 1,
+                "column": 0,
+            },
+            { "id": 10, "name": "<module>", "source": { "path": "/project/app.py" }, "line": 1, "column": 0 },
+        ]
+    });
+    let adapter = FakeAdapter::start(
+        configure(json!({}))
+            .event("stopped", json!({ "reason": "breakpoint", "threadId": 1 }))
+            .respond("stackTrace", stack.clone())
+            .respond("scopes", json!({ "scopes": [] }))
+            .respond("stackTrace", stack)
+            .respond("scopes", json!({ "scopes": [] })),
+    )?;
+
+    let debugger = attach_with(
+        &adapter,
+        AttachArguments {
+            just_my_code: Some(true),
+            ..attach_arguments(adapter.port())
+        },
+    )?;
+    debugger.set_frame_filter(true)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let Some(Event::Paused { stack, .. }) =
+        debugger.wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+    else {
+        panic!("no paused event");
+    };
+    let names: Vec<_> = stack.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["main", "<module>"]);
+
+    let rx = debugger.subscribe();
+    debugger.set_frame_filter(false)?;
+    let event = rx.recv_timeout(TIMEOUT)?;
+    let Event::ScopeChange { stack, .. } = event else {
+        panic!("unexpected event {event:?}");
+    };
+    assert_eq!(stack.len(), 4);
+
+    drop(debugger);
+    let attach = adapter
+        .join()?
+        .into_iter()
+        .find(|r| r["command"] == "attach")
+        .expect("attach request");
+    assert_eq!(attach["arguments"]["justMyCode"], true);
+    Ok(())
+}
+
+#[test]
+fn registry_tags_events_by_session() -> eyre::Result<()> {
+    let script = |output: &str| {
+        Script::initialised(json!({}))
+            .respond("configurationDone", None)
+            .event("output", json!({ "category": "stdout", "output": output }))
+    };
+    let python = FakeAdapter::start(script("from python\n"))?;
+    let go = FakeAdapter::start(script("from go\n"))?;
+
+    let registry = SessionRegistry::new();
+    let python_id = registry.add("python", attach(&python)?);
+    let go_id = registry.add("go", attach(&go)?);
+    assert_eq!(registry.sessions(), vec![python_id, go_id]);
+    assert_eq!(registry.name(go_id).as_deref(), Some("go"));
+    assert_eq!(registry.active(), Some(python_id));
+
+    // e.g. a session switcher and a logger, which both see every event
+    let receivers = [registry.events(), registry.events()];
+    for id in [python_id, go_id] {
+        let debugger = registry.get(id).expect("registered session");
+        debugger.start()?;
+    }
+    for events in receivers {
+        let mut outputs = Vec::new();
+        while outputs.len() < 2 {
+            if let SessionEvent {
+                session,
+                event: Event::Output { output, .. },
+            } = events.recv_timeout(TIMEOUT)?
+            {
+                outputs.push((session, output));
+            }
+        }
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            vec![
+                (python_id, "from python\n".to_string()),
+                (go_id, "from go\n".to_string())
+            ]
+        );
+    }
+
+    registry.set_active(go_id)?;
+    assert_eq!(registry.active(), Some(go_id));
+    assert!(registry.set_active(42).is_err());
+    drop(registry.remove(go_id));
+    assert_eq!(registry.active(), Some(python_id));
+
+    drop(registry);
+    python.join()?;
+    go.join()?;
+    Ok(())
+}
+
+#[test]
+fn registry_shuts_down_removed_sessions() -> eyre::Result<()> {
+    let script = || Script::initialised(json!({}));
+    let python = FakeAdapter::start(script())?;
+    let go = FakeAdapter::start(script().respond("configurationDone", None).event(
+        "output",
+        json!({ "category": "stdout", "output": "still here\n" }),
+    ))?;
+
+    let registry = SessionRegistry::new();
+    let python_id = registry.add("python", attach(&python)?);
+    let go_id = registry.add("go", attach(&go)?);
+    assert!(registry.active_debugger().is_some());
+
+    drop(registry.remove(python_id).expect("registered session"));
+    assert!(registry.remove(python_id).is_none());
+    assert!(registry.get(python_id).is_none());
+    assert_eq!(registry.sessions(), vec![go_id]);
+    assert_eq!(registry.active(), Some(go_id));
+    let requests = python.join()?;
+    assert!(requests.iter().any(|r| r["command"] == "disconnect"));
+
+    // the remaining session carries on
+    let events = registry.events();
+    let debugger = registry.active_debugger().expect("active session");
+    debugger.start()?;
+    drop(debugger);
+    let output = loop {
+        if let SessionEvent {
+            session,
+            event: Event::Output { output, .. },
+        } = events.recv_timeout(TIMEOUT)?
+        {
+            break (session, output);
+        }
+    };
+    assert_eq!(output, (go_id, "still here\n".to_string()));
+
+    drop(registry.remove(go_id));
+    assert!(registry.is_empty());
+    assert_eq!(registry.active(), None);
+    assert!(registry.active_debugger().is_none());
+    go.join()?;
+    Ok(())
+}
+
+#[test]
+fn resends_breakpoints_when_source_is_loaded() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({}))
+            .respond(
+                "setBreakpoints",
+                json!({ "breakpoints": [{ "verified": false, "line": 3 }] }),
+            )
+            .respond("configurationDone", None)
+            .event(
+                "loadedSource",
+                json!({ "reason": "new", "source": { "path": "/project/other.py" } }),
+            )
+            .event(
+                "loadedSource",
+                json!({ "reason": "new", "source": { "path": "/project/main.py" } }),
+            )
+            .respond(
+                "setBreakpoints",
+                json!({ "breakpoints": [{ "verified": true, "line": 3 }] }),
+            ),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    let id = debugger.add_breakpoint(&breakpoint())?;
+    assert_eq!(debugger.breakpoint_verified(id), Some(false));
+    let rx = debugger.subscribe();
+    debugger.start()?;
+
+    loop {
+        if let Event::BreakpointsChanged { .. } = rx.recv_timeout(TIMEOUT)? {
+            break;
+        }
+    }
+    assert_eq!(debugger.breakpoint_verified(id), Some(true));
+
+    drop(debugger);
+    let set_breakpoints = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "setBreakpoints")
+        .count();
+    assert_eq!(set_breakpoints, 2);
+    Ok(())
+}
+
+#[test]
+fn errors_distinguish_failure_kinds() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({ "supportsSetExpression": true }))
+            .fail("setExpression", "cannot assign to literal"),
+    )?;
+
+    let debugger = attach(&adapter)?;
+
+    let err = debugger.set_expression("1", "2", None).unwrap_err();
+    let Error::AdapterError {
+        command, message, ..
+    } = err
+    else {
+        panic!("unexpected error {err:?}");
+    };
+    assert_eq!(command, "setExpression");
+    assert_eq!(message.as_deref(), Some("cannot assign to literal"));
+
+    let err = debugger.read_memory("0x1000", 0, 4).unwrap_err();
+    assert!(matches!(err, Error::Unsupported { command } if command == "readMemory"));
+
+    debugger.shutdown()?;
+    assert!(matches!(debugger.reload(), Err(Error::Disconnected)));
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn connection_errors_distinguish_failure_kinds() -> eyre::Result<()> {
+    // nothing is listening once the listener is dropped
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    let err = attach_to(port)
+        .err()
+        .expect("connecting without an adapter");
+    assert!(
+        matches!(err, Error::Disconnected),
+        "unexpected error {err:?}"
+    );
+
+    let adapter = FakeAdapter::start(Script::new().fail("initialize", "unsupported client"))?;
+    let err = attach_to(adapter.port())
+        .err()
+        .expect("failed initialisation");
+    assert!(
+        matches!(&err, Error::AdapterError { command, .. } if command == "initialize"),
+        "unexpected error {err:?}"
+    );
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn requests_time_out() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({ "supportsSetExpression": true }))
+            .respond_after(
+                "setExpression",
+                json!({ "value": "2" }),
+                Duration::from_millis(500),
+            )
+            .respond("setExpression", json!({ "value": "3" })),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.set_request_timeout(Some(Duration::from_millis(100)));
+
+    let err = debugger.set_expression("a", "2", None).unwrap_err();
+    assert!(matches!(err, Error::Timeout { command } if command == "setExpression"));
+
+    // the late response is ignored rather than answering the next request
+    debugger.set_request_timeout(None);
+    let result = debugger.set_expression("a", "3", None)?;
+    assert_eq!(result.output, "3");
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn reports_unresponsive_adapter() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(Script::initialised(json!({})).sleep(Duration::from_secs(1)))?;
+
+    let debugger = attach(&adapter)?;
+    let events = debugger.subscribe();
+    let log = Arc::new(MessageLog::new(64));
+    debugger.set_message_tap(Some(log.clone()));
+    debugger.enable_liveness(LivenessOptions {
+        interval: Duration::from_millis(50),
+        timeout: Duration::from_millis(200),
+    });
+
+    let reason = events
+        .iter()
+        .find_map(|e| match e {
+            Event::AdapterUnresponsive { reason } => Some(reason),
+            _ => None,
+        })
+        .expect("unresponsive event");
+    assert_eq!(
+        reason,
+        Unresponsive::NoResponse {
+            timeout: Duration::from_millis(200)
+        }
+    );
+
+    // reported once while the adapter stays unresponsive, and not after it recovers
+    std::thread::sleep(Duration::from_millis(1500));
+    assert!(!events
+        .try_iter()
+        .any(|e| matches!(e, Event::AdapterUnresponsive { .. })));
+
+    // the checks are not user requests
+    assert!(adapter.commands().iter().any(|c| c == "threads"));
+    assert!(!log
+        .messages()
+        .iter()
+        .any(|m| String::from_utf8_lossy(&m.body).contains("threads")));
+    assert!(!debugger
+        .metrics()
+        .snapshot()
+        .commands
+        .contains_key("threads"));
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn reports_unresponsive_adapter_while_a_request_is_pending() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(Script::initialised(json!({})).sleep(Duration::from_secs(2)))?;
+
+    let debugger = attach(&adapter)?;
+    debugger.set_request_timeout(None);
+    let events = debugger.subscribe();
+
+    let options = LivenessOptions {
+        interval: Duration::from_millis(200),
+        timeout: Duration::from_millis(200),
+    };
+    let deadline = options.interval + options.timeout + Duration::from_millis(250);
+    let started = Instant::now();
+    debugger.enable_liveness(options);
+
+    std::thread::scope(|s| {
+        // never answered, so the session stays locked until the adapter goes away
+        s.spawn(|| debugger.evaluate("slow()", 7, EvaluateContext::Repl));
+
+        let reason = loop {
+            match events.recv_timeout(deadline.saturating_sub(started.elapsed())) {
+                Ok(Event::AdapterUnresponsive { reason }) => break reason,
+                Ok(_) => continue,
+                Err(_) => panic!("no unresponsive event within {deadline:?}"),
+            }
+        };
+        assert_eq!(
+            reason,
+            Unresponsive::NoResponse {
+                timeout: Duration::from_millis(200)
+            }
+        );
+        // the checks are not user requests
+        assert!(!debugger
+            .metrics()
+            .snapshot()
+            .commands
+            .contains_key("threads"));
+    });
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
+
+#[test]
+fn reports_capabilities_and_skips_unsupported_requests() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(Script::initialised(
+        json!({ "supportsRestartFrame": true, "supportsSetExpression": false }),
+    ))?;
+
+    let debugger = attach(&adapter)?;
+    let capabilities = debugger.capabilities();
+    assert_eq!(capabilities.supports_restart_frame, Some(true));
+    assert_eq!(capabilities.supports_set_expression, Some(false));
+    assert_eq!(capabilities.supports_goto_targets_request, None);
+
+    let err = debugger
+        .jump_to_line(PathBuf::from("/project/main.py"), 5)
+        .unwrap_err();
+    assert!(matches!(err, Error::Unsupported { command } if command == "gotoTargets"));
+    let err = debugger.set_expression("a", "1", None).unwrap_err();
+    assert!(matches!(err, Error::Unsupported { command } if command == "setExpression"));
+
+    drop(debugger);
+    let commands: Vec<_> = adapter
+        .join()?
+        .iter()
+        .filter_map(|r| r["command"].as_str().map(str::to_string))
+        .collect();
+    assert!(!commands.iter().any(|c| c == "gotoTargets" || c == "goto"));
+    assert!(!commands.iter().any(|c| c == "setExpression"));
+    Ok(())
+}
+
+#[test]
+fn restart_frame_requires_capability() -> eyre::Result<()> {
+    let adapter = FakeAdapter::serve([
+        Script::initialised(json!({})),
+        Script::initialised(json!({ "supportsRestartFrame": true })).respond("restartFrame", None),
+    ])?;
+
+    let debugger = attach(&adapter)?;
+    let err = debugger.restart_frame(7).unwrap_err();
+    assert!(matches!(err, Error::Unsupported { .. }));
+    drop(debugger);
+
+    let debugger = attach(&adapter)?;
+    debugger.restart_frame(7)?;
+    drop(debugger);
+
+    let restarts: Vec<_> = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "restartFrame")
+        .collect();
+    assert_eq!(restarts.len(), 1);
+    assert_eq!(restarts[0]["arguments"]["frameId"], 7);
+    Ok(())
+}
+
+#[test]
+fn stop_terminates_debugee() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        Script::initialised(json!({ "supportsTerminateRequest": true })).respond("terminate", None),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.stop(TerminateMode::Terminate)?;
+    drop(debugger);
+
+    let commands: Vec<_> = adapter
+        .join()?
+        .into_iter()
+        .filter(|r| r["command"] == "terminate" || r["command"] == "disconnect")
+        .collect();
+    assert_eq!(commands.len(), 2);
+    assert_eq!(commands[0]["command"], "terminate");
+    assert_eq!(commands[1]["arguments"]["terminateDebuggee"], true);
+    Ok(())
+}
+
+#[test]
+fn reloads_within_adapter() -> eyre::Result<()> {
+    let adapter = FakeAdapter::serve([
+        Script::initialised(json!({})),
+        configure(json!({ "supportsRestartRequest": true })).respond("restart", None),
+    ])?;
+
+    let debugger = attach(&adapter)?;
+    let err = debugger.reload().unwrap_err();
+    assert!(matches!(err, Error::Unsupported { .. }));
+    drop(debugger);
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.add_watch("a");
+    debugger.start()?;
+
+    let rx = debugger.subscribe();
+    debugger.reload()?;
+    assert!(rx.try_iter().any(|e| matches!(e, Event::Running)));
+    assert_eq!(debugger.watches().len(), 1);
+
+    drop(debugger);
+    let requests = adapter.join()?;
+    let restart = requests
+        .iter()
+        .find(|r| r["command"] == "restart")
+        .expect("restart request");
+    assert_eq!(
+        restart["arguments"]["arguments"]["workspaceFolder"],
+        "/project"
+    );
+    // one connection for each session, reloading did not reconnect
+    assert_eq!(
+        requests
+            .iter()
+            .filter(|r| r["command"] == "initialize")
+            .count(),
+        2
+    );
+    Ok(())
+}
+
+#[test]
+fn restart_and_reload_resume_the_session() -> eyre::Result<()> {
+    let adapter = FakeAdapter::start(
+        configure(json!({ "supportsRestartRequest": true }))
+            .paused_with_locals("breakpoint", json!([]))
+            .respond("restart", None)
+            .paused_with_locals("breakpoint", json!([]))
+            .respond("restart", None),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    let events = debugger.subscribe();
+    debugger.start()?;
+
+    for name in ["restart", "reload"] {
+        events
+            .iter()
+            .find(|e| matches!(e, Event::Paused { .. }))
+            .expect("paused event");
+        assert_eq!(debugger.current_thread(), Some(1), "{name}");
+        assert_eq!(debugger.current_frame_id(), Some(7), "{name}");
+
+        if name == "restart" {
+            debugger.restart()?;
+        } else {
+            debugger.reload()?;
+        }
+        assert!(
+            events.try_iter().any(|e| matches!(e, Event::Running)),
+            "{name}"
+        );
+        assert_eq!(debugger.current_thread(), None, "{name}");
+        assert_eq!(debugger.current_frame_id(), None, "{name}");
+        assert!(debugger.threads().is_empty(), "{name}");
+    }
+
+    drop(debugger);
+    let requests = adapter.join()?;
+    let arguments: Vec<_> = requests
+        .iter()
+        .filter(|r| r["command"] == "restart")
+        .map(|r| r["arguments"]["arguments"]["workspaceFolder"].clone())
+        .collect();
+    // only reloading sends the arguments again
+    assert_eq!(arguments, vec![Value::Null, json!("/project")]);
+    Ok(())
+}
+
+#[test]
+fn restarts_on_new_connection() -> eyre::Result<()> {
+    let adapter = FakeAdapter::serve([
+        configure(json!({}))
+            .respond("disconnect", None)
+            .event("terminated", None)
+            .disconnect(),
+        configure(json!({})),
+    ])?;
+
+    let debugger = attach(&adapter)?;
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let rx = debugger.subscribe();
+    debugger.restart()?;
+    let events: Vec<_> = rx.try_iter().collect();
+    assert!(events.iter().any(|e| matches!(e, Event::Running)));
+    // events from the old connection must not end the restarted session
+    std::thread::sleep(Duration::from_millis(200));
+    assert!(!rx.try_iter().any(|e| matches!(e, Event::Ended)));
+
+    drop(debugger);
+    let requests = adapter.join()?;
+    let disconnect = requests
+        .iter()
+        .find(|r| r["command"] == "disconnect")
+        .expect("disconnect request");
+    // attached debugees are left running
+    assert_eq!(disconnect["arguments"]["terminateDebuggee"], false);
+    assert_eq!(
+        requests
+            .iter()
+            .filter(|r| r["command"] == "setBreakpoints")
+            .count(),
+        2
+    );
+    Ok(())
+}
+
+#[test]
+fn lost_connection_ends_session() -> eyre::Result<()> {
+    for recovery in [
+        None,
+        Some(RecoveryOptions {
+            restart_adapter: false,
+            reconnect: ReconnectPolicy {
+                max_attempts: 2,
+                ..Default::default()
+            },
+        }),
+    ] {
+        // every reconnection is dropped straight away, so recovery cannot succeed
+        let attempts = recovery
+            .as_ref()
+            .map_or(0, |options| options.reconnect.max_attempts);
+        let reconnections = (0..attempts).map(|_| Script::new().disconnect());
+        let adapter = FakeAdapter::serve(
+            std::iter::once(configure(json!({})).disconnect()).chain(reconnections),
+        )?;
+        let debugger = attach(&adapter)?;
+        if let Some(options) = recovery.clone() {
+            debugger.enable_recovery(options);
+        }
+        debugger.add_breakpoint(&breakpoint())?;
+        debugger.start()?;
+
+        let event = debugger.wait_for_event_timeout(
+            |e| matches!(e, Event::Ended | Event::Recovered { .. }),
+            TIMEOUT,
+        );
+        assert!(
+            matches!(event, Some(Event::Ended)),
+            "{recovery:?}: got {event:?}"
+        );
+        assert_eq!(debugger.metrics().snapshot().reconnects, 0);
+        // requests fail rather than waiting on the closed connection
+        assert!(debugger.evaluate("1", 7, EvaluateContext::Repl).is_err());
+
+        drop(debugger);
+        adapter.join()?;
+    }
+    Ok(())
+}
+
+#[test]
+fn recovers_after_adapter_disconnects() -> eyre::Result<()> {
+    let adapter = FakeAdapter::serve([configure(json!({})).disconnect(), configure(json!({}))])?;
+
+    let debugger = attach(&adapter)?;
+    debugger.enable_recovery(RecoveryOptions {
+        restart_adapter: false,
+        reconnect: ReconnectPolicy {
+            max_attempts: 1,
+            ..Default::default()
+        },
+    });
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let Some(Event::Recovered {
+        attempts,
+        restarted_adapter,
+    }) = debugger.wait_for_event_timeout(|e| matches!(e, Event::Recovered { .. }), TIMEOUT)
+    else {
+        panic!("no recovered event");
+    };
+    assert_eq!(attempts, 1);
+    assert!(!restarted_adapter);
+    assert_eq!(debugger.metrics().snapshot().reconnects, 1);
+
+    drop(debugger);
+    let commands: Vec<_> = adapter
+        .join()?
+        .iter()
+        .filter_map(|r| r["command"].as_str().map(str::to_string))
+        .collect();
+    // the breakpoint is sent again to the new connection
+    assert_eq!(
+        commands
+            .iter()
+            .filter(|c| c.as_str() == "setBreakpoints")
+            .count(),
+        2
+    );
+    Ok(())
+}
+
+#[test]
+fn recovers_once_restarted_adapter_is_back() -> eyre::Result<()> {
+    // connections are dropped for a while, as when an attached adapter is restarting
+    let adapter = FakeAdapter::restarting(
+        configure(json!({})).disconnect(),
+        Duration::from_secs(1),
+        configure(json!({})),
+    )?;
+
+    let debugger = attach(&adapter)?;
+    // attempts after 250ms, 750ms and 1750ms
+    debugger.enable_recovery(RecoveryOptions {
+        restart_adapter: false,
+        reconnect: ReconnectPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(2),
+        },
+    });
+    debugger.add_breakpoint(&breakpoint())?;
+    debugger.start()?;
+
+    let event = debugger.wait_for_event_timeout(
+        |e| matches!(e, Event::Ended | Event::Recovered { .. }),
+        TIMEOUT,
+    );
+    assert!(
+        matches!(event, Some(Event::Recovered { attempts: 3, .. })),
+        "got {event:?}"
+    );
+
+    drop(debugger);
+    adapter.join()?;
+    Ok(())
+}
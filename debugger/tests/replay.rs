@@ -0,0 +1,141 @@
+//! Replaying recorded sessions without an adapter
+use std::{path::PathBuf, time::Duration};
+
+use debugger::{Breakpoint, Debugger, Event, StopReason};
+use serde_json::{json, Value};
+use tracing_subscriber::EnvFilter;
+use transport::{
+    replay::{Recording, Timing},
+    tap::{Direction, TappedMessage},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[ctor::ctor]
+fn init() {
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .try_init();
+}
+
+/// Recorded request and the adapter's response to it, which has no body if `body` is null
+fn exchange(seq: i64, command: &str, arguments: Value, body: Value) -> [(Direction, Value); 2] {
+    let mut response = json!({
+        "seq": seq,
+        "type": "response",
+        "request_seq": seq,
+        "command": command,
+        "success": true,
+    });
+    if !body.is_null() {
+        response["body"] = body;
+    }
+    [
+        (
+            Direction::ClientToServer,
+            json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments }),
+        ),
+        (Direction::ServerToClient, response),
+    ]
+}
+
+fn event(event: &str, body: Value) -> [(Direction, Value); 1] {
+    let mut message = json!({ "seq": 0, "type": "event", "event": event });
+    if !body.is_null() {
+        message["body"] = body;
+    }
+    [(Direction::ServerToClient, message)]
+}
+
+/// Session that attached to a program and paused at a breakpoint
+fn recording() -> Recording {
+    let stack_trace = json!({
+        "stackFrames": [{
+            "id": 7,
+            "name": "main",
+            "source": { "path": "/project/main.py" },
+            "line": 3,
+            "column": 0,
+        }]
+    });
+    let messages = [
+        &exchange(1, "initialize", json!({}), json!({}))[..],
+        &exchange(2, "attach", json!({ "port": 5678 }), Value::Null),
+        &event("initialized", Value::Null),
+        &exchange(
+            3,
+            "setBreakpoints",
+            json!({ "source": { "path": "/project/main.py" } }),
+            json!({ "breakpoints": [{ "verified": true, "line": 3 }] }),
+        ),
+        &exchange(4, "configurationDone", json!({}), Value::Null),
+        &event("stopped", json!({ "reason": "breakpoint", "threadId": 1 })),
+        &exchange(5, "stackTrace", json!({ "threadId": 1 }), stack_trace),
+        &exchange(
+            6,
+            "scopes",
+            json!({ "frameId": 7 }),
+            json!({ "scopes": [{ "name": "Locals", "variablesReference": 1, "expensive": false }] }),
+        ),
+        &exchange(
+            7,
+            "variables",
+            json!({ "variablesReference": 1 }),
+            json!({ "variables": [{ "name": "a", "value": "1", "variablesReference": 0 }] }),
+        ),
+    ]
+    .concat();
+
+    Recording::new(
+        messages
+            .into_iter()
+            .enumerate()
+            .map(|(i, (direction, payload))| TappedMessage {
+                direction,
+                timestamp: Duration::from_millis(10 * i as u64),
+                body: payload.to_string().into(),
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn replays_paused_session() -> eyre::Result<()> {
+    let debugger = Debugger::replay(recording(), Timing::Accelerated(10.0))?;
+    debugger
+        .wait_for_event_timeout(|e| matches!(e, Event::Initialised), TIMEOUT)
+        .expect("initialised event");
+    let id = debugger.add_breakpoint(&Breakpoint {
+        path: PathBuf::from("/project/main.py"),
+        line: 3,
+        ..Default::default()
+    })?;
+    assert_eq!(debugger.breakpoint_verified(id), Some(true));
+    debugger.start()?;
+
+    let Some(Event::Paused {
+        paused_frame,
+        reason,
+        ..
+    }) = debugger.wait_for_event_timeout(|e| matches!(e, Event::Paused { .. }), TIMEOUT)
+    else {
+        panic!("no paused event");
+    };
+    assert_eq!(reason, StopReason::Breakpoint);
+    assert_eq!(paused_frame.frame.id, 7);
+    assert_eq!(paused_frame.variables[0].name, "a");
+
+    // the session ends cleanly even though `disconnect` was not recorded
+    debugger.shutdown()?;
+    Ok(())
+}
+
+#[test]
+fn replay_cannot_restart() -> eyre::Result<()> {
+    let debugger = Debugger::replay(recording(), Timing::Immediate)?;
+    assert!(matches!(
+        debugger.restart(),
+        Err(debugger::Error::Unsupported { .. })
+    ));
+    Ok(())
+}
@@ -18,6 +18,8 @@ pub struct CodeView<'a> {
     breakpoints: &'a mut HashSet<debugger::Breakpoint>,
     /// Should we jump to the current position or not?
     jump: &'a bool,
+    /// Set to the cursor line (1-indexed) when the user chooses "Set next statement"
+    next_statement: Option<&'a mut Option<usize>>,
 }
 
 impl<'a> CodeView<'a> {
@@ -38,9 +40,16 @@ impl<'a> CodeView<'a> {
             highlight_line,
             breakpoints,
             jump,
+            next_statement: None,
         }
     }
 
+    /// Offer to move execution to the cursor line, writing the chosen line to `next_statement`
+    pub fn with_next_statement(mut self, next_statement: &'a mut Option<usize>) -> Self {
+        self.next_statement = Some(next_statement);
+        self
+    }
+
     fn breakpoint_positions(&self) -> HashSet<usize> {
         HashSet::from_iter(self.breakpoints.iter().map(|b| b.line))
     }
@@ -84,8 +93,27 @@ impl egui::Widget for CodeView<'_> {
             ui.fonts(|f| f.layout_job(layout_job))
         };
         let response = egui::ScrollArea::vertical().show(ui, |ui| {
-            ui.add(TextEdit::multiline(&mut self.content).layouter(&mut layouter))
+            TextEdit::multiline(&mut self.content)
+                .layouter(&mut layouter)
+                .show(ui)
         });
+        let output = &response.inner;
+
+        if let Some(next_statement) = self.next_statement.as_mut() {
+            let cursor_line = output
+                .cursor_range
+                .map(|range| range.primary.pcursor.paragraph + 1);
+            output.response.context_menu(|ui| {
+                let button = ui.add_enabled(
+                    cursor_line.is_some(),
+                    egui::Button::new("Set next statement"),
+                );
+                if button.clicked() {
+                    **next_statement = cursor_line;
+                    ui.close_menu();
+                }
+            });
+        }
 
         //               |  +------------------+ |
         //               |  |                  | |
@@ -127,9 +155,10 @@ impl egui::Widget for CodeView<'_> {
         }
 
         // tracing::debug!(?state.offset, ?response.content_size, ?response.inner_rect.max, "positional info");
-        self.update_breakpoints(&response.inner);
+        let response = response.inner.response;
+        self.update_breakpoints(&response);
 
-        response.inner
+        response
     }
 }
 
@@ -0,0 +1,99 @@
+//! Keyboard shortcuts configured in the user settings
+use debugger::Debugger;
+use eframe::egui::{Context, Key, KeyboardShortcut, Modifiers};
+use settings::Keybindings;
+
+type Action = fn(&Debugger) -> debugger::Result<()>;
+
+/// Parse a shortcut such as `ctrl+shift+F5`
+fn parse(shortcut: &str) -> Option<KeyboardShortcut> {
+    let mut modifiers = Modifiers::NONE;
+    let mut parts: Vec<&str> = shortcut.split('+').map(str::trim).collect();
+    let key = Key::from_name(parts.pop()?)?;
+    for part in parts {
+        modifiers = modifiers
+            | match part.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                "cmd" | "command" => Modifiers::MAC_CMD,
+                _ => return None,
+            };
+    }
+    Some(KeyboardShortcut::new(modifiers, key))
+}
+
+/// Configured shortcuts, parsed once when the settings are loaded or change
+pub(crate) struct Shortcuts(Vec<(KeyboardShortcut, Action)>);
+
+impl Shortcuts {
+    /// Parse the configured shortcuts, skipping any that are invalid with a warning
+    pub(crate) fn new(keybindings: &Keybindings) -> Self {
+        let actions: [(&str, Action); 4] = [
+            (&keybindings.r#continue, Debugger::r#continue),
+            (&keybindings.step_over, Debugger::step_over),
+            (&keybindings.step_in, Debugger::step_in),
+            (&keybindings.step_out, Debugger::step_out),
+        ];
+        let shortcuts = actions
+            .into_iter()
+            .filter_map(|(shortcut, action)| match parse(shortcut) {
+                Some(parsed) => Some((parsed, action)),
+                None => {
+                    tracing::warn!(%shortcut, "invalid keybinding");
+                    None
+                }
+            })
+            .collect();
+        Self(shortcuts)
+    }
+
+    /// Run the debugger action for any shortcut pressed this frame
+    pub(crate) fn handle(&self, ctx: &Context, debugger: &Debugger) {
+        for (shortcut, action) in &self.0 {
+            if ctx.input_mut(|i| i.consume_shortcut(shortcut)) {
+                if let Err(e) = action(debugger) {
+                    tracing::warn!(error = %e, "running keybinding action");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+    use settings::Keybindings;
+
+    use super::{parse, Shortcuts};
+
+    #[test]
+    fn parse_shortcuts() {
+        assert_eq!(
+            parse("F5"),
+            Some(KeyboardShortcut::new(Modifiers::NONE, Key::F5))
+        );
+        assert_eq!(
+            parse("ctrl+shift+N"),
+            Some(KeyboardShortcut::new(
+                Modifiers::CTRL | Modifiers::SHIFT,
+                Key::N
+            ))
+        );
+        assert_eq!(parse("hyper+F5"), None);
+    }
+
+    #[test]
+    fn invalid_shortcuts_are_skipped() {
+        let keybindings = Keybindings {
+            step_over: "hyper+F10".to_string(),
+            ..Default::default()
+        };
+        let shortcuts = Shortcuts::new(&keybindings);
+        assert_eq!(shortcuts.0.len(), 3);
+        assert!(shortcuts
+            .0
+            .iter()
+            .all(|(shortcut, _)| shortcut.logical_key != Key::F10));
+    }
+}
@@ -0,0 +1,176 @@
+//! Capture of the application's own tracing output for display in the UI
+use std::{collections::VecDeque, fmt::Write};
+
+use crossbeam_channel::{Receiver, Sender};
+use tracing::{field::Field, Level, Subscriber};
+use tracing_subscriber::{layer::Context, Layer};
+
+/// Maximum number of log records kept for display
+const MAX_RECORDS: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub(crate) struct LogRecord {
+    pub(crate) level: Level,
+    pub(crate) target: String,
+    pub(crate) message: String,
+}
+
+/// [`tracing_subscriber::Layer`] that forwards each formatted event over a channel
+pub(crate) struct ChannelLayer {
+    tx: Sender<LogRecord>,
+}
+
+impl ChannelLayer {
+    pub(crate) fn new() -> (Self, Receiver<LogRecord>) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        (Self { tx }, rx)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ChannelLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        // the receiver is dropped when the UI closes, after which logs are no longer needed
+        let _ = self.tx.send(LogRecord {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+        });
+    }
+}
+
+/// Formats the `message` field followed by any other fields as `name=value`
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl tracing::field::Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.message);
+            let _ = write!(self.message, "{value:?}{fields}");
+        } else {
+            let _ = write!(self.message, " {}={value:?}", field.name());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.insert_str(0, value);
+        } else {
+            let _ = write!(self.message, " {}={value}", field.name());
+        }
+    }
+}
+
+/// Log records received from a [`ChannelLayer`] along with the current view settings
+pub(crate) struct Logs {
+    rx: Receiver<LogRecord>,
+    records: VecDeque<LogRecord>,
+    /// Least severe level that is displayed
+    pub(crate) level: Level,
+    pub(crate) search: String,
+}
+
+impl Logs {
+    pub(crate) fn new(rx: Receiver<LogRecord>) -> Self {
+        Self {
+            rx,
+            records: VecDeque::new(),
+            level: Level::INFO,
+            search: String::new(),
+        }
+    }
+
+    /// Move any newly received records into the store, dropping the oldest if it is full
+    pub(crate) fn receive(&mut self) {
+        for record in self.rx.try_iter() {
+            if self.records.len() == MAX_RECORDS {
+                self.records.pop_front();
+            }
+            self.records.push_back(record);
+        }
+    }
+
+    /// Records matching the level filter and search text, oldest first
+    pub(crate) fn filtered(&self) -> impl Iterator<Item = &LogRecord> {
+        self.records.iter().filter(|record| {
+            record.level <= self.level
+                && (self.search.is_empty()
+                    || record.message.contains(&self.search)
+                    || record.target.contains(&self.search))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::{ChannelLayer, LogRecord, Logs, MAX_RECORDS};
+
+    fn record(level: Level, target: &str, message: &str) -> LogRecord {
+        LogRecord {
+            level,
+            target: target.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn layer_forwards_formatted_events() {
+        let (layer, rx) = ChannelLayer::new();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(port = 5678, host = "localhost", "connecting");
+        });
+
+        let records: Vec<_> = rx.try_iter().collect();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].level, Level::WARN);
+        assert_eq!(records[0].target, module_path!());
+        assert_eq!(records[0].message, "connecting port=5678 host=localhost");
+    }
+
+    #[test]
+    fn filters_by_level_and_search() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut logs = Logs::new(rx);
+        tx.send(record(Level::ERROR, "transport", "connection lost"))
+            .unwrap();
+        tx.send(record(Level::INFO, "debugger", "paused")).unwrap();
+        tx.send(record(Level::DEBUG, "transport", "sending message"))
+            .unwrap();
+        logs.receive();
+
+        let messages =
+            |logs: &Logs| -> Vec<String> { logs.filtered().map(|r| r.message.clone()).collect() };
+        assert_eq!(messages(&logs), vec!["connection lost", "paused"]);
+
+        logs.level = Level::DEBUG;
+        logs.search = "transport".to_string();
+        assert_eq!(messages(&logs), vec!["connection lost", "sending message"]);
+
+        logs.search = "paused".to_string();
+        assert_eq!(messages(&logs), vec!["paused"]);
+    }
+
+    #[test]
+    fn drops_the_oldest_records() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut logs = Logs::new(rx);
+        for i in 0..MAX_RECORDS + 5 {
+            tx.send(record(Level::INFO, "gui", &i.to_string())).unwrap();
+        }
+        logs.receive();
+
+        let messages: Vec<_> = logs.filtered().map(|r| r.message.as_str()).collect();
+        assert_eq!(messages.len(), MAX_RECORDS);
+        assert_eq!(messages[0], "5");
+        assert_eq!(messages[MAX_RECORDS - 1], (MAX_RECORDS + 4).to_string());
+    }
+}
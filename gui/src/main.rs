@@ -1,5 +1,6 @@
 use std::{
     cell::RefCell,
+    collections::HashMap,
     fs::create_dir_all,
     path::PathBuf,
     sync::{Arc, Mutex},
@@ -7,17 +8,27 @@ use std::{
 };
 
 use clap::Parser;
-use debugger::{AttachArguments, Debugger, LaunchArguments, PausedFrame};
+use debugger::{
+    AttachArguments, BreakpointId, Debugger, ExceptionInfo, LaunchArguments, LivenessOptions,
+    OutputCategory, PausedFrame, RecoveryOptions, TerminateMode,
+};
 use eframe::egui::{self, Visuals};
 use eyre::WrapErr;
 use launch_configuration::{ChosenLaunchConfiguration, Debugpy, LaunchConfiguration};
-use state::StateManager;
-use transport::types::{StackFrame, StackFrameId};
+use logs::{ChannelLayer, LogRecord, Logs};
+use settings::{Settings, SettingsHandle, Theme};
+use state::{ProjectBreakpoints, StateManager};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+use transport::types::{CompletionItem, StackFrame, StackFrameId, Variable, VariablesReference};
 
 mod code_view;
+mod keybindings;
+mod logs;
 mod renderer;
 mod ui;
 
+use ui::console::ConsoleEntry;
+
 #[derive(Parser)]
 struct Args {
     config_path: PathBuf,
@@ -27,6 +38,10 @@ struct Args {
 
     #[clap(short, long)]
     breakpoints: Vec<usize>,
+
+    /// Report messages from the debug adapter that break the protocol in the console
+    #[clap(long)]
+    strict_protocol: bool,
 }
 
 #[cfg(feature = "sentry")]
@@ -68,6 +83,7 @@ impl From<debugger::Event> for State {
                 stack,
                 paused_frame,
                 breakpoints,
+                ..
             } => State::Paused {
                 stack,
                 paused_frame: Box::new(paused_frame),
@@ -80,11 +96,21 @@ impl From<debugger::Event> for State {
                 stack,
                 breakpoints,
                 paused_frame,
+                ..
             } => State::Paused {
                 stack,
                 breakpoints,
                 paused_frame: Box::new(paused_frame),
             },
+            debugger::Event::Output { .. }
+            | debugger::Event::AdapterLog { .. }
+            | debugger::Event::BreakpointsChanged { .. }
+            | debugger::Event::Recovered { .. }
+            | debugger::Event::ProtocolViolation { .. }
+            | debugger::Event::AdapterUnresponsive { .. }
+            | debugger::Event::PauseFailed { .. } => {
+                unreachable!("{event:?} does not change the debugger state")
+            }
         }
     }
 }
@@ -93,6 +119,24 @@ impl From<debugger::Event> for State {
 enum TabState {
     Variables,
     Repl,
+    Logs,
+    Modules,
+}
+
+/// Children of a variable fetched so far
+#[derive(Default)]
+struct LoadedChildren {
+    variables: Vec<Variable>,
+    /// Whether every child has been fetched
+    complete: bool,
+}
+
+/// Position in a source file
+struct SourceLocation {
+    path: PathBuf,
+    line: usize,
+    /// Whether the code viewer should scroll to `line` when next drawn
+    jump: bool,
 }
 
 struct DebuggerAppState {
@@ -104,7 +148,19 @@ struct DebuggerAppState {
     // UI internals
     tab: RefCell<TabState>,
     repl_input: RefCell<String>,
-    repl_output: RefCell<String>,
+    /// Program output and REPL history
+    console: RefCell<Vec<ConsoleEntry>>,
+    /// Source shown in the code viewer instead of the paused frame, after following a link
+    viewed_source: RefCell<Option<SourceLocation>>,
+    /// Exception the debugee is stopped on, if any
+    exception: Option<ExceptionInfo>,
+    /// Children of the variables expanded in the variables panel
+    variable_children: RefCell<HashMap<VariablesReference, LoadedChildren>>,
+    /// Completions offered for the REPL input when there is more than one
+    repl_completions: RefCell<Vec<CompletionItem>>,
+    /// Hit conditions being edited in the breakpoints panel
+    hit_conditions: RefCell<HashMap<BreakpointId, String>>,
+    logs: RefCell<Logs>,
     jump: bool,
 }
 
@@ -118,6 +174,88 @@ impl DebuggerAppState {
     #[tracing::instrument(skip(self), level = "trace")]
     fn handle_event(&mut self, event: &debugger::Event) -> eyre::Result<()> {
         tracing::debug!("handling event");
+        if let debugger::Event::Output {
+            category,
+            output,
+            source,
+            line,
+            ..
+        } = event
+        {
+            if *category != OutputCategory::Telemetry {
+                self.console.borrow_mut().push(ConsoleEntry {
+                    category: category.clone(),
+                    text: output.clone(),
+                    location: source.clone().zip(*line),
+                });
+            }
+            return Ok(());
+        }
+        if let debugger::Event::AdapterLog { line, .. } = event {
+            self.console.borrow_mut().push(ConsoleEntry::new(
+                OutputCategory::Other("adapter".to_string()),
+                line.clone(),
+            ));
+            return Ok(());
+        }
+        if let debugger::Event::Recovered {
+            attempts,
+            restarted_adapter,
+        } = event
+        {
+            let adapter = if *restarted_adapter {
+                ", restarted the debug adapter"
+            } else {
+                ""
+            };
+            self.console.borrow_mut().push(ConsoleEntry::new(
+                OutputCategory::Important,
+                format!(
+                    "--- connection to the debug adapter lost, recovered after {attempts} attempt(s){adapter} ---"
+                ),
+            ));
+            return Ok(());
+        }
+        if let debugger::Event::ProtocolViolation { violation } = event {
+            self.console.borrow_mut().push(ConsoleEntry::new(
+                OutputCategory::Important,
+                format!("--- protocol violation: {violation} ---"),
+            ));
+            return Ok(());
+        }
+        if let debugger::Event::PauseFailed { thread_id, error } = event {
+            self.console.borrow_mut().push(ConsoleEntry::new(
+                OutputCategory::Important,
+                format!("--- thread {thread_id} stopped, but its stack could not be fetched: {error} ---"),
+            ));
+            return Ok(());
+        }
+        if let debugger::Event::AdapterUnresponsive { reason } = event {
+            self.console.borrow_mut().push(ConsoleEntry::new(
+                OutputCategory::Important,
+                format!("--- {reason}, it may have hung ---"),
+            ));
+            return Ok(());
+        }
+        if let debugger::Event::BreakpointsChanged { breakpoints } = event {
+            if let State::Paused {
+                breakpoints: shown, ..
+            } = &mut self.state
+            {
+                *shown = breakpoints.iter().map(|(_, b)| b.clone()).collect();
+            }
+            return Ok(());
+        }
+        match event {
+            debugger::Event::Paused { exception, .. } => {
+                self.exception = exception.as_deref().cloned()
+            }
+            debugger::Event::Running | debugger::Event::Ended => self.exception = None,
+            _ => {}
+        }
+        // variable references are only valid until the debugee resumes or changes frame
+        self.variable_children.borrow_mut().clear();
+        self.viewed_source.borrow_mut().take();
         self.previous_state = Some(self.state.clone());
         self.state = event.clone().into();
         if let State::Paused { paused_frame, .. } = &self.state {
@@ -139,11 +277,40 @@ impl DebuggerAppState {
 
 struct DebuggerApp {
     inner: Arc<Mutex<DebuggerAppState>>,
-    _state_manager: StateManager,
+    /// Kept so that the settings file is watched for as long as the app runs
+    _settings: SettingsHandle,
+    /// New settings each time the settings file changes
+    settings_changes: crossbeam_channel::Receiver<Settings>,
+    /// Theme currently applied, so changes to the settings file can be picked up
+    theme: Theme,
+    keybindings: keybindings::Shortcuts,
+}
+
+/// egui visuals for the configured theme
+fn visuals(theme: Theme) -> Visuals {
+    match theme {
+        Theme::Dark => Visuals::dark(),
+        Theme::Light => Visuals::light(),
+        Theme::System => match dark_light::detect() {
+            dark_light::Mode::Dark | dark_light::Mode::Default => Visuals::dark(),
+            dark_light::Mode::Light => Visuals::light(),
+        },
+    }
 }
 
 impl DebuggerApp {
-    fn new(args: Args, cc: &eframe::CreationContext<'_>) -> eyre::Result<Self> {
+    fn new(
+        args: Args,
+        cc: &eframe::CreationContext<'_>,
+        log_records: crossbeam_channel::Receiver<LogRecord>,
+    ) -> eyre::Result<Self> {
+        let settings_handle = SettingsHandle::watch(Settings::default_path()).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "loading settings, using defaults");
+            SettingsHandle::fixed(Settings::default())
+        });
+        let settings = settings_handle.current();
+        cc.egui_ctx.set_visuals(visuals(settings.theme));
+
         let state_path = dirs::data_local_dir()
             .unwrap_or_else(|| PathBuf::from("/tmp"))
             .join("dapgui")
@@ -165,7 +332,7 @@ impl DebuggerApp {
             {
                 ChosenLaunchConfiguration::Specific(config) => config,
                 ChosenLaunchConfiguration::NotFound => {
-                    eyre::bail!("no matching configuration found")
+                    eyre::bail!("no matching configuration found");
                 }
                 ChosenLaunchConfiguration::ToBeChosen(configurations) => {
                     eprintln!("Configuration name not specified");
@@ -186,7 +353,10 @@ impl DebuggerApp {
                 cwd,
                 connect,
                 path_mappings,
+                just_my_code,
                 program,
+                sub_process,
+                stop_on_entry,
                 ..
             }) => {
                 if let Some(dir) = cwd {
@@ -196,14 +366,18 @@ impl DebuggerApp {
                     "attach" => {
                         let launch_arguments = AttachArguments {
                             working_directory: debug_root_dir.to_owned().to_path_buf(),
-                            port: connect.map(|c| c.port),
+                            host: connect.as_ref().map(|c| c.host.clone()),
+                            port: connect.as_ref().map(|c| c.port),
                             language: debugger::Language::DebugPy,
                             path_mappings,
+                            just_my_code,
                         };
 
                         tracing::debug!(?launch_arguments, "generated launch configuration");
 
-                        Debugger::new(launch_arguments).context("creating internal debugger")?
+                        let port = launch_arguments.port.unwrap_or(settings.default_port);
+                        Debugger::on_port(port, launch_arguments)
+                            .context("creating internal debugger")?
                     }
                     "launch" => {
                         let Some(program) = program else {
@@ -213,10 +387,14 @@ impl DebuggerApp {
                             program: program.clone(),
                             working_directory: Some(debug_root_dir.to_owned().to_path_buf()),
                             language: debugger::Language::DebugPy,
+                            sub_process,
+                            adapter_executable: settings.adapters.python.clone(),
+                            just_my_code,
+                            stop_on_entry: stop_on_entry.unwrap_or(false),
                         };
 
                         tracing::debug!(?launch_arguments, "generated launch configuration");
-                        let debugger = debugger::Debugger::new(launch_arguments)
+                        let debugger = Debugger::on_port(settings.default_port, launch_arguments)
                             .context("creating internal debugger")?;
 
                         for line in args.breakpoints {
@@ -249,27 +427,41 @@ impl DebuggerApp {
             .find(|p| debugger::utils::normalise_path(&p.path) == debug_root_dir)
         {
             tracing::debug!("got project state");
-            for breakpoint in &project_state.breakpoints {
-                {
+            let breakpoints: Vec<_> = project_state
+                .breakpoints
+                .iter()
+                .filter_map(|breakpoint| {
                     let breakpoint_path = debugger::utils::normalise_path(&breakpoint.path);
                     if !breakpoint_path.starts_with(&debug_root_dir) {
-                        continue;
+                        return None;
                     }
                     tracing::debug!(?breakpoint, "adding breakpoint from state file");
 
                     let mut breakpoint = breakpoint.clone();
-                    breakpoint.path = debugger::utils::normalise_path(&breakpoint.path)
-                        .into_owned()
-                        .to_path_buf();
-
-                    debugger
-                        .add_breakpoint(&breakpoint)
-                        .context("adding breakpoint")?;
-                }
-            }
+                    breakpoint.path = breakpoint_path.into_owned().to_path_buf();
+                    Some(breakpoint)
+                })
+                .collect();
+            debugger
+                .with_breakpoints(|store| {
+                    for breakpoint in breakpoints {
+                        store.add(breakpoint);
+                    }
+                })
+                .context("adding breakpoints")?;
         } else {
             tracing::warn!("missing project state");
         }
+        if settings.auto_save.breakpoints {
+            debugger.set_breakpoint_persistence(Box::new(ProjectBreakpoints::new(
+                state_manager,
+                debug_root_dir,
+            )));
+        }
+
+        debugger.enable_recovery(RecoveryOptions::default());
+        debugger.enable_liveness(LivenessOptions::default());
+        debugger.set_strict_protocol(args.strict_protocol);
 
         tracing::debug!("launching debugee");
         debugger.start().context("launching debugee")?;
@@ -282,7 +474,13 @@ impl DebuggerApp {
             jump: false,
             tab: RefCell::new(TabState::Variables),
             repl_input: RefCell::new(String::new()),
-            repl_output: RefCell::new(String::new()),
+            console: RefCell::new(Vec::new()),
+            viewed_source: RefCell::new(None),
+            repl_completions: RefCell::new(Vec::new()),
+            variable_children: RefCell::new(HashMap::new()),
+            exception: None,
+            hit_conditions: RefCell::new(HashMap::new()),
+            logs: RefCell::new(Logs::new(log_records)),
         };
 
         let inner = Arc::new(Mutex::new(temp_state));
@@ -300,15 +498,29 @@ impl DebuggerApp {
 
         Ok(Self {
             inner,
-            _state_manager: state_manager,
+            settings_changes: settings_handle.subscribe(),
+            _settings: settings_handle,
+            theme: settings.theme,
+            keybindings: keybindings::Shortcuts::new(&settings.keybindings),
         })
     }
 }
 
 impl eframe::App for DebuggerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if let Some(settings) = self.settings_changes.try_iter().last() {
+            if settings.theme != self.theme {
+                self.theme = settings.theme;
+                ctx.set_visuals(visuals(self.theme));
+            }
+            self.keybindings = keybindings::Shortcuts::new(&settings.keybindings);
+        }
+        self.keybindings
+            .handle(ctx, &self.inner.lock().unwrap().debugger);
+
         egui::CentralPanel::default().show(ctx, |_ui| {
             let mut inner = self.inner.lock().unwrap();
+            inner.logs.borrow_mut().receive();
             let mut user_interface = crate::renderer::Renderer::new(&inner);
             user_interface.render_ui(ctx);
             if inner.jump {
@@ -316,11 +528,22 @@ impl eframe::App for DebuggerApp {
             }
         });
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let inner = self.inner.lock().unwrap();
+        if let Err(e) = inner.debugger.stop(TerminateMode::Terminate) {
+            tracing::warn!(error = %e, "failed to stop debugee");
+        }
+    }
 }
 
 fn main() -> eyre::Result<()> {
     setup_sentry!();
-    let _ = tracing_subscriber::fmt::try_init();
+    let (log_layer, log_records) = ChannelLayer::new();
+    let _ = tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(EnvFilter::from_default_env()))
+        .with(log_layer.with_filter(tracing::level_filters::LevelFilter::DEBUG))
+        .try_init();
     let _ = color_eyre::install();
 
     let args = Args::parse();
@@ -330,15 +553,7 @@ fn main() -> eyre::Result<()> {
         "DAP Debugger",
         native_options,
         Box::new(|cc| {
-            let style = egui::Style {
-                visuals: match dark_light::detect() {
-                    dark_light::Mode::Dark | dark_light::Mode::Default => Visuals::dark(),
-                    dark_light::Mode::Light => Visuals::light(),
-                },
-                ..Default::default()
-            };
-            cc.egui_ctx.set_style(style);
-            let app = DebuggerApp::new(args, cc).expect("creating main application");
+            let app = DebuggerApp::new(args, cc, log_records).expect("creating main application");
             Box::new(app)
         }),
     )
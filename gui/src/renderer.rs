@@ -1,15 +1,34 @@
 use std::{collections::HashSet, ops::Deref};
 
-use debugger::{EvaluateResult, PausedFrame};
-use eframe::egui::{self, Context, Key, Ui};
-use transport::types::StackFrame;
+use debugger::{
+    utils::apply_completion, EvaluateContext, EvaluateResult, OutputCategory, PausedFrame,
+};
+use eframe::egui::{
+    self,
+    text::{CCursor, CCursorRange},
+    Context, Key, Modifiers, RichText, Ui,
+};
+use transport::types::{StackFrame, StackFrameId, Variable};
 
 use crate::{
     code_view::CodeView,
-    ui::{breakpoints::Breakpoints, call_stack::CallStack, control_panel::ControlPanel},
-    DebuggerAppState, State, TabState,
+    ui::{
+        breakpoints::Breakpoints,
+        call_stack::CallStack,
+        console::{Console, ConsoleEntry},
+        control_panel::ControlPanel,
+        exception_breakpoints::ExceptionBreakpoints,
+        logs::LogsPanel,
+        modules::Modules,
+        status_bar::StatusBar,
+        threads::Threads,
+    },
+    DebuggerAppState, LoadedChildren, State, TabState,
 };
 
+/// Number of children of a variable fetched at once
+const CHILDREN_PAGE_SIZE: usize = 100;
+
 pub(crate) struct Renderer<'a> {
     state: &'a DebuggerAppState,
 }
@@ -20,6 +39,9 @@ impl<'s> Renderer<'s> {
     }
 
     pub(crate) fn render_ui(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::bottom("status-bar").show(ctx, |ui| {
+            ui.add(StatusBar::new(self.state.debugger.metrics().snapshot()));
+        });
         match &self.state.state {
             State::Initialising => {}
             State::Running => {
@@ -49,6 +71,11 @@ impl<'s> Renderer<'s> {
             State::Terminated => {
                 egui::CentralPanel::default().show(ctx, |ui| {
                     ui.label("Program terminated");
+                    if ui.button("Restart").clicked() {
+                        if let Err(e) = self.state.debugger.restart() {
+                            tracing::warn!(error = ?e, "error restarting debugee");
+                        }
+                    }
                 });
             }
         }
@@ -69,7 +96,7 @@ impl<'s> Renderer<'s> {
         show_details: bool,
     ) {
         egui::SidePanel::left("left-panel").show(ctx, |ui| {
-            self.render_sidepanel(ctx, ui, stack, original_breakpoints, show_details);
+            self.render_sidepanel(ctx, ui, stack, show_details);
         });
         egui::TopBottomPanel::bottom("bottom-panel")
             .min_height(200.0)
@@ -77,6 +104,9 @@ impl<'s> Renderer<'s> {
                 self.render_bottom_panel(ctx, ui, paused_frame, show_details);
             });
         egui::CentralPanel::default().show(ctx, |ui| {
+            if show_details {
+                self.render_exception(ui);
+            }
             self.render_code_panel(ctx, ui, paused_frame, original_breakpoints);
             if show_details {
                 self.render_controls_window(ctx, ui);
@@ -84,6 +114,28 @@ impl<'s> Renderer<'s> {
         });
     }
 
+    /// Explain why the debugee stopped, if it stopped on an exception
+    fn render_exception(&mut self, ui: &mut Ui) {
+        let Some(exception) = &self.state.exception else {
+            return;
+        };
+        let summary = match &exception.description {
+            Some(description) => format!("{}: {description}", exception.exception_id),
+            None => exception.exception_id.clone(),
+        };
+        ui.colored_label(ui.visuals().error_fg_color, summary);
+        if let Some(stack_trace) = exception
+            .details
+            .as_ref()
+            .and_then(|d| d.stack_trace.as_deref())
+        {
+            ui.collapsing("Stack trace", |ui| {
+                ui.monospace(stack_trace);
+            });
+        }
+        ui.separator();
+    }
+
     fn render_controls_window(&mut self, ctx: &Context, ui: &mut Ui) {
         ui.add(ControlPanel::new(&self.state.debugger, ctx));
     }
@@ -93,13 +145,16 @@ impl<'s> Renderer<'s> {
         _ctx: &Context,
         ui: &mut Ui,
         stack: &[StackFrame],
-        original_breakpoints: &[debugger::Breakpoint],
         show_details: bool,
     ) {
         ui.vertical(|ui| {
+            ui.add(Threads::new(show_details, self.state));
+            ui.separator();
             ui.add(CallStack::new(stack, show_details, self.state));
             ui.separator();
-            ui.add(Breakpoints::new(original_breakpoints, show_details));
+            ui.add(Breakpoints::new(show_details, self.state));
+            ui.separator();
+            ui.add(ExceptionBreakpoints::new(show_details, self.state));
         });
     }
 
@@ -115,33 +170,90 @@ impl<'s> Renderer<'s> {
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut *tab, TabState::Variables, "Variables");
                 ui.selectable_value(&mut *tab, TabState::Repl, "Repl");
+                ui.selectable_value(&mut *tab, TabState::Logs, "Logs");
+                ui.selectable_value(&mut *tab, TabState::Modules, "Modules");
             });
         }
         match self.state.tab.borrow().deref() {
             TabState::Variables => self.render_variables(ctx, ui, paused_frame, show_details),
             TabState::Repl => self.render_repl(ctx, ui),
+            TabState::Logs => {
+                ui.add(LogsPanel::new(&mut self.state.logs.borrow_mut()));
+            }
+            TabState::Modules => {
+                ui.add(Modules::new(self.state));
+            }
         }
     }
 
     fn render_repl(&mut self, _ctx: &Context, ui: &mut Ui) {
         let repl_input = &mut *self.state.repl_input.borrow_mut();
-        let repl_output = &mut *self.state.repl_output.borrow_mut();
         // We only have a frame id if we are paused. If we are running then there is no frame id,
         // so don't render the REPL.
         if let Some(frame_id) = self.state.current_frame_id {
+            let repl_completions = &mut *self.state.repl_completions.borrow_mut();
             // output/history area
-            ui.text_edit_multiline(repl_output);
-            // input area
-            if ui.text_edit_singleline(repl_input).lost_focus()
-                && ui.input(|i| i.key_pressed(Key::Enter))
-            {
+            ui.add(Console::new(self.state));
+            // input area, keeping focus on tab so it can be used to complete names
+            let input_id = ui.make_persistent_id("repl-input");
+            let complete = ui.memory(|m| m.has_focus(input_id))
+                && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Tab));
+            let mut input = egui::TextEdit::singleline(repl_input)
+                .id(input_id)
+                .lock_focus(true)
+                .show(ui);
+            let cursor = input
+                .cursor_range
+                .map(|range| range.primary.ccursor.index)
+                .unwrap_or_else(|| repl_input.chars().count());
+
+            let mut chosen = None;
+            if complete {
+                match self
+                    .state
+                    .debugger
+                    .completions(repl_input, cursor + 1, Some(frame_id))
+                {
+                    Ok(mut items) if items.len() == 1 => chosen = items.pop(),
+                    Ok(items) => *repl_completions = items,
+                    Err(e) => tracing::debug!(error = %e, "fetching completions"),
+                }
+            }
+            ui.horizontal_wrapped(|ui| {
+                for item in repl_completions.iter() {
+                    if ui.button(&item.label).clicked() {
+                        chosen = Some(item.clone());
+                    }
+                }
+            });
+            if let Some(item) = chosen {
+                let (text, cursor) = apply_completion(repl_input, cursor, &item);
+                *repl_input = text;
+                repl_completions.clear();
+                input
+                    .state
+                    .cursor
+                    .set_char_range(Some(CCursorRange::one(CCursor::new(cursor))));
+                input.state.store(ui.ctx(), input_id);
+                ui.memory_mut(|m| m.request_focus(input_id));
+            }
+
+            if input.response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                repl_completions.clear();
                 // TODO: handle the error case
                 if let Ok(Some(EvaluateResult {
                     output,
                     error: _error,
-                })) = self.state.debugger.evaluate(repl_input, frame_id)
+                    ..
+                })) = self
+                    .state
+                    .debugger
+                    .evaluate(repl_input, frame_id, EvaluateContext::Repl)
                 {
-                    *repl_output += &("\n".to_string() + repl_input + "\n=> " + &output + "\n");
+                    self.state.console.borrow_mut().push(ConsoleEntry::new(
+                        OutputCategory::Console,
+                        format!("{repl_input}\n=> {output}"),
+                    ));
                     repl_input.clear();
                 }
             }
@@ -169,27 +281,168 @@ impl<'s> Renderer<'s> {
             ui.heading("Variables");
             if show_details {
                 for var in &paused_frame.variables {
-                    match &var.r#type {
-                        Some(t) => {
-                            ui.label(format!(
-                                "{name}: {typ} = {value}",
-                                name = var.name,
-                                typ = t,
-                                value = var.value,
-                            ));
-                        }
-                        None => {
-                            ui.label(format!(
-                                "{name} = {value}",
-                                name = var.name,
-                                value = var.value,
-                            ));
-                        }
-                    }
+                    self.render_variable(ui, var, paused_frame.frame.id);
                 }
             }
         });
     }
+
+    /// Render a variable, which can be expanded to show its children if it has any
+    fn render_variable(&self, ui: &mut Ui, var: &Variable, frame_id: StackFrameId) {
+        if var.is_lazy() {
+            self.render_lazy_variable(ui, var, frame_id);
+            return;
+        }
+
+        let mut label = match &var.r#type {
+            Some(t) => format!(
+                "{name}: {typ} = {value}",
+                name = var.name,
+                typ = t,
+                value = var.value,
+            ),
+            None => format!("{name} = {value}", name = var.name, value = var.value),
+        };
+        if var.is_read_only() {
+            label.push_str(" (read only)");
+        }
+        // de-emphasise everything but the data of the variable
+        let mut label = RichText::new(label);
+        if matches!(var.kind(), Some("method" | "class" | "event" | "interface")) {
+            label = label.weak();
+        }
+        if matches!(var.visibility(), Some("private" | "protected" | "internal")) {
+            label = label.italics();
+        }
+        if var.kind() == Some("virtual") {
+            label = label.weak().italics();
+        }
+
+        let response = if var.variables_reference == 0 {
+            ui.label(label)
+        } else {
+            egui::CollapsingHeader::new(label)
+                .id_source(("variable", var.variables_reference))
+                .show(ui, |ui| self.render_children(ui, var, frame_id))
+                .header_response
+        };
+        response.context_menu(|ui| {
+            if ui.button("Copy value").clicked() {
+                self.copy_value(ui, var, frame_id);
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Render a variable whose value is only fetched when asked for, e.g. because computing it
+    /// has side effects
+    fn render_lazy_variable(&self, ui: &mut Ui, var: &Variable, frame_id: StackFrameId) {
+        let resolved = self
+            .state
+            .variable_children
+            .borrow()
+            .get(&var.variables_reference)
+            .and_then(|loaded| loaded.variables.first().cloned());
+        if let Some(resolved) = resolved {
+            self.render_variable(ui, &resolved, frame_id);
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} = ...", var.name));
+            if ui.small_button("Show value").clicked() {
+                let resolved = match self.state.debugger.resolve_lazy(var) {
+                    Ok(resolved) => resolved,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "fetching lazy variable");
+                        return;
+                    }
+                };
+                self.state.variable_children.borrow_mut().insert(
+                    var.variables_reference,
+                    LoadedChildren {
+                        variables: vec![resolved],
+                        complete: true,
+                    },
+                );
+            }
+        });
+    }
+
+    /// Render the children of an expanded variable, fetching them a page at a time
+    fn render_children(&self, ui: &mut Ui, var: &Variable, frame_id: StackFrameId) {
+        let reference = var.variables_reference;
+        if !self
+            .state
+            .variable_children
+            .borrow()
+            .contains_key(&reference)
+        {
+            self.load_children(var);
+        }
+        let (children, complete) = {
+            let loaded = self.state.variable_children.borrow();
+            let page = &loaded[&reference];
+            (page.variables.clone(), page.complete)
+        };
+        for child in &children {
+            self.render_variable(ui, child, frame_id);
+        }
+        if !complete && ui.button("Load more").clicked() {
+            self.load_children(var);
+        }
+    }
+
+    /// Fetch the next page of children of `var`
+    fn load_children(&self, var: &Variable) {
+        let mut loaded = self.state.variable_children.borrow_mut();
+        let page = loaded.entry(var.variables_reference).or_default();
+        let start = page.variables.len();
+        match self.state.debugger.variables_range(
+            var.variables_reference,
+            start,
+            CHILDREN_PAGE_SIZE,
+        ) {
+            Ok(variables) => {
+                // adapters that do not support paging return every child at once
+                let total = match (var.named_variables, var.indexed_variables) {
+                    (None, None) => None,
+                    (named, indexed) => Some(named.unwrap_or(0) + indexed.unwrap_or(0)),
+                };
+                page.complete = variables.len() != CHILDREN_PAGE_SIZE
+                    || total.is_some_and(|total| start + variables.len() >= total);
+                page.variables.extend(variables);
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "fetching children of variable");
+                page.complete = true;
+            }
+        }
+    }
+
+    /// Copy the full value of a variable, which may be longer than the summary shown
+    fn copy_value(&self, ui: &mut Ui, var: &Variable, frame_id: StackFrameId) {
+        let expression = var.evaluate_name.as_deref().unwrap_or(&var.name);
+        let value =
+            match self
+                .state
+                .debugger
+                .evaluate(expression, frame_id, EvaluateContext::Clipboard)
+            {
+                Ok(Some(EvaluateResult {
+                    output,
+                    error: false,
+                    ..
+                })) => output,
+                Ok(_) => var.value.clone(),
+                Err(e) => {
+                    tracing::warn!(error = %e, "evaluating variable for clipboard");
+                    var.value.clone()
+                }
+            };
+        ui.output_mut(|o| o.copied_text = value);
+    }
+
     fn render_code_viewer(
         &mut self,
         _ctx: &Context,
@@ -199,11 +452,23 @@ impl<'s> Renderer<'s> {
     ) {
         // let DebuggerAppState { ref mut jump, .. } = self.state;
         let frame = &paused_frame.frame;
-        let file_path = frame
-            .source
-            .as_ref()
-            .and_then(|s| s.path.as_ref())
-            .expect("no file source given");
+        let mut viewed_source = self.state.viewed_source.borrow_mut();
+        let (file_path, line, jump) = match viewed_source.as_mut() {
+            Some(location) => (
+                &location.path,
+                location.line,
+                std::mem::take(&mut location.jump),
+            ),
+            None => (
+                frame
+                    .source
+                    .as_ref()
+                    .and_then(|s| s.path.as_ref())
+                    .expect("no file source given"),
+                frame.line,
+                self.state.jump,
+            ),
+        };
         let contents =
             std::fs::read_to_string(file_path).expect("reading source from given file path");
         let mut breakpoints = HashSet::from_iter(
@@ -213,12 +478,22 @@ impl<'s> Renderer<'s> {
                 .cloned(),
         );
 
-        ui.add(CodeView::new(
-            &contents,
-            frame.line,
-            true,
-            &mut breakpoints,
-            &self.state.jump,
-        ));
+        let mut next_statement = None;
+        let mut code_view = CodeView::new(&contents, line, true, &mut breakpoints, &jump);
+        if self
+            .state
+            .debugger
+            .capabilities()
+            .supports_goto_targets_request
+            == Some(true)
+        {
+            code_view = code_view.with_next_statement(&mut next_statement);
+        }
+        ui.add(code_view);
+        if let Some(line) = next_statement {
+            if let Err(e) = self.state.debugger.jump_to_line(file_path, line) {
+                tracing::warn!(error = ?e, "error setting next statement");
+            }
+        }
     }
 }
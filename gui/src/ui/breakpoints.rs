@@ -1,15 +1,17 @@
-use eframe::egui::Widget;
+use eframe::egui::{Key, Widget};
+
+use crate::DebuggerAppState;
 
 pub(crate) struct Breakpoints<'s> {
-    breakpoints: &'s [debugger::Breakpoint],
     show_details: bool,
+    state: &'s DebuggerAppState,
 }
 
 impl<'s> Breakpoints<'s> {
-    pub(crate) fn new(breakpoints: &'s [debugger::Breakpoint], show_details: bool) -> Self {
+    pub(crate) fn new(show_details: bool, state: &'s DebuggerAppState) -> Self {
         Self {
-            breakpoints,
             show_details,
+            state,
         }
     }
 }
@@ -18,7 +20,8 @@ impl Widget for Breakpoints<'_> {
     fn ui(self, ui: &mut eframe::egui::Ui) -> eframe::egui::Response {
         let mut final_response = ui.label("Breakpoints");
         if self.show_details {
-            for breakpoint in self.breakpoints {
+            let mut hit_conditions = self.state.hit_conditions.borrow_mut();
+            for (id, breakpoint) in self.state.debugger.breakpoints_with_ids() {
                 if let Some(name) = &breakpoint.name {
                     final_response |= ui.label(format!(
                         "{path}:{line} ({name})",
@@ -33,8 +36,51 @@ impl Widget for Breakpoints<'_> {
                         line = breakpoint.line,
                     ));
                 }
+
+                if let Some(log_message) = &breakpoint.log_message {
+                    final_response |= ui.label(format!("logs: {log_message}"));
+                }
+
+                // e.g. ">= 5" to only break from the fifth hit onwards
+                let hit_condition = hit_conditions
+                    .entry(id)
+                    .or_insert_with(|| breakpoint.hit_condition.clone().unwrap_or_default());
+                let response = ui.horizontal(|ui| {
+                    ui.label("Hit condition");
+                    ui.text_edit_singleline(hit_condition)
+                });
+                let response = response.inner;
+                if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    let updated = debugger::Breakpoint {
+                        hit_condition: parse_hit_condition(hit_condition),
+                        ..breakpoint
+                    };
+                    if let Err(e) = self.state.debugger.update_breakpoint(id, &updated) {
+                        tracing::warn!(error = ?e, "error setting hit condition");
+                    }
+                }
+                final_response |= response;
             }
         }
         final_response
     }
 }
+
+/// Hit condition entered in the breakpoints panel, where a blank entry removes it
+fn parse_hit_condition(input: &str) -> Option<String> {
+    let input = input.trim();
+    (!input.is_empty()).then(|| input.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_hit_condition;
+
+    #[test]
+    fn parse_hit_conditions() {
+        assert_eq!(parse_hit_condition(" >= 5 "), Some(">= 5".to_string()));
+        assert_eq!(parse_hit_condition("%2"), Some("%2".to_string()));
+        assert_eq!(parse_hit_condition("  "), None);
+        assert_eq!(parse_hit_condition(""), None);
+    }
+}
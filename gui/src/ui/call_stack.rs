@@ -27,13 +27,33 @@ impl Widget for CallStack<'_> {
     fn ui(self, ui: &mut eframe::egui::Ui) -> Response {
         let final_response = ui.heading("Call Stack");
 
+        let mut frame_filter = self.state.debugger.frame_filter();
+        if ui.checkbox(&mut frame_filter, "Only my code").changed() {
+            if let Err(e) = self.state.debugger.set_frame_filter(frame_filter) {
+                tracing::warn!(error = ?e, "error changing frame filter");
+            }
+        }
+
         if self.show_details {
+            let can_restart =
+                self.state.debugger.capabilities().supports_restart_frame == Some(true);
             for frame in self.stack {
-                if ui.link(frame.name.to_string()).clicked() {
+                let response = ui.link(frame.name.to_string());
+                if response.clicked() {
                     if let Err(e) = self.state.change_scope(frame.id) {
                         tracing::warn!(error = ?e, "error changing scope");
                     }
                 }
+                if can_restart {
+                    response.context_menu(|ui| {
+                        if ui.button("Restart frame").clicked() {
+                            if let Err(e) = self.state.debugger.restart_frame(frame.id) {
+                                tracing::warn!(error = ?e, "error restarting frame");
+                            }
+                            ui.close_menu();
+                        }
+                    });
+                }
             }
         }
 
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use debugger::OutputCategory;
+use eframe::egui::{self, Response, RichText, Widget};
+
+use crate::{DebuggerAppState, SourceLocation};
+
+/// Line of text shown in the console, e.g. program output or the result of a REPL evaluation
+pub(crate) struct ConsoleEntry {
+    pub(crate) category: OutputCategory,
+    pub(crate) text: String,
+    /// Where the output was produced, which can be clicked to show it in the code viewer
+    pub(crate) location: Option<(PathBuf, usize)>,
+}
+
+impl ConsoleEntry {
+    pub(crate) fn new(category: OutputCategory, text: impl Into<String>) -> Self {
+        Self {
+            category,
+            text: text.into(),
+            location: None,
+        }
+    }
+}
+
+/// Program and debugger output, coloured by the stream it was written to
+pub(crate) struct Console<'s> {
+    state: &'s DebuggerAppState,
+}
+
+impl<'s> Console<'s> {
+    pub(crate) fn new(state: &'s DebuggerAppState) -> Self {
+        Self { state }
+    }
+}
+
+impl Widget for Console<'_> {
+    fn ui(self, ui: &mut eframe::egui::Ui) -> Response {
+        let entries = self.state.console.borrow();
+        egui::ScrollArea::vertical()
+            .max_height(ui.available_height() / 2.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                ui.vertical(|ui| {
+                    for entry in entries.iter() {
+                        ui.horizontal_wrapped(|ui| {
+                            let text = RichText::new(entry.text.trim_end()).monospace();
+                            let text = match entry.category {
+                                OutputCategory::Stderr => text.color(ui.visuals().error_fg_color),
+                                OutputCategory::Important => text.color(ui.visuals().warn_fg_color),
+                                OutputCategory::Console | OutputCategory::Other(_) => text.weak(),
+                                _ => text,
+                            };
+                            ui.label(text);
+                            if let Some((path, line)) = &entry.location {
+                                let name = path
+                                    .file_name()
+                                    .map(|name| name.to_string_lossy())
+                                    .unwrap_or_else(|| path.to_string_lossy());
+                                if ui
+                                    .link(format!("{name}:{line}"))
+                                    .on_hover_text(path.display().to_string())
+                                    .clicked()
+                                {
+                                    *self.state.viewed_source.borrow_mut() = Some(SourceLocation {
+                                        path: path.clone(),
+                                        line: *line,
+                                        jump: true,
+                                    });
+                                }
+                            }
+                        });
+                    }
+                })
+                .response
+            })
+            .inner
+    }
+}
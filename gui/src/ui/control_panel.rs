@@ -30,6 +30,35 @@ impl Widget for ControlPanel<'_> {
                     if ui.add(Button::new("step-out").small()).clicked() {
                         self.debugger.step_out().unwrap();
                     }
+                    if ui.add(Button::new("restart").small()).clicked() {
+                        if let Err(e) = self.debugger.restart() {
+                            tracing::warn!(error = ?e, "error restarting debugee");
+                        }
+                    }
+                    if self.debugger.capabilities().supports_restart_request == Some(true)
+                        && ui.add(Button::new("reload").small()).clicked()
+                    {
+                        if let Err(e) = self.debugger.reload() {
+                            tracing::warn!(error = ?e, "error reloading debugee");
+                        }
+                    }
+                    if self
+                        .debugger
+                        .capabilities()
+                        .supports_single_thread_execution_requests
+                        == Some(true)
+                    {
+                        let mut single_thread = self.debugger.single_thread();
+                        if ui
+                            .checkbox(&mut single_thread, "single thread")
+                            .on_hover_text("Only resume the current thread when stepping")
+                            .changed()
+                        {
+                            if let Err(e) = self.debugger.set_single_thread(single_thread) {
+                                tracing::warn!(error = ?e, "error changing single thread execution");
+                            }
+                        }
+                    }
                 })
                 .response
             })
@@ -0,0 +1,46 @@
+use eframe::egui::Widget;
+
+use crate::DebuggerAppState;
+
+/// Checkbox for each kind of exception the adapter can break on
+pub(crate) struct ExceptionBreakpoints<'s> {
+    show_details: bool,
+    state: &'s DebuggerAppState,
+}
+
+impl<'s> ExceptionBreakpoints<'s> {
+    pub(crate) fn new(show_details: bool, state: &'s DebuggerAppState) -> Self {
+        Self {
+            show_details,
+            state,
+        }
+    }
+}
+
+impl Widget for ExceptionBreakpoints<'_> {
+    fn ui(self, ui: &mut eframe::egui::Ui) -> eframe::egui::Response {
+        let mut final_response = ui.label("Exceptions");
+        if self.show_details {
+            let mut enabled = self.state.debugger.exception_breakpoints();
+            for filter in self.state.debugger.exception_breakpoint_filters() {
+                let mut checked = enabled.contains(&filter.filter);
+                let mut response = ui.checkbox(&mut checked, &filter.label);
+                if let Some(description) = &filter.description {
+                    response = response.on_hover_text(description);
+                }
+                if response.changed() {
+                    if checked {
+                        enabled.push(filter.filter);
+                    } else {
+                        enabled.retain(|f| f != &filter.filter);
+                    }
+                    if let Err(e) = self.state.debugger.set_exception_breakpoints(&enabled) {
+                        tracing::warn!(error = ?e, "error setting exception breakpoints");
+                    }
+                }
+                final_response |= response;
+            }
+        }
+        final_response
+    }
+}
@@ -0,0 +1,68 @@
+use eframe::egui::{self, Color32, Response, RichText, Widget};
+use tracing::Level;
+
+use crate::logs::Logs;
+
+pub(crate) struct LogsPanel<'s> {
+    logs: &'s mut Logs,
+}
+
+impl<'s> LogsPanel<'s> {
+    pub(crate) fn new(logs: &'s mut Logs) -> Self {
+        Self { logs }
+    }
+}
+
+fn level_colour(level: Level) -> Color32 {
+    match level {
+        Level::ERROR => Color32::LIGHT_RED,
+        Level::WARN => Color32::YELLOW,
+        Level::INFO => Color32::LIGHT_GREEN,
+        Level::DEBUG => Color32::LIGHT_BLUE,
+        Level::TRACE => Color32::GRAY,
+    }
+}
+
+impl Widget for LogsPanel<'_> {
+    fn ui(self, ui: &mut eframe::egui::Ui) -> Response {
+        // pick up new records while the panel is visible, even if nothing else changes
+        ui.ctx()
+            .request_repaint_after(std::time::Duration::from_millis(500));
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Level")
+                    .selected_text(self.logs.level.as_str())
+                    .show_ui(ui, |ui| {
+                        for level in [
+                            Level::ERROR,
+                            Level::WARN,
+                            Level::INFO,
+                            Level::DEBUG,
+                            Level::TRACE,
+                        ] {
+                            ui.selectable_value(&mut self.logs.level, level, level.as_str());
+                        }
+                    });
+                ui.label("Search");
+                ui.text_edit_singleline(&mut self.logs.search);
+            });
+            egui::ScrollArea::vertical()
+                .stick_to_bottom(true)
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    for record in self.logs.filtered() {
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                RichText::new(format!("{:>5}", record.level.as_str()))
+                                    .monospace()
+                                    .color(level_colour(record.level)),
+                            );
+                            ui.label(RichText::new(&record.target).monospace().weak());
+                            ui.label(RichText::new(&record.message).monospace());
+                        });
+                    }
+                });
+        })
+        .response
+    }
+}
@@ -1,3 +1,9 @@
 pub(crate) mod breakpoints;
 pub(crate) mod call_stack;
+pub(crate) mod console;
 pub(crate) mod control_panel;
+pub(crate) mod exception_breakpoints;
+pub(crate) mod logs;
+pub(crate) mod modules;
+pub(crate) mod status_bar;
+pub(crate) mod threads;
@@ -0,0 +1,51 @@
+use eframe::egui::{self, Response, Widget};
+
+use crate::DebuggerAppState;
+
+/// Modules loaded by the debugee, e.g. shared libraries when debugging native code
+pub(crate) struct Modules<'s> {
+    state: &'s DebuggerAppState,
+}
+
+impl<'s> Modules<'s> {
+    pub(crate) fn new(state: &'s DebuggerAppState) -> Self {
+        Self { state }
+    }
+}
+
+impl Widget for Modules<'_> {
+    fn ui(self, ui: &mut eframe::egui::Ui) -> Response {
+        let modules = self.state.debugger.modules();
+        if modules.is_empty() {
+            return ui.label("No modules reported by the debug adapter");
+        }
+
+        egui::ScrollArea::vertical()
+            .show(ui, |ui| {
+                egui::Grid::new("modules")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Name");
+                        ui.strong("Path");
+                        ui.strong("Version");
+                        ui.strong("Symbols");
+                        ui.end_row();
+                        for module in &modules {
+                            ui.label(&module.name);
+                            ui.label(
+                                module
+                                    .path
+                                    .as_ref()
+                                    .map(|p| p.display().to_string())
+                                    .unwrap_or_default(),
+                            );
+                            ui.label(module.version.as_deref().unwrap_or_default());
+                            ui.label(module.symbol_status.as_deref().unwrap_or_default());
+                            ui.end_row();
+                        }
+                    })
+                    .response
+            })
+            .inner
+    }
+}
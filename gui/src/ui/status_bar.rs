@@ -0,0 +1,30 @@
+use eframe::egui::{Response, Widget};
+
+/// One line summary of the adapter traffic, shown at the bottom of the window
+pub(crate) struct StatusBar {
+    metrics: debugger::MetricsSnapshot,
+}
+
+impl StatusBar {
+    pub(crate) fn new(metrics: debugger::MetricsSnapshot) -> Self {
+        Self { metrics }
+    }
+}
+
+impl Widget for StatusBar {
+    fn ui(self, ui: &mut eframe::egui::Ui) -> Response {
+        let latency = match self.metrics.last_latency {
+            Some(latency) => format!("{:.1} ms", latency.as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        let mut status = format!(
+            "latency {latency} | {} requests | {} events",
+            self.metrics.total_requests(),
+            self.metrics.total_events()
+        );
+        if self.metrics.reconnects > 0 {
+            status.push_str(&format!(" | {} reconnects", self.metrics.reconnects));
+        }
+        ui.small(status)
+    }
+}
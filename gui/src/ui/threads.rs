@@ -0,0 +1,42 @@
+use eframe::egui::{Response, Widget};
+
+use crate::DebuggerAppState;
+
+/// Picker for the thread whose call stack is shown
+pub(crate) struct Threads<'s> {
+    show_details: bool,
+    state: &'s DebuggerAppState,
+}
+
+impl<'s> Threads<'s> {
+    pub(crate) fn new(show_details: bool, state: &'s DebuggerAppState) -> Self {
+        Self {
+            show_details,
+            state,
+        }
+    }
+}
+
+impl Widget for Threads<'_> {
+    fn ui(self, ui: &mut eframe::egui::Ui) -> Response {
+        let mut final_response = ui.heading("Threads");
+
+        if self.show_details {
+            let current_thread = self.state.debugger.current_thread();
+            for thread in self.state.debugger.threads() {
+                let response = ui.selectable_label(
+                    current_thread == Some(thread.id),
+                    format!("{} ({})", thread.name, thread.id),
+                );
+                if response.clicked() {
+                    if let Err(e) = self.state.debugger.select_thread(thread.id) {
+                        tracing::warn!(error = ?e, "error selecting thread");
+                    }
+                }
+                final_response |= response;
+            }
+        }
+
+        final_response
+    }
+}
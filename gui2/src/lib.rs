@@ -14,11 +14,11 @@ use iced::{
 };
 use iced_aw::Tabs;
 use launch_configuration::{ChosenLaunchConfiguration, Debugpy, LaunchConfiguration};
-use state::StateManager;
+use settings::{Settings, SettingsHandle, Theme};
+use state::{ProjectBreakpoints, StateManager};
 use transport::types::{StackFrame, StackFrameId};
 
 pub mod code_view;
-mod highlight;
 
 #[derive(Debug, Parser, Default)]
 pub struct Args {
@@ -41,6 +41,8 @@ pub enum Message {
     DebuggerMessage(Box<Event>),
     Window(WindowEvent),
     StackFrameChanged(StackFrameId),
+    /// Re-run the debugee, keeping the breakpoints
+    Restart,
     Quit,
 }
 
@@ -75,6 +77,7 @@ pub enum AppState {
 pub struct DebuggerApp {
     state: AppState,
     debugger: Debugger,
+    settings: SettingsHandle,
 }
 
 impl DebuggerApp {
@@ -82,6 +85,12 @@ impl DebuggerApp {
     fn init() -> eyre::Result<Self> {
         let args = Args::parse();
 
+        let settings_handle = SettingsHandle::watch(Settings::default_path()).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "loading settings, using defaults");
+            SettingsHandle::fixed(Settings::default())
+        });
+        let settings = settings_handle.current();
+
         let state_path = dirs::data_local_dir()
             .unwrap_or_else(|| PathBuf::from("/tmp"))
             .join("dapgui")
@@ -104,7 +113,7 @@ impl DebuggerApp {
             {
                 ChosenLaunchConfiguration::Specific(config) => config,
                 ChosenLaunchConfiguration::NotFound => {
-                    eyre::bail!("no matching configuration found")
+                    eyre::bail!("no matching configuration found");
                 }
                 ChosenLaunchConfiguration::ToBeChosen(configurations) => {
                     eprintln!("Configuration name not specified");
@@ -125,6 +134,7 @@ impl DebuggerApp {
                 cwd,
                 connect,
                 path_mappings,
+                just_my_code,
                 ..
             }) => {
                 if let Some(dir) = cwd {
@@ -134,14 +144,18 @@ impl DebuggerApp {
                     "attach" => {
                         let launch_arguments = AttachArguments {
                             working_directory: debug_root_dir.to_owned().to_path_buf(),
-                            port: connect.map(|c| c.port),
+                            host: connect.as_ref().map(|c| c.host.clone()),
+                            port: connect.as_ref().map(|c| c.port),
                             language: debugger::Language::DebugPy,
                             path_mappings,
+                            just_my_code,
                         };
 
                         tracing::debug!(?launch_arguments, "generated launch configuration");
 
-                        Debugger::new(launch_arguments).context("creating internal debugger")?
+                        let port = launch_arguments.port.unwrap_or(settings.default_port);
+                        Debugger::on_port(port, launch_arguments)
+                            .context("creating internal debugger")?
                     }
                     _ => todo!(),
                 };
@@ -158,27 +172,37 @@ impl DebuggerApp {
             .find(|p| debugger::utils::normalise_path(&p.path) == debug_root_dir)
         {
             tracing::debug!("got project state");
-            for breakpoint in &project_state.breakpoints {
-                {
+            let breakpoints: Vec<_> = project_state
+                .breakpoints
+                .iter()
+                .filter_map(|breakpoint| {
                     let breakpoint_path = debugger::utils::normalise_path(&breakpoint.path);
                     if !breakpoint_path.starts_with(&debug_root_dir) {
-                        continue;
+                        return None;
                     }
                     tracing::debug!(?breakpoint, "adding breakpoint from state file");
 
                     let mut breakpoint = breakpoint.clone();
-                    breakpoint.path = debugger::utils::normalise_path(&breakpoint.path)
-                        .into_owned()
-                        .to_path_buf();
-
-                    debugger
-                        .add_breakpoint(&breakpoint)
-                        .context("adding breakpoint")?;
-                }
-            }
+                    breakpoint.path = breakpoint_path.into_owned().to_path_buf();
+                    Some(breakpoint)
+                })
+                .collect();
+            debugger
+                .with_breakpoints(|store| {
+                    for breakpoint in breakpoints {
+                        store.add(breakpoint);
+                    }
+                })
+                .context("adding breakpoints")?;
         } else {
             tracing::warn!("missing project state");
         }
+        if settings.auto_save.breakpoints {
+            debugger.set_breakpoint_persistence(Box::new(ProjectBreakpoints::new(
+                state_manager,
+                debug_root_dir,
+            )));
+        }
 
         tracing::debug!("launching debugee");
         debugger.start().context("launching debugee")?;
@@ -188,6 +212,7 @@ impl DebuggerApp {
                 breakpoints: HashSet::new(),
             },
             debugger,
+            settings: settings_handle,
         })
     }
 
@@ -277,6 +302,12 @@ impl Application for DebuggerApp {
 
     #[tracing::instrument(skip(self))]
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
+        if let Message::Restart = message {
+            if let Err(e) = self.debugger.restart() {
+                tracing::warn!(error = %e, "failed to restart debugee");
+            }
+            return Command::none();
+        }
         match &mut self.state {
             AppState::Running { .. } => match message {
                 Message::DebuggerMessage(event) => match *event {
@@ -297,6 +328,13 @@ impl Application for DebuggerApp {
                     Event::ScopeChange { .. } => todo!(),
                     Event::Running => {}
                     Event::Ended => todo!(),
+                    Event::Output { .. }
+                    | Event::AdapterLog { .. }
+                    | Event::BreakpointsChanged { .. }
+                    | Event::Recovered { .. }
+                    | Event::ProtocolViolation { .. }
+                    | Event::AdapterUnresponsive { .. }
+                    | Event::PauseFailed { .. } => {}
                 },
                 other => {
                     tracing::debug!(message = ?other, "unhandled message");
@@ -328,6 +366,9 @@ impl Application for DebuggerApp {
                 }
                 Message::Quit => {
                     tracing::info!("got quit event");
+                    if let Err(e) = self.debugger.stop(debugger::TerminateMode::Terminate) {
+                        tracing::warn!(error = %e, "failed to stop debugee");
+                    }
                     return iced::window::close(iced::window::Id::MAIN);
                 }
                 Message::Window(WindowEvent::Window(id, iced::window::Event::Closed)) => {
@@ -385,6 +426,7 @@ impl Application for DebuggerApp {
         });
         let events_sub = iced::keyboard::on_key_press(|key, mods| match (key, mods) {
             (Key::Character(c), Modifiers::CTRL) if c == "q" => Some(Message::Quit),
+            (Key::Character(c), Modifiers::CTRL) if c == "r" => Some(Message::Restart),
             _ => None,
         });
         let window_sub = iced::event::listen().map(Message::Window);
@@ -393,9 +435,13 @@ impl Application for DebuggerApp {
     }
 
     fn theme(&self) -> Self::Theme {
-        match dark_light::detect() {
-            Mode::Dark | Mode::Default => iced::Theme::Dark,
-            Mode::Light => iced::Theme::Light,
+        match self.settings.current().theme {
+            Theme::Dark => iced::Theme::Dark,
+            Theme::Light => iced::Theme::Light,
+            Theme::System => match dark_light::detect() {
+                Mode::Dark | Mode::Default => iced::Theme::Dark,
+                Mode::Light => iced::Theme::Light,
+            },
         }
     }
 }
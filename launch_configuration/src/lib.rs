@@ -2,10 +2,7 @@
 //!
 //! This crate handles parsing the launch configurations, primarily of VS Code.
 
-use std::{
-    collections::HashMap,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 use eyre::Context;
 use serde::Deserialize;
@@ -36,18 +33,12 @@ struct VsCodeLaunchConfiguration {
 #[serde(untagged)]
 enum ConfigFormat {
     VsCode(VsCodeLaunchConfiguration),
+    /// `.code-workspace` file, of which only the launch configuration is used
     VsCodeWorkspace {
-        folders: Vec<Folder>,
-        settings: HashMap<String, serde_json::Value>,
         launch: VsCodeLaunchConfiguration,
     },
 }
 
-#[derive(Deserialize)]
-struct Folder {
-    path: String,
-}
-
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum LaunchConfiguration {
@@ -105,7 +96,6 @@ fn from_str(name: Option<&String>, contents: &str) -> eyre::Result<ChosenLaunchC
         }
         ConfigFormat::VsCodeWorkspace {
             launch: VsCodeLaunchConfiguration { configurations, .. },
-            ..
         } => {
             if let Some(name) = name {
                 for configuration in configurations {
@@ -165,6 +155,10 @@ pub struct Debugpy {
     pub path_mappings: Option<Vec<PathMapping>>,
     pub just_my_code: Option<bool>,
     pub cwd: Option<PathBuf>,
+    /// Whether child processes should be debugged too
+    pub sub_process: Option<bool>,
+    /// Whether to pause before running any of the program
+    pub stop_on_entry: Option<bool>,
 }
 impl Debugpy {
     fn resolve(&mut self, root: impl AsRef<Path>) {
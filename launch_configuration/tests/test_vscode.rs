@@ -66,3 +66,17 @@ fn test_read_code_workspace() {
     );
     assert!(!config.just_my_code.unwrap());
 }
+
+#[test]
+fn test_read_code_workspace_without_settings() {
+    let path = "./testdata/vscode/no-settings.code-workspace";
+    let ChosenLaunchConfiguration::Specific(LaunchConfiguration::Debugpy(config)) =
+        launch_configuration::load_from_path(Some(&"Python: Current File".to_string()), path)
+            .unwrap()
+    else {
+        panic!("specified launch configuration not found");
+    };
+
+    assert_eq!(config.name, "Python: Current File");
+    assert_eq!(config.request, "launch");
+}
@@ -1,98 +1,453 @@
-use etherparse::{SlicedPacket, TransportSlice};
+use etherparse::{NetSlice, SlicedPacket, TransportSlice};
 use eyre::WrapErr;
-use pcap_file::pcapng::{blocks::enhanced_packet::EnhancedPacketBlock, PcapNgParser};
-use std::{io::BufReader, path::Path};
-use transport::{Message, Reader};
+use pcap_file::{
+    pcap::PcapReader,
+    pcapng::{blocks::enhanced_packet::EnhancedPacketBlock, Block, PcapNgReader},
+    DataLink,
+};
+use reassembly::Reassembler;
+use serde::Serialize;
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, VecDeque},
+    io::{BufReader, Read, Write},
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    time::Duration,
+};
+use transport::{replay::Recording, tap::TappedMessage, Message};
 
-pub fn extract_messages(path: impl AsRef<Path>, port: u16) -> eyre::Result<Vec<Message>> {
+mod reassembly;
+
+/// Capture file formats understood by pcaplog
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Classic libpcap format, e.g. from `tcpdump -w`
+    Pcap,
+    PcapNg,
+}
+
+impl Format {
+    /// Determine the capture format from the file extension
+    pub fn from_path(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        match path.as_ref().extension().and_then(|s| s.to_str()) {
+            Some("pcap") => Ok(Self::Pcap),
+            Some("pcapng") => Ok(Self::PcapNg),
+            Some(_) | None => {
+                eyre::bail!("invalid extension, expected .pcap or .pcapng");
+            }
+        }
+    }
+}
+
+/// Which side of the connection sent a message, shared with [`transport::tap`] so that
+/// recorded sessions use the same format as captures
+pub use transport::tap::Direction;
+
+/// DAP message decoded from a capture, along with where and when it was seen
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedMessage {
+    pub direction: Direction,
+    /// Capture timestamp of the packet that completed the message, since the unix epoch
+    pub timestamp: Duration,
+    /// Index of the TCP connection carrying the message, in the order connections were seen
+    pub stream_id: usize,
+    /// Address of the DAP client end of the connection
+    pub client: SocketAddr,
+    /// Address of the DAP server end of the connection
+    pub server: SocketAddr,
+    pub message: Message,
+}
+
+/// Messages exchanged over a single TCP connection, i.e. one debug session
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub stream_id: usize,
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    pub messages: Vec<CapturedMessage>,
+}
+
+impl Session {
+    /// Messages of the session in the form replayed by [`transport::replay`]
+    pub fn recording(&self) -> eyre::Result<Recording> {
+        let messages = self
+            .messages
+            .iter()
+            .map(|message| {
+                let body = serde_json::to_vec(&message.message).context("encoding message")?;
+                Ok(TappedMessage {
+                    direction: message.direction,
+                    timestamp: message.timestamp,
+                    body: body.into(),
+                })
+            })
+            .collect::<eyre::Result<_>>()?;
+        Ok(Recording::new(messages))
+    }
+}
+
+/// Read the DAP messages to or from `port` in the capture file at `path`, grouped by connection
+pub fn extract_sessions(path: impl AsRef<Path>, port: u16) -> eyre::Result<Vec<Session>> {
+    let messages = extract_messages(path, port)?;
+    Ok(split_sessions(messages))
+}
+
+/// Group messages by the connection they were sent over, in the order connections were seen
+pub fn split_sessions(messages: impl IntoIterator<Item = CapturedMessage>) -> Vec<Session> {
+    let mut sessions: Vec<Session> = Vec::new();
+    for message in messages {
+        match sessions
+            .iter_mut()
+            .find(|s| s.stream_id == message.stream_id)
+        {
+            Some(session) => session.messages.push(message),
+            None => sessions.push(Session {
+                stream_id: message.stream_id,
+                client: message.client,
+                server: message.server,
+                messages: vec![message],
+            }),
+        }
+    }
+    sessions.sort_by_key(|s| s.stream_id);
+    sessions
+}
+
+/// Read all DAP messages to or from `port` in the capture file at `path`
+pub fn extract_messages(path: impl AsRef<Path>, port: u16) -> eyre::Result<Vec<CapturedMessage>> {
     let path = path.as_ref();
+    let format = Format::from_path(path)?;
+    let file = std::fs::File::open(path).context("opening capture file")?;
+    let messages = read_messages(BufReader::new(file), format, port)?.collect();
+    Ok(messages)
+}
 
-    // TODO: not great for memory usage or DOS...
-    tracing::debug!("reading file into memory");
-    let file_bytes = std::fs::read(path).context("reading file bytes")?;
-
-    let mut result = Vec::new();
-    match path.extension().and_then(|s| s.to_str()) {
-        Some("pcap") => todo!(),
-        Some("pcapng") => {
-            tracing::debug!("parsing file");
-
-            let mut src = &file_bytes[..];
-
-            let (rem, mut pcap_parser) = PcapNgParser::new(src).context("parsing file")?;
-            src = rem;
-
-            let mut messages = Vec::new();
-
-            let mut i = 0;
-            loop {
-                tracing::trace!(packet = %i, "next packet");
-                match pcap_parser.next_block(src) {
-                    Ok((rem, block)) => {
-                        tracing::trace!("got block");
-                        match block {
-                            pcap_file::pcapng::Block::EnhancedPacket(EnhancedPacketBlock {
-                                data,
-                                ..
-                            }) => {
-                                tracing::trace!("block length {}", data.len());
-                                match SlicedPacket::from_ethernet(&data) {
-                                    Ok(value) => {
-                                        tracing::trace!("got sliced packet");
-                                        if let Some(TransportSlice::Tcp(tcph)) = value.transport {
-                                            tracing::trace!("got tcp layer");
-
-                                            let payload = tcph.payload();
-                                            if payload.is_empty() {
-                                                tracing::trace!("no payload");
-                                                src = rem;
-                                                i += 1;
-                                                continue;
-                                            }
-                                            // skip packets that are not for the specified port
-                                            if tcph.source_port() != port
-                                                && tcph.destination_port() != port
-                                            {
-                                                i += 1;
-                                                src = rem;
-                                                continue;
-                                            }
-
-                                            messages.extend_from_slice(payload);
-                                        }
-                                    }
-                                    Err(e) => {
-                                        tracing::warn!(error = %e, "error parsing package as ethernet frame");
-                                        continue;
-                                    }
-                                }
-                            }
-                            e => tracing::warn!("unhandled block type {e:?}"),
-                        }
+/// Write messages as JSON lines, one object per message
+///
+/// Each object contains the `direction`, the capture `timestamp` as fractional seconds since the
+/// unix epoch, the `stream_id` and the decoded DAP `payload`.
+pub fn export_jsonl(
+    messages: impl IntoIterator<Item = impl Borrow<CapturedMessage>>,
+    mut writer: impl Write,
+) -> eyre::Result<()> {
+    #[derive(Serialize)]
+    struct Record<'a> {
+        direction: Direction,
+        timestamp: f64,
+        stream_id: usize,
+        payload: &'a Message,
+    }
 
-                        src = rem;
-                    }
-                    Err(e) => {
-                        tracing::error!(error = %e, "parsing next block");
-                        break;
-                    }
-                }
-                i += 1;
+    for message in messages {
+        let message = message.borrow();
+        let record = Record {
+            direction: message.direction,
+            timestamp: message.timestamp.as_secs_f64(),
+            stream_id: message.stream_id,
+            payload: &message.message,
+        };
+        serde_json::to_writer(&mut writer, &record).context("serializing message")?;
+        writeln!(writer).context("writing message separator")?;
+    }
+    writer.flush().context("flushing output")?;
+    Ok(())
+}
+
+/// Lazily decode DAP messages to or from `port` from a capture
+///
+/// Packets are read from `source` as the iterator is advanced, so the whole capture is never
+/// held in memory.
+pub fn read_messages<R>(source: R, format: Format, port: u16) -> eyre::Result<Messages<R>>
+where
+    R: Read,
+{
+    let packets = Packets::new(source, format).context("parsing file header")?;
+    Ok(Messages {
+        packets,
+        port,
+        flows: HashMap::new(),
+        stream_ids: HashMap::new(),
+        ready: VecDeque::new(),
+    })
+}
+
+/// One direction of a TCP connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Flow {
+    source: SocketAddr,
+    destination: SocketAddr,
+}
+
+impl Flow {
+    /// Key identifying the connection regardless of direction
+    fn connection(&self) -> (SocketAddr, SocketAddr) {
+        if self.source <= self.destination {
+            (self.source, self.destination)
+        } else {
+            (self.destination, self.source)
+        }
+    }
+}
+
+/// Reassembled bytes for a [`Flow`] that have not yet formed a complete message
+#[derive(Default)]
+struct FlowStream {
+    reassembler: Reassembler,
+    buffer: Vec<u8>,
+}
+
+/// Iterator over the DAP messages in a capture, see [`read_messages`]
+///
+/// Each direction of each TCP connection is reassembled separately, so retransmitted,
+/// reordered or interleaved segments do not corrupt the decoded messages. Messages are
+/// yielded in the order they are completed.
+pub struct Messages<R: Read> {
+    packets: Packets<R>,
+    port: u16,
+    flows: HashMap<Flow, FlowStream>,
+    stream_ids: HashMap<(SocketAddr, SocketAddr), usize>,
+    ready: VecDeque<CapturedMessage>,
+}
+
+impl<R: Read> Messages<R> {
+    fn handle_frame(&mut self, frame: &Frame) {
+        let value = match frame.slice() {
+            Ok(value) => value,
+            Err(e) => {
+                tracing::warn!(error = %e, "error parsing frame");
+                return;
             }
+        };
+        tracing::trace!("got sliced packet");
 
-            let mut reader = transport::reader::get(BufReader::new(messages.as_slice()));
-            loop {
-                match reader.poll_message() {
-                    Ok(Some(message)) => {
-                        result.push(message);
-                    }
-                    Ok(None) => break,
-                    Err(e) => tracing::warn!(error = ?e, "invalid message"),
+        let (Some(net), Some(TransportSlice::Tcp(tcph))) = (value.net, value.transport) else {
+            return;
+        };
+        tracing::trace!("got tcp layer");
+
+        // skip packets that are not for the specified port
+        if tcph.source_port() != self.port && tcph.destination_port() != self.port {
+            return;
+        }
+
+        let (source, destination) = match net {
+            NetSlice::Ipv4(ip) => (
+                IpAddr::from(ip.header().source_addr()),
+                IpAddr::from(ip.header().destination_addr()),
+            ),
+            NetSlice::Ipv6(ip) => (
+                IpAddr::from(ip.header().source_addr()),
+                IpAddr::from(ip.header().destination_addr()),
+            ),
+            NetSlice::Arp(_) => return,
+        };
+        let flow = Flow {
+            source: SocketAddr::new(source, tcph.source_port()),
+            destination: SocketAddr::new(destination, tcph.destination_port()),
+        };
+
+        let (direction, client, server) = if tcph.destination_port() == self.port {
+            (Direction::ClientToServer, flow.source, flow.destination)
+        } else {
+            (Direction::ServerToClient, flow.destination, flow.source)
+        };
+        let next_stream_id = self.stream_ids.len();
+        let stream_id = *self
+            .stream_ids
+            .entry(flow.connection())
+            .or_insert(next_stream_id);
+
+        let stream = self.flows.entry(flow).or_default();
+        stream.reassembler.push(
+            tcph.sequence_number(),
+            tcph.syn(),
+            tcph.payload(),
+            &mut stream.buffer,
+        );
+        while let Some(message) = take_message(&mut stream.buffer) {
+            match message {
+                Ok(message) => self.ready.push_back(CapturedMessage {
+                    direction,
+                    timestamp: frame.timestamp,
+                    stream_id,
+                    client,
+                    server,
+                    message,
+                }),
+                Err(e) => tracing::warn!(error = ?e, "invalid message"),
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for Messages<R> {
+    type Item = CapturedMessage;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.ready.pop_front() {
+                return Some(message);
+            }
+
+            match self.packets.next_frame()? {
+                Ok(frame) => self.handle_frame(&frame),
+                Err(e) => {
+                    // treat a truncated or corrupt capture as the end of the stream
+                    tracing::error!(error = %e, "reading capture");
+                    return None;
                 }
             }
         }
-        Some(_) | None => eyre::bail!("invalid extension, expected .pcap or .pcapng"),
+    }
+}
+
+/// Remove the first complete `Content-Length` framed message from `buffer`, if there is one
+fn take_message(buffer: &mut Vec<u8>) -> Option<eyre::Result<Message>> {
+    const SEPARATOR: &[u8] = b"\r\n\r\n";
+
+    let header_end = buffer
+        .windows(SEPARATOR.len())
+        .position(|w| w == SEPARATOR)?;
+    let body_start = header_end + SEPARATOR.len();
+
+    let header = String::from_utf8_lossy(&buffer[..header_end]).into_owned();
+    let content_length = header.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == "Content-Length").then(|| value.trim().parse::<usize>())
+    });
+    let content_length = match content_length {
+        Some(Ok(length)) => length,
+        Some(Err(_)) | None => {
+            // skip the header so we can resynchronise on the next message
+            buffer.drain(..body_start);
+            return Some(Err(eyre::eyre!(
+                "invalid or missing content length in header {header:?}"
+            )));
+        }
     };
 
-    Ok(result)
+    if buffer.len() < body_start + content_length {
+        return None;
+    }
+
+    let body: Vec<u8> = buffer
+        .drain(..body_start + content_length)
+        .skip(body_start)
+        .collect();
+    Some(serde_json::from_slice(&body).with_context(|| {
+        format!(
+            "could not construct message from: {}",
+            String::from_utf8_lossy(&body)
+        )
+    }))
+}
+
+/// Link layer frame read from a capture
+struct Frame {
+    timestamp: Duration,
+    datalink: DataLink,
+    data: Vec<u8>,
+}
+
+impl Frame {
+    /// Parse the frame down to its network and transport layers
+    fn slice(&self) -> eyre::Result<SlicedPacket<'_>> {
+        let packet = match self.datalink {
+            DataLink::ETHERNET => SlicedPacket::from_ethernet(&self.data),
+            // e.g. `tcpdump -i any` on linux
+            DataLink::LINUX_SLL => SlicedPacket::from_linux_sll(&self.data),
+            // loopback captures on macOS and the BSDs start with a 4 byte address family
+            DataLink::NULL | DataLink::LOOP => {
+                SlicedPacket::from_ip(self.data.get(4..).unwrap_or_default())
+            }
+            DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => SlicedPacket::from_ip(&self.data),
+            datalink => return Err(eyre::eyre!("unsupported datalink {datalink:?}")),
+        };
+        packet.context("slicing packet")
+    }
+}
+
+/// Fail for link types that [`Frame::slice`] cannot parse, rather than silently finding no
+/// messages in the capture
+fn check_datalink(datalink: DataLink) -> eyre::Result<()> {
+    match datalink {
+        DataLink::ETHERNET
+        | DataLink::LINUX_SLL
+        | DataLink::NULL
+        | DataLink::LOOP
+        | DataLink::RAW
+        | DataLink::IPV4
+        | DataLink::IPV6 => Ok(()),
+        datalink => Err(eyre::eyre!("unsupported datalink {datalink:?}")),
+    }
+}
+
+enum Packets<R: Read> {
+    Pcap(PcapReader<R>),
+    PcapNg(PcapNgReader<R>),
+}
+
+impl<R: Read> Packets<R> {
+    fn new(source: R, format: Format) -> eyre::Result<Self> {
+        match format {
+            Format::Pcap => {
+                let reader = PcapReader::new(source).context("parsing pcap header")?;
+                check_datalink(reader.header().datalink)?;
+                Ok(Packets::Pcap(reader))
+            }
+            Format::PcapNg => Ok(Packets::PcapNg(
+                PcapNgReader::new(source).context("parsing pcapng header")?,
+            )),
+        }
+    }
+
+    /// Read the next link layer frame from the capture
+    fn next_frame(&mut self) -> Option<eyre::Result<Frame>> {
+        match self {
+            Packets::Pcap(reader) => {
+                let datalink = reader.header().datalink;
+                let packet = reader.next_packet()?;
+                Some(
+                    packet
+                        .map(|p| Frame {
+                            timestamp: p.timestamp,
+                            datalink,
+                            data: p.data.into_owned(),
+                        })
+                        .context("parsing next packet"),
+                )
+            }
+            Packets::PcapNg(reader) => loop {
+                match reader.next_block()? {
+                    Ok(Block::InterfaceDescription(interface)) => {
+                        if let Err(e) = check_datalink(interface.linktype) {
+                            return Some(Err(e));
+                        }
+                    }
+                    Ok(Block::EnhancedPacket(EnhancedPacketBlock {
+                        interface_id,
+                        timestamp,
+                        data,
+                        ..
+                    })) => {
+                        tracing::trace!("block length {}", data.len());
+                        let data = data.into_owned();
+                        let Some(interface) = reader.interfaces().get(interface_id as usize) else {
+                            return Some(Err(eyre::eyre!(
+                                "packet for unknown interface {interface_id}"
+                            )));
+                        };
+                        return Some(Ok(Frame {
+                            timestamp,
+                            datalink: interface.linktype,
+                            data,
+                        }));
+                    }
+                    Ok(e) => tracing::warn!("unhandled block type {e:?}"),
+                    Err(e) => return Some(Err(e).context("parsing next block")),
+                }
+            },
+        }
+    }
 }
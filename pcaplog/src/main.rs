@@ -1,8 +1,8 @@
-use std::path::PathBuf;
+use std::{fs::File, io::BufReader, path::PathBuf};
 
 use clap::Parser;
 use eyre::WrapErr;
-use pcaplog::extract_messages;
+use pcaplog::{export_jsonl, extract_messages, read_messages, CapturedMessage, Format};
 use serde::Serialize;
 use tracing_subscriber::EnvFilter;
 
@@ -12,10 +12,14 @@ struct Args {
 
     #[clap(short, long, default_value_t = 5678)]
     port: u16,
+
+    /// Write one JSON object per line instead of a pretty printed array
+    #[clap(long)]
+    jsonl: bool,
 }
 
 #[derive(Serialize)]
-struct Messages(Vec<transport::Message>);
+struct Messages(Vec<CapturedMessage>);
 
 fn main() -> eyre::Result<()> {
     tracing_subscriber::fmt()
@@ -26,6 +30,15 @@ fn main() -> eyre::Result<()> {
     let args = Args::parse();
     tracing::debug!(?args, "parsed command line arguments");
 
+    if args.jsonl {
+        // write messages as they are decoded rather than holding the whole capture in memory
+        let format = Format::from_path(&args.file)?;
+        let file = File::open(&args.file).context("opening capture file")?;
+        let messages =
+            read_messages(BufReader::new(file), format, args.port).context("reading capture")?;
+        return export_jsonl(messages, std::io::stdout().lock()).context("exporting messages");
+    }
+
     let messages =
         Messages(extract_messages(&args.file, args.port).context("extracting messages")?);
     println!(
@@ -0,0 +1,126 @@
+//! Reassembly of the byte stream carried by one direction of a TCP connection
+use std::collections::BTreeMap;
+
+/// Orders TCP segments by sequence number, dropping retransmitted data
+#[derive(Debug, Default)]
+pub(crate) struct Reassembler {
+    /// Sequence number of the next byte we expect, unknown until the first segment is seen
+    next_seq: Option<u32>,
+    /// Segments received ahead of `next_seq`, keyed by their sequence number
+    pending: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Reassembler {
+    /// Process a segment, appending any bytes that are now contiguous to `out`
+    pub(crate) fn push(&mut self, seq: u32, syn: bool, payload: &[u8], out: &mut Vec<u8>) {
+        if syn {
+            // the SYN flag consumes one sequence number
+            self.next_seq = Some(seq.wrapping_add(1));
+            self.pending.clear();
+            return;
+        }
+
+        if payload.is_empty() {
+            return;
+        }
+
+        // captures may start part way through a connection
+        let next = *self.next_seq.get_or_insert(seq);
+        if offset(seq, next) > 0 {
+            tracing::trace!(%seq, %next, "buffering out of order segment");
+            let entry = self.pending.entry(seq).or_default();
+            if payload.len() > entry.len() {
+                *entry = payload.to_vec();
+            }
+            return;
+        }
+
+        self.apply(seq, payload, out);
+        self.drain_pending(out);
+    }
+
+    /// Append the part of a segment at or before `next_seq` that has not been seen yet
+    fn apply(&mut self, seq: u32, payload: &[u8], out: &mut Vec<u8>) {
+        let next = self
+            .next_seq
+            .expect("next sequence number set before applying segments");
+        let overlap = (-offset(seq, next)) as usize;
+        if overlap >= payload.len() {
+            tracing::trace!(%seq, "dropping retransmitted segment");
+            return;
+        }
+
+        let new = &payload[overlap..];
+        out.extend_from_slice(new);
+        self.next_seq = Some(next.wrapping_add(new.len() as u32));
+    }
+
+    fn drain_pending(&mut self, out: &mut Vec<u8>) {
+        loop {
+            let next = self
+                .next_seq
+                .expect("next sequence number set before draining");
+            let Some(seq) = self
+                .pending
+                .keys()
+                .copied()
+                .find(|&seq| offset(seq, next) <= 0)
+            else {
+                return;
+            };
+            let payload = self.pending.remove(&seq).expect("key found in map");
+            self.apply(seq, &payload, out);
+        }
+    }
+}
+
+/// Signed distance from `base` to `seq`, accounting for sequence number wrap around
+fn offset(seq: u32, base: u32) -> i32 {
+    seq.wrapping_sub(base) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reassembler;
+
+    fn reassemble(segments: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut reassembler = Reassembler::default();
+        let mut out = Vec::new();
+        reassembler.push(99, true, &[], &mut out);
+        for (seq, payload) in segments {
+            reassembler.push(*seq, false, payload, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn in_order() {
+        assert_eq!(reassemble(&[(100, b"abc"), (103, b"def")]), b"abcdef");
+    }
+
+    #[test]
+    fn out_of_order() {
+        assert_eq!(
+            reassemble(&[(103, b"def"), (106, b"g"), (100, b"abc")]),
+            b"abcdefg"
+        );
+    }
+
+    #[test]
+    fn retransmissions() {
+        assert_eq!(
+            reassemble(&[(100, b"abc"), (100, b"abc"), (102, b"cde"), (103, b"def")]),
+            b"abcdef"
+        );
+    }
+
+    #[test]
+    fn sequence_wrap_around() {
+        let mut reassembler = Reassembler::default();
+        let mut out = Vec::new();
+        reassembler.push(u32::MAX - 1, false, b"ab", &mut out);
+        reassembler.push(1, false, b"d", &mut out);
+        reassembler.push(0, false, b"c", &mut out);
+        assert_eq!(out, b"abcd");
+    }
+}
@@ -1,9 +1,23 @@
-use std::{io::IsTerminal, path::PathBuf};
+use std::{
+    cell::Cell,
+    io::{IsTerminal, Read},
+    path::PathBuf,
+    rc::Rc,
+};
 
 use eyre::WrapErr;
-use pcaplog::extract_messages;
+use pcap_file::{
+    pcap::{PcapHeader, PcapPacket, PcapWriter},
+    pcapng::{Block, PcapNgReader},
+    DataLink,
+};
+use pcaplog::{
+    export_jsonl, extract_messages, extract_sessions, read_messages, split_sessions, Direction,
+    Format,
+};
 use rstest::rstest;
 use tracing_subscriber::EnvFilter;
+use transport::{replay::Recording, requests::StartDebuggingRequest};
 
 #[derive(Debug)]
 pub struct Failure {
@@ -41,6 +55,323 @@ fn capture(
     Ok(())
 }
 
+#[test]
+fn message_metadata() -> eyre::Result<()> {
+    let messages = extract_messages("../captures/vscode/vscode-attach-connect.pcapng", 5678)
+        .context("extracting messages")?;
+
+    assert!(messages
+        .iter()
+        .any(|m| m.direction == Direction::ClientToServer));
+    assert!(messages
+        .iter()
+        .any(|m| m.direction == Direction::ServerToClient));
+    assert!(messages
+        .windows(2)
+        .all(|w| w[0].timestamp <= w[1].timestamp));
+    assert!(messages.iter().all(|m| m.stream_id == 0));
+
+    Ok(())
+}
+
+/// Replace the TCP port `from` with `to` in an ethernet frame carrying IPv4 or IPv6
+fn rewrite_port(frame: &[u8], from: u16, to: u16) -> Vec<u8> {
+    let mut frame = frame.to_vec();
+    let tcp = match frame[12..14] {
+        [0x08, 0x00] => 14 + usize::from(frame[14] & 0x0f) * 4,
+        [0x86, 0xdd] => 14 + 40,
+        _ => return frame,
+    };
+    for offset in [tcp, tcp + 2] {
+        if frame[offset..offset + 2] == from.to_be_bytes() {
+            frame[offset..offset + 2].copy_from_slice(&to.to_be_bytes());
+        }
+    }
+    frame
+}
+
+#[test]
+fn sessions_split_by_connection() -> eyre::Result<()> {
+    let pcapng_path = "../captures/vscode/vscode-attach-connect.pcapng";
+    let first = extract_messages(pcapng_path, 5678).context("extracting messages")?;
+    let first_client = first[0].client;
+    let second_client = std::net::SocketAddr::new(first_client.ip(), 40000);
+
+    // replay the session over a second connection from another client port, interleaving the
+    // packets of both connections
+    let pcap_path = std::env::temp_dir().join(format!(
+        "pcaplog-{}-two-connections.pcap",
+        std::process::id()
+    ));
+    let mut reader = PcapNgReader::new(std::fs::File::open(pcapng_path)?)?;
+    let mut writer = PcapWriter::new(std::fs::File::create(&pcap_path)?)?;
+    while let Some(block) = reader.next_block() {
+        if let Block::EnhancedPacket(packet) = block? {
+            let copy = rewrite_port(&packet.data, first_client.port(), second_client.port());
+            for data in [packet.data.to_vec(), copy] {
+                writer.write_packet(&PcapPacket::new(
+                    packet.timestamp,
+                    data.len() as u32,
+                    &data,
+                ))?;
+            }
+        }
+    }
+    drop(writer);
+
+    let sessions = extract_sessions(&pcap_path, 5678).context("extracting sessions");
+    let _ = std::fs::remove_file(&pcap_path);
+    let sessions = sessions?;
+
+    let expected: Vec<_> = first
+        .iter()
+        .map(|m| serde_json::to_value(&m.message))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].stream_id, 0);
+    assert_eq!(sessions[0].client, first_client);
+    assert_eq!(sessions[1].stream_id, 1);
+    assert_eq!(sessions[1].client, second_client);
+    for session in &sessions {
+        assert_eq!(session.server.port(), 5678);
+        let messages: Vec<_> = session
+            .messages
+            .iter()
+            .map(|m| serde_json::to_value(&m.message))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(messages, expected);
+        assert!(session
+            .messages
+            .iter()
+            .all(|m| m.stream_id == session.stream_id && m.client == session.client));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn jsonl_export() -> eyre::Result<()> {
+    let messages = extract_messages("../captures/vscode/vscode-attach-connect.pcapng", 5678)
+        .context("extracting messages")?;
+
+    let mut output = Vec::new();
+    export_jsonl(&messages, &mut output).context("exporting messages")?;
+
+    let lines: Vec<serde_json::Value> = std::str::from_utf8(&output)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(lines.len(), messages.len());
+    assert_eq!(lines[0]["direction"], "server_to_client");
+    assert_eq!(lines[0]["stream_id"], 0);
+    assert!(lines[0]["timestamp"].is_f64());
+    assert_eq!(lines[0]["payload"]["type"], "event");
+
+    Ok(())
+}
+
+#[test]
+fn jsonl_export_from_reader() -> eyre::Result<()> {
+    let path = "../captures/vscode/vscode-attach-connect.pcapng";
+    let messages = extract_messages(path, 5678).context("extracting messages")?;
+    let mut expected = Vec::new();
+    export_jsonl(&messages, &mut expected).context("exporting messages")?;
+
+    let file = std::fs::File::open(path)?;
+    let streamed = read_messages(std::io::BufReader::new(file), Format::PcapNg, 5678)?;
+    let mut output = Vec::new();
+    export_jsonl(streamed, &mut output).context("exporting streamed messages")?;
+
+    assert_eq!(output, expected);
+
+    Ok(())
+}
+
+#[test]
+fn sessions_convert_to_recordings() -> eyre::Result<()> {
+    let messages = extract_messages("../captures/vscode/vscode-attach-connect.pcapng", 5678)
+        .context("extracting messages")?;
+    let mut output = Vec::new();
+    export_jsonl(&messages, &mut output).context("exporting messages")?;
+    let from_jsonl = Recording::from_jsonl(output.as_slice()).context("loading recording")?;
+
+    let recording = split_sessions(messages)[0]
+        .recording()
+        .context("converting session")?;
+    assert_eq!(recording.messages().len(), from_jsonl.messages().len());
+    assert!(matches!(
+        recording.start_request().map(|r| r.request),
+        Some(StartDebuggingRequest::Attach)
+    ));
+
+    Ok(())
+}
+
+/// Convert the ethernet pcapng capture at `path` into a classic pcap capture with `datalink`,
+/// rewriting the link layer header of each frame with `relink`
+fn convert_to_pcap(
+    path: &str,
+    datalink: DataLink,
+    relink: impl Fn(&[u8]) -> Vec<u8>,
+) -> eyre::Result<PathBuf> {
+    let pcap_path =
+        std::env::temp_dir().join(format!("pcaplog-{}-{datalink:?}.pcap", std::process::id()));
+
+    let mut reader = PcapNgReader::new(std::fs::File::open(path)?)?;
+    let header = PcapHeader {
+        datalink,
+        ..Default::default()
+    };
+    let mut writer = PcapWriter::with_header(std::fs::File::create(&pcap_path)?, header)?;
+    while let Some(block) = reader.next_block() {
+        if let Block::EnhancedPacket(packet) = block? {
+            let data = relink(&packet.data);
+            writer.write_packet(&PcapPacket::new(packet.timestamp, data.len() as u32, &data))?;
+        }
+    }
+    Ok(pcap_path)
+}
+
+#[test]
+fn pcap_matches_pcapng() -> eyre::Result<()> {
+    let pcapng_path = "../captures/vscode/vscode-attach-connect.pcapng";
+    let pcap_path = convert_to_pcap(pcapng_path, DataLink::ETHERNET, <[u8]>::to_vec)?;
+
+    let from_pcap = extract_messages(&pcap_path, 5678).context("extracting pcap messages");
+    let _ = std::fs::remove_file(&pcap_path);
+    let from_pcapng = extract_messages(pcapng_path, 5678).context("extracting pcapng messages")?;
+
+    assert_eq!(from_pcap?.len(), from_pcapng.len());
+
+    Ok(())
+}
+
+/// Length of an ethernet header, without VLAN tags
+const ETHERNET_HEADER: usize = 14;
+
+/// Linux "cooked" header, as written by `tcpdump -i any`
+fn linux_sll(frame: &[u8]) -> Vec<u8> {
+    let mut data = vec![0, 0, 0, 1, 0, 6];
+    data.extend_from_slice(&frame[6..12]);
+    data.extend_from_slice(&[0, 0]);
+    // ether type
+    data.extend_from_slice(&frame[12..ETHERNET_HEADER]);
+    data.extend_from_slice(&frame[ETHERNET_HEADER..]);
+    data
+}
+
+/// BSD loopback header, as written when capturing on `lo0` on macOS
+fn null_loopback(frame: &[u8]) -> Vec<u8> {
+    let family: u32 = if frame[12..ETHERNET_HEADER] == [0x86, 0xdd] {
+        30
+    } else {
+        2
+    };
+    let mut data = family.to_le_bytes().to_vec();
+    data.extend_from_slice(&frame[ETHERNET_HEADER..]);
+    data
+}
+
+fn raw_ip(frame: &[u8]) -> Vec<u8> {
+    frame[ETHERNET_HEADER..].to_vec()
+}
+
+#[rstest]
+#[case(DataLink::LINUX_SLL, linux_sll)]
+#[case(DataLink::NULL, null_loopback)]
+#[case(DataLink::RAW, raw_ip)]
+fn link_types_match_ethernet(
+    #[case] datalink: DataLink,
+    #[case] relink: fn(&[u8]) -> Vec<u8>,
+) -> eyre::Result<()> {
+    let pcapng_path = "../captures/vscode/vscode-attach-connect.pcapng";
+    let pcap_path = convert_to_pcap(pcapng_path, datalink, relink)?;
+
+    let converted = extract_messages(&pcap_path, 5678).context("extracting converted messages");
+    let _ = std::fs::remove_file(&pcap_path);
+    let converted = converted?;
+    let from_ethernet = extract_messages(pcapng_path, 5678)?;
+
+    assert_eq!(converted.len(), from_ethernet.len());
+    assert_eq!(converted[0].client, from_ethernet[0].client);
+
+    Ok(())
+}
+
+#[test]
+fn unsupported_link_types_are_an_error() -> eyre::Result<()> {
+    let header = PcapHeader {
+        datalink: DataLink::IEEE802_11,
+        ..Default::default()
+    };
+    let capture = PcapWriter::with_header(Vec::new(), header)?.into_writer();
+
+    let Err(e) = read_messages(capture.as_slice(), Format::Pcap, 5678) else {
+        panic!("reading a wifi capture succeeded");
+    };
+    assert!(format!("{e:?}").contains("unsupported datalink"), "{e:?}");
+
+    Ok(())
+}
+
+/// Reader that hands out at most `chunk` bytes at a time, recording how many it has handed out
+struct ChunkedReader {
+    data: Vec<u8>,
+    chunk: usize,
+    consumed: Rc<Cell<usize>>,
+}
+
+impl Read for ChunkedReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let start = self.consumed.get();
+        let end = self
+            .data
+            .len()
+            .min(start + self.chunk)
+            .min(start + buf.len());
+        buf[..end - start].copy_from_slice(&self.data[start..end]);
+        self.consumed.set(end);
+        Ok(end - start)
+    }
+}
+
+#[test]
+fn messages_are_read_as_the_capture_is_consumed() -> eyre::Result<()> {
+    let data = std::fs::read("../captures/vscode/vscode-attach-connect.pcapng")?;
+    let total = data.len();
+    let consumed = Rc::new(Cell::new(0));
+    let reader = ChunkedReader {
+        data,
+        chunk: 512,
+        consumed: Rc::clone(&consumed),
+    };
+
+    let mut messages = read_messages(reader, Format::PcapNg, 5678)?;
+    assert!(messages.next().is_some());
+    assert!(
+        consumed.get() < total / 2,
+        "read {} of {total} bytes for the first message",
+        consumed.get()
+    );
+
+    assert_eq!(messages.count(), 33);
+    assert_eq!(consumed.get(), total);
+
+    Ok(())
+}
+
+#[test]
+fn truncated_capture_ends_the_stream() -> eyre::Result<()> {
+    let mut data = std::fs::read("../captures/vscode/vscode-attach-connect.pcapng")?;
+    data.truncate(data.len() * 2 / 3);
+
+    let count = read_messages(data.as_slice(), Format::PcapNg, 5678)?.count();
+    assert!(count > 0);
+    assert!(count < 34);
+
+    Ok(())
+}
+
 #[ctor::ctor]
 fn init() {
     let in_ci = std::env::var("CI")
@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use debugger::Event;
+use pyo3::exceptions::{PyRuntimeError, PyStopAsyncIteration};
+use pyo3::prelude::*;
+
+use crate::debugger::{create_debugger, wait_for_program_state};
+use crate::errors::dap_error;
+use crate::events::PyEvent;
+use crate::metrics::{spawn_metrics_callback, PyMetrics};
+
+/// asyncio compatible version of `Debugger`
+///
+/// Blocking operations are run on the tokio blocking pool so that awaiting them does not
+/// block the Python event loop.
+#[pyclass]
+pub(crate) struct AsyncDebugger {
+    internal_debugger: Arc<debugger::Debugger>,
+    launched: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl AsyncDebugger {
+    #[new]
+    #[pyo3(signature = (/, breakpoints, config_path, config_name=None, file=None, program=None, port=None))]
+    pub fn new(
+        breakpoints: Vec<usize>,
+        config_path: PathBuf,
+        config_name: Option<String>,
+        file: Option<PathBuf>,
+        program: Option<PathBuf>,
+        port: Option<u16>,
+    ) -> PyResult<Self> {
+        let debugger = create_debugger(port, breakpoints, config_path, config_name, file, program)?;
+        Ok(Self {
+            internal_debugger: Arc::new(debugger),
+            launched: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Start or continue execution, resolving to the program state when the debugee next
+    /// pauses, or `None` if it ends
+    pub fn continue_<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let debugger = Arc::clone(&self.internal_debugger);
+        let launched = Arc::clone(&self.launched);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            tokio::task::spawn_blocking(move || {
+                if !launched.swap(true, Ordering::SeqCst) {
+                    debugger
+                        .start()
+                        .map_err(|e| dap_error("launching debugger", e))?;
+                } else {
+                    debugger
+                        .r#continue()
+                        .map_err(|e| dap_error("continuing execution", e))?;
+                }
+
+                tracing::debug!("waiting for debugee to run");
+                debugger.wait_for_event(|evt| matches!(evt, Event::Running));
+                wait_for_program_state(&debugger, None)
+            })
+            .await
+            .map_err(|e| PyRuntimeError::new_err(format!("joining background task: {e}")))?
+        })
+    }
+
+    /// Step over the current line, resolving to the program state when the debugee next
+    /// pauses, or `None` if it ends
+    pub fn step_over<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let debugger = Arc::clone(&self.internal_debugger);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            tokio::task::spawn_blocking(move || {
+                debugger
+                    .step_over()
+                    .map_err(|e| dap_error("stepping debugee", e))?;
+                wait_for_program_state(&debugger, None)
+            })
+            .await
+            .map_err(|e| PyRuntimeError::new_err(format!("joining background task: {e}")))?
+        })
+    }
+
+    /// Request and event metrics for the session so far
+    pub fn metrics(&self) -> PyMetrics {
+        PyMetrics::new(self.internal_debugger.metrics().snapshot())
+    }
+
+    /// Call `callback` with the session metrics every `interval` seconds, from a background
+    /// thread
+    #[pyo3(signature = (callback, interval=1.0))]
+    pub fn on_metrics(&self, callback: PyObject, interval: f64) {
+        spawn_metrics_callback(&self.internal_debugger, callback, interval);
+    }
+
+    /// Async iterator over debugger events, which stops after the debugee ends
+    pub fn events(&self) -> EventStream {
+        EventStream {
+            rx: self.internal_debugger.subscribe(),
+            debugger: Arc::clone(&self.internal_debugger),
+            ended: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// Async iterator returned by [`AsyncDebugger::events`]
+#[pyclass]
+pub(crate) struct EventStream {
+    rx: crossbeam_channel::Receiver<Event>,
+    debugger: Arc<debugger::Debugger>,
+    /// Set once [`Event::Ended`] has been yielded
+    ended: Arc<AtomicBool>,
+}
+
+impl EventStream {
+    /// Wait for the next event, or `None` once the stream is over
+    fn next_event(rx: &crossbeam_channel::Receiver<Event>, ended: &AtomicBool) -> Option<Event> {
+        if ended.load(Ordering::SeqCst) {
+            return None;
+        }
+        let event = rx.recv().ok()?;
+        if matches!(event, Event::Ended) {
+            ended.store(true, Ordering::SeqCst);
+        }
+        Some(event)
+    }
+}
+
+#[pymethods]
+impl EventStream {
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let rx = self.rx.clone();
+        let debugger = Arc::clone(&self.debugger);
+        let ended = Arc::clone(&self.ended);
+        pyo3_async_runtimes::tokio::future_into_py(py, async move {
+            let event = tokio::task::spawn_blocking(move || Self::next_event(&rx, &ended))
+                .await
+                .map_err(|e| PyRuntimeError::new_err(format!("joining background task: {e}")))?;
+            match event {
+                Some(event) => Ok(PyEvent::new(event, &debugger)),
+                None => Err(PyStopAsyncIteration::new_err("debugger closed")),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use dap_test_harness::{FakeAdapter, Script};
+    use debugger::Event;
+    use serde_json::json;
+
+    use super::{AsyncDebugger, EventStream};
+
+    #[test]
+    fn event_stream_ends_with_debugee() {
+        let adapter = FakeAdapter::start(
+            Script::initialised(json!({}))
+                .respond("configurationDone", None)
+                .event("output", json!({ "category": "stdout", "output": "1\n" }))
+                .event("output", json!({ "category": "stdout", "output": "2\n" }))
+                .event("terminated", None),
+        )
+        .unwrap();
+        let debugger = dap_test_harness::attach(&adapter).unwrap();
+        let debugger = AsyncDebugger {
+            internal_debugger: Arc::new(debugger),
+            launched: Arc::new(AtomicBool::new(true)),
+        };
+        let stream = debugger.events();
+        debugger.internal_debugger.start().unwrap();
+
+        let EventStream { rx, ended, .. } = &stream;
+        let events: Vec<_> = std::iter::from_fn(|| EventStream::next_event(rx, ended))
+            .filter_map(|e| match e {
+                Event::Output { output, .. } => Some(output),
+                Event::Ended => Some("ended".to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(events, vec!["1\n", "2\n", "ended"]);
+
+        drop(stream);
+        drop(debugger);
+        adapter.join().unwrap();
+    }
+}
@@ -1,16 +1,128 @@
-use debugger::{AttachArguments, Event, LaunchArguments, PausedFrame};
+use debugger::{
+    AttachArguments, EvaluateContext, Event, LaunchArguments, PausedFrame, TerminateMode,
+};
 use launch_configuration::{ChosenLaunchConfiguration, LaunchConfiguration};
-use pyo3::exceptions::PyRuntimeError;
+use pyo3::exceptions::{
+    PyAttributeError, PyKeyError, PyRuntimeError, PyTimeoutError, PyValueError,
+};
 use pyo3::prelude::*;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use std::{collections::HashMap, path::Path};
-use transport::types::StackFrame;
-use tree_sitter::{Parser, Point};
+use transport::replay::{Recording, Timing};
+use transport::tap::{JsonlTap, MessageTap};
+use transport::types::{
+    ExceptionBreakpointsFilter, StackFrame, StackFrameId, ThreadId, VariablesReference,
+};
+use tree_sitter::{Language, Parser, Point};
+
+use crate::errors::dap_error;
+use crate::events::{spawn_callback, EventIterator};
+use crate::metrics::{spawn_metrics_callback, PyMetrics};
+
+/// Tree-sitter grammar and node kinds used to show the source of a paused frame
+struct SourceLanguage {
+    language: Language,
+    /// Node kinds that represent the body of a function
+    function_kinds: &'static [&'static str],
+}
+
+impl SourceLanguage {
+    /// Pick the grammar by file extension
+    ///
+    /// Anything other than Go or Rust is parsed as Python, e.g. `.pyw` files and scripts
+    /// without an extension.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("go") => Self {
+                language: tree_sitter_go::LANGUAGE.into(),
+                function_kinds: &["function_declaration", "method_declaration", "func_literal"],
+            },
+            Some("rs") => Self {
+                language: tree_sitter_rust::LANGUAGE.into(),
+                function_kinds: &["function_item", "closure_expression"],
+            },
+            _ => Self {
+                language: tree_sitter_python::LANGUAGE.into(),
+                function_kinds: &["function_definition"],
+            },
+        }
+    }
+
+    /// Source of the innermost function containing `line` (1-based) of `contents`
+    fn enclosing_function<'a>(&self, contents: &'a str, line: usize) -> PyResult<&'a str> {
+        let line_text = line
+            .checked_sub(1)
+            .and_then(|row| contents.split('\n').nth(row))
+            .ok_or_else(|| PyRuntimeError::new_err(format!("no line {line} in source")))?;
+        let start = Point {
+            row: line - 1,
+            column: 0,
+        };
+        let end = Point {
+            row: line - 1,
+            column: line_text.len(),
+        };
+
+        let mut parser = Parser::new();
+        parser
+            .set_language(&self.language)
+            .map_err(|e| PyRuntimeError::new_err(format!("setting treesitter language: {e}")))?;
+        let tree = parser
+            .parse(contents.as_bytes(), None)
+            .ok_or_else(|| PyRuntimeError::new_err("error parsing file".to_string()))?;
+        let root = tree.root_node();
+        let descendant = root
+            .descendant_for_point_range(start, end)
+            .ok_or_else(|| PyRuntimeError::new_err("getting descendant"))?;
+
+        // find up until function body
+        let mut n = descendant;
+
+        loop {
+            tracing::debug!(node = ?n, "loop iteration");
+            if self.function_kinds.contains(&n.kind()) {
+                return n.utf8_text(contents.as_bytes()).map_err(|e| {
+                    PyRuntimeError::new_err(format!(
+                        "error getting utf8 text from input source: {e}"
+                    ))
+                });
+            }
+
+            match n.parent() {
+                Some(parent) => {
+                    n = parent;
+                }
+                None => {
+                    return Err(PyRuntimeError::new_err(
+                        "no function body found".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+}
 
 #[pyclass]
+#[derive(Clone)]
 pub struct Breakpoint {
+    #[pyo3(get)]
+    pub id: debugger::BreakpointId,
+    #[pyo3(get)]
     pub line: usize,
+    #[pyo3(get)]
     pub file: String,
+    #[pyo3(get)]
+    pub condition: Option<String>,
+    #[pyo3(get)]
+    pub hit_condition: Option<String>,
+    /// Message logged instead of pausing, if this is a logpoint
+    #[pyo3(get)]
+    pub log_message: Option<String>,
+    /// Whether the adapter bound the breakpoint to executable code, `None` if not yet known
+    #[pyo3(get)]
+    pub verified: Option<bool>,
 }
 
 #[pymethods]
@@ -20,44 +132,102 @@ impl Breakpoint {
     }
 }
 
-impl From<debugger::Breakpoint> for Breakpoint {
-    fn from(value: debugger::Breakpoint) -> Self {
+impl Breakpoint {
+    pub(crate) fn new(
+        id: debugger::BreakpointId,
+        value: debugger::Breakpoint,
+        verified: Option<bool>,
+    ) -> Self {
         Self {
+            id,
             line: value.line,
             file: format!("{}", value.path.display()),
+            condition: value.condition,
+            hit_condition: value.hit_condition,
+            log_message: value.log_message,
+            verified,
         }
     }
 }
 
-#[pyclass]
+#[pyclass(name = "StackFrame")]
 #[derive(Clone)]
-pub struct PyStackFrame(StackFrame);
+pub struct PyStackFrame {
+    frame: StackFrame,
+    debugger: Arc<debugger::Debugger>,
+}
 
-impl From<StackFrame> for PyStackFrame {
-    fn from(value: StackFrame) -> Self {
-        Self(value)
+impl PyStackFrame {
+    pub(crate) fn new(frame: StackFrame, debugger: &Arc<debugger::Debugger>) -> Self {
+        Self {
+            frame,
+            debugger: Arc::clone(debugger),
+        }
     }
 }
 
 #[pymethods]
 impl PyStackFrame {
+    #[getter]
+    fn id(&self) -> StackFrameId {
+        self.frame.id
+    }
+
     #[getter]
     fn name(&self) -> String {
-        self.0.name.clone()
+        self.frame.name.clone()
     }
 
     #[getter]
     fn line(&self) -> usize {
-        self.0.line
+        self.frame.line
     }
 
+    /// Path of the source file for this frame, raising `RuntimeError` if it is not known,
+    /// see `path`
     #[getter]
     fn source(&self) -> PyResult<PathBuf> {
-        if let Some(path) = self.0.source.as_ref().and_then(|s| s.path.clone()) {
-            return Ok(path);
+        self.path().ok_or_else(|| {
+            PyRuntimeError::new_err(format!(
+                "stack frame {} has no source file",
+                self.frame.name
+            ))
+        })
+    }
+
+    /// Path of the source file for this frame, if known
+    #[getter]
+    fn path(&self) -> Option<PathBuf> {
+        self.frame.source.as_ref().and_then(|s| s.path.clone())
+    }
+
+    /// Make this the current frame, returning the program state as seen from it
+    fn switch(&self) -> PyResult<ProgramState> {
+        self.debugger
+            .change_scope(self.frame.id)
+            .map_err(|e| dap_error("changing scope", e))?;
+        match self
+            .debugger
+            .wait_for_event(|evt| matches!(evt, Event::ScopeChange { .. }))
+        {
+            Event::ScopeChange {
+                stack,
+                paused_frame,
+                ..
+            } => Ok(ProgramState::new(stack, paused_frame, &self.debugger)),
+            _ => unreachable!(),
         }
+    }
 
-        todo!()
+    /// Re-run this frame from its start, returning the program state once the debugee stops
+    /// again
+    ///
+    /// Only supported by some adapters.
+    fn restart(&self) -> PyResult<Option<ProgramState>> {
+        self.debugger
+            .restart_frame(self.frame.id)
+            .map_err(|e| dap_error("restarting frame", e))?;
+        wait_for_program_state(&self.debugger, None)
     }
 
     fn __repr__(&self) -> String {
@@ -67,11 +237,17 @@ impl PyStackFrame {
 
 #[pyclass(name = "PausedFrame")]
 #[derive(Clone)]
-pub struct PyPausedFrame(PausedFrame);
+pub struct PyPausedFrame {
+    frame: PausedFrame,
+    debugger: Arc<debugger::Debugger>,
+}
 
-impl From<PausedFrame> for PyPausedFrame {
-    fn from(value: PausedFrame) -> Self {
-        Self(value)
+impl PyPausedFrame {
+    pub(crate) fn new(frame: PausedFrame, debugger: &Arc<debugger::Debugger>) -> Self {
+        Self {
+            frame,
+            debugger: Arc::clone(debugger),
+        }
     }
 }
 
@@ -79,60 +255,263 @@ impl From<PausedFrame> for PyPausedFrame {
 impl PyPausedFrame {
     #[getter]
     fn variables(&self) -> HashMap<String, PyVariable> {
-        self.0
+        self.frame
             .variables
             .iter()
             .cloned()
-            .map(|v| (v.name.clone(), v.into()))
+            .map(|v| (v.name.clone(), PyVariable::new(v, &self.debugger)))
             .collect()
     }
 
+    /// Alias of `variables`
+    #[getter]
+    fn locals(&self) -> HashMap<String, PyVariable> {
+        self.variables()
+    }
+
     #[getter]
     fn stack(&self) -> PyStackFrame {
-        self.0.frame.clone().into()
+        PyStackFrame::new(self.frame.frame.clone(), &self.debugger)
     }
 }
 
+/// Variable whose children are fetched from the debugger on demand
 #[pyclass(name = "Variable")]
 #[derive(Clone)]
-pub struct PyVariable(transport::types::Variable);
+pub struct PyVariable {
+    variable: transport::types::Variable,
+    debugger: Arc<debugger::Debugger>,
+}
+
+impl PyVariable {
+    pub(crate) fn new(
+        variable: transport::types::Variable,
+        debugger: &Arc<debugger::Debugger>,
+    ) -> Self {
+        Self {
+            variable,
+            debugger: Arc::clone(debugger),
+        }
+    }
+}
 
 #[pymethods]
 impl PyVariable {
     #[getter]
     fn name(&self) -> String {
-        self.0.name.clone()
+        self.variable.name.clone()
     }
 
+    /// Value of the variable, fetched from the debugger first if the adapter marked it as lazy
     #[getter]
-    fn value(&self) -> String {
-        self.0.value.clone()
+    fn value(&self) -> PyResult<String> {
+        let variable = self
+            .debugger
+            .resolve_lazy(&self.variable)
+            .map_err(|e| dap_error("fetching value", e))?;
+        Ok(variable.value)
     }
 
     #[getter]
     fn r#type(&self) -> Option<String> {
-        self.0.r#type.clone()
+        self.variable.r#type.clone()
+    }
+
+    /// Kind of variable according to the adapter, e.g. `property`, `method` or `class`
+    #[getter]
+    fn kind(&self) -> Option<String> {
+        self.variable.kind().map(str::to_string)
+    }
+
+    #[getter]
+    fn read_only(&self) -> bool {
+        self.variable.is_read_only()
+    }
+
+    /// Fetch the children of this variable, e.g. object fields or list items
+    #[getter]
+    fn children(&self) -> PyResult<HashMap<String, PyVariable>> {
+        fetch_children(&self.debugger, self.variable.variables_reference)
+    }
+
+    fn __getitem__(&self, name: &str) -> PyResult<PyVariable> {
+        self.children()?
+            .remove(name)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    fn __getattr__(&self, name: &str) -> PyResult<PyVariable> {
+        self.children()?
+            .remove(name)
+            .ok_or_else(|| PyAttributeError::new_err(name.to_string()))
     }
 
     fn __repr__(&self) -> String {
-        match &self.0.r#type {
+        match &self.variable.r#type {
             Some(ty) => {
-                format!("<Variable {}={} ({})", self.0.name, self.0.value, ty)
+                format!(
+                    "<Variable {}={} ({})",
+                    self.variable.name, self.variable.value, ty
+                )
             }
             None => {
-                format!("<Variable {}={} (???)", self.0.name, self.0.value)
+                format!(
+                    "<Variable {}={} (???)",
+                    self.variable.name, self.variable.value
+                )
             }
         }
     }
 }
 
-impl From<transport::types::Variable> for PyVariable {
-    fn from(value: transport::types::Variable) -> Self {
-        Self(value)
+fn fetch_children(
+    debugger: &Arc<debugger::Debugger>,
+    variables_reference: VariablesReference,
+) -> PyResult<HashMap<String, PyVariable>> {
+    if variables_reference == 0 {
+        return Ok(HashMap::new());
+    }
+
+    let variables = debugger
+        .variables(variables_reference)
+        .map_err(|e| dap_error("fetching children", e))?;
+    Ok(variables
+        .into_iter()
+        .map(|v| (v.name.clone(), PyVariable::new(v, debugger)))
+        .collect())
+}
+
+/// Thread of the debugee
+#[pyclass(name = "Thread")]
+#[derive(Clone)]
+pub struct PyThread {
+    #[pyo3(get)]
+    id: ThreadId,
+    #[pyo3(get)]
+    name: String,
+}
+
+#[pymethods]
+impl PyThread {
+    fn __repr__(&self) -> String {
+        format!("'{} ({})'", self.name, self.id)
+    }
+}
+
+/// Thread given either by id or by name
+#[derive(FromPyObject)]
+pub enum ThreadRef {
+    Id(ThreadId),
+    Name(String),
+}
+
+/// Exception the debugee stopped on
+#[pyclass(name = "ExceptionInfo")]
+#[derive(Clone)]
+pub struct PyExceptionInfo {
+    #[pyo3(get)]
+    exception_id: String,
+    #[pyo3(get)]
+    description: Option<String>,
+    #[pyo3(get)]
+    message: Option<String>,
+    #[pyo3(get)]
+    type_name: Option<String>,
+    /// Stack trace at the time the exception was thrown, formatted by the adapter
+    #[pyo3(get)]
+    stack_trace: Option<String>,
+}
+
+impl From<debugger::ExceptionInfo> for PyExceptionInfo {
+    fn from(info: debugger::ExceptionInfo) -> Self {
+        let details = info.details.unwrap_or_default();
+        Self {
+            exception_id: info.exception_id,
+            description: info.description,
+            message: details.message,
+            type_name: details.type_name,
+            stack_trace: details.stack_trace,
+        }
+    }
+}
+
+#[pymethods]
+impl PyExceptionInfo {
+    fn __repr__(&self) -> String {
+        match &self.description {
+            Some(description) => format!("<ExceptionInfo {}: {description}>", self.exception_id),
+            None => format!("<ExceptionInfo {}>", self.exception_id),
+        }
+    }
+}
+
+/// Kind of exception the adapter can break on, see `Debugger.set_exception_breakpoints`
+#[pyclass(name = "ExceptionFilter")]
+#[derive(Clone)]
+pub struct PyExceptionFilter {
+    /// Value to pass to `Debugger.set_exception_breakpoints`
+    #[pyo3(get)]
+    filter: String,
+    #[pyo3(get)]
+    label: String,
+    #[pyo3(get)]
+    description: Option<String>,
+    /// Whether the adapter suggests enabling this filter by default
+    #[pyo3(get)]
+    default: bool,
+}
+
+#[pymethods]
+impl PyExceptionFilter {
+    fn __repr__(&self) -> String {
+        format!("'{}'", self.filter)
+    }
+}
+
+impl From<ExceptionBreakpointsFilter> for PyExceptionFilter {
+    fn from(value: ExceptionBreakpointsFilter) -> Self {
+        Self {
+            filter: value.filter,
+            label: value.label,
+            description: value.description,
+            default: value.default.unwrap_or(false),
+        }
+    }
+}
+
+/// Result of `Debugger.evaluate`
+#[pyclass(name = "EvaluateResult")]
+pub struct PyEvaluateResult {
+    #[pyo3(get)]
+    value: String,
+    #[pyo3(get)]
+    r#type: Option<String>,
+    /// True if the adapter could not evaluate the expression, in which case `value` holds the
+    /// error message
+    #[pyo3(get)]
+    error: bool,
+    variables_reference: VariablesReference,
+    debugger: Arc<debugger::Debugger>,
+}
+
+#[pymethods]
+impl PyEvaluateResult {
+    /// Fetch the children of the result, e.g. object fields or list items
+    #[getter]
+    fn children(&self) -> PyResult<HashMap<String, PyVariable>> {
+        fetch_children(&self.debugger, self.variables_reference)
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.r#type {
+            Some(ty) => format!("<EvaluateResult {} ({})>", self.value, ty),
+            None => format!("<EvaluateResult {}>", self.value),
+        }
     }
 }
 
 #[pyclass]
+#[derive(Clone)]
 pub struct ProgramState {
     #[pyo3(get)]
     pub stack: Vec<PyStackFrame>,
@@ -140,6 +519,22 @@ pub struct ProgramState {
     pub paused_frame: PyPausedFrame,
 }
 
+impl ProgramState {
+    pub(crate) fn new(
+        stack: Vec<StackFrame>,
+        paused_frame: PausedFrame,
+        debugger: &Arc<debugger::Debugger>,
+    ) -> Self {
+        Self {
+            stack: stack
+                .into_iter()
+                .map(|frame| PyStackFrame::new(frame, debugger))
+                .collect(),
+            paused_frame: PyPausedFrame::new(paused_frame, debugger),
+        }
+    }
+}
+
 #[pymethods]
 impl ProgramState {
     fn __getattr__(&self, name: &Bound<'_, PyAny>) -> PyResult<String> {
@@ -154,63 +549,15 @@ impl ProgramState {
 
         let contents = std::fs::read_to_string(&source)
             .map_err(|e| PyRuntimeError::new_err(format!("error reading from file {}", e)))?;
-        let line_text = contents.split('\n').nth(line - 1).unwrap();
-        let start = Point {
-            row: line - 1,
-            column: 0,
-        };
-        let end = Point {
-            row: line - 1,
-            column: line_text.len(),
-        };
-
-        // set up treesitter
-        let mut parser = Parser::new();
-        parser
-            .set_language(&tree_sitter_python::LANGUAGE.into())
-            .map_err(|e| PyRuntimeError::new_err(format!("setting treesitter language: {e}")))?;
-        let tree = parser
-            .parse(contents.as_bytes(), None)
-            .ok_or_else(|| PyRuntimeError::new_err("error parsing file".to_string()))?;
-        let root = tree.root_node();
-        let descendant = root
-            .descendant_for_point_range(start, end)
-            .ok_or_else(|| PyRuntimeError::new_err("getting descendant"))?;
-
-        // find up until function body
-        let mut n = descendant;
-
-        loop {
-            tracing::debug!(node = ?n, "loop iteration");
-            if n.kind() == "function_definition" {
-                let s = n.utf8_text(contents.as_bytes()).map_err(|e| {
-                    PyRuntimeError::new_err(format!(
-                        "error getting utf8 text from input source: {e}"
-                    ))
-                })?;
-
-                println!("{s}");
-
-                return Ok(());
-            }
-
-            match n.parent() {
-                Some(parent) => {
-                    n = parent;
-                }
-                None => {
-                    return Err(PyRuntimeError::new_err(
-                        "no function body found".to_string(),
-                    ))
-                }
-            }
-        }
+        let function = SourceLanguage::from_path(&source).enclosing_function(&contents, line)?;
+        println!("{function}");
+        Ok(())
     }
 }
 
 #[pyclass]
 pub(crate) struct Debugger {
-    internal_debugger: debugger::Debugger,
+    internal_debugger: Arc<debugger::Debugger>,
     launched: bool,
 }
 
@@ -248,77 +595,433 @@ impl Debugger {
         )
     }
 
+    /// Replay a session recorded with `record_messages` or `pcaplog --jsonl`, without an
+    /// adapter
+    ///
+    /// Events are replayed `speed` times faster than they were recorded, or as soon as
+    /// possible if `speed` is infinite. Recorded responses are only sent once the same
+    /// requests are made again, e.g. by calling `resume`.
+    #[staticmethod]
+    #[pyo3(signature = (path, speed=1.0))]
+    pub fn replay(path: PathBuf, speed: f64) -> PyResult<Self> {
+        let timing = if speed == f64::INFINITY {
+            Timing::Immediate
+        } else if speed > 0.0 {
+            Timing::Accelerated(speed)
+        } else {
+            return Err(PyValueError::new_err(format!(
+                "invalid speed {speed}, expected a positive number"
+            )));
+        };
+        let recording = Recording::load(path).map_err(|e| dap_error("loading recording", e))?;
+        let debugger = debugger::Debugger::replay(recording, timing)
+            .map_err(|e| dap_error("replaying recording", e))?;
+        Ok(Self {
+            internal_debugger: Arc::new(debugger),
+            launched: false,
+        })
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Disconnect from the debug adapter and clean up the adapter process, without suppressing
+    /// any exception raised in the `with` block
+    #[pyo3(signature = (_exc_type=None, _exc_value=None, _traceback=None))]
+    fn __exit__(
+        &self,
+        _exc_type: Option<PyObject>,
+        _exc_value: Option<PyObject>,
+        _traceback: Option<PyObject>,
+    ) -> PyResult<bool> {
+        self.shutdown()?;
+        Ok(false)
+    }
+
+    /// Iterate over debugger events, blocking until each one arrives, until the debugee ends
+    ///
+    /// Each iterator receives its own copy of every event, so this does not interfere with
+    /// `resume` or `step_over`.
+    pub fn events(&self) -> EventIterator {
+        EventIterator::new(&self.internal_debugger)
+    }
+
+    /// Call `callback` with every subsequent debugger event, from a background thread
+    pub fn on_event(&self, callback: PyObject) {
+        spawn_callback(&self.internal_debugger, callback);
+    }
+
+    /// Make `line` of `path` the next statement to run, without running the code in between
+    pub fn jump_to_line(&self, path: PathBuf, line: usize) -> PyResult<Option<ProgramState>> {
+        self.internal_debugger
+            .jump_to_line(path, line)
+            .map_err(|e| dap_error("jumping to line", e))?;
+        wait_for_program_state(&self.internal_debugger, None)
+    }
+
+    /// Threads of the debugee, as of when it last stopped
+    pub fn threads(&self) -> Vec<PyThread> {
+        self.internal_debugger
+            .threads()
+            .into_iter()
+            .map(|t| PyThread {
+                id: t.id,
+                name: t.name,
+            })
+            .collect()
+    }
+
+    /// Inspect another thread, given its id or name, returning the program state as seen from
+    /// its top frame
+    pub fn select_thread(&self, thread: ThreadRef) -> PyResult<ProgramState> {
+        match thread {
+            ThreadRef::Id(thread_id) => self.internal_debugger.select_thread(thread_id),
+            ThreadRef::Name(name) => self
+                .internal_debugger
+                .select_thread_by_name(&name)
+                .map(|_| ()),
+        }
+        .map_err(|e| dap_error("selecting thread", e))?;
+        match self
+            .internal_debugger
+            .wait_for_event(|evt| matches!(evt, Event::ScopeChange { .. }))
+        {
+            Event::ScopeChange {
+                stack,
+                paused_frame,
+                ..
+            } => Ok(ProgramState::new(
+                stack,
+                paused_frame,
+                &self.internal_debugger,
+            )),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Give up on requests the adapter does not answer within `timeout` seconds, raising
+    /// `AdapterTimeout`, or wait forever if `None`
+    #[pyo3(signature = (timeout))]
+    pub fn set_request_timeout(&self, timeout: Option<f64>) -> PyResult<()> {
+        self.internal_debugger
+            .set_request_timeout(to_duration(timeout)?);
+        Ok(())
+    }
+
+    /// Check every `interval` seconds that the adapter answers within `timeout` seconds,
+    /// emitting an `AdapterUnresponsive` event when it stops responding
+    #[pyo3(signature = (interval=5.0, timeout=5.0))]
+    pub fn enable_liveness(&self, interval: f64, timeout: f64) -> PyResult<()> {
+        self.internal_debugger
+            .enable_liveness(debugger::LivenessOptions {
+                interval: to_duration(Some(interval))?.unwrap_or_default(),
+                timeout: to_duration(Some(timeout))?.unwrap_or_default(),
+            });
+        Ok(())
+    }
+
+    /// Write every message exchanged with the adapter to `path` as JSON lines, or stop
+    /// recording if `None`
+    #[pyo3(signature = (path))]
+    pub fn record_messages(&self, path: Option<PathBuf>) -> PyResult<()> {
+        let tap = match path {
+            Some(path) => {
+                let tap =
+                    JsonlTap::create(path).map_err(|e| dap_error("creating message log", e))?;
+                Some(Arc::new(tap) as Arc<dyn MessageTap>)
+            }
+            None => None,
+        };
+        self.internal_debugger.set_message_tap(tap);
+        Ok(())
+    }
+
+    /// Check every message from the adapter against the protocol, emitting a
+    /// `ProtocolViolation` event for each problem found
+    pub fn set_strict_protocol(&self, enabled: bool) {
+        self.internal_debugger.set_strict_protocol(enabled);
+    }
+
+    /// Only resume the current thread when stepping or continuing
+    ///
+    /// Raises `Unsupported` if the adapter cannot resume a single thread.
+    pub fn set_single_thread(&self, enabled: bool) -> PyResult<()> {
+        self.internal_debugger
+            .set_single_thread(enabled)
+            .map_err(|e| dap_error("changing single thread execution", e))
+    }
+
+    /// Kinds of exception the adapter can break on
+    pub fn exception_breakpoint_filters(&self) -> Vec<PyExceptionFilter> {
+        self.internal_debugger
+            .exception_breakpoint_filters()
+            .into_iter()
+            .map(PyExceptionFilter::from)
+            .collect()
+    }
+
+    /// Break on the given kinds of exception, e.g. `["raised"]`, replacing any previously
+    /// enabled
+    pub fn set_exception_breakpoints(&self, filters: Vec<String>) -> PyResult<()> {
+        self.internal_debugger
+            .set_exception_breakpoints(&filters)
+            .map_err(|e| dap_error("setting exception breakpoints", e))
+    }
+
+    /// Request and event metrics for the session so far
+    pub fn metrics(&self) -> PyMetrics {
+        PyMetrics::new(self.internal_debugger.metrics().snapshot())
+    }
+
+    /// Call `callback` with the session metrics every `interval` seconds, from a background
+    /// thread
+    #[pyo3(signature = (callback, interval=1.0))]
+    pub fn on_metrics(&self, callback: PyObject, interval: f64) {
+        spawn_metrics_callback(&self.internal_debugger, callback, interval);
+    }
+
+    /// Re-run the debugee from the start, keeping the breakpoints
+    ///
+    /// Returns the program state when the debugee next pauses, or `None` if it ends.
+    pub fn restart(&self) -> PyResult<Option<ProgramState>> {
+        self.internal_debugger
+            .restart()
+            .map_err(|e| dap_error("restarting debugee", e))?;
+        wait_for_program_state(&self.internal_debugger, None)
+    }
+
+    /// Re-run the debugee within the running adapter after editing it, keeping the
+    /// breakpoints and watches
+    ///
+    /// Raises `Unsupported` if the adapter cannot restart the debugee itself. Returns the
+    /// program state when the debugee next pauses, or `None` if it ends.
+    pub fn reload(&self) -> PyResult<Option<ProgramState>> {
+        self.internal_debugger
+            .reload()
+            .map_err(|e| dap_error("reloading debugee", e))?;
+        wait_for_program_state(&self.internal_debugger, None)
+    }
+
+    /// End the debugging session
+    pub fn shutdown(&self) -> PyResult<()> {
+        self.internal_debugger
+            .shutdown()
+            .map_err(|e| dap_error("shutting down debugger", e))
+    }
+
+    /// End the debugging session, choosing what happens to the debugee
+    ///
+    /// `mode` is one of `"terminate"`, `"disconnect"` (disconnect and terminate the debugee)
+    /// or `"detach"` (leave the debugee running).
+    #[pyo3(signature = (mode="terminate"))]
+    pub fn stop(&self, mode: &str) -> PyResult<()> {
+        let mode = match mode {
+            "terminate" => TerminateMode::Terminate,
+            "disconnect" => TerminateMode::DisconnectAndTerminate,
+            "detach" => TerminateMode::Detach,
+            other => {
+                return Err(PyValueError::new_err(format!(
+                    "invalid mode {other:?}, expected terminate, disconnect or detach"
+                )))
+            }
+        };
+        self.internal_debugger
+            .stop(mode)
+            .map_err(|e| dap_error("stopping debugger", e))
+    }
+
     pub fn resume(&mut self) -> PyResult<Option<ProgramState>> {
         if !self.launched {
             self.launched = true;
             self.internal_debugger
                 .start()
-                .map_err(|e| PyRuntimeError::new_err(format!("launching debugger: {e}")))?;
+                .map_err(|e| dap_error("launching debugger", e))?;
         } else {
             self.internal_debugger
                 .r#continue()
-                .map_err(|e| PyRuntimeError::new_err(format!("continuing execution: {e}")))?;
+                .map_err(|e| dap_error("continuing execution", e))?;
         }
 
         tracing::debug!("waiting for debugee to run");
         self.internal_debugger
-            .wait_for_event(|evt| matches!(evt, Event::Running { .. }));
+            .wait_for_event(|evt| matches!(evt, Event::Running));
 
         // wait for stopped or terminated event
-        tracing::trace!("waiting for paused or ended event");
-        match self.internal_debugger.wait_for_event(|evt| {
-            matches!(evt, Event::Paused { .. }) || matches!(evt, Event::Ended)
-        }) {
-            Event::Paused {
-                stack,
-                paused_frame,
-                ..
-            } => {
-                tracing::debug!("paused");
-                Ok(Some(ProgramState {
-                    stack: stack.into_iter().map(From::from).collect(),
-                    paused_frame: paused_frame.into(),
-                }))
-            }
-            Event::Ended => {
-                eprintln!("Debugee ended");
-                Ok(None)
-            }
-            _ => unreachable!(),
-        }
+        wait_for_program_state(&self.internal_debugger, None)
     }
 
-    pub fn step_over(&mut self) -> PyResult<Option<ProgramState>> {
+    /// Step over the current line, returning the program state once the debugee stops
+    ///
+    /// Raises `TimeoutError` if `timeout` seconds pass without the debugee stopping.
+    #[pyo3(signature = (timeout=None))]
+    pub fn step_over(&mut self, timeout: Option<f64>) -> PyResult<Option<ProgramState>> {
+        let timeout = to_duration(timeout)?;
         self.internal_debugger
             .step_over()
-            .map_err(|e| PyRuntimeError::new_err(format!("stepping debugee: {e}")))?;
-        tracing::trace!("waiting for paused or ended event");
-        match self.internal_debugger.wait_for_event(|evt| {
-            matches!(evt, Event::Paused { .. }) || matches!(evt, Event::Ended)
-        }) {
-            Event::Paused {
-                stack,
-                paused_frame,
-                ..
-            } => {
-                tracing::debug!("paused");
-                Ok(Some(ProgramState {
-                    stack: stack.into_iter().map(From::from).collect(),
-                    paused_frame: paused_frame.into(),
-                }))
-            }
-            Event::Ended => {
-                eprintln!("Debugee ended");
-                Ok(None)
-            }
-            _ => unreachable!(),
-        }
+            .map_err(|e| dap_error("stepping debugee", e))?;
+        wait_for_program_state(&self.internal_debugger, timeout)
+    }
+
+    /// Step into the current function call, returning the program state once the debugee stops
+    #[pyo3(signature = (timeout=None))]
+    pub fn step_in(&mut self, timeout: Option<f64>) -> PyResult<Option<ProgramState>> {
+        let timeout = to_duration(timeout)?;
+        self.internal_debugger
+            .step_in()
+            .map_err(|e| dap_error("stepping into function", e))?;
+        wait_for_program_state(&self.internal_debugger, timeout)
+    }
+
+    /// Step out of the current function, returning the program state once the debugee stops
+    #[pyo3(signature = (timeout=None))]
+    pub fn step_out(&mut self, timeout: Option<f64>) -> PyResult<Option<ProgramState>> {
+        let timeout = to_duration(timeout)?;
+        self.internal_debugger
+            .step_out()
+            .map_err(|e| dap_error("stepping out of function", e))?;
+        wait_for_program_state(&self.internal_debugger, timeout)
+    }
+
+    /// Pause the running debugee, returning the program state once it stops
+    #[pyo3(signature = (timeout=None))]
+    pub fn pause(&mut self, timeout: Option<f64>) -> PyResult<Option<ProgramState>> {
+        let timeout = to_duration(timeout)?;
+        self.internal_debugger
+            .pause()
+            .map_err(|e| dap_error("pausing debugee", e))?;
+        wait_for_program_state(&self.internal_debugger, timeout)
+    }
+
+    /// Evaluate an expression in the given stack frame, or the current frame if not given
+    #[pyo3(signature = (expr, frame=None, context="repl"))]
+    pub fn evaluate(
+        &self,
+        expr: &str,
+        frame: Option<StackFrameId>,
+        context: &str,
+    ) -> PyResult<PyEvaluateResult> {
+        let frame_id = frame
+            .or_else(|| self.internal_debugger.current_frame_id())
+            .ok_or_else(|| PyRuntimeError::new_err("debugee is not paused"))?;
+        let context: EvaluateContext = context
+            .parse()
+            .map_err(|e| PyValueError::new_err(format!("{e}")))?;
+        let result = self
+            .internal_debugger
+            .evaluate(expr, frame_id, context)
+            .map_err(|e| dap_error("evaluating expression", e))?
+            .ok_or_else(|| PyRuntimeError::new_err("unexpected response to evaluate request"))?;
+        Ok(PyEvaluateResult {
+            value: result.output,
+            r#type: result.r#type,
+            error: result.error,
+            variables_reference: result.variables_reference,
+            debugger: Arc::clone(&self.internal_debugger),
+        })
+    }
+
+    /// Assign `value` to an expression such as `obj.attr`, in the given stack frame or the
+    /// current frame if not given
+    #[pyo3(signature = (expr, value, frame=None))]
+    pub fn set_expression(
+        &self,
+        expr: &str,
+        value: &str,
+        frame: Option<StackFrameId>,
+    ) -> PyResult<PyEvaluateResult> {
+        let frame_id = frame.or_else(|| self.internal_debugger.current_frame_id());
+        let result = self
+            .internal_debugger
+            .set_expression(expr, value, frame_id)
+            .map_err(|e| dap_error("setting expression", e))?;
+        Ok(PyEvaluateResult {
+            value: result.output,
+            r#type: result.r#type,
+            error: result.error,
+            variables_reference: result.variables_reference,
+            debugger: Arc::clone(&self.internal_debugger),
+        })
+    }
+
+    /// Names that could complete `text`, e.g. for tab completion in a REPL
+    ///
+    /// `column` is the 0-indexed cursor position within `text`, defaulting to the end. Names
+    /// are looked up in the given stack frame, or the current frame if not given.
+    #[pyo3(signature = (text, column=None, frame=None))]
+    pub fn completions(
+        &self,
+        text: &str,
+        column: Option<usize>,
+        frame: Option<StackFrameId>,
+    ) -> PyResult<Vec<String>> {
+        let column = column.unwrap_or_else(|| text.chars().count());
+        let frame_id = frame.or_else(|| self.internal_debugger.current_frame_id());
+        let items = self
+            .internal_debugger
+            .completions(text, column + 1, frame_id)
+            .map_err(|e| dap_error("fetching completions", e))?;
+        Ok(items.into_iter().map(|item| item.label).collect())
+    }
+
+    /// Add a breakpoint, optionally only breaking when `condition` is true or according to
+    /// the adapter-specific `hit_condition`
+    ///
+    /// If `log_message` is given the breakpoint is a logpoint, which logs the message as
+    /// output instead of pausing.
+    #[pyo3(signature = (path, line, condition=None, hit_condition=None, log_message=None))]
+    pub fn add_breakpoint(
+        &self,
+        path: PathBuf,
+        line: usize,
+        condition: Option<String>,
+        hit_condition: Option<String>,
+        log_message: Option<String>,
+    ) -> PyResult<Breakpoint> {
+        let path = path
+            .canonicalize()
+            .map_err(|_| PyRuntimeError::new_err("invalid file path given"))?;
+        let breakpoint = debugger::Breakpoint {
+            path,
+            line,
+            condition,
+            hit_condition,
+            log_message,
+            ..Default::default()
+        };
+        let id = self
+            .internal_debugger
+            .add_breakpoint(&breakpoint)
+            .map_err(|e| dap_error("adding breakpoint", e))?;
+        Ok(Breakpoint::new(
+            id,
+            breakpoint,
+            self.internal_debugger.breakpoint_verified(id),
+        ))
     }
 
-    // /// List the breakpoints the debugger knows about
-    pub fn breakpoints(&mut self) -> Vec<Breakpoint> {
-        let debugger_breakpoints = self.internal_debugger.breakpoints();
-        debugger_breakpoints.into_iter().map(From::from).collect()
+    /// Remove the breakpoint with the given id
+    pub fn remove_breakpoint(&self, id: debugger::BreakpointId) -> PyResult<()> {
+        self.internal_debugger
+            .remove_breakpoint(id)
+            .map_err(|e| dap_error("removing breakpoint", e))
+    }
+
+    /// List the breakpoints the debugger knows about
+    pub fn list_breakpoints(&self) -> Vec<Breakpoint> {
+        self.internal_debugger
+            .breakpoints_with_ids()
+            .into_iter()
+            .map(|(id, breakpoint)| {
+                let verified = self.internal_debugger.breakpoint_verified(id);
+                Breakpoint::new(id, breakpoint, verified)
+            })
+            .collect()
+    }
+
+    /// Alias of `list_breakpoints`
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        self.list_breakpoints()
     }
 }
 
@@ -331,105 +1034,760 @@ impl Debugger {
         file: Option<PathBuf>,
         program: Option<PathBuf>,
     ) -> PyResult<Self> {
-        let port = port.unwrap_or(5678);
-        tracing::debug!(%port, "creating Python debugger");
-
-        let config_path = config_path.as_ref();
-        let mut config =
-            match launch_configuration::load_from_path(config_name.as_ref(), config_path).map_err(
-                |e| PyRuntimeError::new_err(format!("loading launch configuration: {e}")),
-            )? {
-                ChosenLaunchConfiguration::Specific(config) => config,
-                ChosenLaunchConfiguration::NotFound => {
-                    return Err(PyRuntimeError::new_err("no matching configuration found"));
+        let debugger = create_debugger(port, breakpoints, config_path, config_name, file, program)?;
+        Ok(Self {
+            internal_debugger: Arc::new(debugger),
+            launched: false,
+        })
+    }
+}
+
+fn to_duration(seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    seconds
+        .map(|s| {
+            Duration::try_from_secs_f64(s)
+                .map_err(|e| PyValueError::new_err(format!("invalid timeout: {e}")))
+        })
+        .transpose()
+}
+
+/// Block until the debugee pauses or ends, returning the program state if paused
+///
+/// Raises `TimeoutError` if `timeout` is given and elapses first.
+pub(crate) fn wait_for_program_state(
+    debugger: &Arc<debugger::Debugger>,
+    timeout: Option<Duration>,
+) -> PyResult<Option<ProgramState>> {
+    tracing::trace!("waiting for paused or ended event");
+    let pred = |evt: &Event| matches!(evt, Event::Paused { .. }) || matches!(evt, Event::Ended);
+    let event = match timeout {
+        Some(timeout) => debugger
+            .wait_for_event_timeout(pred, timeout)
+            .ok_or_else(|| PyTimeoutError::new_err("timed out waiting for the debugee to stop"))?,
+        None => debugger.wait_for_event(pred),
+    };
+    match event {
+        Event::Paused {
+            stack,
+            paused_frame,
+            ..
+        } => {
+            tracing::debug!("paused");
+            Ok(Some(ProgramState::new(stack, paused_frame, debugger)))
+        }
+        Event::Ended => {
+            eprintln!("Debugee ended");
+            Ok(None)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Create a debugger from a launch configuration and wait until it is ready for configuration
+pub(crate) fn create_debugger(
+    port: Option<u16>,
+    breakpoints: Vec<usize>,
+    config_path: impl AsRef<Path>,
+    config_name: Option<String>,
+    file: Option<PathBuf>,
+    program: Option<PathBuf>,
+) -> PyResult<debugger::Debugger> {
+    let settings = settings::Settings::load_from(settings::Settings::default_path())
+        .unwrap_or_else(|e| {
+            tracing::warn!(error = ?e, "loading settings, using defaults");
+            settings::Settings::default()
+        });
+    let port = port.unwrap_or(settings.default_port);
+    tracing::debug!(%port, "creating Python debugger");
+
+    let config_path = config_path.as_ref();
+    let mut config = match launch_configuration::load_from_path(config_name.as_ref(), config_path)
+        .map_err(|e| {
+        PyRuntimeError::new_err(format!("loading launch configuration: {e}"))
+    })? {
+        ChosenLaunchConfiguration::Specific(config) => config,
+        ChosenLaunchConfiguration::NotFound => {
+            return Err(PyRuntimeError::new_err("no matching configuration found"));
+        }
+        ChosenLaunchConfiguration::ToBeChosen(configurations) => {
+            eprintln!("Configuration name not specified");
+            eprintln!("Available options:");
+            for config in &configurations {
+                eprintln!("- {config}");
+            }
+            // TODO: best option?
+            std::process::exit(1);
+        }
+    };
+    tracing::debug!(config = ?config, "chosen config");
+    let root = config_path
+        .parent()
+        .expect("getting parent for config path");
+    config.resolve(root);
+
+    let mut debug_root_dir = std::env::current_dir().unwrap();
+
+    let debugger = match config {
+        LaunchConfiguration::Debugpy(launch_configuration::Debugpy {
+            request,
+            cwd,
+            connect,
+            path_mappings,
+            just_my_code,
+            sub_process,
+            stop_on_entry,
+            ..
+        }) => {
+            if let Some(dir) = cwd {
+                debug_root_dir = debugger::utils::normalise_path(&dir).into_owned();
+            }
+            let debugger = match request.as_str() {
+                "attach" => {
+                    let launch_arguments = AttachArguments {
+                        working_directory: debug_root_dir.to_owned().to_path_buf(),
+                        host: connect.as_ref().map(|c| c.host.clone()),
+                        port: connect.as_ref().map(|c| c.port),
+                        language: debugger::Language::DebugPy,
+                        path_mappings,
+                        just_my_code,
+                    };
+
+                    tracing::debug!(?launch_arguments, "generated launch configuration");
+
+                    let port = launch_arguments.port.unwrap_or(port);
+                    debugger::Debugger::on_port(port, launch_arguments)
+                        .map_err(|e| dap_error("creating internal debugger", e))?
                 }
-                ChosenLaunchConfiguration::ToBeChosen(configurations) => {
-                    eprintln!("Configuration name not specified");
-                    eprintln!("Available options:");
-                    for config in &configurations {
-                        eprintln!("- {config}");
-                    }
-                    // TODO: best option?
-                    std::process::exit(1);
+                "launch" => {
+                    let launch_arguments = LaunchArguments {
+                        program: program.ok_or_else(|| {
+                            PyRuntimeError::new_err("program is a required argument")
+                        })?,
+                        working_directory: Some(debug_root_dir.to_owned().to_path_buf()),
+                        language: debugger::Language::DebugPy,
+                        sub_process,
+                        adapter_executable: settings.adapters.python.clone(),
+                        just_my_code,
+                        stop_on_entry: stop_on_entry.unwrap_or(false),
+                    };
+
+                    tracing::debug!(?launch_arguments, "generated launch configuration");
+                    debugger::Debugger::on_port(port, launch_arguments)
+                        .map_err(|e| dap_error("creating internal debugger", e))?
                 }
+                other => todo!("Configuration type: '{other}' not implemented yet, or invalid"),
             };
-        tracing::debug!(config = ?config, "chosen config");
-        let root = config_path
-            .parent()
-            .expect("getting parent for config path");
-        config.resolve(root);
-
-        let mut debug_root_dir = std::env::current_dir().unwrap();
-
-        let debugger = match config {
-            LaunchConfiguration::Debugpy(launch_configuration::Debugpy {
-                request,
-                cwd,
-                connect,
-                path_mappings,
-                ..
-            }) => {
-                if let Some(dir) = cwd {
-                    debug_root_dir = debugger::utils::normalise_path(&dir).into_owned();
-                }
-                let debugger = match request.as_str() {
-                    "attach" => {
-                        let launch_arguments = AttachArguments {
-                            working_directory: debug_root_dir.to_owned().to_path_buf(),
-                            port: connect.map(|c| c.port),
-                            language: debugger::Language::DebugPy,
-                            path_mappings,
-                        };
-
-                        tracing::debug!(?launch_arguments, "generated launch configuration");
-
-                        debugger::Debugger::on_port(port, launch_arguments).map_err(|e| {
-                            PyRuntimeError::new_err(format!("creating internal debugger: {e}"))
-                        })?
-                    }
-                    "launch" => {
-                        let launch_arguments = LaunchArguments {
-                            program: program.ok_or_else(|| {
-                                PyRuntimeError::new_err("program is a required argument")
-                            })?,
-                            working_directory: Some(debug_root_dir.to_owned().to_path_buf()),
-                            language: debugger::Language::DebugPy,
-                        };
-
-                        tracing::debug!(?launch_arguments, "generated launch configuration");
-                        debugger::Debugger::on_port(port, launch_arguments).map_err(|e| {
-                            PyRuntimeError::new_err(format!("creating internal debugger: {e}"))
-                        })?
-                    }
-                    other => todo!("Configuration type: '{other}' not implemented yet, or invalid"),
-                };
-                debugger
-            }
+            debugger
+        }
+    };
+
+    tracing::trace!("waiting for initialised event");
+    debugger.wait_for_event(|e| matches!(e, debugger::Event::Initialised));
+
+    if let Some(file_path) = file {
+        let file_path = file_path
+            .canonicalize()
+            .map_err(|_| PyRuntimeError::new_err("invalid file path given"))?;
+        // breakpoints
+        for &line in &breakpoints {
+            let breakpoint = debugger::Breakpoint {
+                name: None,
+                path: file_path.clone(),
+                line,
+                ..Default::default()
+            };
+            debugger
+                .add_breakpoint(&breakpoint)
+                .map_err(|e| dap_error("adding breakpoint", e))?;
+        }
+    }
+
+    Ok(debugger)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::{Path, PathBuf},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use dap_test_harness::{FakeAdapter, Script};
+    use pyo3::{
+        exceptions::{PyAttributeError, PyKeyError, PyRuntimeError, PyTimeoutError, PyValueError},
+        prelude::*,
+        types::PyList,
+        PyTypeInfo,
+    };
+    use serde_json::json;
+
+    use super::{create_debugger, Debugger, SourceLanguage};
+    use crate::{
+        errors::{AdapterTimeout, DapError, RequestFailed, Unsupported},
+        events::PyEvent,
+    };
+
+    /// Session attached to `adapter`, ready for configuration
+    fn attach(adapter: &FakeAdapter) -> Debugger {
+        Debugger {
+            internal_debugger: Arc::new(dap_test_harness::attach(adapter).unwrap()),
+            launched: false,
+        }
+    }
+
+    /// Check that `err` is a `T`, and can still be caught as a `DapError` or `RuntimeError`
+    fn assert_raises<T: PyTypeInfo>(err: PyErr) {
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            assert!(err.is_instance_of::<T>(py), "unexpected exception {err}");
+            assert!(err.is_instance_of::<DapError>(py));
+            assert!(err.is_instance_of::<PyRuntimeError>(py));
+        });
+    }
+
+    #[test]
+    fn raises_typed_exceptions() {
+        let adapter = FakeAdapter::start(
+            Script::initialised(json!({ "supportsSetExpression": true }))
+                .fail("setExpression", "cannot assign to a constant")
+                .respond_after(
+                    "evaluate",
+                    json!({ "result": "1", "variablesReference": 0 }),
+                    Duration::from_secs(1),
+                )
+                .respond(
+                    "evaluate",
+                    json!({ "result": "2", "variablesReference": 0 }),
+                ),
+        )
+        .unwrap();
+        let debugger = attach(&adapter);
+
+        let Err(err) = debugger.set_expression("CONSTANT", "2", Some(1)) else {
+            panic!("assigning to a constant succeeded");
+        };
+        assert_raises::<RequestFailed>(err);
+
+        let Err(err) = debugger.reload() else {
+            panic!("reload succeeded without support from the adapter");
         };
+        assert_raises::<Unsupported>(err);
+
+        debugger.set_request_timeout(Some(0.1)).unwrap();
+        let Err(err) = debugger.evaluate("slow()", Some(1), "repl") else {
+            panic!("evaluate succeeded despite the timeout");
+        };
+        assert_raises::<AdapterTimeout>(err);
+
+        // the late response is ignored, and the client stays connected to receive it
+        debugger.set_request_timeout(None).unwrap();
+        let value = debugger.evaluate("fast()", Some(1), "repl").unwrap().value;
+        assert_eq!(value, "2");
+
+        drop(debugger);
+        adapter.join().unwrap();
+    }
+
+    /// Output text, or "ended", to compare event sequences
+    fn summary(event: PyEvent) -> Option<String> {
+        match event {
+            PyEvent::Output { output, .. } => Some(output),
+            PyEvent::Ended() => Some("ended".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn delivers_events_until_the_debugee_ends() {
+        let adapter = FakeAdapter::start(
+            Script::initialised(json!({}))
+                .respond("configurationDone", None)
+                .event("output", json!({ "category": "stdout", "output": "1\n" }))
+                .event("output", json!({ "category": "stdout", "output": "2\n" }))
+                .event("terminated", None),
+        )
+        .unwrap();
+        let debugger = attach(&adapter);
+        pyo3::prepare_freethreaded_python();
+        let received = Python::with_gil(|py| {
+            let received = PyList::empty(py);
+            debugger.on_event(received.getattr("append").unwrap().unbind());
+            received.unbind()
+        });
+        let events = debugger.events();
+        debugger.internal_debugger.start().unwrap();
+
+        let expected = vec!["1\n", "2\n", "ended"];
+        let iterated: Vec<_> = Python::with_gil(|py| {
+            std::iter::from_fn(|| events.__next__(py))
+                .filter_map(summary)
+                .collect()
+        });
+        assert_eq!(iterated, expected);
 
-        tracing::trace!("waiting for initialised event");
-        debugger.wait_for_event(|e| matches!(e, debugger::Event::Initialised));
-
-        if let Some(file_path) = file {
-            let file_path = file_path
-                .canonicalize()
-                .map_err(|_| PyRuntimeError::new_err("invalid file path given"))?;
-            // breakpoints
-            for &line in &breakpoints {
-                let breakpoint = debugger::Breakpoint {
-                    name: None,
-                    path: file_path.clone(),
-                    line,
-                };
-                debugger
-                    .add_breakpoint(&breakpoint)
-                    .map_err(|_| PyRuntimeError::new_err("adding breakpoint"))?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let called: Vec<_> = Python::with_gil(|py| {
+                received
+                    .bind(py)
+                    .iter()
+                    .filter_map(|event| summary(event.extract().unwrap()))
+                    .collect()
+            });
+            if called == expected || Instant::now() > deadline {
+                assert_eq!(called, expected);
+                break;
             }
+            thread::sleep(Duration::from_millis(10));
         }
 
-        Ok(Self {
-            internal_debugger: debugger,
-            launched: false,
-        })
+        // the callback does not keep the session open
+        drop(events);
+        drop(debugger);
+        adapter.join().unwrap();
+    }
+
+    #[test]
+    fn manages_breakpoints_with_verification() {
+        let adapter = FakeAdapter::start(
+            Script::initialised(json!({}))
+                .respond(
+                    "setBreakpoints",
+                    json!({ "breakpoints": [{ "verified": true, "line": 3 }] }),
+                )
+                .respond(
+                    "setBreakpoints",
+                    json!({ "breakpoints": [{ "verified": false, "line": 100 }] }),
+                )
+                .respond("setBreakpoints", json!({ "breakpoints": [] })),
+        )
+        .unwrap();
+        let debugger = attach(&adapter);
+        let dir = std::env::temp_dir().join(format!(
+            "dap-gui-pythondap-{}-manages_breakpoints",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (main, other) = (dir.join("main.py"), dir.join("other.py"));
+        std::fs::write(&main, "").unwrap();
+        std::fs::write(&other, "").unwrap();
+        let other = other.canonicalize().unwrap();
+
+        let bound = debugger
+            .add_breakpoint(main.clone(), 3, Some("x > 1".to_string()), None, None)
+            .unwrap();
+        assert_eq!(bound.verified, Some(true));
+        let unbound = debugger
+            .add_breakpoint(other.clone(), 100, None, None, None)
+            .unwrap();
+        assert_eq!(unbound.verified, Some(false));
+
+        let mut listed: Vec<_> = debugger
+            .list_breakpoints()
+            .into_iter()
+            .map(|b| (b.id, b.line, b.condition, b.verified))
+            .collect();
+        listed.sort();
+        assert_eq!(
+            listed,
+            vec![
+                (bound.id, 3, Some("x > 1".to_string()), Some(true)),
+                (unbound.id, 100, None, Some(false)),
+            ]
+        );
+
+        debugger.remove_breakpoint(unbound.id).unwrap();
+        let remaining: Vec<_> = debugger.list_breakpoints().iter().map(|b| b.id).collect();
+        assert_eq!(remaining, vec![bound.id]);
+
+        drop(debugger);
+        let _ = std::fs::remove_dir_all(&dir);
+        let requests = adapter.join().unwrap();
+        let last = requests
+            .iter()
+            .rfind(|r| r["command"] == "setBreakpoints")
+            .expect("setBreakpoints request");
+        assert_eq!(last["arguments"]["source"]["path"], json!(other));
+        assert_eq!(last["arguments"]["breakpoints"], json!([]));
+    }
+
+    #[test]
+    fn evaluates_expressions_with_children() {
+        let adapter = FakeAdapter::start(
+            Script::initialised(json!({}))
+                .respond(
+                    "evaluate",
+                    json!({ "result": "[1, 'a']", "type": "list", "variablesReference": 7 }),
+                )
+                .respond(
+                    "variables",
+                    json!({ "variables": [
+                        { "name": "0", "value": "1", "type": "int", "variablesReference": 0 },
+                        { "name": "1", "value": "'a'", "type": "str", "variablesReference": 0 },
+                    ] }),
+                )
+                .fail("evaluate", "name 'missing' is not defined"),
+        )
+        .unwrap();
+        let debugger = attach(&adapter);
+
+        let result = debugger.evaluate("items", Some(5), "hover").unwrap();
+        assert_eq!(result.value, "[1, 'a']");
+        assert_eq!(result.r#type.as_deref(), Some("list"));
+        assert!(!result.error);
+        let mut children: Vec<_> = result
+            .children()
+            .unwrap()
+            .into_values()
+            .map(|v| (v.name(), v.value().unwrap(), v.r#type()))
+            .collect();
+        children.sort();
+        assert_eq!(
+            children,
+            vec![
+                ("0".to_string(), "1".to_string(), Some("int".to_string())),
+                ("1".to_string(), "'a'".to_string(), Some("str".to_string())),
+            ]
+        );
+
+        let failed = debugger.evaluate("missing", Some(5), "repl").unwrap();
+        assert!(failed.error);
+        assert!(failed.value.contains("name 'missing' is not defined"));
+        assert!(failed.children().unwrap().is_empty());
+
+        let Err(err) = debugger.evaluate("items", Some(5), "everywhere") else {
+            panic!("evaluated in an invalid context");
+        };
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyValueError>(py)));
+
+        drop(result);
+        drop(failed);
+        drop(debugger);
+        let requests = adapter.join().unwrap();
+        let evaluate = requests
+            .iter()
+            .find(|r| r["command"] == "evaluate")
+            .expect("evaluate request");
+        assert_eq!(evaluate["arguments"]["expression"], "items");
+        assert_eq!(evaluate["arguments"]["frameId"], 5);
+        assert_eq!(evaluate["arguments"]["context"], "hover");
+    }
+
+    #[test]
+    fn steps_and_pauses_with_timeout() {
+        let script = Script::initialised(json!({}))
+            .respond("configurationDone", None)
+            .paused("breakpoint")
+            .respond("next", None)
+            .respond(
+                "threads",
+                json!({ "threads": [{ "id": 1, "name": "MainThread" }] }),
+            )
+            .respond("pause", None);
+        let adapter = FakeAdapter::start(script.paused("pause")).unwrap();
+        let mut debugger = attach(&adapter);
+        let state = debugger.resume().unwrap().expect("paused at breakpoint");
+        assert_eq!(state.paused_frame.stack().id(), 7);
+        drop(state);
+
+        // the adapter never reports the step finishing
+        let Err(err) = debugger.step_over(Some(0.2)) else {
+            panic!("step finished without the debugee stopping");
+        };
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyTimeoutError>(py)));
+
+        let state = debugger.pause(Some(5.0)).unwrap().expect("paused");
+        assert_eq!(state.paused_frame.stack().line(), 3);
+
+        let Err(err) = debugger.step_in(Some(-1.0)) else {
+            panic!("stepped with a negative timeout");
+        };
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyValueError>(py)));
+
+        drop(state);
+        drop(debugger);
+        let commands: Vec<_> = adapter
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|r| r["command"].clone())
+            .collect();
+        assert!(commands.contains(&json!("next")));
+        assert!(commands.contains(&json!("pause")));
+        assert!(
+            !commands.contains(&json!("stepIn")),
+            "invalid timeouts are rejected before stepping"
+        );
+    }
+
+    #[test]
+    fn fetches_variable_children_on_access() {
+        let user = json!({ "variables": [{
+            "name": "email",
+            "value": "",
+            "presentationHint": { "lazy": true },
+            "variablesReference": 9,
+        }] });
+        let adapter = FakeAdapter::start(
+            Script::initialised(json!({}))
+                .respond("configurationDone", None)
+                .paused_with_locals(
+                    "breakpoint",
+                    json!([{
+                        "name": "user",
+                        "value": "User()",
+                        "type": "User",
+                        "variablesReference": 8,
+                    }]),
+                )
+                .respond("variables", user.clone())
+                .respond(
+                    "variables",
+                    json!({ "variables": [{ "name": "email", "value": "'a@b.c'", "variablesReference": 0 }] }),
+                )
+                .respond("variables", user.clone())
+                .respond("variables", user),
+        )
+        .unwrap();
+        let mut debugger = attach(&adapter);
+        let variables_requests = || {
+            adapter
+                .commands()
+                .iter()
+                .filter(|c| *c == "variables")
+                .count()
+        };
+
+        let state = debugger.resume().unwrap().expect("paused at breakpoint");
+        let user = state.paused_frame.variables().remove("user").unwrap();
+        assert_eq!(user.r#type().as_deref(), Some("User"));
+        assert_eq!(
+            variables_requests(),
+            1,
+            "children are not fetched until accessed"
+        );
+
+        let email = user.__getattr__("email").unwrap();
+        assert_eq!(variables_requests(), 2);
+        assert_eq!(email.name(), "email");
+        assert_eq!(email.value().unwrap(), "'a@b.c'");
+        assert_eq!(variables_requests(), 3, "lazy values are fetched when read");
+
+        pyo3::prepare_freethreaded_python();
+        let Err(err) = user.__getitem__("missing") else {
+            panic!("found a missing child");
+        };
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyKeyError>(py)));
+        let Err(err) = user.__getattr__("missing") else {
+            panic!("found a missing attribute");
+        };
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyAttributeError>(py)));
+
+        drop((state, user, email));
+        drop(debugger);
+        adapter.join().unwrap();
+    }
+
+    #[test]
+    fn switches_to_another_stack_frame() {
+        let frame = |id, name, path, line| json!({ "id": id, "name": name, "source": { "path": path }, "line": line, "column": 0 });
+        let top = frame(7, "helper", "/project/lib.py", 10);
+        // e.g. a frame in native code
+        let native = json!({ "id": 9, "name": "start", "line": 0, "column": 0 });
+        let stack_trace = json!({
+            "stackFrames": [top, frame(8, "main", "/project/main.py", 3), native],
+        });
+        let adapter = FakeAdapter::start(
+            Script::initialised(json!({}))
+                .respond("configurationDone", None)
+                .event("stopped", json!({ "reason": "breakpoint", "threadId": 1 }))
+                .respond("stackTrace", stack_trace.clone())
+                .respond("scopes", json!({ "scopes": [] }))
+                .respond("stackTrace", stack_trace)
+                .respond(
+                    "scopes",
+                    json!({ "scopes": [{ "name": "Locals", "variablesReference": 2, "expensive": false }] }),
+                )
+                .respond(
+                    "variables",
+                    json!({ "variables": [{ "name": "n", "value": "5", "variablesReference": 0 }] }),
+                ),
+        )
+        .unwrap();
+        let mut debugger = attach(&adapter);
+
+        let state = debugger.resume().unwrap().expect("paused at breakpoint");
+        let frames: Vec<_> = state
+            .stack
+            .iter()
+            .map(|f| (f.id(), f.name(), f.line(), f.path()))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                (7, "helper".to_string(), 10, Some("/project/lib.py".into())),
+                (8, "main".to_string(), 3, Some("/project/main.py".into())),
+                (9, "start".to_string(), 0, None),
+            ]
+        );
+        assert_eq!(state.paused_frame.stack().id(), 7);
+        assert_eq!(
+            state.stack[0].source().unwrap(),
+            PathBuf::from("/project/lib.py")
+        );
+        let Err(err) = state.stack[2].source() else {
+            panic!("frame without a source file has a source");
+        };
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyRuntimeError>(py)));
+
+        let switched = state.stack[1].switch().unwrap();
+        assert_eq!(switched.paused_frame.stack().id(), 8);
+        assert_eq!(switched.paused_frame.variables()["n"].value().unwrap(), "5");
+        assert_eq!(debugger.internal_debugger.current_frame_id(), Some(8));
+
+        drop((state, switched));
+        drop(debugger);
+        let requests = adapter.join().unwrap();
+        let scopes = requests
+            .iter()
+            .rfind(|r| r["command"] == "scopes")
+            .expect("scopes request");
+        assert_eq!(scopes["arguments"]["frameId"], 8);
+    }
+
+    #[test]
+    fn exiting_context_disconnects_once() {
+        let adapter = FakeAdapter::start(Script::initialised(json!({}))).unwrap();
+        let debugger = attach(&adapter);
+
+        let suppressed = debugger.__exit__(None, None, None).unwrap();
+        assert!(
+            !suppressed,
+            "exceptions from the with block are not suppressed"
+        );
+        drop(debugger);
+
+        let requests = adapter.join().unwrap();
+        let disconnects: Vec<_> = requests
+            .iter()
+            .filter(|r| r["command"] == "disconnect")
+            .collect();
+        assert_eq!(
+            disconnects.len(),
+            1,
+            "dropping after exiting does not disconnect again"
+        );
+        assert_eq!(disconnects[0]["arguments"]["terminateDebuggee"], false);
+    }
+
+    #[test]
+    fn attaches_on_configured_port() {
+        let adapter = FakeAdapter::start(
+            Script::new()
+                .respond("initialize", json!({}))
+                .event("initialized", None)
+                .respond("attach", None),
+        )
+        .unwrap();
+        let port = adapter.port();
+        let config_path = std::env::temp_dir().join(format!(
+            "dap-gui-pythondap-{}-attaches_on_configured_port.json",
+            std::process::id()
+        ));
+        let config = json!({
+            "version": "0.2.0",
+            "configurations": [{
+                "name": "Attach",
+                "type": "debugpy",
+                "request": "attach",
+                "connect": { "host": "127.0.0.1", "port": port },
+            }],
+        });
+        std::fs::write(&config_path, config.to_string()).unwrap();
+
+        let debugger = create_debugger(
+            None,
+            Vec::new(),
+            &config_path,
+            Some("Attach".to_string()),
+            None,
+            None,
+        );
+        let _ = std::fs::remove_file(&config_path);
+        drop(debugger.expect("attaching to the configured port"));
+
+        let requests = adapter.join().unwrap();
+        let attach = requests
+            .iter()
+            .find(|r| r["command"] == "attach")
+            .expect("attach request");
+        assert_eq!(attach["arguments"]["connect"]["port"], port);
+    }
+
+    #[test]
+    fn picks_source_language_by_extension() {
+        let kinds = |path: &str| SourceLanguage::from_path(Path::new(path)).function_kinds;
+        assert!(kinds("main.go").contains(&"method_declaration"));
+        assert!(kinds("src/main.rs").contains(&"function_item"));
+        for path in ["main.py", "gui.pyw", "bin/script", "<string>"] {
+            assert_eq!(kinds(path), ["function_definition"], "{path}");
+        }
+    }
+
+    #[test]
+    fn shows_enclosing_function() {
+        let python = "import os\n\ndef main():\n    x = 1\n    print(x)\n";
+        let function = SourceLanguage::from_path(Path::new("script"))
+            .enclosing_function(python, 4)
+            .unwrap();
+        assert_eq!(function, "def main():\n    x = 1\n    print(x)");
+
+        let go = "package main
+
+func add(a, b int) int {
+\treturn a + b
+}
+
+func (s *Server) Start() {
+\ts.running = true
+\tgo func() {
+\t\ts.serve()
+\t}()
+}
+";
+        let go_language = SourceLanguage::from_path(Path::new("main.go"));
+        let function = go_language.enclosing_function(go, 4).unwrap();
+        assert_eq!(function, "func add(a, b int) int {\n\treturn a + b\n}");
+        let function = go_language.enclosing_function(go, 8).unwrap();
+        assert!(
+            function.starts_with("func (s *Server) Start() {"),
+            "{function}"
+        );
+        let function = go_language.enclosing_function(go, 10).unwrap();
+        assert_eq!(function, "func() {\n\t\ts.serve()\n\t}");
+
+        let rust = "fn main() {
+    let total: i32 = (1..4).map(|x| {
+        x * 2
+    }).sum();
+    println!(\"{total}\");
+}
+";
+        let rust_language = SourceLanguage::from_path(Path::new("src/main.rs"));
+        let function = rust_language.enclosing_function(rust, 5).unwrap();
+        assert!(function.starts_with("fn main() {"), "{function}");
+        let function = rust_language.enclosing_function(rust, 3).unwrap();
+        assert_eq!(function, "|x| {\n        x * 2\n    }");
+
+        // outside of any function, or past the end of the file
+        assert!(rust_language
+            .enclosing_function("use std::io;\n", 1)
+            .is_err());
+        assert!(rust_language.enclosing_function(rust, 20).is_err());
     }
 }
@@ -0,0 +1,48 @@
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+
+// subclass RuntimeError so existing `except RuntimeError` handlers keep working
+create_exception!(
+    pythondap,
+    DapError,
+    PyRuntimeError,
+    "Base class for errors communicating with the debug adapter"
+);
+create_exception!(
+    pythondap,
+    AdapterDisconnected,
+    DapError,
+    "The connection to the debug adapter was lost"
+);
+create_exception!(
+    pythondap,
+    AdapterTimeout,
+    DapError,
+    "The debug adapter did not respond in time"
+);
+create_exception!(
+    pythondap,
+    Unsupported,
+    DapError,
+    "The debug adapter does not support the request"
+);
+create_exception!(
+    pythondap,
+    RequestFailed,
+    DapError,
+    "The debug adapter reported that a request failed"
+);
+
+/// Convert an error from the debugger into the most specific Python exception
+pub(crate) fn dap_error(context: &str, error: impl Into<debugger::Error>) -> PyErr {
+    let error = error.into();
+    let message = format!("{context}: {error}");
+    match error {
+        debugger::Error::Unsupported { .. } => Unsupported::new_err(message),
+        debugger::Error::AdapterError { .. } => RequestFailed::new_err(message),
+        debugger::Error::Disconnected => AdapterDisconnected::new_err(message),
+        debugger::Error::Timeout { .. } => AdapterTimeout::new_err(message),
+        debugger::Error::Protocol(_) => DapError::new_err(message),
+    }
+}
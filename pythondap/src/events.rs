@@ -0,0 +1,212 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use debugger::Event;
+use pyo3::prelude::*;
+use transport::types::ThreadId;
+
+use crate::debugger::{Breakpoint, ProgramState, PyExceptionInfo};
+
+/// Event emitted by the debugger
+#[pyclass(name = "Event")]
+#[derive(Clone)]
+pub enum PyEvent {
+    Uninitialised(),
+    Initialised(),
+    Paused {
+        state: ProgramState,
+        thread_id: ThreadId,
+        all_threads_stopped: bool,
+        /// Why the debugee stopped, e.g. `entry`, `breakpoint`, `step`, `exception` or `pause`
+        reason: String,
+        /// Exception the debugee stopped on, if it stopped because of one
+        exception: Option<PyExceptionInfo>,
+    },
+    ScopeChange {
+        state: ProgramState,
+    },
+    Running(),
+    Ended(),
+    Output {
+        /// e.g. `stdout`, `stderr` or `console`
+        category: String,
+        output: String,
+        /// File the output was produced from, if known
+        source: Option<PathBuf>,
+        line: Option<usize>,
+        /// Id of the logpoint that produced the output, if any
+        logpoint: Option<debugger::BreakpointId>,
+    },
+    /// Line written by the debug adapter process
+    AdapterLog {
+        /// `stdout` or `stderr`
+        stream: String,
+        line: String,
+    },
+    BreakpointsChanged {
+        breakpoints: Vec<Breakpoint>,
+    },
+    Recovered {
+        attempts: u32,
+        restarted_adapter: bool,
+    },
+    /// Message from the adapter that broke the protocol, see `Debugger.set_strict_protocol`
+    ProtocolViolation {
+        message: String,
+    },
+    /// The debugee stopped but its stack could not be fetched
+    PauseFailed {
+        thread_id: i64,
+        message: String,
+    },
+    /// The adapter stopped responding, see `Debugger.enable_liveness`
+    AdapterUnresponsive {
+        message: String,
+    },
+}
+
+impl PyEvent {
+    pub(crate) fn new(value: Event, debugger: &Arc<debugger::Debugger>) -> Self {
+        match value {
+            Event::Uninitialised => PyEvent::Uninitialised(),
+            Event::Initialised => PyEvent::Initialised(),
+            Event::Paused {
+                stack,
+                paused_frame,
+                thread_id,
+                all_threads_stopped,
+                reason,
+                exception,
+                ..
+            } => PyEvent::Paused {
+                state: ProgramState::new(stack, paused_frame, debugger),
+                thread_id,
+                all_threads_stopped,
+                reason: reason.as_str().to_string(),
+                exception: exception.map(|e| PyExceptionInfo::from(*e)),
+            },
+            Event::ScopeChange {
+                stack,
+                paused_frame,
+                ..
+            } => PyEvent::ScopeChange {
+                state: ProgramState::new(stack, paused_frame, debugger),
+            },
+            Event::Running => PyEvent::Running(),
+            Event::Ended => PyEvent::Ended(),
+            Event::Output {
+                category,
+                output,
+                source,
+                line,
+                logpoint,
+            } => PyEvent::Output {
+                category: category.as_str().to_string(),
+                output,
+                source,
+                line,
+                logpoint,
+            },
+            Event::AdapterLog { stream, line } => PyEvent::AdapterLog {
+                stream: match stream {
+                    debugger::OutputStream::Stdout => "stdout",
+                    debugger::OutputStream::Stderr => "stderr",
+                }
+                .to_string(),
+                line,
+            },
+            Event::Recovered {
+                attempts,
+                restarted_adapter,
+            } => PyEvent::Recovered {
+                attempts,
+                restarted_adapter,
+            },
+            Event::ProtocolViolation { violation } => PyEvent::ProtocolViolation {
+                message: violation.to_string(),
+            },
+            Event::PauseFailed { thread_id, error } => PyEvent::PauseFailed {
+                thread_id,
+                message: error,
+            },
+            Event::AdapterUnresponsive { reason } => PyEvent::AdapterUnresponsive {
+                message: reason.to_string(),
+            },
+            Event::BreakpointsChanged { breakpoints } => PyEvent::BreakpointsChanged {
+                breakpoints: breakpoints
+                    .into_iter()
+                    .map(|(id, breakpoint)| {
+                        Breakpoint::new(id, breakpoint, debugger.breakpoint_verified(id))
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Blocking iterator over debugger events, returned by `Debugger.events()`, which stops after
+/// the debugee ends
+#[pyclass]
+pub(crate) struct EventIterator {
+    rx: crossbeam_channel::Receiver<Event>,
+    debugger: Arc<debugger::Debugger>,
+    /// Set once [`Event::Ended`] has been yielded
+    ended: AtomicBool,
+}
+
+impl EventIterator {
+    pub(crate) fn new(debugger: &Arc<debugger::Debugger>) -> Self {
+        Self {
+            rx: debugger.subscribe(),
+            debugger: Arc::clone(debugger),
+            ended: AtomicBool::new(false),
+        }
+    }
+}
+
+#[pymethods]
+impl EventIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    pub(crate) fn __next__(&self, py: Python<'_>) -> Option<PyEvent> {
+        if self.ended.load(Ordering::SeqCst) {
+            return None;
+        }
+        // release the GIL so other Python threads can run while we wait
+        let rx = self.rx.clone();
+        let event = py.allow_threads(move || rx.recv()).ok()?;
+        if matches!(event, Event::Ended) {
+            self.ended.store(true, Ordering::SeqCst);
+        }
+        Some(PyEvent::new(event, &self.debugger))
+    }
+}
+
+/// Call `callback` with every subsequent debugger event, from a background thread
+///
+/// The thread only holds a weak reference, so that it does not keep the session open, and
+/// stops once the debugger is dropped.
+pub(crate) fn spawn_callback(debugger: &Arc<debugger::Debugger>, callback: PyObject) {
+    let rx = debugger.subscribe();
+    let debugger = Arc::downgrade(debugger);
+    std::thread::spawn(move || {
+        for event in rx {
+            let Some(debugger) = debugger.upgrade() else {
+                break;
+            };
+            Python::with_gil(|py| {
+                if let Err(e) = callback.call1(py, (PyEvent::new(event, &debugger),)) {
+                    tracing::warn!(error = %e, "event callback raised an exception");
+                    e.print(py);
+                }
+            });
+        }
+    });
+}
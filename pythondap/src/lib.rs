@@ -1,9 +1,15 @@
+use crate::async_debugger::{AsyncDebugger, EventStream};
 use crate::debugger::{Debugger, ProgramState};
 use launch_configuration::py_load_from_path;
 use pyo3::prelude::*;
 
+mod async_debugger;
 mod debugger;
+mod errors;
+mod events;
 mod launch_configuration;
+mod metrics;
+mod state;
 
 #[pymodule]
 fn pythondap(m: &Bound<'_, PyModule>) -> PyResult<()> {
@@ -13,8 +19,45 @@ fn pythondap(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<Debugger>()?;
     m.add_class::<ProgramState>()?;
     m.add_class::<debugger::PyPausedFrame>()?;
+    m.add_class::<debugger::PyStackFrame>()?;
+    m.add_class::<debugger::Breakpoint>()?;
+    m.add_class::<debugger::PyEvaluateResult>()?;
+    m.add_class::<debugger::PyExceptionFilter>()?;
+    m.add_class::<debugger::PyThread>()?;
+    m.add_class::<debugger::PyExceptionInfo>()?;
+
+    // async debugger
+    m.add_class::<AsyncDebugger>()?;
+    m.add_class::<EventStream>()?;
+
+    // metrics
+    m.add_class::<metrics::PyMetrics>()?;
+
+    // events
+    m.add_class::<events::PyEvent>()?;
+    m.add_class::<events::EventIterator>()?;
+
+    // errors
+    m.add("DapError", m.py().get_type::<errors::DapError>())?;
+    m.add(
+        "AdapterDisconnected",
+        m.py().get_type::<errors::AdapterDisconnected>(),
+    )?;
+    m.add(
+        "AdapterTimeout",
+        m.py().get_type::<errors::AdapterTimeout>(),
+    )?;
+    m.add("RequestFailed", m.py().get_type::<errors::RequestFailed>())?;
+    m.add("Unsupported", m.py().get_type::<errors::Unsupported>())?;
 
     // launch_configuration
     m.add_function(wrap_pyfunction!(py_load_from_path, m)?)?;
+
+    // state
+    m.add_class::<state::PyState>()?;
+    m.add_class::<state::PyProject>()?;
+    m.add_class::<state::PySavedBreakpoint>()?;
+    m.add_function(wrap_pyfunction!(state::load_state, m)?)?;
+    m.add_function(wrap_pyfunction!(state::save_state, m)?)?;
     Ok(())
 }
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use debugger::MetricsSnapshot;
+use pyo3::prelude::*;
+
+/// Request and event metrics for a debugging session, returned by `Debugger.metrics()`
+///
+/// Latencies are in seconds.
+#[pyclass(name = "Metrics")]
+#[derive(Clone)]
+pub struct PyMetrics {
+    snapshot: MetricsSnapshot,
+}
+
+impl PyMetrics {
+    pub(crate) fn new(snapshot: MetricsSnapshot) -> Self {
+        Self { snapshot }
+    }
+
+    fn per_command<T>(&self, f: impl Fn(&debugger::CommandMetrics) -> T) -> HashMap<String, T> {
+        self.snapshot
+            .commands
+            .iter()
+            .map(|(command, metrics)| (command.clone(), f(metrics)))
+            .collect()
+    }
+}
+
+#[pymethods]
+impl PyMetrics {
+    /// Number of requests sent, by command
+    #[getter]
+    fn requests(&self) -> HashMap<String, u64> {
+        self.per_command(|m| m.requests)
+    }
+
+    /// Number of failed requests, by command
+    #[getter]
+    fn failures(&self) -> HashMap<String, u64> {
+        self.per_command(|m| m.failures)
+    }
+
+    /// Mean latency of requests that waited for a response, by command
+    #[getter]
+    fn mean_latency(&self) -> HashMap<String, f64> {
+        self.snapshot
+            .commands
+            .iter()
+            .filter_map(|(command, m)| Some((command.clone(), m.latency.mean()?.as_secs_f64())))
+            .collect()
+    }
+
+    /// Approximate latency below which `quantile` (between 0 and 1) of `command` requests
+    /// completed
+    fn latency_quantile(&self, command: &str, quantile: f64) -> Option<f64> {
+        self.snapshot
+            .commands
+            .get(command)?
+            .latency
+            .quantile(quantile)
+            .map(|d| d.as_secs_f64())
+    }
+
+    /// Number of events received, by event name
+    #[getter]
+    fn events(&self) -> HashMap<String, u64> {
+        self.snapshot.events.clone().into_iter().collect()
+    }
+
+    /// Average number of `event` events received per second
+    fn event_rate(&self, event: &str) -> f64 {
+        self.snapshot.event_rate(event)
+    }
+
+    #[getter]
+    fn reconnects(&self) -> u64 {
+        self.snapshot.reconnects
+    }
+
+    /// Latency of the most recent request that waited for a response
+    #[getter]
+    fn last_latency(&self) -> Option<f64> {
+        self.snapshot.last_latency.map(|d| d.as_secs_f64())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Metrics(requests={}, events={}, reconnects={})",
+            self.snapshot.total_requests(),
+            self.snapshot.total_events(),
+            self.snapshot.reconnects
+        )
+    }
+}
+
+/// Call `callback` with the session metrics every `interval` seconds, from a background thread
+pub(crate) fn spawn_metrics_callback(
+    debugger: &Arc<debugger::Debugger>,
+    callback: PyObject,
+    interval: f64,
+) {
+    debugger
+        .metrics()
+        .export_every(Duration::from_secs_f64(interval), move |snapshot| {
+            Python::with_gil(|py| {
+                if let Err(e) = callback.call1(py, (PyMetrics::new(snapshot.clone()),)) {
+                    tracing::warn!(error = %e, "metrics callback raised an exception");
+                    e.print(py);
+                }
+            });
+        });
+}
@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use pyo3::{exceptions::PyRuntimeError, prelude::*};
+
+/// Breakpoint as persisted in the state file
+#[pyclass(name = "SavedBreakpoint")]
+#[derive(Clone)]
+pub struct PySavedBreakpoint {
+    #[pyo3(get, set)]
+    pub path: PathBuf,
+    #[pyo3(get, set)]
+    pub line: usize,
+    #[pyo3(get, set)]
+    pub name: Option<String>,
+    #[pyo3(get, set)]
+    pub condition: Option<String>,
+    #[pyo3(get, set)]
+    pub hit_condition: Option<String>,
+    #[pyo3(get, set)]
+    pub log_message: Option<String>,
+}
+
+#[pymethods]
+impl PySavedBreakpoint {
+    #[new]
+    #[pyo3(signature = (path, line, name=None, condition=None, hit_condition=None, log_message=None))]
+    fn new(
+        path: PathBuf,
+        line: usize,
+        name: Option<String>,
+        condition: Option<String>,
+        hit_condition: Option<String>,
+        log_message: Option<String>,
+    ) -> Self {
+        Self {
+            path,
+            line,
+            name,
+            condition,
+            hit_condition,
+            log_message,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("'{}:{}'", self.path.display(), self.line)
+    }
+}
+
+impl From<debugger::Breakpoint> for PySavedBreakpoint {
+    fn from(value: debugger::Breakpoint) -> Self {
+        Self {
+            path: value.path,
+            line: value.line,
+            name: value.name,
+            condition: value.condition,
+            hit_condition: value.hit_condition,
+            log_message: value.log_message,
+        }
+    }
+}
+
+impl From<PySavedBreakpoint> for debugger::Breakpoint {
+    fn from(value: PySavedBreakpoint) -> Self {
+        Self {
+            name: value.name,
+            path: value.path,
+            line: value.line,
+            condition: value.condition,
+            hit_condition: value.hit_condition,
+            log_message: value.log_message,
+        }
+    }
+}
+
+/// Persisted state for a single project
+#[pyclass(name = "Project")]
+#[derive(Clone)]
+pub struct PyProject {
+    #[pyo3(get, set)]
+    pub path: PathBuf,
+    #[pyo3(get, set)]
+    pub breakpoints: Vec<PySavedBreakpoint>,
+}
+
+#[pymethods]
+impl PyProject {
+    #[new]
+    #[pyo3(signature = (path, breakpoints=Vec::new()))]
+    fn new(path: PathBuf, breakpoints: Vec<PySavedBreakpoint>) -> Self {
+        Self { path, breakpoints }
+    }
+}
+
+/// State shared with the GUI frontends, see `load_state` and `save_state`
+#[pyclass(name = "State")]
+#[derive(Clone, Default)]
+pub struct PyState {
+    #[pyo3(get, set)]
+    pub version: String,
+    #[pyo3(get, set)]
+    pub projects: Vec<PyProject>,
+}
+
+#[pymethods]
+impl PyState {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl From<state::Persistence> for PyState {
+    fn from(value: state::Persistence) -> Self {
+        Self {
+            version: value.version,
+            projects: value
+                .projects
+                .into_iter()
+                .map(|project| PyProject {
+                    path: project.path,
+                    breakpoints: project.breakpoints.into_iter().map(From::from).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<PyState> for state::Persistence {
+    fn from(value: PyState) -> Self {
+        Self {
+            version: value.version,
+            projects: value
+                .projects
+                .into_iter()
+                .map(|project| state::PerFile {
+                    path: project.path,
+                    breakpoints: project.breakpoints.into_iter().map(From::from).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[pyfunction]
+pub fn load_state(path: PathBuf) -> PyResult<PyState> {
+    let state = state::load_from(&path).map_err(|e| {
+        PyRuntimeError::new_err(format!(
+            "Error loading state from path {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    Ok(state.into())
+}
+
+#[pyfunction]
+pub fn save_state(path: PathBuf, state: PyState) -> PyResult<()> {
+    state::save_to(&state.into(), &path).map_err(|e| {
+        PyRuntimeError::new_err(format!(
+            "Error saving state to path {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use pyo3::{exceptions::PyRuntimeError, Python};
+
+    use super::{load_state, save_state, PyProject, PySavedBreakpoint, PyState};
+
+    fn state_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "dap-gui-pythondap-{}-{name}.json",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn saves_state_readable_by_the_frontends() {
+        let path = state_path("saves_state");
+        let breakpoint = PySavedBreakpoint::new(
+            PathBuf::from("/project/main.py"),
+            3,
+            Some("entry".to_string()),
+            Some("x > 1".to_string()),
+            Some("5".to_string()),
+            Some("x is {x}".to_string()),
+        );
+        let state = PyState {
+            version: "0.1.0".to_string(),
+            projects: vec![PyProject::new(PathBuf::from("/project"), vec![breakpoint])],
+        };
+
+        save_state(path.clone(), state).unwrap();
+        let persisted = state::load_from(&path);
+        let loaded = load_state(path.clone());
+        let _ = std::fs::remove_file(&path);
+
+        let persisted = persisted.unwrap();
+        assert_eq!(persisted.projects[0].path, PathBuf::from("/project"));
+        let saved = &persisted.projects[0].breakpoints[0];
+        assert_eq!(saved.hit_condition.as_deref(), Some("5"));
+        assert_eq!(saved.log_message.as_deref(), Some("x is {x}"));
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.version, "0.1.0");
+        let [project] = loaded.projects.as_slice() else {
+            panic!("expected a single project");
+        };
+        assert_eq!(project.path, PathBuf::from("/project"));
+        let [breakpoint] = project.breakpoints.as_slice() else {
+            panic!("expected a single breakpoint");
+        };
+        assert_eq!(breakpoint.path, PathBuf::from("/project/main.py"));
+        assert_eq!(breakpoint.line, 3);
+        assert_eq!(breakpoint.name.as_deref(), Some("entry"));
+        assert_eq!(breakpoint.condition.as_deref(), Some("x > 1"));
+        assert_eq!(breakpoint.hit_condition.as_deref(), Some("5"));
+        assert_eq!(breakpoint.log_message.as_deref(), Some("x is {x}"));
+    }
+
+    #[test]
+    fn loading_missing_state_raises() {
+        let Err(err) = load_state(state_path("missing")) else {
+            panic!("loaded state that does not exist");
+        };
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| assert!(err.is_instance_of::<PyRuntimeError>(py)));
+    }
+}
@@ -3,6 +3,7 @@ import copy
 import io
 import json
 from os import PathLike
+from pathlib import Path
 import shutil
 import socket
 import subprocess as sp
@@ -12,6 +13,7 @@ import time
 
 import pytest
 
+from pythondap.pythondap import Debugger
 from pythondap.session import DebugSession
 
 
@@ -118,9 +120,8 @@ def write_config():
 
 
 @pytest.fixture
-def environment(run_server, tmp_path, write_config):
-    @contextmanager
-    def inner(python_code: str, breakpoints: list[int]):
+def launch_files(tmp_path, write_config):
+    def inner(python_code: str) -> tuple[Path, Path, str]:
         script = tmp_path.joinpath("script.py")
         with script.open("w") as outfile:
             outfile.write(python_code)
@@ -130,6 +131,32 @@ def environment(run_server, tmp_path, write_config):
         with config_path.open("w") as outfile:
             write_config(outfile, script, config_name)
 
+        return script, config_path, config_name
+
+    return inner
+
+
+@pytest.fixture
+def create_debugger(launch_files):
+    def inner(python_code: str, breakpoints: list[int]) -> Debugger:
+        script, config_path, config_name = launch_files(python_code)
+        return Debugger(
+            breakpoints=breakpoints,
+            config_path=str(config_path),
+            config_name=config_name,
+            file=str(script),
+            program=script,
+        )
+
+    return inner
+
+
+@pytest.fixture
+def environment(run_server, launch_files):
+    @contextmanager
+    def inner(python_code: str, breakpoints: list[int]):
+        script, config_path, config_name = launch_files(python_code)
+
         debugger = DebugSession(
             breakpoints=breakpoints,
             file=str(script),
@@ -0,0 +1,73 @@
+import pytest
+
+PYTHON_CODE = """a = 10
+b = 20
+a = a + b
+print(a)
+"""
+
+SLEEPING_CODE = """import time
+time.sleep(30)
+print("done")
+"""
+
+OBJECT_CODE = """class User:
+    def __init__(self, email):
+        self.email = email
+
+
+user = User("a@b.c")
+print(user)
+"""
+
+
+def test_context_manager_ends_session(create_debugger):
+    with create_debugger(PYTHON_CODE, breakpoints=[2]) as d:
+        state = d.resume()
+        assert state is not None
+        assert state.paused_frame.stack.line == 2
+
+    # the session was shut down when the block exited
+    with pytest.raises(RuntimeError):
+        d.step_over()
+
+
+def test_context_manager_does_not_suppress_exceptions(create_debugger):
+    with pytest.raises(ValueError, match="boom"):
+        with create_debugger(PYTHON_CODE, breakpoints=[2]) as d:
+            d.resume()
+            raise ValueError("boom")
+
+
+def test_step_over_with_timeout(create_debugger):
+    with create_debugger(PYTHON_CODE, breakpoints=[2]) as d:
+        d.resume()
+
+        state = d.step_over(timeout=5.0)
+        assert state is not None
+        assert state.paused_frame.stack.line == 3
+
+        with pytest.raises(ValueError):
+            d.step_over(timeout=-1.0)
+
+
+def test_step_over_times_out(create_debugger):
+    with create_debugger(SLEEPING_CODE, breakpoints=[2]) as d:
+        d.resume()
+
+        # the debugee is still sleeping when the timeout expires
+        with pytest.raises(TimeoutError):
+            d.step_over(timeout=0.5)
+
+
+def test_variable_attribute_access(create_debugger):
+    with create_debugger(OBJECT_CODE, breakpoints=[7]) as d:
+        state = d.resume()
+        assert state is not None
+
+        user = state.paused_frame.variables["user"]
+        assert user.email.value == "'a@b.c'"
+        assert user.email.value == user["email"].value
+
+        with pytest.raises(AttributeError):
+            user.missing
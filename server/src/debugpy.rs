@@ -1,25 +1,36 @@
 use std::{
-    io::{BufRead, BufReader},
+    path::Path,
     process::{Child, Stdio},
     sync::mpsc,
-    thread,
 };
 
 use eyre::WrapErr;
 
-use crate::Server;
+use crate::{read_output, OutputLine, OutputStream, Server};
 
 pub struct DebugpyServer {
     child: Child,
+    output: Option<mpsc::Receiver<OutputLine>>,
 }
 
 impl Server for DebugpyServer {
     fn on_port(port: impl Into<u16>) -> eyre::Result<Self> {
+        Self::with_executable("python", port)
+    }
+
+    fn take_output(&mut self) -> Option<mpsc::Receiver<OutputLine>> {
+        self.output.take()
+    }
+}
+
+impl DebugpyServer {
+    /// Start the adapter with the given Python interpreter, which must have debugpy installed
+    pub fn with_executable(python: impl AsRef<Path>, port: impl Into<u16>) -> eyre::Result<Self> {
         let port = port.into();
 
         tracing::debug!(port = ?port, "starting server process");
         let cwd = std::env::current_dir().unwrap();
-        let mut child = std::process::Command::new("python")
+        let mut child = std::process::Command::new(python.as_ref())
             .args([
                 "-m",
                 "debugpy.adapter",
@@ -37,23 +48,20 @@ impl Server for DebugpyServer {
         // wait until server is ready
         tracing::debug!("waiting until server is ready");
         let stderr = child.stderr.take().unwrap();
-        let reader = BufReader::new(stderr);
-
-        let (tx, rx) = mpsc::channel();
-        thread::spawn(move || {
-            let mut should_signal = true;
-            for line in reader.lines() {
-                let line = line.unwrap();
-                if should_signal && line.contains("Listening for incoming Client connections") {
-                    should_signal = false;
-                    let _ = tx.send(());
-                }
-            }
-        });
-        let _ = rx.recv();
+        let (output_tx, output) = mpsc::channel();
+        let ready = read_output(
+            stderr,
+            OutputStream::Stderr,
+            "Listening for incoming Client connections",
+            output_tx,
+        );
+        let _ = ready.recv();
 
         tracing::debug!("server ready");
-        Ok(Self { child })
+        Ok(Self {
+            child,
+            output: Some(output),
+        })
     }
 }
 
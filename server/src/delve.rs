@@ -1,16 +1,16 @@
 use std::{
-    io::{BufRead, BufReader},
+    path::Path,
     process::{Child, Stdio},
     sync::mpsc,
-    thread,
 };
 
 use eyre::WrapErr;
 
-use crate::Server;
+use crate::{read_output, OutputLine, OutputStream, Server};
 
 pub struct DelveServer {
     child: Child,
+    output: Option<mpsc::Receiver<OutputLine>>,
 }
 
 impl Server for DelveServer {
@@ -18,11 +18,22 @@ impl Server for DelveServer {
     where
         Self: Sized,
     {
+        Self::with_executable("dlv", port)
+    }
+
+    fn take_output(&mut self) -> Option<mpsc::Receiver<OutputLine>> {
+        self.output.take()
+    }
+}
+
+impl DelveServer {
+    /// Start the adapter with the given `dlv` executable
+    pub fn with_executable(dlv: impl AsRef<Path>, port: impl Into<u16>) -> eyre::Result<Self> {
         let port = port.into();
 
         tracing::debug!(port = ?port, "starting server process");
         let cwd = std::env::current_dir().unwrap();
-        let mut child = std::process::Command::new("dlv")
+        let mut child = std::process::Command::new(dlv.as_ref())
             .args(["dap", "--listen", &format!("127.0.0.1:{port}")])
             .stdout(Stdio::piped())
             .current_dir(cwd.join("..").canonicalize().unwrap())
@@ -31,24 +42,21 @@ impl Server for DelveServer {
 
         // wait until server is ready
         tracing::debug!("waiting until server is ready");
-        let stderr = child.stdout.take().unwrap();
-        let reader = BufReader::new(stderr);
-
-        let (tx, rx) = mpsc::channel();
-        thread::spawn(move || {
-            let mut should_signal = true;
-            for line in reader.lines() {
-                let line = dbg!(line.unwrap());
-                if should_signal && line.contains("DAP server listening") {
-                    should_signal = false;
-                    let _ = tx.send(());
-                }
-            }
-        });
-        let _ = rx.recv();
+        let stdout = child.stdout.take().unwrap();
+        let (output_tx, output) = mpsc::channel();
+        let ready = read_output(
+            stdout,
+            OutputStream::Stdout,
+            "DAP server listening",
+            output_tx,
+        );
+        let _ = ready.recv();
 
         tracing::debug!("server ready");
-        Ok(Self { child })
+        Ok(Self {
+            child,
+            output: Some(output),
+        })
     }
 }
 
@@ -1,14 +1,37 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    sync::mpsc,
+    thread,
+};
+
 use eyre::WrapErr;
 use transport::DEFAULT_DAP_PORT;
 
 pub mod debugpy;
 pub mod delve;
+pub mod ssh;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Implementation {
     Debugpy,
     Delve,
 }
 
+/// Stream of the adapter process that a line of output was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Line written by the adapter process once it was ready
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+}
+
 pub trait Server {
     fn on_port(port: impl Into<u16>) -> eyre::Result<Self>
     where
@@ -20,6 +43,49 @@ pub trait Server {
     {
         Self::on_port(DEFAULT_DAP_PORT)
     }
+
+    /// Take the receiver for output written by the adapter after it became ready
+    ///
+    /// Only the first call returns the receiver.
+    fn take_output(&mut self) -> Option<mpsc::Receiver<OutputLine>> {
+        None
+    }
+}
+
+/// Read the output of an adapter process on a background thread
+///
+/// The returned receiver gets a message once a line containing `ready_message` is read, and
+/// every later line is sent to `output`. The receiver is disconnected without a message if the
+/// process exits before becoming ready.
+pub(crate) fn read_output(
+    reader: impl Read + Send + 'static,
+    stream: OutputStream,
+    ready_message: &'static str,
+    output: mpsc::Sender<OutputLine>,
+) -> mpsc::Receiver<()> {
+    let (ready_tx, ready_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut ready = Some(ready_tx);
+        for line in BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            tracing::trace!(%line, ?stream, "server output");
+            match &ready {
+                Some(tx) if line.contains(ready_message) => {
+                    let _ = tx.send(());
+                    ready = None;
+                }
+                Some(_) => {}
+                None => {
+                    // keep draining the pipe even if nobody is listening, so the adapter
+                    // does not block writing to it
+                    let _ = output.send(OutputLine { stream, line });
+                }
+            }
+        }
+    });
+    ready_rx
 }
 
 pub fn for_implementation(implementation: Implementation) -> eyre::Result<Box<dyn Server + Send>> {
@@ -29,15 +95,56 @@ pub fn for_implementation(implementation: Implementation) -> eyre::Result<Box<dy
 pub fn for_implementation_on_port(
     implementation: Implementation,
     port: impl Into<u16>,
+) -> eyre::Result<Box<dyn Server + Send>> {
+    for_implementation_with_executable(implementation, port, None)
+}
+
+/// Start an adapter using `executable` (the Python interpreter for debugpy, or `dlv`), or the
+/// default executable found on `PATH`
+pub fn for_implementation_with_executable(
+    implementation: Implementation,
+    port: impl Into<u16>,
+    executable: Option<&Path>,
 ) -> eyre::Result<Box<dyn Server + Send>> {
     match implementation {
         Implementation::Debugpy => {
-            let server = crate::debugpy::DebugpyServer::on_port(port).context("creating server")?;
+            let python = executable.unwrap_or(Path::new("python"));
+            let server = crate::debugpy::DebugpyServer::with_executable(python, port)
+                .context("creating server")?;
             Ok(Box::new(server))
         }
         Implementation::Delve => {
-            let server = crate::delve::DelveServer::on_port(port).context("creating server")?;
+            let dlv = executable.unwrap_or(Path::new("dlv"));
+            let server =
+                crate::delve::DelveServer::with_executable(dlv, port).context("creating server")?;
             Ok(Box::new(server))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, sync::mpsc, time::Duration};
+
+    use super::{read_output, OutputLine, OutputStream};
+
+    #[test]
+    fn forwards_output_after_ready() {
+        let (tx, rx) = mpsc::channel();
+        let ready = read_output(
+            Cursor::new("starting\nserver listening\nconnection accepted\n"),
+            OutputStream::Stderr,
+            "listening",
+            tx,
+        );
+        ready.recv_timeout(Duration::from_secs(5)).unwrap();
+        let lines: Vec<_> = rx.iter().collect();
+        assert_eq!(
+            lines,
+            vec![OutputLine {
+                stream: OutputStream::Stderr,
+                line: "connection accepted".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Debug adapters started on a remote machine over SSH
+use std::{
+    path::Path,
+    process::{Child, Stdio},
+    sync::mpsc,
+};
+
+use eyre::WrapErr;
+
+use crate::{read_output, OutputLine, OutputStream, Server};
+
+/// Debug adapter running on a remote host, reachable through an SSH port forward
+///
+/// A single `ssh` process both forwards `local_port` on this machine to the adapter and runs
+/// the adapter, so dropping the server closes the tunnel and stops the adapter.
+pub struct SshServer {
+    child: Child,
+    output: Option<mpsc::Receiver<OutputLine>>,
+}
+
+impl SshServer {
+    /// Start the debugpy adapter listening on `remote_port` on `destination` (e.g.
+    /// `user@host`) and forward `local_port` to it
+    ///
+    /// `remote_port` must be free on the remote machine.
+    pub fn start(
+        destination: &str,
+        remote_working_directory: &Path,
+        local_port: u16,
+        remote_port: u16,
+    ) -> eyre::Result<Self> {
+        // everything is logged to stderr so readiness can be detected in one place
+        let adapter_command =
+            format!("python -m debugpy.adapter --host 127.0.0.1 --port {remote_port} --log-stderr");
+        let ready_message = "Listening for incoming Client connections";
+        let remote_command = format!(
+            "cd {} && exec {adapter_command}",
+            shell_quote(&remote_working_directory.to_string_lossy())
+        );
+
+        tracing::debug!(%destination, %local_port, %remote_port, %remote_command, "starting remote server process");
+        let mut child = std::process::Command::new("ssh")
+            .args([
+                "-o",
+                "ExitOnForwardFailure=yes",
+                "-L",
+                &format!("127.0.0.1:{local_port}:127.0.0.1:{remote_port}"),
+                destination,
+                &remote_command,
+            ])
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("spawning ssh process")?;
+
+        // wait until server is ready
+        tracing::debug!("waiting until server is ready");
+        let stderr = child.stderr.take().unwrap();
+        let (output_tx, output) = mpsc::channel();
+        let ready = read_output(stderr, OutputStream::Stderr, ready_message, output_tx);
+        if ready.recv().is_err() {
+            let _ = child.kill();
+            let _ = child.wait();
+            eyre::bail!("ssh exited before the remote server was ready");
+        }
+
+        tracing::debug!("server ready");
+        Ok(Self {
+            child,
+            output: Some(output),
+        })
+    }
+
+    /// Forward `local_port` to `remote_port` on `destination` without starting anything
+    /// there, e.g. to reach a headless `dlv` server, which speaks DAP itself
+    ///
+    /// Connections to `local_port` are refused until the tunnel is up. Errors from `ssh` are
+    /// written to stderr.
+    pub fn forward(destination: &str, local_port: u16, remote_port: u16) -> eyre::Result<Self> {
+        tracing::debug!(%destination, %local_port, %remote_port, "starting ssh port forward");
+        let child = std::process::Command::new("ssh")
+            .args([
+                "-N",
+                "-o",
+                "ExitOnForwardFailure=yes",
+                "-L",
+                &format!("127.0.0.1:{local_port}:127.0.0.1:{remote_port}"),
+                destination,
+            ])
+            .stdin(Stdio::null())
+            .spawn()
+            .context("spawning ssh process")?;
+        Ok(Self {
+            child,
+            output: None,
+        })
+    }
+}
+
+impl Server for SshServer {
+    fn on_port(_port: impl Into<u16>) -> eyre::Result<Self>
+    where
+        Self: Sized,
+    {
+        Err(eyre::eyre!(
+            "remote servers need a destination, use SshServer::start or SshServer::forward"
+        ))
+    }
+
+    fn take_output(&mut self) -> Option<mpsc::Receiver<OutputLine>> {
+        self.output.take()
+    }
+}
+
+impl Drop for SshServer {
+    fn drop(&mut self) {
+        tracing::debug!("terminating ssh process");
+        match self.child.kill() {
+            Ok(_) => {
+                tracing::debug!("ssh process terminated");
+                let _ = self.child.wait();
+            }
+            Err(e) => tracing::warn!(error = %e, "could not terminate ssh process"),
+        }
+    }
+}
+
+/// Quote `s` for use as a single word in a POSIX shell command
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shell_quote;
+
+    #[test]
+    fn quoting() {
+        assert_eq!(shell_quote("/srv/app"), "'/srv/app'");
+        assert_eq!(shell_quote("it's here"), r"'it'\''s here'");
+    }
+}
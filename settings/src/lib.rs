@@ -0,0 +1,277 @@
+//! User settings shared by the frontends
+//!
+//! Settings are read from a TOML file, by default `settings.toml` in the `dapgui` user
+//! configuration directory. Every key is optional:
+//!
+//! ```toml
+//! default_port = 5678
+//! theme = "dark"
+//!
+//! [adapters]
+//! python = "/usr/local/bin/python3"
+//!
+//! [keybindings]
+//! continue = "F5"
+//! step_over = "F10"
+//!
+//! [auto_save]
+//! breakpoints = false
+//! ```
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+
+/// How often [`SettingsHandle::watch`] checks the settings file for changes
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Port used to talk to debug adapters when a launch configuration does not give one
+    pub default_port: u16,
+    pub theme: Theme,
+    pub adapters: Adapters,
+    pub keybindings: Keybindings,
+    pub auto_save: AutoSave,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_port: 5678,
+            theme: Theme::default(),
+            adapters: Adapters::default(),
+            keybindings: Keybindings::default(),
+            auto_save: AutoSave::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Follow the operating system
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+/// Executables used to start debug adapters, found on `PATH` if not given
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Adapters {
+    /// Python interpreter with `debugpy` installed
+    pub python: Option<PathBuf>,
+}
+
+/// Keyboard shortcuts, written as key names with optional modifiers, e.g. `ctrl+shift+F5`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keybindings {
+    pub r#continue: String,
+    pub step_over: String,
+    pub step_in: String,
+    pub step_out: String,
+}
+
+impl Default for Keybindings {
+    fn default() -> Self {
+        Self {
+            r#continue: "F5".to_string(),
+            step_over: "F10".to_string(),
+            step_in: "F11".to_string(),
+            step_out: "shift+F11".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoSave {
+    /// Save breakpoints to the state file whenever they change
+    pub breakpoints: bool,
+}
+
+impl Default for AutoSave {
+    fn default() -> Self {
+        Self { breakpoints: true }
+    }
+}
+
+impl Settings {
+    /// Path of the user settings file
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("/tmp"))
+            .join("dapgui")
+            .join("settings.toml")
+    }
+
+    /// Load settings from `path`, using the defaults if the file does not exist
+    pub fn load_from(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(contents) => contents
+                .parse::<Self>()
+                .wrap_err_with(|| format!("loading settings from {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                tracing::debug!(path = %path.display(), "no settings file, using defaults");
+                Ok(Self::default())
+            }
+            Err(e) => Err(e).wrap_err("reading settings file"),
+        }
+    }
+}
+
+impl FromStr for Settings {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        toml::from_str(s).wrap_err("parsing settings")
+    }
+}
+
+/// Current settings, kept up to date as the settings file changes
+///
+/// Cloning the handle gives access to the same settings.
+#[derive(Debug, Clone)]
+pub struct SettingsHandle {
+    current: Arc<Mutex<Settings>>,
+    subscribers: Arc<Mutex<Vec<crossbeam_channel::Sender<Settings>>>>,
+}
+
+impl SettingsHandle {
+    /// Load the settings at `path` and reload them whenever the file changes
+    ///
+    /// If the file later becomes invalid the previous settings are kept and a warning is
+    /// logged.
+    pub fn watch(path: impl Into<PathBuf>) -> eyre::Result<Self> {
+        Self::watch_with_interval(path, DEFAULT_POLL_INTERVAL)
+    }
+
+    /// Like [`SettingsHandle::watch`] but checks for changes every `interval`
+    pub fn watch_with_interval(path: impl Into<PathBuf>, interval: Duration) -> eyre::Result<Self> {
+        let path = path.into();
+        let settings = Settings::load_from(&path)?;
+        let handle = Self {
+            current: Arc::new(Mutex::new(settings)),
+            subscribers: Arc::default(),
+        };
+
+        let current = Arc::downgrade(&handle.current);
+        let subscribers = Arc::downgrade(&handle.subscribers);
+        let mut last_modified = modified(&path);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let (Some(current), Some(subscribers)) = (current.upgrade(), subscribers.upgrade())
+            else {
+                return;
+            };
+
+            let modified = modified(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match Settings::load_from(&path) {
+                Ok(settings) => {
+                    let mut current = current.lock().unwrap();
+                    if *current == settings {
+                        continue;
+                    }
+                    tracing::info!(path = %path.display(), "settings changed");
+                    *current = settings.clone();
+                    subscribers
+                        .lock()
+                        .unwrap()
+                        .retain(|tx| tx.send(settings.clone()).is_ok());
+                }
+                Err(e) => tracing::warn!(error = ?e, "reloading settings, keeping previous"),
+            }
+        });
+        Ok(handle)
+    }
+
+    /// Settings that never change, e.g. for tests
+    pub fn fixed(settings: Settings) -> Self {
+        Self {
+            current: Arc::new(Mutex::new(settings)),
+            subscribers: Arc::default(),
+        }
+    }
+
+    pub fn current(&self) -> Settings {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Receive the new settings every time they change
+    pub fn subscribe(&self) -> crossbeam_channel::Receiver<Settings> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Settings, SettingsHandle, Theme};
+
+    #[test]
+    fn missing_keys_use_defaults() {
+        let settings: Settings = r#"
+            theme = "light"
+
+            [keybindings]
+            step_over = "ctrl+N"
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!(settings.theme, Theme::Light);
+        assert_eq!(settings.keybindings.step_over, "ctrl+N");
+        assert_eq!(settings.keybindings.r#continue, "F5");
+        assert_eq!(settings.default_port, 5678);
+        assert!(settings.auto_save.breakpoints);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!("colour = \"red\"".parse::<Settings>().is_err());
+    }
+
+    #[test]
+    fn reloads_on_change() {
+        let path = std::env::temp_dir().join(format!(
+            "dap-gui-settings-{}-reloads_on_change.toml",
+            std::process::id()
+        ));
+        std::fs::write(&path, "default_port = 1234").unwrap();
+
+        let handle = SettingsHandle::watch_with_interval(&path, Duration::from_millis(10)).unwrap();
+        let changes = handle.subscribe();
+        assert_eq!(handle.current().default_port, 1234);
+
+        // make sure the modification time changes on filesystems with coarse timestamps
+        std::thread::sleep(Duration::from_millis(50));
+        std::fs::write(&path, "default_port = 4321").unwrap();
+        let changed = changes.recv_timeout(Duration::from_secs(5));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(changed.unwrap().default_port, 4321);
+        assert_eq!(handle.current().default_port, 4321);
+    }
+}
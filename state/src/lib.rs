@@ -57,6 +57,69 @@ impl StateManager {
     pub fn current(&self) -> &Persistence {
         &self.current
     }
+
+    /// Mutate the current state and immediately write it to disk
+    pub fn update<F>(&mut self, f: F) -> eyre::Result<()>
+    where
+        F: FnOnce(&mut Persistence),
+    {
+        f(&mut self.current);
+        crate::save_to(&self.current, &self.save_path).wrap_err("saving state")?;
+        Ok(())
+    }
+
+    /// Replace the breakpoints persisted for a project, creating the project entry if required
+    pub fn set_project_breakpoints(
+        &mut self,
+        project_path: impl AsRef<Path>,
+        breakpoints: Vec<debugger::Breakpoint>,
+    ) -> eyre::Result<()> {
+        let project_path = project_path.as_ref();
+        let normalised = debugger::utils::normalise_path(project_path);
+        self.update(|state| {
+            match state
+                .projects
+                .iter_mut()
+                .find(|p| debugger::utils::normalise_path(&p.path) == normalised)
+            {
+                Some(project) => project.breakpoints = breakpoints,
+                None => state.projects.push(PerFile {
+                    path: project_path.to_path_buf(),
+                    breakpoints,
+                }),
+            }
+        })
+    }
+}
+
+/// Saves a debugging session's breakpoints for one project whenever they change
+///
+/// ```no_run
+/// # fn f(debugger: &debugger::Debugger) -> eyre::Result<()> {
+/// let manager = state::StateManager::new("state.json")?;
+/// debugger.set_breakpoint_persistence(Box::new(state::ProjectBreakpoints::new(manager, "/project")));
+/// # Ok(())
+/// # }
+/// ```
+pub struct ProjectBreakpoints {
+    manager: StateManager,
+    project: PathBuf,
+}
+
+impl ProjectBreakpoints {
+    pub fn new(manager: StateManager, project: impl Into<PathBuf>) -> Self {
+        Self {
+            manager,
+            project: project.into(),
+        }
+    }
+}
+
+impl debugger::PersistBreakpoints for ProjectBreakpoints {
+    fn save(&mut self, breakpoints: &[debugger::Breakpoint]) -> eyre::Result<()> {
+        self.manager
+            .set_project_breakpoints(&self.project, breakpoints.to_vec())
+    }
 }
 
 /// State that is persisted
@@ -96,3 +159,78 @@ pub fn load_from(path: impl AsRef<Path>) -> eyre::Result<Persistence> {
     let state = load(f).context("reading from state file")?;
     Ok(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::StateManager;
+
+    #[test]
+    fn set_project_breakpoints_round_trip() {
+        let state_path = std::env::temp_dir().join(format!(
+            "dap-gui-state-{}-{}.json",
+            std::process::id(),
+            "set_project_breakpoints"
+        ));
+        let _ = std::fs::remove_file(&state_path);
+
+        let project = PathBuf::from("/project");
+        let breakpoint = debugger::Breakpoint {
+            path: project.join("main.py"),
+            line: 10,
+            hit_condition: Some(">= 5".to_string()),
+            ..Default::default()
+        };
+
+        let mut manager = StateManager::new(&state_path).unwrap();
+        manager
+            .set_project_breakpoints(&project, vec![breakpoint.clone()])
+            .unwrap();
+        manager
+            .set_project_breakpoints(&project, vec![breakpoint.clone()])
+            .unwrap();
+
+        let reloaded = StateManager::new(&state_path).unwrap();
+        let _ = std::fs::remove_file(&state_path);
+
+        assert_eq!(reloaded.current().projects.len(), 1);
+        assert_eq!(reloaded.current().projects[0].breakpoints, vec![breakpoint]);
+    }
+
+    #[test]
+    fn set_project_breakpoints_matches_home_relative_paths() {
+        let state_path = std::env::temp_dir().join(format!(
+            "dap-gui-state-{}-{}.json",
+            std::process::id(),
+            "set_project_breakpoints_home"
+        ));
+        let _ = std::fs::remove_file(&state_path);
+
+        let project = PathBuf::from("~/project");
+        let expanded = debugger::utils::normalise_path(&project).into_owned();
+        let breakpoint = |line| debugger::Breakpoint {
+            path: expanded.join("main.py"),
+            line,
+            ..Default::default()
+        };
+
+        let mut manager = StateManager::new(&state_path).unwrap();
+        manager
+            .set_project_breakpoints(&project, vec![breakpoint(1)])
+            .unwrap();
+        manager
+            .set_project_breakpoints(&project, vec![breakpoint(2)])
+            .unwrap();
+        manager
+            .set_project_breakpoints(&expanded, vec![breakpoint(3)])
+            .unwrap();
+        let _ = std::fs::remove_file(&state_path);
+
+        assert_eq!(manager.current().projects.len(), 1);
+        assert_eq!(
+            manager.current().projects[0].breakpoints,
+            vec![breakpoint(3)]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    io::{BufReader, Cursor},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dap_test_harness::{FakeAdapter, Script};
+use serde_json::json;
+use transport::{reader::hand_written_reader::HandWrittenReader, requests::RequestBody, Reader};
+
+fn variables_response(count: usize) -> String {
+    let variables: Vec<_> = (0..count)
+        .map(|i| {
+            json!({
+                "name": format!("variable_{i}"),
+                "value": format!("{{'key': {i}, 'items': [1, 2, 3]}}"),
+                "type": "dict",
+                "variablesReference": i + 1,
+            })
+        })
+        .collect();
+    let body = json!({
+        "seq": 1,
+        "type": "response",
+        "request_seq": 1,
+        "success": true,
+        "command": "variables",
+        "body": { "variables": variables },
+    })
+    .to_string();
+    format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
+
+fn connect(
+    adapter: &FakeAdapter,
+) -> (
+    transport::Client,
+    crossbeam_channel::Receiver<transport::events::Event>,
+) {
+    let stream = TcpStream::connect(("127.0.0.1", adapter.port())).unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let client = transport::Client::new(stream, tx).unwrap();
+    (client, rx)
+}
+
+/// Parse a `variables` response for a scope with many variables
+pub fn decode_variables_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode variables");
+    for count in [100, 1_000, 10_000] {
+        let input = variables_response(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &input, |b, input| {
+            b.iter(|| {
+                let mut reader = HandWrittenReader::new(BufReader::new(Cursor::new(input)));
+                reader.poll_message().unwrap().expect("message parsed")
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Send a request over loopback and wait for the response
+pub fn round_trip_benchmark(c: &mut Criterion) {
+    let adapter = FakeAdapter::start(Script::new()).unwrap();
+    let (client, _events) = connect(&adapter);
+    c.bench_function("round trip", |b| {
+        b.iter(|| client.send(RequestBody::ConfigurationDone).unwrap())
+    });
+}
+
+/// Receive a burst of events from the adapter
+pub fn event_throughput_benchmark(c: &mut Criterion) {
+    const EVENTS: usize = 1_000;
+
+    let mut group = c.benchmark_group("event throughput");
+    group.sample_size(20);
+    group.bench_function(BenchmarkId::from_parameter(EVENTS), |b| {
+        b.iter_custom(|iters| {
+            let mut total = Duration::ZERO;
+            for _ in 0..iters {
+                // the burst starts once the adapter has answered our request
+                let mut script = Script::new().respond("configurationDone", None);
+                for i in 0..EVENTS {
+                    script = script.event(
+                        "output",
+                        json!({ "category": "stdout", "output": format!("line {i}\n") }),
+                    );
+                }
+                let adapter = FakeAdapter::start(script).unwrap();
+                let (client, events) = connect(&adapter);
+
+                let start = Instant::now();
+                client.send(RequestBody::ConfigurationDone).unwrap();
+                for _ in 0..EVENTS {
+                    events.recv().unwrap();
+                }
+                total += start.elapsed();
+            }
+            total
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    decode_variables_benchmark,
+    round_trip_benchmark,
+    event_throughput_benchmark
+);
+criterion_main!(benches);
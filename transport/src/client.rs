@@ -1,17 +1,182 @@
-use std::io::{BufReader, Write};
-use std::net::TcpStream;
-use std::sync::atomic::{AtomicI64, Ordering};
+use std::io::{BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use std::{fmt, io};
 
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError, Weak};
 // TODO: use internal error type
 use eyre::{Context, Result};
 
+use crate::reader::hand_written_reader::HandWrittenReader;
 use crate::request_store::{RequestStore, WaitingRequest};
-use crate::responses::Response;
-use crate::{events, reader, requests, responses, Reader};
+use crate::responses::ResponseBody;
+use crate::tap::{Direction, MessageTap, TapSlot};
+use crate::types::{self, Seq};
+use crate::validation::Validator;
+use crate::{events, requests, responses, Reader};
+
+/// How long [`Client::send`] waits for a response unless configured otherwise
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Error returned when the adapter does not respond to a request in time
+#[derive(Debug, Clone)]
+pub struct RequestTimeout {
+    pub command: String,
+    pub timeout: Duration,
+}
+
+impl fmt::Display for RequestTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no response to {} request within {:?}",
+            self.command, self.timeout
+        )
+    }
+}
+
+impl std::error::Error for RequestTimeout {}
+
+/// Error returned when the connection to the adapter is lost before it responds to a request
+#[derive(Debug, Clone)]
+pub struct ConnectionLost {
+    pub command: String,
+}
+
+impl fmt::Display for ConnectionLost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "connection to the adapter lost before {} response",
+            self.command
+        )
+    }
+}
+
+impl std::error::Error for ConnectionLost {}
+
+/// Error returned when the adapter responds to a request with `success: false`
+#[derive(Debug, Clone)]
+pub struct AdapterError {
+    pub command: String,
+    /// Short reason for the failure, e.g. `cancelled` or `notStopped`
+    pub message: Option<String>,
+    /// Detailed error from the body of the response, if the adapter gave one
+    pub error: Option<types::ErrorMessage>,
+}
+
+impl AdapterError {
+    /// Most detailed description of the failure given by the adapter
+    pub fn reason(&self) -> Option<String> {
+        self.error
+            .as_ref()
+            .map(types::ErrorMessage::formatted)
+            .or_else(|| self.message.clone())
+    }
+}
+
+impl fmt::Display for AdapterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason() {
+            Some(reason) => write!(f, "{} request failed: {reason}", self.command),
+            None => write!(f, "{} request failed", self.command),
+        }
+    }
+}
+
+impl std::error::Error for AdapterError {}
+
+/// Error returned by [`Client::send`]
+#[derive(Debug)]
+pub enum SendError {
+    /// The adapter responded with `success: false`
+    Adapter(Box<AdapterError>),
+    /// The adapter did not respond in time
+    Timeout(RequestTimeout),
+    /// The connection to the adapter was lost before it responded, or before the request was
+    /// sent
+    ConnectionLost(ConnectionLost),
+    /// Any other failure, e.g. writing the request
+    Other(eyre::Report),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Adapter(e) => e.fmt(f),
+            SendError::Timeout(e) => e.fmt(f),
+            SendError::ConnectionLost(e) => e.fmt(f),
+            SendError::Other(report) => write!(f, "{report:#}"),
+        }
+    }
+}
+
+impl std::error::Error for SendError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SendError::Other(report) => Some(report.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<AdapterError> for SendError {
+    fn from(e: AdapterError) -> Self {
+        SendError::Adapter(Box::new(e))
+    }
+}
+
+impl From<RequestTimeout> for SendError {
+    fn from(e: RequestTimeout) -> Self {
+        SendError::Timeout(e)
+    }
+}
+
+impl From<ConnectionLost> for SendError {
+    fn from(e: ConnectionLost) -> Self {
+        SendError::ConnectionLost(e)
+    }
+}
+
+impl From<eyre::Report> for SendError {
+    fn from(report: eyre::Report) -> Self {
+        SendError::Other(report)
+    }
+}
+
+/// How a [`Client`] reconnects to the adapter after the connection is lost, see
+/// [`Client::set_reconnect_policy`]
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Number of times to try to connect before giving up
+    pub max_attempts: u32,
+    /// How long to wait before the first attempt, doubling for each attempt after that
+    pub initial_backoff: Duration,
+    /// Longest time to wait between attempts
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// How long to wait before the given attempt, starting from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
 
 #[allow(dead_code)]
 #[derive(Debug)]
@@ -31,12 +196,22 @@ pub enum Message {
 
 pub struct ClientInternals {
     // writer
-    // TODO: trait implementor
-    output: TcpStream,
+    output: Box<dyn Write + Send>,
+    /// Closed for writing when the client is dropped, so that the adapter closes the
+    /// connection and the reader stops waiting for input
+    socket: Option<TcpStream>,
 
     // common
     sequence_number: Arc<AtomicI64>,
     store: RequestStore,
+    /// Cleared by the reader when the connection is lost, until it reconnects
+    connected: Arc<AtomicBool>,
+    /// How long [`Client::send`] waits for a response by default, or forever if `None`
+    timeout: Option<Duration>,
+    reconnect: Option<ReconnectPolicy>,
+    tap: TapSlot,
+    /// Whether messages from the adapter are validated, see [`Client::set_strict`]
+    strict: Arc<AtomicBool>,
 
     // Option because of drop and take
     exit: Option<oneshot::Sender<()>>,
@@ -52,86 +227,244 @@ impl Client {
     pub fn new(
         stream: TcpStream,
         responses: crossbeam_channel::Sender<events::Event>,
+    ) -> Result<Self> {
+        Self::with_reverse_requests(stream, responses, None)
+    }
+
+    /// Create a client that forwards requests sent by the adapter (e.g. `startDebugging`) to
+    /// `reverse_requests`
+    ///
+    /// The receiver must answer each request with [`Client::respond`]. Without a channel,
+    /// reverse requests are answered with a failure response.
+    pub fn with_reverse_requests(
+        stream: TcpStream,
+        responses: crossbeam_channel::Sender<events::Event>,
+        reverse_requests: Option<crossbeam_channel::Sender<requests::Request>>,
+    ) -> Result<Self> {
+        // remember where the adapter is, in case we need to reconnect
+        let connect = stream.peer_addr().ok().map(|addr| -> Connect {
+            Box::new(move || {
+                let stream = TcpStream::connect(addr)?;
+                let (input, output, socket) = split_tcp_stream(stream)?;
+                Ok((Box::new(input), Box::new(output), socket))
+            })
+        });
+        let (input, output, socket) = split_tcp_stream(stream).context("splitting stream")?;
+        Self::start(
+            input,
+            output,
+            Some(socket),
+            connect,
+            responses,
+            reverse_requests,
+        )
+    }
+
+    /// Create a client that reads messages from the adapter on `input` and writes to `output`,
+    /// e.g. the stdout and stdin of an adapter process
+    ///
+    /// See [`Client::with_reverse_requests`] for how `reverse_requests` is used. Clients created
+    /// this way cannot reconnect to the adapter.
+    pub fn from_streams(
+        input: impl Read + Send + 'static,
+        output: impl Write + Send + 'static,
+        responses: crossbeam_channel::Sender<events::Event>,
+        reverse_requests: Option<crossbeam_channel::Sender<requests::Request>>,
+    ) -> Result<Self> {
+        Self::start(input, output, None, None, responses, reverse_requests)
+    }
+
+    fn start(
+        input: impl Read + Send + 'static,
+        output: impl Write + Send + 'static,
+        socket: Option<TcpStream>,
+        mut connect: Option<Connect>,
+        responses: crossbeam_channel::Sender<events::Event>,
+        reverse_requests: Option<crossbeam_channel::Sender<requests::Request>>,
     ) -> Result<Self> {
         // internal state
         let sequence_number = Arc::new(AtomicI64::new(0));
+        let connected = Arc::new(AtomicBool::new(true));
+        let tap = TapSlot::default();
+        let strict = Arc::new(AtomicBool::new(false));
 
         // Background poller to send responses and events
-        let input_stream = stream.try_clone().unwrap();
-        input_stream
-            .set_read_timeout(Some(Duration::from_secs(1)))
-            .unwrap();
         let store = RequestStore::default();
-        let store_clone = Arc::clone(&store);
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
-        thread::spawn(move || {
-            let input = BufReader::new(input_stream);
-            let mut reader = reader::get(input);
+        let internals = Arc::new(Mutex::new(ClientInternals {
+            output: Box::new(output),
+            socket,
+            sequence_number,
+            store: Arc::clone(&store),
+            connected: Arc::clone(&connected),
+            timeout: Some(DEFAULT_REQUEST_TIMEOUT),
+            reconnect: None,
+            tap: tap.clone(),
+            strict: Arc::clone(&strict),
+            exit: Some(shutdown_tx),
+        }));
+        // weak so that the reader thread does not keep the client alive
+        let reader_internals = Arc::downgrade(&internals);
 
-            // poll loop
+        thread::spawn(move || {
+            let mut input: Box<dyn Read + Send> = Box::new(input);
             loop {
-                // check for shutdown
-                match shutdown_rx.try_recv() {
-                    Ok(_) => return,
-                    Err(oneshot::TryRecvError::Empty) => {}
-                    Err(e) => {
-                        tracing::error!(error = %e, "shutdown sender closed");
-                        return;
-                    }
-                }
+                // each connection starts a new sequence
+                let validator =
+                    Validator::new(Arc::clone(&strict), Arc::clone(&store), responses.clone());
+                let closed = poll_messages(
+                    HandWrittenReader::new(BufReader::new(input))
+                        .with_tap(tap.clone())
+                        .with_validator(validator),
+                    &shutdown_rx,
+                    &responses,
+                    &store,
+                    reverse_requests.as_ref(),
+                    &reader_internals,
+                );
 
-                match reader.poll_message() {
-                    Ok(Some(msg)) => match msg {
-                        Message::Event(evt) => {
-                            let _ = responses.send(evt);
-                        }
-                        Message::Response(r) => {
-                            with_lock(
-                                "Reader.store",
-                                store_clone.as_ref(),
-                                |mut store| match store.remove(&r.request_seq) {
-                                    Some(WaitingRequest(_, tx)) => {
-                                        let _ = tx.send(r);
-                                    }
-                                    None => {
-                                        tracing::warn!(response = ?r, "no message in request store")
-                                    }
-                                },
-                            );
-                        }
-                        Message::Request(_) => unreachable!("we should not be parsing requests"),
-                    },
-                    Ok(None) => {
-                        tracing::debug!("ok none");
-                        return;
-                    }
-                    Err(e) => tracing::warn!("reader error: {e}"),
+                // fail any requests still waiting for a response, and any sent from now on
+                connected.store(false, Ordering::SeqCst);
+                with_lock("Reader.store", store.as_ref(), |mut store| {
+                    store.clear();
+                });
+                // the connection also ends when the client closes the socket on shutdown
+                if matches!(closed, Closed::Shutdown) || shutting_down(&shutdown_rx) {
+                    return;
                 }
+
+                let _ = responses.send(events::Event::Disconnected);
+                let Some(new_input) = reconnect(
+                    connect.as_mut(),
+                    &shutdown_rx,
+                    &responses,
+                    &reader_internals,
+                ) else {
+                    return;
+                };
+                input = new_input;
             }
         });
 
-        let internal = ClientInternals {
-            output: stream,
-            sequence_number,
-            store,
-            exit: Some(shutdown_tx),
-        };
+        Ok(Self { internals })
+    }
 
-        Ok(Self {
-            internals: Arc::new(Mutex::new(internal)),
-        })
+    /// Reply to a reverse request received from the adapter
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub fn respond(
+        &self,
+        request_seq: Seq,
+        command: &str,
+        success: bool,
+        message: Option<String>,
+    ) -> Result<()> {
+        with_lock(
+            "Client.internals",
+            self.internals.as_ref(),
+            |mut internals| internals.respond(request_seq, command, success, message),
+        )
+    }
+
+    /// Send a request and wait for the response, returning its body if the request succeeded
+    ///
+    /// Fails with [`SendError::Adapter`] if the adapter reports that the request failed, or
+    /// with [`SendError::Timeout`] if the adapter does not respond within the timeout set with
+    /// [`Client::set_timeout`], [`DEFAULT_REQUEST_TIMEOUT`] by default. Responses to commands
+    /// that are not modelled, e.g. `next`, are returned as [`ResponseBody::Other`].
+    #[tracing::instrument(skip(self, body), level = "debug")]
+    pub fn send(&self, body: requests::RequestBody) -> Result<ResponseBody, SendError> {
+        // the lock is only held while writing, so requests wait for responses concurrently
+        let (pending, timeout) = with_lock(
+            "Client.internals",
+            self.internals.as_ref(),
+            |mut internals| {
+                let timeout = internals.timeout;
+                internals
+                    .write_request(body)
+                    .map(|pending| (pending, timeout))
+            },
+        )?;
+        pending.wait(timeout)
     }
 
+    /// Like [`Client::send`] but wait at most `timeout` for the response, or forever if `None`
     #[tracing::instrument(skip(self, body), level = "debug")]
-    pub fn send(&self, body: requests::RequestBody) -> Result<Response> {
+    pub fn send_with_timeout(
+        &self,
+        body: requests::RequestBody,
+        timeout: Option<Duration>,
+    ) -> Result<ResponseBody, SendError> {
+        with_lock(
+            "Client.internals",
+            self.internals.as_ref(),
+            |mut internals| internals.write_request(body),
+        )?
+        .wait(timeout)
+    }
+
+    /// Check that the adapter is still handling requests, by sending a `threads` request and
+    /// waiting at most `timeout` for any response, including a failure
+    ///
+    /// The deadline starts before waiting to write the request, so a probe is not held up by
+    /// other requests. Probes are not passed to the tap. Fails with [`SendError::Timeout`] if
+    /// the adapter does not respond in time.
+    pub fn probe(&self, timeout: Duration) -> Result<(), SendError> {
+        self.downgrade().probe(timeout)
+    }
+
+    /// Reference to this client that does not keep the connection open
+    pub fn downgrade(&self) -> WeakClient {
+        WeakClient {
+            internals: Arc::downgrade(&self.internals),
+        }
+    }
+
+    /// Change how long [`Client::send`] waits for a response, or wait forever if `None`
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        with_lock(
+            "Client.internals",
+            self.internals.as_ref(),
+            |mut internals| internals.timeout = timeout,
+        )
+    }
+
+    /// Reconnect to the adapter following `policy` if the connection is lost, or give up
+    /// straight away if `None`
+    ///
+    /// [`events::Event::Disconnected`], [`events::Event::Reconnecting`] and
+    /// [`events::Event::Reconnected`] are sent to the event channel as this happens. Requests
+    /// waiting for a response when the connection is lost fail with [`ConnectionLost`], as do
+    /// requests sent before the client has reconnected. Only clients connected over TCP can
+    /// reconnect.
+    pub fn set_reconnect_policy(&self, policy: Option<ReconnectPolicy>) {
         with_lock(
             "Client.internals",
             self.internals.as_ref(),
-            |mut internals| internals.send(body),
+            |mut internals| internals.reconnect = policy,
         )
     }
 
+    /// Mirror every message sent to or received from the adapter to `tap`, or stop mirroring
+    /// if `None`
+    pub fn set_tap(&self, tap: Option<Arc<dyn MessageTap>>) {
+        with_lock("Client.internals", self.internals.as_ref(), |internals| {
+            internals.tap.set(tap)
+        })
+    }
+
+    /// Check every message received from the adapter against the protocol, e.g. that sequence
+    /// numbers increase and responses answer outstanding requests
+    ///
+    /// Violations are logged and sent to the event channel as
+    /// [`events::Event::ProtocolViolation`], but the messages are otherwise handled as usual.
+    /// See [`crate::validation`] for the checks made.
+    pub fn set_strict(&self, enabled: bool) {
+        with_lock("Client.internals", self.internals.as_ref(), |internals| {
+            internals.strict.store(enabled, Ordering::SeqCst)
+        })
+    }
+
     #[tracing::instrument(skip(self, body), level = "debug")]
     pub fn execute(&self, body: requests::RequestBody) -> Result<()> {
         with_lock(
@@ -142,6 +475,176 @@ impl Client {
     }
 }
 
+/// Opens a new connection to the adapter, returning the streams to read from and write to,
+/// and the socket to close when the client is dropped
+type Connect =
+    Box<dyn FnMut() -> io::Result<(Box<dyn Read + Send>, Box<dyn Write + Send>, TcpStream)> + Send>;
+
+/// Split a TCP stream into halves for reading and writing, and a handle for closing it
+fn split_tcp_stream(stream: TcpStream) -> io::Result<(TcpStream, TcpStream, TcpStream)> {
+    // requests are small writes that need answering promptly, don't let Nagle's algorithm
+    // hold them back
+    stream.set_nodelay(true)?;
+    let input = stream.try_clone()?;
+    // so that the reader notices when the client shuts down
+    input.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let socket = stream.try_clone()?;
+    Ok((input, stream, socket))
+}
+
+/// Why [`poll_messages`] stopped
+enum Closed {
+    /// The client was dropped
+    Shutdown,
+    /// The adapter closed the connection, or it failed
+    Lost,
+}
+
+/// Dispatch the messages received on `input` until the client shuts down or the connection
+/// is lost
+fn poll_messages(
+    mut reader: impl Reader<BufReader<Box<dyn Read + Send>>>,
+    shutdown_rx: &oneshot::Receiver<()>,
+    responses: &crossbeam_channel::Sender<events::Event>,
+    store: &RequestStore,
+    reverse_requests: Option<&crossbeam_channel::Sender<requests::Request>>,
+    internals: &Weak<Mutex<ClientInternals>>,
+) -> Closed {
+    loop {
+        if shutting_down(shutdown_rx) {
+            return Closed::Shutdown;
+        }
+
+        match reader.poll_message() {
+            Ok(Some(Message::Event(evt))) => {
+                let _ = responses.send(evt);
+            }
+            Ok(Some(Message::Response(r))) => {
+                with_lock("Reader.store", store.as_ref(), |mut store| {
+                    match store.remove(&r.request_seq) {
+                        Some(WaitingRequest(_, tx)) => {
+                            let _ = tx.send(r);
+                        }
+                        None => {
+                            tracing::warn!(response = ?r, "no message in request store")
+                        }
+                    }
+                });
+            }
+            Ok(Some(Message::Request(request))) => {
+                handle_reverse_request(request, reverse_requests, internals);
+            }
+            Ok(None) => {
+                tracing::debug!("connection closed");
+                return Closed::Lost;
+            }
+            // gives the loop a chance to check whether the client has shut down
+            Err(e) if read_timed_out(&e) => {}
+            Err(e) if e.chain().any(|cause| cause.is::<io::Error>()) => {
+                tracing::warn!(error = %e, "connection failed");
+                return Closed::Lost;
+            }
+            Err(e) => tracing::warn!("reader error: {e}"),
+        }
+    }
+}
+
+/// Whether reading stopped because the read timeout expired, rather than the connection failing
+fn read_timed_out(e: &eyre::Report) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|e| {
+            matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            )
+        })
+}
+
+/// Whether the client has been dropped
+fn shutting_down(shutdown_rx: &oneshot::Receiver<()>) -> bool {
+    match shutdown_rx.try_recv() {
+        Ok(_) => true,
+        Err(oneshot::TryRecvError::Empty) => false,
+        Err(e) => {
+            tracing::error!(error = %e, "shutdown sender closed");
+            true
+        }
+    }
+}
+
+/// Connect to the adapter again as configured with [`Client::set_reconnect_policy`],
+/// returning the new stream to read from
+fn reconnect(
+    connect: Option<&mut Connect>,
+    shutdown_rx: &oneshot::Receiver<()>,
+    responses: &crossbeam_channel::Sender<events::Event>,
+    internals: &Weak<Mutex<ClientInternals>>,
+) -> Option<Box<dyn Read + Send>> {
+    let connect = connect?;
+    let policy = with_lock(
+        "Reader.internals",
+        internals.upgrade()?.as_ref(),
+        |internals| internals.reconnect.clone(),
+    )?;
+
+    for attempt in 1..=policy.max_attempts {
+        let _ = responses.send(events::Event::Reconnecting { attempt });
+        thread::sleep(policy.backoff(attempt));
+        if shutting_down(shutdown_rx) {
+            return None;
+        }
+
+        match connect() {
+            Ok((input, output, socket)) => {
+                let internals = internals.upgrade()?;
+                with_lock("Reader.internals", internals.as_ref(), |mut internals| {
+                    internals.output = output;
+                    internals.socket = Some(socket);
+                    internals.connected.store(true, Ordering::SeqCst);
+                });
+                tracing::info!(%attempt, "reconnected to adapter");
+                let _ = responses.send(events::Event::Reconnected);
+                return Some(input);
+            }
+            Err(e) => tracing::warn!(error = %e, %attempt, "reconnecting to adapter"),
+        }
+    }
+    tracing::warn!("giving up reconnecting to adapter");
+    None
+}
+
+/// Pass a reverse request on to the client owner, or reject it if nobody is listening
+fn handle_reverse_request(
+    request: requests::Request,
+    reverse_requests: Option<&crossbeam_channel::Sender<requests::Request>>,
+    internals: &Weak<Mutex<ClientInternals>>,
+) {
+    tracing::debug!(?request, "received reverse request");
+    let seq = request.seq;
+    let command = request.body.command();
+    if let Some(tx) = reverse_requests {
+        if tx.send(request).is_ok() {
+            return;
+        }
+    }
+
+    let Some(internals) = internals.upgrade() else {
+        return;
+    };
+    let res = with_lock("Reader.internals", internals.as_ref(), |mut internals| {
+        internals.respond(
+            seq,
+            &command,
+            false,
+            Some(format!("{command} is not supported")),
+        )
+    });
+    if let Err(e) = res {
+        tracing::warn!(error = %e, "rejecting reverse request");
+    }
+}
+
 fn with_lock<T, F, R>(name: &str, lock: &Mutex<T>, f: F) -> R
 where
     F: FnOnce(MutexGuard<'_, T>) -> R,
@@ -153,9 +656,69 @@ where
     res
 }
 
+/// Request written to the adapter, waiting for its response
+struct Pending {
+    seq: Seq,
+    command: String,
+    rx: oneshot::Receiver<responses::Response>,
+    store: RequestStore,
+}
+
+impl Pending {
+    /// Wait at most `timeout` for the response, or forever if `None`
+    fn wait(self, timeout: Option<Duration>) -> Result<ResponseBody, SendError> {
+        let res = match timeout {
+            Some(timeout) => self.rx.recv_timeout(timeout),
+            None => self
+                .rx
+                .recv()
+                .map_err(|_| oneshot::RecvTimeoutError::Disconnected),
+        };
+        match res {
+            Ok(response) if response.success => Ok(response.body.unwrap_or(ResponseBody::Other)),
+            Ok(response) => Err(AdapterError {
+                command: self.command,
+                message: response.message,
+                error: response.error,
+            }
+            .into()),
+            Err(oneshot::RecvTimeoutError::Timeout) => {
+                // nobody is waiting any more, so a late response is dropped by the reader
+                with_lock("Pending.store", self.store.as_ref(), |mut store| {
+                    store.remove(&self.seq);
+                });
+                Err(RequestTimeout {
+                    command: self.command,
+                    timeout: timeout.unwrap_or_default(),
+                }
+                .into())
+            }
+            Err(oneshot::RecvTimeoutError::Disconnected) => Err(ConnectionLost {
+                command: self.command,
+            }
+            .into()),
+        }
+    }
+}
+
 impl ClientInternals {
     #[tracing::instrument(skip(self), level = "trace", fields(request))]
-    pub fn send(&mut self, body: requests::RequestBody) -> Result<Response> {
+    fn write_request(&mut self, body: requests::RequestBody) -> Result<Pending, SendError> {
+        self.write_tracked(body, true)
+    }
+
+    /// Write a liveness probe, which is kept from the tap
+    fn write_probe(&mut self) -> Result<Pending, SendError> {
+        self.write_tracked(requests::RequestBody::Threads, false)
+    }
+
+    /// Register a request for its response and write it to the adapter, passing it to the tap
+    /// if `tapped`
+    fn write_tracked(
+        &mut self,
+        body: requests::RequestBody,
+        tapped: bool,
+    ) -> Result<Pending, SendError> {
         self.sequence_number.fetch_add(1, Ordering::SeqCst);
         let message = requests::Request {
             seq: self.sequence_number.load(Ordering::SeqCst),
@@ -171,50 +734,163 @@ impl ClientInternals {
         with_lock("ClientInternals.store", self.store.as_ref(), |mut store| {
             store.insert(message.seq, waiting_request);
         });
+        // checked after registering the request, so that the reader either sees the request
+        // when failing waiting requests or we see that the connection is lost
+        if !self.connected.load(Ordering::SeqCst) {
+            with_lock("ClientInternals.store", self.store.as_ref(), |mut store| {
+                store.remove(&message.seq);
+            });
+            return Err(ConnectionLost {
+                command: message.body.command(),
+            }
+            .into());
+        }
 
-        write!(
-            self.output,
-            "Content-Length: {}\r\n\r\n{}",
-            resp_json.len(),
-            resp_json
-        )
-        .wrap_err("writing message to output buffer")?;
+        if tapped {
+            self.tap
+                .record(Direction::ClientToServer, resp_json.as_bytes());
+        } else {
+            self.tap.hide_response(message.seq);
+        }
+        self.write_message(&resp_json)?;
+
+        Ok(Pending {
+            seq: message.seq,
+            command: message.body.command(),
+            rx,
+            store: Arc::clone(&self.store),
+        })
+    }
+
+    /// Frame `json` with its header and write it to the adapter
+    fn write_message(&mut self, json: &str) -> Result<()> {
+        // a single write, so the body is not held back waiting for the header to be acked
+        let message = format!("Content-Length: {}\r\n\r\n{}", json.len(), json);
+        self.output
+            .write_all(message.as_bytes())
+            .wrap_err("writing message to output buffer")?;
         self.output.flush().wrap_err("flushing output buffer")?;
+        Ok(())
+    }
 
-        let res = rx.recv().expect("sender dropped");
-        Ok(res)
+    /// Send a response to a request made by the adapter
+    pub fn respond(
+        &mut self,
+        request_seq: Seq,
+        command: &str,
+        success: bool,
+        message: Option<String>,
+    ) -> Result<()> {
+        let seq = self.sequence_number.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut response = serde_json::json!({
+            "seq": seq,
+            "type": "response",
+            "request_seq": request_seq,
+            "command": command,
+            "success": success,
+        });
+        if let Some(message) = message {
+            response["message"] = message.into();
+        }
+        let resp_json = serde_json::to_string(&response).wrap_err("encoding json body")?;
+        tracing::debug!(response = %resp_json, "sending response");
+        self.tap
+            .record(Direction::ClientToServer, resp_json.as_bytes());
+        self.write_message(&resp_json)?;
+        Ok(())
     }
 
     /// Execute a call on the client but do not wait for a response
     #[tracing::instrument(skip(self), level = "trace", fields(request))]
     pub fn execute(&mut self, body: requests::RequestBody) -> Result<()> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(ConnectionLost {
+                command: body.command(),
+            }
+            .into());
+        }
         self.sequence_number.fetch_add(1, Ordering::SeqCst);
         let message = requests::Request {
             seq: self.sequence_number.load(Ordering::SeqCst),
             r#type: "request".to_string(),
             body: body.clone(),
         };
-        let resp_json = serde_json::to_string(&message).unwrap();
+        let resp_json = serde_json::to_string(&message).wrap_err("encoding json body")?;
         tracing::Span::current().record("request", &resp_json);
         tracing::debug!("sending message");
-        write!(
-            self.output,
-            "Content-Length: {}\r\n\r\n{}",
-            resp_json.len(),
-            resp_json
-        )
-        .unwrap();
-        self.output.flush().unwrap();
+        self.tap
+            .record(Direction::ClientToServer, resp_json.as_bytes());
+        self.write_message(&resp_json)?;
 
         Ok(())
     }
 }
 
+/// Reference to a [`Client`] that does not keep the connection to the adapter open, see
+/// [`Client::downgrade`]
+#[derive(Clone)]
+pub struct WeakClient {
+    internals: Weak<Mutex<ClientInternals>>,
+}
+
+impl WeakClient {
+    /// See [`Client::probe`]
+    ///
+    /// The client is only held while writing the request, so a probe waiting for its response
+    /// does not stop the client shutting down. Fails with [`SendError::ConnectionLost`] if
+    /// every [`Client`] has been dropped.
+    pub fn probe(&self, timeout: Duration) -> Result<(), SendError> {
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+        let command = requests::RequestBody::Threads.command();
+        let deadline = Instant::now() + timeout;
+        let timed_out = || -> SendError {
+            RequestTimeout {
+                command: command.clone(),
+                timeout,
+            }
+            .into()
+        };
+        let pending = loop {
+            let Some(internals) = self.internals.upgrade() else {
+                return Err(ConnectionLost {
+                    command: command.clone(),
+                }
+                .into());
+            };
+            // bind the result so that the guard is dropped before `internals`
+            let res = match internals.try_lock() {
+                Ok(mut internals) => Some(internals.write_probe()),
+                Err(TryLockError::WouldBlock) => None,
+                Err(TryLockError::Poisoned(e)) => panic!("client lock poisoned: {e}"),
+            };
+            match res {
+                Some(pending) => break pending?,
+                None if Instant::now() < deadline => thread::sleep(POLL_INTERVAL),
+                None => return Err(timed_out()),
+            }
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match pending.wait(Some(remaining)) {
+            Ok(_) | Err(SendError::Adapter(_)) => Ok(()),
+            Err(SendError::Timeout(_)) => Err(timed_out()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
 impl Drop for ClientInternals {
     fn drop(&mut self) {
         tracing::debug!("shutting down client");
         // Shutdown the background thread
         let _ = self.exit.take().unwrap().send(());
+        // the reader notices the shutdown within its read timeout, but end the connection
+        // straight away so the adapter sees the client go. Only the write half is closed so
+        // that anything the adapter is still sending, e.g. the response to `disconnect`, is
+        // not cut off.
+        if let Some(socket) = self.socket.take() {
+            let _ = socket.shutdown(Shutdown::Write);
+        }
     }
 }
 
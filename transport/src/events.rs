@@ -1,7 +1,7 @@
 //! Events emitted by a DAP server
 use serde::{Deserialize, Serialize};
 
-use crate::types::{BreakpointId, Module, Source, ThreadId};
+use crate::types::{BreakpointId, Module, Source, StackFrameId, ThreadId};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "event", content = "body", rename_all = "camelCase")]
@@ -17,13 +17,58 @@ pub enum Event {
     Terminated,
     // TODO: handle unknown event types
     // debugpy types
-    DebugpyWaitingForServer { host: String, port: u16 },
+    DebugpyWaitingForServer {
+        host: String,
+        port: u16,
+    },
     Module(ModuleEventBody),
+    LoadedSource(LoadedSourceEventBody),
+    Invalidated(InvalidatedEventBody),
+    // connection status, sent by the client rather than the adapter
+    /// The connection to the adapter was lost
+    #[serde(skip)]
+    Disconnected,
+    /// The client is trying to reconnect, see [`crate::ReconnectPolicy`]
+    #[serde(skip)]
+    Reconnecting {
+        attempt: u32,
+    },
+    /// The client has reconnected to the adapter
+    #[serde(skip)]
+    Reconnected,
+    /// A message from the adapter broke the protocol, see [`crate::Client::set_strict`]
+    #[serde(skip)]
+    ProtocolViolation(crate::validation::Violation),
+}
+
+impl Event {
+    /// Name of the event as sent on the wire, e.g. `stopped`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Initialized => "initialized",
+            Event::Output(_) => "output",
+            Event::Process(_) => "process",
+            Event::Stopped(_) => "stopped",
+            Event::Continued(_) => "continued",
+            Event::Thread(_) => "thread",
+            Event::Exited(_) => "exited",
+            Event::Terminated => "terminated",
+            Event::DebugpyWaitingForServer { .. } => "debugpyWaitingForServer",
+            Event::Module(_) => "module",
+            Event::LoadedSource(_) => "loadedSource",
+            Event::Invalidated(_) => "invalidated",
+            Event::Disconnected => "disconnected",
+            Event::Reconnecting { .. } => "reconnecting",
+            Event::Reconnected => "reconnected",
+            Event::ProtocolViolation(_) => "protocolViolation",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct OutputEventBody {
-    // pub category: Option<OutputEventCategory>,
+    pub category: Option<String>,
     pub output: String,
     // pub group: Option<OutputEventGroup>,
     pub variables_reference: Option<i64>,
@@ -51,6 +96,32 @@ pub struct StoppedEventBody {
     pub hit_breakpoint_ids: Option<Vec<BreakpointId>>,
     pub description: Option<String>,
     pub text: Option<String>,
+    /// If true, all threads were stopped rather than just `thread_id`
+    pub all_threads_stopped: Option<bool>,
+}
+
+/// State previously fetched from the adapter that is now out of date, e.g. after the value of a
+/// variable was changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvalidatedEventBody {
+    /// Areas that are invalid, or everything if not given
+    pub areas: Option<Vec<InvalidatedArea>>,
+    /// Thread whose state is invalid, or every thread if not given
+    pub thread_id: Option<ThreadId>,
+    pub stack_frame_id: Option<StackFrameId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InvalidatedArea {
+    All,
+    Stacks,
+    Threads,
+    Variables,
+    /// Area added in a later version of the protocol
+    #[serde(other)]
+    Unknown,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,7 +151,22 @@ pub struct ContinuedEventBody {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ModuleEventBody {
-    // TODO: enum
-    pub reason: String,
+    pub reason: ChangeReason,
     pub module: Module,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedSourceEventBody {
+    pub reason: ChangeReason,
+    pub source: Source,
+}
+
+/// How a module or loaded source changed, see [`ModuleEventBody`] and [`LoadedSourceEventBody`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeReason {
+    New,
+    Changed,
+    Removed,
+}
@@ -5,15 +5,24 @@ pub mod bindings;
 mod client;
 pub mod events;
 pub mod reader;
+pub mod replay;
 mod request_store;
 pub mod requests;
 pub mod responses;
+mod stdio;
+pub mod tap;
 pub mod types;
+pub mod validation;
 
 pub use client::Client;
 pub use client::Message;
 pub use client::Received;
+pub use client::{
+    AdapterError, ConnectionLost, ReconnectPolicy, RequestTimeout, SendError, WeakClient,
+    DEFAULT_REQUEST_TIMEOUT,
+};
 pub use reader::Reader;
+pub use stdio::StdioTransport;
 
 /// The default port the DAP protocol listens on
 pub const DEFAULT_DAP_PORT: u16 = 5678;
@@ -1,16 +1,42 @@
 use std::io::{self, BufRead};
 
-use eyre::WrapErr;
-
-use crate::Reader;
-
+use crate::{tap::TapSlot, validation::Validator, Reader};
+
+/// Name of the only header the protocol defines, which starts every message
+const CONTENT_LENGTH: &[u8] = b"Content-Length";
+
+/// Reader for messages framed with a `Content-Length` header
+///
+/// Input that cannot be parsed, e.g. a malformed header or a message torn by a misbehaving
+/// adapter, is reported as an error by [`Reader::poll_message`] after dropping it, so that the
+/// next call resynchronises on the following `Content-Length` header. Headers other than
+/// `Content-Length` are skipped.
+///
+/// If the input has a read timeout, it expiring is returned as an error so the caller can
+/// decide whether to carry on. Partially read messages are kept for the next call.
 pub struct HandWrittenReader<R> {
     input: R,
+    /// Bytes read from the input but not yet parsed
+    buffer: Vec<u8>,
+    tap: TapSlot,
+    validator: Option<Validator>,
 }
 
-enum ReaderState {
-    Header,
-    Content,
+/// Result of parsing the start of the buffer
+// messages are the common case, so avoid boxing them
+#[allow(clippy::large_enum_variant)]
+enum Parsed {
+    Message {
+        message: crate::Message,
+        len: usize,
+    },
+    /// More input is needed
+    Incomplete,
+    /// The first `skip` bytes of the buffer could not be parsed
+    Malformed {
+        error: eyre::Report,
+        skip: usize,
+    },
 }
 
 impl<R> Reader<R> for HandWrittenReader<R>
@@ -18,78 +44,311 @@ where
     R: BufRead,
 {
     fn new(input: R) -> Self {
-        Self { input }
+        Self {
+            input,
+            buffer: Vec::new(),
+            tap: TapSlot::default(),
+            validator: None,
+        }
     }
 
     fn poll_message(&mut self) -> eyre::Result<Option<crate::Message>> {
-        let mut state = ReaderState::Header;
-        let mut buffer = String::new();
-        let mut content_length: usize = 0;
-
         loop {
-            match self.input.read_line(&mut buffer) {
-                Ok(read_size) => {
-                    if read_size == 0 {
-                        return Ok(None);
-                    }
-
-                    match state {
-                        ReaderState::Header => {
-                            let parts: Vec<&str> = buffer.trim_end().split(':').collect();
-                            match parts[0] {
-                                "Content-Length" => {
-                                    content_length = match parts[1].trim().parse() {
-                                        Ok(val) => val,
-                                        Err(_) => {
-                                            eyre::bail!("failed to parse content length")
-                                        }
-                                    };
-                                    buffer.clear();
-                                    buffer.reserve(content_length);
-                                    state = ReaderState::Content;
-                                }
-                                other => {
-                                    eyre::bail!("header {} not implemented", other);
-                                }
-                            }
-                        }
-                        ReaderState::Content => {
-                            buffer.clear();
-                            let mut content = vec![0; content_length];
-                            self.input
-                                .read_exact(content.as_mut_slice())
-                                .map_err(|e| eyre::eyre!("failed to read: {:?}", e))?;
-                            let content =
-                                std::str::from_utf8(content.as_slice()).context("invalid utf8")?;
-                            let message = serde_json::from_str(content).with_context(|| {
-                                format!("could not construct message from: {content}")
-                            })?;
-                            return Ok(Some(message));
-                        }
-                    }
+            match self.parse() {
+                Parsed::Message { message, len } => {
+                    self.buffer.drain(..len);
+                    return Ok(Some(message));
+                }
+                Parsed::Malformed { error, skip } => {
+                    tracing::debug!(bytes = skip, "skipping malformed input");
+                    self.buffer.drain(..skip);
+                    return Err(error);
+                }
+                Parsed::Incomplete => {}
+            }
+
+            let read_size = match self.input.fill_buf() {
+                Ok(data) => {
+                    self.buffer.extend_from_slice(data);
+                    data.len()
                 }
-                Err(e) => {
-                    if e.kind() == io::ErrorKind::WouldBlock {
-                        continue;
-                    }
-                    return Err(eyre::eyre!("error reading from buffer: {e:?}"));
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(eyre::Report::new(e).wrap_err("error reading from buffer")),
+            };
+            if read_size == 0 {
+                return Ok(None);
+            }
+            self.input.consume(read_size);
+        }
+    }
+}
+
+impl<R> HandWrittenReader<R> {
+    /// Mirror the content of every message received to `tap`
+    pub(crate) fn with_tap(mut self, tap: TapSlot) -> Self {
+        self.tap = tap;
+        self
+    }
+
+    /// Check the content of every message received with `validator`
+    pub(crate) fn with_validator(mut self, validator: Validator) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Parse the message at the start of the buffer, skipping anything before its header
+    fn parse(&mut self) -> Parsed {
+        let Some(start) = find(&self.buffer, CONTENT_LENGTH) else {
+            // keep only the bytes that could be the start of a header
+            let keep = partial_header_len(&self.buffer);
+            self.buffer.drain(..self.buffer.len() - keep);
+            return Parsed::Incomplete;
+        };
+        if start > 0 {
+            tracing::debug!(bytes = start, "skipping input before header");
+            self.buffer.drain(..start);
+        }
+
+        let Some(header_len) = find(&self.buffer, b"\r\n\r\n") else {
+            return Parsed::Incomplete;
+        };
+        let content_length = match parse_content_length(&self.buffer[..header_len]) {
+            Ok(content_length) => content_length,
+            Err(error) => {
+                return Parsed::Malformed {
+                    error,
+                    skip: resync_len(&self.buffer, self.buffer.len()),
                 }
             }
+        };
+
+        let len = header_len + 4 + content_length;
+        if self.buffer.len() < len {
+            self.buffer.reserve(len - self.buffer.len());
+            return Parsed::Incomplete;
+        }
+
+        let content = &self.buffer[header_len + 4..len];
+        self.tap.record_received(content);
+        if let Some(validator) = &mut self.validator {
+            validator.check(content);
+        }
+        match serde_json::from_slice(content) {
+            Ok(message) => Parsed::Message { message, len },
+            Err(e) => Parsed::Malformed {
+                error: eyre::Report::new(e).wrap_err(format!(
+                    "could not construct message from: {}",
+                    String::from_utf8_lossy(content)
+                )),
+                // a message torn by the adapter is followed by the next header
+                skip: resync_len(&self.buffer, len),
+            },
         }
     }
 }
 
+/// Content length given by the header section of a message
+///
+/// Unknown headers are skipped, but lines that are not headers or conflicting content lengths
+/// are errors.
+fn parse_content_length(headers: &[u8]) -> eyre::Result<usize> {
+    let mut content_length = None;
+    for line in headers.split(|&b| b == b'\n') {
+        let line = line.trim_ascii();
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            eyre::bail!("invalid header {:?}", String::from_utf8_lossy(line));
+        };
+        let (key, value) = (line[..colon].trim_ascii(), line[colon + 1..].trim_ascii());
+        if key != CONTENT_LENGTH {
+            tracing::debug!(header = %String::from_utf8_lossy(line), "skipping unknown header");
+            continue;
+        }
+
+        let length = std::str::from_utf8(value)
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "failed to parse content length {:?}",
+                    String::from_utf8_lossy(value)
+                )
+            })?;
+        match content_length {
+            Some(previous) if previous != length => {
+                eyre::bail!("conflicting content lengths {previous} and {length}");
+            }
+            _ => content_length = Some(length),
+        }
+    }
+    content_length.ok_or_else(|| eyre::eyre!("missing content length header"))
+}
+
+/// Number of bytes to skip after malformed input at the start of `buffer`, so that parsing
+/// resumes at the next `Content-Length` header within the first `limit` bytes
+///
+/// Only the first `limit` bytes belong to the malformed input, but a header starting
+/// inside them is still found.
+fn resync_len(buffer: &[u8], limit: usize) -> usize {
+    let end = buffer.len().min(limit + CONTENT_LENGTH.len() - 1);
+    match find(&buffer[1..end], CONTENT_LENGTH) {
+        Some(pos) => pos + 1,
+        None => limit - partial_header_len(&buffer[..limit]).min(limit - 1),
+    }
+}
+
+/// Length of the longest suffix of `buffer` that could be the start of a `Content-Length`
+/// header
+fn partial_header_len(buffer: &[u8]) -> usize {
+    (1..CONTENT_LENGTH.len())
+        .rev()
+        .find(|&n| buffer.ends_with(&CONTENT_LENGTH[..n]))
+        .unwrap_or(0)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|s| s == needle)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
-        io::{BufReader, Write},
+        collections::VecDeque,
+        io::{self, BufReader, Cursor, Read, Write},
         net::{TcpListener, TcpStream},
     };
 
-    use crate::{bindings::get_random_tcp_port, events, responses, Message, Reader};
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    use crate::{bindings::get_random_tcp_port, events, responses, types, Message, Reader};
 
     use super::HandWrittenReader;
 
+    fn encode(message: &serde_json::Value) -> Vec<u8> {
+        let body = message.to_string();
+        format!("Content-Length: {}\r\n\r\n{body}", body.len()).into_bytes()
+    }
+
+    fn output_event(output: &str) -> serde_json::Value {
+        json!({"seq": 1, "type": "event", "event": "output", "body": {"output": output}})
+    }
+
+    /// Input delivered in chunks, failing with `WouldBlock` before each one as a socket with a
+    /// read timeout does
+    struct Chunked {
+        chunks: VecDeque<Vec<u8>>,
+        blocked: bool,
+    }
+
+    impl Chunked {
+        fn new(input: &[u8], split_points: &[usize]) -> Self {
+            let mut split_points: Vec<usize> =
+                split_points.iter().map(|&p| p.min(input.len())).collect();
+            split_points.sort_unstable();
+            split_points.push(input.len());
+
+            let mut start = 0;
+            let mut chunks = VecDeque::new();
+            for point in split_points {
+                // an empty read would mean the end of the input
+                if point > start {
+                    chunks.push_back(input[start..point].to_vec());
+                }
+                start = point;
+            }
+            Self {
+                chunks,
+                blocked: false,
+            }
+        }
+    }
+
+    impl Read for Chunked {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.blocked {
+                self.blocked = true;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.blocked = false;
+            let Some(chunk) = self.chunks.front_mut() else {
+                return Ok(0);
+            };
+            let len = chunk.len().min(buf.len());
+            buf[..len].copy_from_slice(&chunk[..len]);
+            chunk.drain(..len);
+            if chunk.is_empty() {
+                self.chunks.pop_front();
+            }
+            Ok(len)
+        }
+    }
+
+    fn is_would_block(e: &eyre::Report) -> bool {
+        e.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::WouldBlock)
+    }
+
+    /// Poll messages until the input is exhausted, returning the outputs of the decoded output
+    /// events and the number of errors, not counting read timeouts
+    fn read_outputs(input: impl io::BufRead) -> (Vec<String>, usize) {
+        let mut reader = HandWrittenReader::new(input);
+        let mut outputs = Vec::new();
+        let mut errors = 0;
+        loop {
+            match reader.poll_message() {
+                Ok(Some(Message::Event(events::Event::Output(body)))) => outputs.push(body.output),
+                Ok(Some(message)) => panic!("unexpected message {message:?}"),
+                Ok(None) => return (outputs, errors),
+                Err(e) if is_would_block(&e) => {}
+                Err(_) => errors += 1,
+            }
+        }
+    }
+
+    /// Input preceding a message that must not stop it being decoded
+    fn arb_corruption() -> impl Strategy<Value = Vec<u8>> {
+        prop_oneof![
+            // noise that cannot contain a header
+            prop::collection::vec(
+                any::<u8>().prop_filter("header start", |b| *b != b'C'),
+                0..32
+            ),
+            // a message torn off part way through
+            (any::<String>(), any::<prop::sample::Index>()).prop_map(|(output, index)| {
+                let message = encode(&output_event(&output));
+                message[..index.index(message.len())].to_vec()
+            }),
+            prop_oneof![
+                Just("Content-Length\r\n\r\n"),
+                Just("Content-Length: x\r\n\r\n{}"),
+                Just("Content-Length: 1\r\nContent-Length: 2\r\n\r\n"),
+                Just("Content-Length: 2\r\n\r\n{]"),
+                Just("Content-Type: application/json\r\n\r\n"),
+            ]
+            .prop_map(|header| header.as_bytes().to_vec()),
+        ]
+    }
+
+    /// Arbitrary bytes, biased towards fragments of message framing
+    fn arb_noise() -> impl Strategy<Value = Vec<u8>> {
+        prop::collection::vec(
+            prop_oneof![
+                prop::collection::vec(any::<u8>(), 0..16),
+                prop_oneof![
+                    Just("Content-Length"),
+                    Just(": "),
+                    Just("\r\n"),
+                    Just("\r\n\r\n"),
+                    Just("12"),
+                    Just("{}"),
+                ]
+                .prop_map(|fragment| fragment.as_bytes().to_vec()),
+            ],
+            0..32,
+        )
+        .prop_map(|fragments| fragments.concat())
+    }
+
     macro_rules! execute_test {
         // multiple bodies for single message
         ($($body:expr),+ => $match_expr:pat) => {{
@@ -110,7 +369,9 @@ mod tests {
                 Some(msg) => {
                     assert!(matches!(msg, $match_expr), "Got message {:?}", msg);
                 }
-                None => eyre::bail!("no message found"),
+                None => {
+                    eyre::bail!("no message found");
+                }
             }
         }};
 
@@ -137,7 +398,9 @@ mod tests {
                 Some(msg) => {
                     assert!(matches!(msg, $match_expr));
                 }
-                None => eyre::bail!("no message found"),
+                None => {
+                    eyre::bail!("no message found");
+                }
             }
 
             )+
@@ -172,6 +435,82 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn skips_unknown_headers() {
+        let event = output_event("hello").to_string();
+        let input = format!(
+            "Content-Type: application/json\r\nContent-Length: {len}\r\n\r\n{event}\
+             Content-Length: {len}\r\nX-Vendor:\r\n\r\n{event}",
+            len = event.len()
+        );
+
+        let (outputs, errors) = read_outputs(Cursor::new(input));
+        assert_eq!(outputs, vec!["hello", "hello"]);
+        assert_eq!(errors, 0);
+    }
+
+    #[test]
+    fn recovers_from_malformed_header() {
+        let input = [
+            b"Content-Length\r\n\r\n".to_vec(),
+            encode(&output_event("hello")),
+        ]
+        .concat();
+        let mut reader = HandWrittenReader::new(Cursor::new(input));
+
+        assert!(reader.poll_message().is_err());
+        assert!(matches!(
+            reader.poll_message(),
+            Ok(Some(Message::Event(events::Event::Output(_))))
+        ));
+        assert!(reader.poll_message().unwrap().is_none());
+    }
+
+    #[test]
+    fn returns_read_timeouts_keeping_partial_messages() {
+        let input = encode(&output_event("hello"));
+        let mut reader = HandWrittenReader::new(BufReader::new(Chunked::new(&input, &[10])));
+
+        // the caller regains control each time the read times out
+        let err = reader.poll_message().unwrap_err();
+        assert!(is_would_block(&err), "{err:?}");
+        let err = reader.poll_message().unwrap_err();
+        assert!(is_would_block(&err), "{err:?}");
+        assert!(matches!(
+            reader.poll_message(),
+            Ok(Some(Message::Event(events::Event::Output(_))))
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_input_does_not_panic(
+            input in arb_noise(),
+            split_points in prop::collection::vec(0..512usize, 0..16),
+        ) {
+            read_outputs(BufReader::new(Chunked::new(&input, &split_points)));
+        }
+
+        #[test]
+        fn resynchronises_after_corrupt_input(
+            messages in prop::collection::vec((arb_corruption(), any::<String>()), 1..6),
+            split_points in prop::collection::vec(0..4096usize, 0..16),
+        ) {
+            let mut input = Vec::new();
+            for (corruption, output) in &messages {
+                input.extend(corruption);
+                input.extend(encode(&output_event(output)));
+            }
+            // complete any message torn off before the last one, so that it cannot hold back
+            // the messages it overlaps
+            input.extend(vec![b' '; input.len()]);
+
+            let (outputs, _) = read_outputs(BufReader::new(Chunked::new(&input, &split_points)));
+            let expected: Vec<&String> = messages.iter().map(|(_, output)| output).collect();
+            prop_assert_eq!(outputs.iter().collect::<Vec<_>>(), expected);
+        }
+    }
+
     #[test]
     fn evaluate_error() -> eyre::Result<()> {
         let body = r#"Content-Length: 220"#.to_owned()
@@ -186,4 +525,26 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn error_body() -> eyre::Result<()> {
+        let content = r#"{"seq": 4, "type": "response", "request_seq": 3, "success": false, "command": "stackTrace", "message": "notStopped", "body": {"error": {"id": 2001, "format": "thread {thread} is not stopped", "variables": {"thread": "7"}, "showUser": true}}}"#;
+        let body = format!("Content-Length: {}\r\n\r\n{content}", content.len());
+
+        execute_test!(body => Message::Response(responses::Response {
+            success: false,
+            body: None,
+            error: Some(types::ErrorMessage { id: 2001, show_user: Some(true), .. }),
+            ..
+        }));
+
+        let Message::Response(response) = serde_json::from_str(content)? else {
+            eyre::bail!("not a response");
+        };
+        assert_eq!(
+            response.error.map(|error| error.formatted()).as_deref(),
+            Some("thread 7 is not stopped")
+        );
+        Ok(())
+    }
 }
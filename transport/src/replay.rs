@@ -0,0 +1,448 @@
+//! Replaying a recorded session in place of a live adapter
+//!
+//! A [`Recording`] holds the messages exchanged with an adapter, e.g. as written by
+//! [`crate::tap::JsonlTap`] or `pcaplog --jsonl`. [`Recording::replay`] connects a [`Client`]
+//! to a stand-in adapter, which sends the recorded events with their original spacing and
+//! answers each request with the recorded response to the same command. This allows looking
+//! into bug reports, or working on the UI, without the original program or adapter.
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use crossbeam_channel::RecvTimeoutError;
+use eyre::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    events,
+    reader::hand_written_reader::HandWrittenReader,
+    requests,
+    tap::{Direction, TapSlot, TappedMessage},
+    types::Seq,
+    Client, Reader,
+};
+
+/// How quickly to replay a recording
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    /// Keep the original gaps between messages
+    Original,
+    /// Divide the gaps between messages by the given factor, which must be positive
+    Accelerated(f64),
+    /// Send messages as soon as possible
+    Immediate,
+}
+
+impl Timing {
+    /// How long to wait before sending a message recorded `gap` after the previous one
+    fn delay(&self, gap: Duration) -> Duration {
+        match *self {
+            Timing::Original => gap,
+            Timing::Accelerated(factor) if factor > 0.0 => {
+                Duration::try_from_secs_f64(gap.as_secs_f64() / factor).unwrap_or(Duration::MAX)
+            }
+            Timing::Accelerated(_) | Timing::Immediate => Duration::ZERO,
+        }
+    }
+}
+
+/// Messages exchanged with an adapter during a single session, in the order they were seen
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    messages: Vec<TappedMessage>,
+}
+
+/// Line of a recording, in the format written by [`crate::tap::JsonlTap`]
+#[derive(Deserialize)]
+struct Record {
+    direction: Direction,
+    timestamp: f64,
+    #[serde(default)]
+    stream_id: usize,
+    payload: Value,
+}
+
+impl Recording {
+    pub fn new(messages: Vec<TappedMessage>) -> Self {
+        Self { messages }
+    }
+
+    /// Read the JSON lines written by [`crate::tap::JsonlTap`] or `pcaplog --jsonl`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).wrap_err_with(|| format!("opening {}", path.display()))?;
+        Self::from_jsonl(BufReader::new(file))
+    }
+
+    /// Read a recording from JSON lines, see [`Recording::load`]
+    ///
+    /// Only the messages of the first connection in the input are kept, as captures can
+    /// contain several sessions.
+    pub fn from_jsonl(reader: impl BufRead) -> Result<Self> {
+        let mut messages = Vec::new();
+        let mut stream_id = None;
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.wrap_err("reading recording")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(&line)
+                .wrap_err_with(|| format!("parsing line {}", index + 1))?;
+            if *stream_id.get_or_insert(record.stream_id) != record.stream_id {
+                continue;
+            }
+
+            let timestamp = Duration::try_from_secs_f64(record.timestamp)
+                .wrap_err_with(|| format!("invalid timestamp on line {}", index + 1))?;
+            let body = match record.payload {
+                // malformed messages are recorded as their raw content
+                Value::String(content) => Bytes::from(content),
+                payload => Bytes::from(serde_json::to_vec(&payload).wrap_err("encoding payload")?),
+            };
+            messages.push(TappedMessage {
+                direction: record.direction,
+                timestamp,
+                body,
+            });
+        }
+        Ok(Self::new(messages))
+    }
+
+    pub fn messages(&self) -> &[TappedMessage] {
+        &self.messages
+    }
+
+    /// Recorded `launch` or `attach` request that started the session
+    pub fn start_request(&self) -> Option<requests::StartDebugging> {
+        self.client_requests().find_map(|message| {
+            let request = match request_command(&message)?.1 {
+                "launch" => requests::StartDebuggingRequest::Launch,
+                "attach" => requests::StartDebuggingRequest::Attach,
+                _ => return None,
+            };
+            Some(requests::StartDebugging {
+                configuration: message.get("arguments").cloned().unwrap_or(json!({})),
+                request,
+            })
+        })
+    }
+
+    /// Start a stand-in adapter sending the adapter's side of the recording, returning a client
+    /// talking to it
+    ///
+    /// Events and reverse requests are sent with the gaps between them given by `timing`.
+    /// Each request from the client is matched with the next recorded request with the same
+    /// command, and recorded responses are only sent once their request has been matched, so
+    /// the replay advances as the client repeats the recorded requests. Recorded requests the
+    /// client passes over are dropped along with their responses, and requests that are not
+    /// in the recording fail straight away. See [`Client::with_reverse_requests`] for how
+    /// `reverse_requests` is used.
+    pub fn replay(
+        self,
+        timing: Timing,
+        responses: crossbeam_channel::Sender<events::Event>,
+        reverse_requests: Option<crossbeam_channel::Sender<requests::Request>>,
+    ) -> Result<Client> {
+        let (client_input, adapter_output) = io::pipe().wrap_err("creating pipe to client")?;
+        let (adapter_input, client_output) = io::pipe().wrap_err("creating pipe from client")?;
+
+        let replayer = Replayer::new(&self, timing, adapter_output, read_messages(adapter_input));
+        thread::spawn(move || {
+            if let Err(e) = replayer.run(self.messages) {
+                tracing::debug!(error = %e, "stopped replaying recording");
+            }
+        });
+        Client::from_streams(client_input, client_output, responses, reverse_requests)
+    }
+
+    /// Requests sent by the client, parsed as JSON
+    fn client_requests(&self) -> impl Iterator<Item = Value> + '_ {
+        self.messages
+            .iter()
+            .filter(|m| m.direction == Direction::ClientToServer)
+            .filter_map(|m| serde_json::from_slice(&m.body).ok())
+            .filter(|message| request_command(message).is_some())
+    }
+}
+
+/// Sequence number and command of `message`, if it is a request
+fn request_command(message: &Value) -> Option<(Seq, &str)> {
+    if message.get("type")?.as_str()? != "request" {
+        return None;
+    }
+    Some((
+        message.get("seq")?.as_i64()?,
+        message.get("command")?.as_str()?,
+    ))
+}
+
+/// Content of each message written by the client
+///
+/// Messages are not parsed as [`requests::Request`], as the arguments of e.g. `launch` may
+/// only be understood by the recorded adapter.
+fn read_messages(input: impl Read + Send + 'static) -> crossbeam_channel::Receiver<TappedMessage> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let tap = TapSlot::default();
+    tap.set(Some(Arc::new(tx)));
+    thread::spawn(move || {
+        let mut reader = HandWrittenReader::new(BufReader::new(input)).with_tap(tap);
+        loop {
+            match reader.poll_message() {
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) if e.downcast_ref::<io::Error>().is_some() => {
+                    tracing::debug!(error = %e, "reading from client");
+                    break;
+                }
+                // the content has already been passed to the tap
+                Err(_) => {}
+            }
+        }
+    });
+    rx
+}
+
+/// Stand-in adapter, see [`Recording::replay`]
+struct Replayer<W> {
+    output: W,
+    timing: Timing,
+    /// Messages written by the client
+    incoming: crossbeam_channel::Receiver<TappedMessage>,
+    /// Requests made in the recorded session, in order
+    requests: Vec<RecordedRequest>,
+    /// Index into `requests` by recorded sequence number
+    by_seq: HashMap<Seq, usize>,
+    /// Index of the first recorded request the client has not made or passed over
+    next: usize,
+    /// Sequence number of the last message sent
+    seq: Seq,
+}
+
+struct RecordedRequest {
+    command: String,
+    claim: Claim,
+}
+
+/// Whether the client has made a recorded request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Claim {
+    Unclaimed,
+    /// Made by the client with the given sequence number
+    Claimed(Seq),
+    /// Passed over by the client making a later request, so its response is not sent
+    Skipped,
+}
+
+impl<W: Write> Replayer<W> {
+    fn new(
+        recording: &Recording,
+        timing: Timing,
+        output: W,
+        incoming: crossbeam_channel::Receiver<TappedMessage>,
+    ) -> Self {
+        let mut requests = Vec::new();
+        let mut by_seq = HashMap::new();
+        for message in recording.client_requests() {
+            if let Some((seq, command)) = request_command(&message) {
+                by_seq.insert(seq, requests.len());
+                requests.push(RecordedRequest {
+                    command: command.to_string(),
+                    claim: Claim::Unclaimed,
+                });
+            }
+        }
+        Self {
+            output,
+            timing,
+            incoming,
+            requests,
+            by_seq,
+            next: 0,
+            seq: 0,
+        }
+    }
+
+    /// Send the adapter's side of `messages`, returning once the client disconnects
+    fn run(mut self, messages: Vec<TappedMessage>) -> Result<()> {
+        let mut previous = None;
+        for message in messages {
+            if let Some(previous) = previous {
+                self.wait(
+                    self.timing
+                        .delay(message.timestamp.saturating_sub(previous)),
+                )?;
+            }
+            previous = Some(message.timestamp);
+            if message.direction == Direction::ServerToClient {
+                self.send_recorded(&message.body)?;
+            }
+        }
+
+        tracing::debug!("finished replaying recording");
+        // keep answering requests, e.g. the `disconnect` ending the session
+        loop {
+            let message = self.incoming.recv().wrap_err("client disconnected")?;
+            self.on_message(&message.body)?;
+        }
+    }
+
+    /// Handle messages from the client for `delay`
+    fn wait(&mut self, delay: Duration) -> Result<()> {
+        let deadline = Instant::now().checked_add(delay);
+        loop {
+            let message = match deadline {
+                Some(deadline) => self.incoming.recv_deadline(deadline),
+                None => self.incoming.recv().map_err(RecvTimeoutError::from),
+            };
+            match message {
+                Ok(message) => self.on_message(&message.body)?,
+                Err(RecvTimeoutError::Timeout) => return Ok(()),
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(eyre::eyre!("client disconnected"))
+                }
+            }
+        }
+    }
+
+    /// Match requests with the next recorded request with the same command, and reject the rest
+    fn on_message(&mut self, body: &[u8]) -> Result<()> {
+        let Ok(message) = serde_json::from_slice::<Value>(body) else {
+            tracing::debug!("ignoring malformed message from client");
+            return Ok(());
+        };
+        // responses to reverse requests need no answer
+        let Some((seq, command)) = request_command(&message) else {
+            return Ok(());
+        };
+
+        let matched = self.requests[self.next..]
+            .iter()
+            .position(|r| r.command == command)
+            .map(|offset| self.next + offset);
+        match matched {
+            Some(index) => {
+                for skipped in &mut self.requests[self.next..index] {
+                    tracing::debug!(command = %skipped.command, "client skipped recorded request");
+                    skipped.claim = Claim::Skipped;
+                }
+                self.requests[index].claim = Claim::Claimed(seq);
+                self.next = index + 1;
+                Ok(())
+            }
+            None => {
+                tracing::debug!(command, "request is not in the recording");
+                self.send(json!({
+                    "type": "response",
+                    "request_seq": seq,
+                    "command": command,
+                    "success": false,
+                    "message": format!("{command} request is not in the recording"),
+                }))
+            }
+        }
+    }
+
+    /// Send a message from the recording, waiting for the client to make the request first if
+    /// it is a response
+    fn send_recorded(&mut self, body: &[u8]) -> Result<()> {
+        let Ok(mut message) = serde_json::from_slice::<Value>(body) else {
+            // send malformed messages as they were, to reproduce how the client handles them
+            return self.write(body);
+        };
+        if message.get("type").and_then(Value::as_str) != Some("response") {
+            return self.send(message);
+        }
+
+        let Some(&index) = message
+            .get("request_seq")
+            .and_then(Value::as_i64)
+            .and_then(|seq| self.by_seq.get(&seq))
+        else {
+            tracing::warn!("skipping response to a request missing from the recording");
+            return Ok(());
+        };
+        let request_seq = loop {
+            match self.requests[index].claim {
+                Claim::Claimed(seq) => break seq,
+                Claim::Skipped => return Ok(()),
+                Claim::Unclaimed => {
+                    let incoming = self.incoming.recv().wrap_err("client disconnected")?;
+                    self.on_message(&incoming.body)?;
+                }
+            }
+        };
+        message["request_seq"] = request_seq.into();
+        self.send(message)
+    }
+
+    /// Send `message` with the next sequence number
+    fn send(&mut self, mut message: Value) -> Result<()> {
+        if let Some(fields) = message.as_object_mut() {
+            self.seq += 1;
+            fields.insert("seq".to_string(), self.seq.into());
+        }
+        let body = serde_json::to_vec(&message).wrap_err("encoding message")?;
+        self.write(&body)
+    }
+
+    fn write(&mut self, body: &[u8]) -> Result<()> {
+        write!(self.output, "Content-Length: {}\r\n\r\n", body.len())
+            .wrap_err("writing message header")?;
+        self.output
+            .write_all(body)
+            .wrap_err("writing message content")?;
+        self.output.flush().wrap_err("flushing output")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Recording, Timing};
+    use crate::tap::Direction;
+
+    #[test]
+    fn timing() {
+        let gap = Duration::from_secs(2);
+        assert_eq!(Timing::Original.delay(gap), gap);
+        assert_eq!(
+            Timing::Accelerated(4.0).delay(gap),
+            Duration::from_millis(500)
+        );
+        assert_eq!(Timing::Accelerated(0.0).delay(gap), Duration::ZERO);
+        assert_eq!(Timing::Immediate.delay(gap), Duration::ZERO);
+    }
+
+    #[test]
+    fn load_first_session() {
+        let input = [
+            r#"{"direction":"client_to_server","timestamp":1.0,"stream_id":0,"payload":{"seq":1,"type":"request","command":"attach","arguments":{"port":5678}}}"#,
+            r#"{"direction":"server_to_client","timestamp":1.5,"stream_id":1,"payload":{"seq":1,"type":"event","event":"initialized"}}"#,
+            r#"{"direction":"server_to_client","timestamp":2.0,"stream_id":0,"payload":"{bad"}"#,
+        ]
+        .join("\n");
+        let recording = Recording::from_jsonl(input.as_bytes()).unwrap();
+
+        let messages = recording.messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].direction, Direction::ClientToServer);
+        assert_eq!(messages[1].timestamp, Duration::from_secs(2));
+        assert_eq!(&messages[1].body[..], b"{bad");
+
+        let start = recording.start_request().unwrap();
+        assert!(matches!(
+            start.request,
+            crate::requests::StartDebuggingRequest::Attach
+        ));
+        assert_eq!(start.configuration["port"], 5678);
+    }
+}
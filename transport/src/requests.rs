@@ -11,7 +11,8 @@ use crate::types::{
 #[serde(rename_all = "camelCase")]
 pub struct Request {
     pub seq: Seq,
-    #[serde(skip_deserializing)]
+    // parsed requests are wrapped in `Message`, which writes the type itself
+    #[serde(skip_deserializing, skip_serializing_if = "String::is_empty")]
     pub r#type: String,
     #[serde(flatten)]
     pub body: RequestBody,
@@ -34,30 +35,74 @@ pub enum RequestBody {
     Variables(Variables),
     BreakpointLocations(BreakpointLocations),
     LoadedSources,
+    Modules(Modules),
+    ReadMemory(ReadMemory),
+    WriteMemory(WriteMemory),
     Terminate(Terminate),
     Disconnect(Disconnect),
     Next(Next),
     StepIn(StepIn),
     StepOut(StepOut),
+    Pause(Pause),
     Evaluate(Evaluate),
+    GotoTargets(GotoTargets),
+    Goto(Goto),
+    RestartFrame(RestartFrame),
+    Restart(Restart),
+    Completions(Completions),
+    SetExpression(SetExpression),
+    ExceptionInfo(ExceptionInfo),
+    /// Reverse request from the adapter asking the client to start a new session
+    StartDebugging(StartDebugging),
+    /// `attach` request with arguments supplied by the adapter, see [`StartDebugging`]
+    #[serde(rename = "attach", skip_deserializing)]
+    AttachConfiguration(serde_json::Value),
+    /// `launch` request with arguments supplied by the adapter, see [`StartDebugging`]
+    #[serde(rename = "launch", skip_deserializing)]
+    LaunchConfiguration(serde_json::Value),
+}
+
+impl RequestBody {
+    /// Name of the request, e.g. `"stackTrace"`
+    pub fn command(&self) -> String {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v.get("command")?.as_str().map(ToString::to_string))
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Next {
     pub thread_id: ThreadId,
+    /// Only resume `thread_id`, leaving other threads suspended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub single_thread: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StepIn {
     pub thread_id: ThreadId,
+    /// Only resume `thread_id`, leaving other threads suspended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub single_thread: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StepOut {
     pub thread_id: ThreadId,
+    /// Only resume `thread_id`, leaving other threads suspended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub single_thread: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Pause {
+    pub thread_id: ThreadId,
 }
 
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
@@ -68,6 +113,16 @@ pub struct Evaluate {
     pub context: Option<String>,
 }
 
+/// Assign `value` to an assignable expression, e.g. `obj.attr`, only supported if the adapter
+/// has the `supportsSetExpression` capability
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SetExpression {
+    pub expression: String,
+    pub value: String,
+    pub frame_id: Option<StackFrameId>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StackTrace {
@@ -99,13 +154,16 @@ pub struct Initialize {
     pub supports_variable_paging: bool,
     pub supports_progress_reporting: bool,
     pub supports_memory_event: bool,
+    pub supports_invalidated_event: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Continue {
     pub thread_id: ThreadId,
-    pub single_thread: bool,
+    /// Only resume `thread_id`, leaving other threads suspended
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub single_thread: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -193,6 +251,9 @@ pub struct DebugpyLaunchArguments {
     pub debug_options: Vec<String>,
     pub stop_on_entry: bool,
     pub is_output_redirected: bool,
+    /// Whether to debug child processes, defaults to true in debugpy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub_process: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -210,16 +271,112 @@ pub struct Launch {
     pub launch_arguments: Option<LaunchArguments>,
 }
 
+/// Find the locations that execution can jump to with [`Goto`] for a source line
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GotoTargets {
+    pub source: Source,
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+/// Set the location where the debugee continues executing, without running intermediate code
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Goto {
+    pub thread_id: ThreadId,
+    /// Target id from a [`GotoTargets`] response
+    pub target_id: i64,
+}
+
+/// Restart the debugee, only supported if the adapter has the `supportsRestartRequest`
+/// capability
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Restart {
+    /// New `launch` or `attach` arguments, if they should change
+    pub arguments: Option<serde_json::Value>,
+}
+
+/// Restart execution of a stack frame, only supported if the adapter has the
+/// `supportsRestartFrame` capability
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
-pub struct Scopes {
+pub struct RestartFrame {
     pub frame_id: StackFrameId,
 }
 
+/// Fetch the modules loaded by the debugee, only supported if the adapter has the
+/// `supportsModulesRequest` capability
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Modules {
+    pub start_module: Option<usize>,
+    /// Maximum number of modules to return, or all if not given
+    pub module_count: Option<usize>,
+}
+
+/// Read bytes of the debugee's memory, only supported if the adapter has the
+/// `supportsReadMemoryRequest` capability
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadMemory {
+    /// Opaque reference to a memory location, e.g. from a variable or stack frame
+    pub memory_reference: String,
+    /// Offset in bytes from `memory_reference`, which may be negative
+    pub offset: Option<i64>,
+    pub count: usize,
+}
+
+/// Write bytes to the debugee's memory, only supported if the adapter has the
+/// `supportsWriteMemoryRequest` capability
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteMemory {
+    pub memory_reference: String,
+    pub offset: Option<i64>,
+    /// Whether to write as many bytes as possible if some cannot be written
+    pub allow_partial: Option<bool>,
+    /// Bytes to write, base64 encoded
+    pub data: String,
+}
+
+/// Fetch details of the exception that a thread stopped on, only supported if the adapter has
+/// the `supportsExceptionInfoRequest` capability
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExceptionInfo {
+    pub thread_id: ThreadId,
+}
+
+/// Ask for possible completions of partially typed REPL input, only supported if the adapter
+/// has the `supportsCompletionsRequest` capability
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Completions {
+    /// Frame whose scope is used to look up names, or the global scope if not given
+    pub frame_id: Option<StackFrameId>,
+    /// Input typed so far, possibly spanning multiple lines
+    pub text: String,
+    /// Position of the cursor within `text`, 1-indexed in UTF-16 code units
+    pub column: usize,
+    pub line: Option<usize>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
+pub struct Scopes {
+    pub frame_id: StackFrameId,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Variables {
     pub variables_reference: VariablesReference,
+    /// Index of the first child to return, for paging through large collections
+    pub start: Option<usize>,
+    /// Maximum number of children to return, or all if not given
+    pub count: Option<usize>,
 }
 
 #[derive(Default, Debug, Deserialize, Serialize, Clone)]
@@ -238,10 +395,26 @@ pub struct Terminate {
     pub restart: Option<bool>,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum StartDebuggingRequest {
+    Launch,
+    Attach,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StartDebugging {
+    /// Arguments to pass to the `launch` or `attach` request of the new session
+    pub configuration: serde_json::Value,
+    pub request: StartDebuggingRequest,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Disconnect {
-    pub terminate_debugee: bool,
+    /// Whether the adapter should also terminate the debugee, e.g. when it was attached to
+    pub terminate_debuggee: bool,
 }
 
 #[cfg(test)]
@@ -260,6 +433,7 @@ mod tests {
                 debug_options: vec!["DebugStdLib".to_string(), "ShowReturnValue".to_string()],
                 stop_on_entry: false,
                 is_output_redirected: false,
+                sub_process: None,
             })),
         });
 
@@ -281,6 +455,76 @@ mod tests {
         assert!(just_my_code);
     }
 
+    #[test]
+    fn start_debugging() {
+        let request: Request = serde_json::from_value(serde_json::json!({
+            "seq": 3,
+            "type": "request",
+            "command": "startDebugging",
+            "arguments": {
+                "request": "attach",
+                "configuration": {"subProcessId": 1234},
+            },
+        }))
+        .unwrap();
+        let RequestBody::StartDebugging(arguments) = request.body else {
+            panic!("unexpected request {request:?}");
+        };
+        assert!(matches!(arguments.request, StartDebuggingRequest::Attach));
+
+        let attach = RequestBody::AttachConfiguration(arguments.configuration);
+        assert_eq!(attach.command(), "attach");
+        let v = serde_json::to_value(&attach).unwrap();
+        assert_eq!(v["arguments"]["subProcessId"], 1234);
+    }
+
+    #[test]
+    fn completions_arguments() {
+        let body = RequestBody::Completions(Completions {
+            frame_id: Some(7),
+            text: "a.b".to_string(),
+            column: 4,
+            line: None,
+        });
+        let v = serde_json::to_value(&body).unwrap();
+        assert_eq!(v["command"], "completions");
+        assert_eq!(v["arguments"]["frameId"], 7);
+        assert_eq!(v["arguments"]["column"], 4);
+    }
+
+    #[test]
+    fn set_breakpoints_arguments() {
+        let body = RequestBody::SetBreakpoints(SetBreakpoints {
+            source: Source {
+                path: Some(PathBuf::from("/project/main.py")),
+                ..Default::default()
+            },
+            breakpoints: Some(vec![SourceBreakpoint {
+                line: 3,
+                condition: Some("a > 1".to_string()),
+                hit_condition: Some(">= 5".to_string()),
+                ..Default::default()
+            }]),
+            lines: Some(vec![3]),
+            source_modified: None,
+        });
+        let v = serde_json::to_value(&body).unwrap();
+        assert_eq!(v["command"], "setBreakpoints");
+        let breakpoint = &v["arguments"]["breakpoints"][0];
+        assert_eq!(breakpoint["line"], 3);
+        assert_eq!(breakpoint["condition"], "a > 1");
+        assert_eq!(breakpoint["hitCondition"], ">= 5");
+    }
+
+    #[test]
+    fn disconnect_arguments() {
+        let body = RequestBody::Disconnect(Disconnect {
+            terminate_debuggee: true,
+        });
+        let v = serde_json::to_value(&body).unwrap();
+        assert_eq!(v["arguments"]["terminateDebuggee"], true);
+    }
+
     #[test]
     fn path_mapping_resolving() {
         let root = std::env::current_dir().unwrap();
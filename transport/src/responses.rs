@@ -2,9 +2,9 @@
 use crate::types::{
     self, Scope, StackFrame, Thread, Variable, VariablePresentationHint, VariablesReference,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Response {
     #[serde(rename = "request_seq")]
@@ -13,6 +13,53 @@ pub struct Response {
     pub message: Option<String>,
     #[serde(flatten)]
     pub body: Option<ResponseBody>,
+    /// Error from the body of a failed response, if the adapter gave one
+    #[serde(skip_serializing)]
+    pub error: Option<types::ErrorMessage>,
+}
+
+impl<'de> Deserialize<'de> for Response {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // the body of a failed response holds an error rather than the body for the command
+        #[derive(Deserialize)]
+        struct Raw {
+            request_seq: i64,
+            success: bool,
+            message: Option<String>,
+            command: Option<String>,
+            body: Option<serde_json::Value>,
+        }
+
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            error: Option<types::ErrorMessage>,
+        }
+
+        let raw = Raw::deserialize(deserializer)?;
+        let (body, error) = if raw.success {
+            let mut tagged = serde_json::json!({ "command": raw.command });
+            if let Some(body) = raw.body {
+                tagged["body"] = body;
+            }
+            (serde_json::from_value(tagged).ok(), None)
+        } else {
+            let error = raw
+                .body
+                .and_then(|body| serde_json::from_value::<ErrorBody>(body).ok())
+                .and_then(|body| body.error);
+            (None, error)
+        };
+        Ok(Response {
+            request_seq: raw.request_seq,
+            success: raw.success,
+            message: raw.message,
+            body,
+            error,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,9 +79,26 @@ pub enum ResponseBody {
     Terminate,
     Disconnect,
     Evaluate(EvaluateResponse),
+    GotoTargets(GotoTargetsResponse),
+    Goto,
+    RestartFrame,
+    Restart,
+    Completions(CompletionsResponse),
+    SetExpression(SetExpressionResponse),
+    ExceptionInfo(ExceptionInfoResponse),
+    Modules(ModulesResponse),
+    LoadedSources(LoadedSourcesResponse),
+    ReadMemory(ReadMemoryResponse),
+    WriteMemory(Option<WriteMemoryResponse>),
+    /// Successful response to a command that is not modelled above, e.g. `next`, or whose body
+    /// could not be parsed
+    ///
+    /// Only returned by [`crate::Client::send`], so it is never (de)serialized.
+    #[serde(skip)]
+    Other,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Capabilities {
     pub supports_configuration_done_request: Option<bool>,
@@ -42,7 +106,7 @@ pub struct Capabilities {
     pub supports_conditional_breakpoints: Option<bool>,
     pub supports_hit_conditional_breakpoints: Option<bool>,
     pub supports_evaluate_for_hovers: Option<bool>,
-    // pub exception_breakpoint_filters: Option<Vec<ExceptionBreakpointsFilter>>,
+    pub exception_breakpoint_filters: Option<Vec<types::ExceptionBreakpointsFilter>>,
     pub supports_step_back: Option<bool>,
     pub supports_set_variable: Option<bool>,
     pub supports_restart_frame: Option<bool>,
@@ -126,6 +190,72 @@ pub struct VariablesResponse {
     pub variables: Vec<Variable>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GotoTargetsResponse {
+    pub targets: Vec<types::GotoTarget>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadMemoryResponse {
+    /// Address of the first byte read, which may differ from the requested location
+    pub address: String,
+    /// Number of bytes after `data` that could not be read
+    pub unreadable_bytes: Option<usize>,
+    /// Bytes read, base64 encoded
+    pub data: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteMemoryResponse {
+    pub offset: Option<i64>,
+    pub bytes_written: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModulesResponse {
+    pub modules: Vec<types::Module>,
+    pub total_modules: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedSourcesResponse {
+    pub sources: Vec<types::Source>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExceptionInfoResponse {
+    /// Identifier of the exception, e.g. its type name
+    pub exception_id: String,
+    pub description: Option<String>,
+    /// When the adapter breaks on this kind of exception, e.g. `always` or `unhandled`
+    pub break_mode: String,
+    pub details: Option<types::ExceptionDetails>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetExpressionResponse {
+    /// New value of the expression
+    pub value: String,
+    pub r#type: Option<String>,
+    pub presentation_hint: Option<VariablePresentationHint>,
+    pub variables_reference: Option<VariablesReference>,
+    pub named_variables: Option<usize>,
+    pub indexed_variables: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionsResponse {
+    pub targets: Vec<types::CompletionItem>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EvaluateResponse {
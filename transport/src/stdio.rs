@@ -0,0 +1,64 @@
+//! Debug adapters that speak DAP over their stdin and stdout
+//!
+//! Many adapters, e.g. `lldb-dap`, `codelldb` and `js-debug`, are started by the client as a
+//! subprocess rather than listening on a TCP port.
+use std::process::{Child, ChildStderr, Command, Stdio};
+
+use eyre::{Context, Result};
+
+use crate::{events, requests, Client};
+
+/// Adapter process whose stdin and stdout carry the DAP messages of a [`Client`]
+///
+/// The process is killed when the transport is dropped.
+pub struct StdioTransport {
+    child: Child,
+}
+
+impl StdioTransport {
+    /// Start the adapter, returning the transport along with a client talking to it
+    ///
+    /// The stdin, stdout and stderr of `command` are replaced with pipes. See
+    /// [`Client::with_reverse_requests`] for how `reverse_requests` is used.
+    pub fn spawn(
+        mut command: Command,
+        responses: crossbeam_channel::Sender<events::Event>,
+        reverse_requests: Option<crossbeam_channel::Sender<requests::Request>>,
+    ) -> Result<(Self, Client)> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("spawning adapter {:?}", command.get_program()))?;
+        tracing::debug!(pid = child.id(), "spawned stdio adapter");
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let transport = Self { child };
+        let client = Client::from_streams(stdout, stdin, responses, reverse_requests)?;
+        Ok((transport, client))
+    }
+
+    /// Take the stderr of the adapter process, e.g. to show its logs
+    ///
+    /// Only the first call returns the stream.
+    pub fn take_stderr(&mut self) -> Option<ChildStderr> {
+        self.child.stderr.take()
+    }
+
+    /// Process id of the adapter
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+}
+
+impl Drop for StdioTransport {
+    fn drop(&mut self) {
+        tracing::debug!(pid = self.child.id(), "stopping stdio adapter");
+        if let Err(e) = self.child.kill() {
+            tracing::warn!(error = %e, "killing adapter process");
+        }
+        let _ = self.child.wait();
+    }
+}
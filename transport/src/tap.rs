@@ -0,0 +1,279 @@
+//! Mirroring the raw messages exchanged with the adapter, e.g. for a protocol log
+//!
+//! Register a [`MessageTap`] with [`crate::Client::set_tap`] to observe the JSON content of
+//! every message as it is sent or received, including messages the client cannot parse.
+//! Observers are called on the thread sending or receiving the message, so slow observers
+//! should hand messages off, e.g. by using a [`crossbeam_channel::Sender`] as the tap.
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+
+use crate::types::Seq;
+
+/// Which side of the connection sent a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Sent to the adapter, e.g. requests
+    ClientToServer,
+    /// Sent by the adapter, e.g. responses and events
+    ServerToClient,
+}
+
+/// Raw message seen by a [`MessageTap`]
+#[derive(Debug, Clone)]
+pub struct TappedMessage {
+    pub direction: Direction,
+    /// Time the message was sent or received, since the unix epoch
+    pub timestamp: Duration,
+    /// JSON content of the message, without the header
+    pub body: Bytes,
+}
+
+/// Observer of the messages exchanged with the adapter
+pub trait MessageTap: Send + Sync {
+    fn on_message(&self, message: &TappedMessage);
+}
+
+/// Forward messages to a receiver on another thread or task
+impl MessageTap for crossbeam_channel::Sender<TappedMessage> {
+    fn on_message(&self, message: &TappedMessage) {
+        // the receiver going away only stops the mirroring
+        let _ = self.send(message.clone());
+    }
+}
+
+/// Write each message as a line of JSON, in the same format as `pcaplog --jsonl`
+pub struct JsonlTap<W> {
+    writer: Mutex<W>,
+}
+
+/// Same shape as the records written by `pcaplog --jsonl`
+#[derive(Serialize)]
+struct Record {
+    direction: Direction,
+    timestamp: f64,
+    stream_id: usize,
+    payload: serde_json::Value,
+}
+
+impl JsonlTap<BufWriter<File>> {
+    /// Write messages to a new file at `path`, replacing any existing file
+    pub fn create(path: impl AsRef<Path>) -> eyre::Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).wrap_err_with(|| format!("creating {}", path.display()))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> JsonlTap<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    fn write(&self, message: &TappedMessage) -> eyre::Result<()> {
+        let payload = serde_json::from_slice(&message.body).unwrap_or_else(|_| {
+            // keep malformed messages, which are often the interesting ones
+            serde_json::Value::String(String::from_utf8_lossy(&message.body).into_owned())
+        });
+        let record = Record {
+            direction: message.direction,
+            timestamp: message.timestamp.as_secs_f64(),
+            stream_id: 0,
+            payload,
+        };
+
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, &record).wrap_err("serializing message")?;
+        writeln!(writer).wrap_err("writing message separator")?;
+        // flush every message so the log is complete if we crash
+        writer.flush().wrap_err("flushing message")
+    }
+}
+
+impl<W: Write + Send> MessageTap for JsonlTap<W> {
+    fn on_message(&self, message: &TappedMessage) {
+        if let Err(e) = self.write(message) {
+            tracing::warn!(error = %e, "recording message");
+        }
+    }
+}
+
+/// Keep the most recent messages in memory, e.g. to show in a protocol log panel
+#[derive(Debug)]
+pub struct MessageLog {
+    capacity: usize,
+    messages: Mutex<VecDeque<TappedMessage>>,
+}
+
+impl MessageLog {
+    /// Keep at most `capacity` messages, dropping the oldest ones first
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            messages: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// The recorded messages, oldest first
+    pub fn messages(&self) -> Vec<TappedMessage> {
+        self.messages.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.messages.lock().unwrap().clear();
+    }
+}
+
+impl MessageTap for MessageLog {
+    fn on_message(&self, message: &TappedMessage) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() == self.capacity {
+            messages.pop_front();
+        }
+        messages.push_back(message.clone());
+    }
+}
+
+/// Tap shared by the halves of a client, which may be replaced at any time
+#[derive(Clone, Default)]
+pub(crate) struct TapSlot {
+    tap: Arc<Mutex<Option<Arc<dyn MessageTap>>>>,
+    /// Requests made by the client itself, e.g. liveness probes, whose responses are not
+    /// passed to the tap
+    hidden: Arc<Mutex<HashSet<Seq>>>,
+}
+
+impl TapSlot {
+    pub(crate) fn set(&self, tap: Option<Arc<dyn MessageTap>>) {
+        *self.tap.lock().unwrap() = tap;
+    }
+
+    /// Keep the response to request `seq` from the tap
+    pub(crate) fn hide_response(&self, seq: Seq) {
+        self.hidden.lock().unwrap().insert(seq);
+    }
+
+    /// Pass `body` received from the adapter to the tap, unless it is the response to a
+    /// hidden request
+    pub(crate) fn record_received(&self, body: &[u8]) {
+        #[derive(Deserialize)]
+        struct Reply {
+            r#type: String,
+            request_seq: Option<Seq>,
+        }
+
+        {
+            let mut hidden = self.hidden.lock().unwrap();
+            if !hidden.is_empty() {
+                if let Ok(Reply {
+                    r#type,
+                    request_seq: Some(request_seq),
+                }) = serde_json::from_slice(body)
+                {
+                    if r#type == "response" && hidden.remove(&request_seq) {
+                        return;
+                    }
+                }
+            }
+        }
+        self.record(Direction::ServerToClient, body);
+    }
+
+    /// Pass `body` to the tap, if there is one
+    pub(crate) fn record(&self, direction: Direction, body: &[u8]) {
+        let Some(tap) = self.tap.lock().unwrap().clone() else {
+            return;
+        };
+        tap.on_message(&TappedMessage {
+            direction,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            body: Bytes::copy_from_slice(body),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use bytes::Bytes;
+
+    use super::{Direction, JsonlTap, MessageLog, MessageTap, TapSlot, TappedMessage};
+
+    #[test]
+    fn message_log_keeps_most_recent() {
+        let log = Arc::new(MessageLog::new(2));
+        let slot = TapSlot::default();
+        slot.record(Direction::ClientToServer, b"{}");
+        assert!(log.messages().is_empty());
+
+        slot.set(Some(log.clone()));
+        for body in [&b"1"[..], b"2", b"3"] {
+            slot.record(Direction::ServerToClient, body);
+        }
+        let bodies: Vec<_> = log.messages().into_iter().map(|m| m.body).collect();
+        assert_eq!(bodies, vec![&b"2"[..], &b"3"[..]]);
+    }
+
+    #[test]
+    fn hidden_responses_are_not_recorded() {
+        let log = Arc::new(MessageLog::new(8));
+        let slot = TapSlot::default();
+        slot.set(Some(log.clone()));
+        slot.hide_response(2);
+
+        let response = |request_seq| {
+            format!(
+                r#"{{"seq":1,"type":"response","request_seq":{request_seq},"command":"threads","success":true}}"#
+            )
+        };
+        slot.record_received(response(2).as_bytes());
+        slot.record_received(response(3).as_bytes());
+        // only the first response to a hidden request is skipped
+        slot.record_received(response(2).as_bytes());
+        let bodies: Vec<_> = log.messages().into_iter().map(|m| m.body).collect();
+        assert_eq!(bodies, vec![response(3).as_bytes(), response(2).as_bytes()]);
+    }
+
+    #[test]
+    fn jsonl_records() {
+        let tap = JsonlTap::new(Vec::new());
+        for (direction, body) in [
+            (Direction::ClientToServer, &br#"{"seq":1}"#[..]),
+            (Direction::ServerToClient, b"{bad"),
+        ] {
+            tap.on_message(&TappedMessage {
+                direction,
+                timestamp: Duration::from_millis(1500),
+                body: Bytes::from_static(body),
+            });
+        }
+
+        let output = String::from_utf8(tap.writer.into_inner().unwrap()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"direction":"client_to_server","timestamp":1.5,"stream_id":0,"payload":{"seq":1}}"#,
+                r#"{"direction":"server_to_client","timestamp":1.5,"stream_id":0,"payload":"{bad"}"#,
+            ]
+        );
+    }
+}
@@ -1,5 +1,5 @@
 //! General types used common to [`crate::requests`], [`crate::responses`] or [`crate::events`].
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
@@ -121,6 +121,78 @@ pub struct SourceBreakpoint {
     pub log_message: Option<String>,
 }
 
+/// Location that execution can jump to, see [`crate::requests::Goto`]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GotoTarget {
+    /// Unique identifier for a goto target. This is used in the `goto` request.
+    pub id: i64,
+    /// The name of the goto target (shown in the UI).
+    pub label: String,
+    /// The line of the goto target.
+    pub line: usize,
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    /// A memory reference for the instruction pointer value represented by this target.
+    pub instruction_pointer_reference: Option<String>,
+}
+
+/// Possible completion of REPL input, see [`crate::requests::Completions`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionItem {
+    /// The text shown in the completion list, and inserted if `text` is not given
+    pub label: String,
+    pub text: Option<String>,
+    pub sort_text: Option<String>,
+    /// Extra information shown alongside the label, e.g. a type signature
+    pub detail: Option<String>,
+    /// Kind of item, e.g. `variable`, `function` or `module`
+    pub r#type: Option<String>,
+    /// Position in the request `text` where the completion is inserted, 1-indexed. If not
+    /// given it is inserted at the request `column`
+    pub start: Option<usize>,
+    /// Number of characters the completion replaces
+    pub length: Option<usize>,
+    pub selection_start: Option<usize>,
+    pub selection_length: Option<usize>,
+}
+
+/// Details of an exception, see [`crate::requests::ExceptionInfo`]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExceptionDetails {
+    pub message: Option<String>,
+    /// Short type name of the exception, e.g. `ValueError`
+    pub type_name: Option<String>,
+    pub full_type_name: Option<String>,
+    /// Expression that evaluates to the exception object in the stopped frame
+    pub evaluate_name: Option<String>,
+    /// Stack trace at the time the exception was thrown, formatted by the adapter
+    pub stack_trace: Option<String>,
+    pub inner_exception: Option<Vec<ExceptionDetails>>,
+}
+
+/// Kind of exception that the debug adapter can break on, e.g. raised or uncaught exceptions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExceptionBreakpointsFilter {
+    /// The internal ID of the filter option. This value is passed to the
+    /// `setExceptionBreakpoints` request.
+    pub filter: String,
+    /// The name of the filter option. This is shown in the UI.
+    pub label: String,
+    /// A help text providing additional information about the exception filter.
+    pub description: Option<String>,
+    /// Initial value of the filter option. If not specified a value false is assumed.
+    pub default: Option<bool>,
+    /// Controls whether a condition can be specified for this filter option.
+    pub supports_condition: Option<bool>,
+    /// A help text providing information about the condition.
+    pub condition_description: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct StackFrame {
@@ -136,15 +208,29 @@ pub struct StackFrame {
     pub presentation_hint: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// How a frontend should present a variable
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct VariablePresentationHint {
+    /// Kind of variable, e.g. `property`, `method`, `class`, `data` or `virtual`
     pub kind: Option<String>,
-    pub attributes: Option<String>,
+    /// Attributes of the variable, e.g. `readOnly`, `constant` or `hasSideEffects`
+    pub attributes: Option<Vec<String>>,
+    /// Visibility of the variable, e.g. `public`, `private` or `internal`
     pub visibility: Option<String>,
+    /// If true, the value is only fetched when the user asks for it, by requesting the single
+    /// child of the variable
     pub lazy: Option<bool>,
 }
 
+impl VariablePresentationHint {
+    pub fn has_attribute(&self, attribute: &str) -> bool {
+        self.attributes
+            .as_ref()
+            .is_some_and(|attributes| attributes.iter().any(|a| a == attribute))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Variable {
@@ -153,17 +239,89 @@ pub struct Variable {
     pub r#type: Option<String>,
     pub variables_reference: VariablesReference,
     pub presentation_hint: Option<VariablePresentationHint>,
+    /// Expression that evaluates to this variable, e.g. for copying its value
+    pub evaluate_name: Option<String>,
+    /// Location of the variable's value, for use with the `readMemory` request
+    pub memory_reference: Option<String>,
+    /// Number of named children, if known, e.g. the fields of an object
+    pub named_variables: Option<usize>,
+    /// Number of indexed children, if known, e.g. the items of a list
+    pub indexed_variables: Option<usize>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl Variable {
+    /// Whether the value has not been fetched yet, see [`VariablePresentationHint::lazy`]
+    pub fn is_lazy(&self) -> bool {
+        self.presentation_hint
+            .as_ref()
+            .and_then(|hint| hint.lazy)
+            .unwrap_or(false)
+            && self.variables_reference > 0
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.presentation_hint
+            .as_ref()
+            .is_some_and(|hint| hint.has_attribute("readOnly"))
+    }
+
+    /// Kind of variable from the presentation hint, e.g. `property` or `method`
+    pub fn kind(&self) -> Option<&str> {
+        self.presentation_hint.as_ref()?.kind.as_deref()
+    }
+
+    /// Visibility from the presentation hint, e.g. `private` or `internal`
+    pub fn visibility(&self) -> Option<&str> {
+        self.presentation_hint.as_ref()?.visibility.as_deref()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(untagged)]
 pub enum ModuleId {
     Number(i64),
     String(String),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
 pub struct Module {
-    pub id: i64,
+    pub id: ModuleId,
     pub name: String,
     pub path: Option<PathBuf>,
+    pub is_optimized: Option<bool>,
+    pub is_user_code: Option<bool>,
+    pub version: Option<String>,
+    /// Whether symbols were loaded, e.g. `Symbols loaded.`
+    pub symbol_status: Option<String>,
+    pub symbol_file_path: Option<PathBuf>,
+    /// Address range covered by the module, e.g. `0x1000-0x2000`
+    pub address_range: Option<String>,
+}
+
+/// Structured error reported by the adapter in the body of a failed response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorMessage {
+    pub id: i64,
+    /// Message to show, where `{name}` is replaced by the value of `name` in `variables`
+    pub format: String,
+    pub variables: Option<HashMap<String, String>>,
+    pub send_telemetry: Option<bool>,
+    /// Whether the user should see this message rather than just having it logged
+    pub show_user: Option<bool>,
+    /// Link to more information about the error
+    pub url: Option<String>,
+    pub url_label: Option<String>,
+}
+
+impl ErrorMessage {
+    /// `format` with the `variables` substituted in
+    pub fn formatted(&self) -> String {
+        let mut message = self.format.clone();
+        for (name, value) in self.variables.iter().flatten() {
+            message = message.replace(&format!("{{{name}}}"), value);
+        }
+        message
+    }
 }
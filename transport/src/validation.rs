@@ -0,0 +1,286 @@
+//! Checking that the messages received from the adapter follow the protocol
+//!
+//! Adapters that send messages out of order, or answer requests that were never made, tend to
+//! cause confusing failures much later on. With [`crate::Client::set_strict`] enabled, each
+//! message is checked as it arrives, and any [`Violation`] is logged and sent to the event
+//! channel as [`crate::events::Event::ProtocolViolation`]. Messages are still handled as usual.
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use serde_json::Value;
+
+use crate::{
+    events,
+    request_store::{RequestStore, WaitingRequest},
+    types::Seq,
+};
+
+/// Way in which a message from the adapter broke the protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// `seq` did not increase from the previous message
+    SequenceNotIncreasing { seq: Seq, previous: Seq },
+    /// Response to a request that is not waiting for one, e.g. a second response to the same
+    /// request, or a late response to a request that timed out
+    UnexpectedResponse { request_seq: Seq, command: String },
+    /// Response for a different command than the request it answers
+    CommandMismatch {
+        request_seq: Seq,
+        expected: String,
+        actual: String,
+    },
+    /// Message missing fields required for its type, e.g. an event without a name
+    InvalidMessage { reason: String },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::SequenceNotIncreasing { seq, previous } => {
+                write!(f, "sequence number {seq} does not follow {previous}")
+            }
+            Violation::UnexpectedResponse {
+                request_seq,
+                command,
+            } => write!(
+                f,
+                "{command} response to request {request_seq}, which is not waiting for one"
+            ),
+            Violation::CommandMismatch {
+                request_seq,
+                expected,
+                actual,
+            } => write!(f, "{actual} response to {expected} request {request_seq}"),
+            Violation::InvalidMessage { reason } => write!(f, "invalid message: {reason}"),
+        }
+    }
+}
+
+/// Checks the messages read from one connection to the adapter
+pub(crate) struct Validator {
+    enabled: Arc<AtomicBool>,
+    store: RequestStore,
+    events: crossbeam_channel::Sender<events::Event>,
+    /// Sequence number of the previous message
+    previous: Option<Seq>,
+}
+
+impl Validator {
+    pub(crate) fn new(
+        enabled: Arc<AtomicBool>,
+        store: RequestStore,
+        events: crossbeam_channel::Sender<events::Event>,
+    ) -> Self {
+        Self {
+            enabled,
+            store,
+            events,
+            previous: None,
+        }
+    }
+
+    /// Check the content of the next message, reporting any violations
+    pub(crate) fn check(&mut self, content: &[u8]) {
+        if !self.enabled.load(Ordering::SeqCst) {
+            // start afresh if enabled part way through the session
+            self.previous = None;
+            return;
+        }
+        for violation in self.violations(content) {
+            tracing::warn!(%violation, "protocol violation");
+            let _ = self
+                .events
+                .send(events::Event::ProtocolViolation(violation));
+        }
+    }
+
+    fn violations(&mut self, content: &[u8]) -> Vec<Violation> {
+        let message: Value = match serde_json::from_slice(content) {
+            Ok(message) => message,
+            Err(e) => {
+                return vec![Violation::InvalidMessage {
+                    reason: format!("not valid JSON: {e}"),
+                }]
+            }
+        };
+
+        let mut violations = Vec::new();
+        match message.get("seq").and_then(Value::as_i64) {
+            Some(seq) => {
+                if let Some(previous) = self.previous.filter(|previous| seq <= *previous) {
+                    violations.push(Violation::SequenceNotIncreasing { seq, previous });
+                }
+                self.previous = Some(seq);
+            }
+            None => violations.push(invalid("missing sequence number")),
+        }
+
+        match message.get("type").and_then(Value::as_str) {
+            Some("event") => {
+                if message.get("event").and_then(Value::as_str).is_none() {
+                    violations.push(invalid("event without a name"));
+                }
+            }
+            Some("request") => {
+                if message.get("command").and_then(Value::as_str).is_none() {
+                    violations.push(invalid("request without a command"));
+                }
+            }
+            Some("response") => self.check_response(&message, &mut violations),
+            Some(other) => violations.push(invalid(&format!("unknown message type {other:?}"))),
+            None => violations.push(invalid("missing message type")),
+        }
+        violations
+    }
+
+    fn check_response(&self, message: &Value, violations: &mut Vec<Violation>) {
+        if message.get("success").and_then(Value::as_bool).is_none() {
+            violations.push(invalid("response without a success flag"));
+        }
+        let command = message.get("command").and_then(Value::as_str);
+        if command.is_none() {
+            violations.push(invalid("response without a command"));
+        }
+        let Some(request_seq) = message.get("request_seq").and_then(Value::as_i64) else {
+            violations.push(invalid("response without a request sequence number"));
+            return;
+        };
+
+        let expected = self
+            .store
+            .lock()
+            .unwrap()
+            .get(&request_seq)
+            .map(|WaitingRequest(body, _)| body.command());
+        match (expected, command) {
+            (None, command) => violations.push(Violation::UnexpectedResponse {
+                request_seq,
+                command: command.unwrap_or_default().to_string(),
+            }),
+            (Some(expected), Some(actual)) if expected != actual => {
+                violations.push(Violation::CommandMismatch {
+                    request_seq,
+                    expected,
+                    actual: actual.to_string(),
+                })
+            }
+            (Some(_), _) => {}
+        }
+    }
+}
+
+fn invalid(reason: &str) -> Violation {
+    Violation::InvalidMessage {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicBool, Arc};
+
+    use serde_json::json;
+
+    use super::{Validator, Violation};
+    use crate::{
+        events,
+        request_store::{RequestStore, WaitingRequest},
+        requests::RequestBody,
+    };
+
+    fn validator(store: RequestStore) -> (Validator, crossbeam_channel::Receiver<events::Event>) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        (
+            Validator::new(Arc::new(AtomicBool::new(true)), store, tx),
+            rx,
+        )
+    }
+
+    fn check(validator: &mut Validator, message: serde_json::Value) -> Vec<Violation> {
+        validator.violations(message.to_string().as_bytes())
+    }
+
+    #[test]
+    fn sequence_must_increase() {
+        let (mut validator, _rx) = validator(RequestStore::default());
+        let event = |seq| json!({ "seq": seq, "type": "event", "event": "output" });
+
+        assert!(check(&mut validator, event(1)).is_empty());
+        assert!(check(&mut validator, event(5)).is_empty());
+        assert_eq!(
+            check(&mut validator, event(5)),
+            vec![Violation::SequenceNotIncreasing {
+                seq: 5,
+                previous: 5
+            }]
+        );
+        assert_eq!(
+            check(&mut validator, event(2)),
+            vec![Violation::SequenceNotIncreasing {
+                seq: 2,
+                previous: 5
+            }]
+        );
+    }
+
+    #[test]
+    fn responses_must_match_requests() {
+        let store = RequestStore::default();
+        let (tx, _) = oneshot::channel();
+        store
+            .lock()
+            .unwrap()
+            .insert(3, WaitingRequest(RequestBody::Threads, tx));
+        let (mut validator, _rx) = validator(store);
+        let response = |seq, request_seq, command| {
+            json!({
+                "seq": seq,
+                "type": "response",
+                "request_seq": request_seq,
+                "command": command,
+                "success": true,
+            })
+        };
+
+        assert!(check(&mut validator, response(1, 3, "threads")).is_empty());
+        assert_eq!(
+            check(&mut validator, response(2, 3, "stackTrace")),
+            vec![Violation::CommandMismatch {
+                request_seq: 3,
+                expected: "threads".to_string(),
+                actual: "stackTrace".to_string(),
+            }]
+        );
+        assert_eq!(
+            check(&mut validator, response(3, 4, "threads")),
+            vec![Violation::UnexpectedResponse {
+                request_seq: 4,
+                command: "threads".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn messages_need_required_fields() {
+        let (mut validator, rx) = validator(RequestStore::default());
+        let violations = check(&mut validator, json!({ "type": "event" }));
+        assert_eq!(violations.len(), 2);
+        assert!(violations
+            .iter()
+            .all(|v| matches!(v, Violation::InvalidMessage { .. })));
+
+        // violations are reported on the event channel
+        validator.check(br#"{"seq":1,"type":"notification"}"#);
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(events::Event::ProtocolViolation(
+                Violation::InvalidMessage { .. }
+            ))
+        ));
+    }
+}
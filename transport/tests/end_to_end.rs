@@ -62,6 +62,7 @@ fn test_loop() -> Result<()> {
         supports_variable_paging: true,
         supports_progress_reporting: true,
         supports_memory_event: true,
+        supports_invalidated_event: true,
     });
     client.send(req).unwrap();
 
@@ -78,13 +79,14 @@ fn test_loop() -> Result<()> {
                 debug_options: vec!["DebugStdLib".to_string(), "ShowReturnValue".to_string()],
                 stop_on_entry: false,
                 is_output_redirected: false,
+                sub_process: None,
             })),
         }))
         .unwrap();
 
     // wait for initialized event
     let _initialized_event = wait_for_event("initialized", &rx, |e| {
-        matches!(e, events::Event::Initialized { .. })
+        matches!(e, events::Event::Initialized)
     });
 
     // set function breakpoints
@@ -128,12 +130,8 @@ fn test_loop() -> Result<()> {
         thread_id,
         ..Default::default()
     });
-    let responses::Response {
-        body:
-            Some(responses::ResponseBody::StackTrace(responses::StackTraceResponse { stack_frames })),
-        success: true,
-        ..
-    } = client.send(req).unwrap()
+    let responses::ResponseBody::StackTrace(responses::StackTraceResponse { stack_frames }) =
+        client.send(req).unwrap()
     else {
         unreachable!()
     };
@@ -142,11 +140,8 @@ fn test_loop() -> Result<()> {
         // scopes
         let req = requests::RequestBody::Scopes(requests::Scopes { frame_id: frame.id });
 
-        let responses::Response {
-            body: Some(responses::ResponseBody::Scopes(responses::ScopesResponse { scopes })),
-            success: true,
-            ..
-        } = client.send(req).unwrap()
+        let responses::ResponseBody::Scopes(responses::ScopesResponse { scopes }) =
+            client.send(req).unwrap()
         else {
             unreachable!()
         };
@@ -155,6 +150,7 @@ fn test_loop() -> Result<()> {
         for scope in scopes {
             let req = requests::RequestBody::Variables(requests::Variables {
                 variables_reference: scope.variables_reference,
+                ..Default::default()
             });
 
             let _ = client.send(req).unwrap();
@@ -164,7 +160,7 @@ fn test_loop() -> Result<()> {
     // continue
     let req = requests::RequestBody::Continue(requests::Continue {
         thread_id,
-        single_thread: false,
+        single_thread: None,
     });
     tracing::debug!(?req, "sending continue request");
     let _ = client.send(req).unwrap();
@@ -185,7 +181,7 @@ fn test_loop() -> Result<()> {
 
     // disconnect
     let req = requests::RequestBody::Disconnect(requests::Disconnect {
-        terminate_debugee: true,
+        terminate_debuggee: true,
     });
     let _ = client.send(req).unwrap();
     Ok(())
@@ -55,6 +55,7 @@ fn localstack() -> Result<()> {
             supports_variable_paging: true,
             supports_progress_reporting: true,
             supports_memory_event: true,
+            supports_invalidated_event: true,
         });
         client.send(req).unwrap();
 
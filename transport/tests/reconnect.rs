@@ -0,0 +1,146 @@
+//! Losing and re-establishing the connection to the adapter
+use std::{
+    io::{BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use eyre::Result;
+use serde_json::json;
+use transport::{
+    events,
+    requests::{self, Initialize, PathFormat},
+    Message, Reader, ReconnectPolicy, SendError,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn initialize() -> requests::RequestBody {
+    requests::RequestBody::Initialize(Initialize {
+        adapter_id: "dap gui".to_string(),
+        lines_start_at_one: false,
+        path_format: PathFormat::Path,
+        supports_start_debugging_request: false,
+        supports_variable_type: false,
+        supports_variable_paging: false,
+        supports_progress_reporting: false,
+        supports_memory_event: false,
+        supports_invalidated_event: false,
+    })
+}
+
+/// Wait for the next request sent over `conn`
+fn receive_request(conn: &TcpStream) -> requests::Request {
+    let mut reader = transport::reader::get(BufReader::new(conn.try_clone().unwrap()));
+    match reader.poll_message() {
+        Ok(Some(Message::Request(request))) => request,
+        other => panic!("expected request, got {other:?}"),
+    }
+}
+
+fn respond(conn: &mut TcpStream, request: &requests::Request) {
+    let body = json!({
+        "seq": 1,
+        "type": "response",
+        "request_seq": request.seq,
+        "command": "initialize",
+        "success": true,
+        "body": { "supportsConfigurationDoneRequest": true },
+    })
+    .to_string();
+    write!(conn, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+}
+
+fn next_event(rx: &crossbeam_channel::Receiver<events::Event>) -> events::Event {
+    rx.recv_timeout(TIMEOUT).expect("waiting for event")
+}
+
+#[test]
+fn reconnects_after_connection_lost() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let adapter = thread::spawn(move || {
+        // drop the first connection without responding
+        let (conn, _) = listener.accept().unwrap();
+        receive_request(&conn);
+        drop(conn);
+
+        let (mut conn, _) = listener.accept().unwrap();
+        let request = receive_request(&conn);
+        respond(&mut conn, &request);
+        // keep the connection open until the client is done
+        receive_request(&conn);
+    });
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let client = transport::Client::new(TcpStream::connect(addr)?, tx)?;
+    client.set_reconnect_policy(Some(ReconnectPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(100),
+    }));
+
+    let err = client.send(initialize()).unwrap_err();
+    assert!(
+        matches!(err, SendError::ConnectionLost(_)),
+        "unexpected error {err:?}"
+    );
+
+    assert!(matches!(next_event(&rx), events::Event::Disconnected));
+    assert!(matches!(
+        next_event(&rx),
+        events::Event::Reconnecting { attempt: 1 }
+    ));
+    assert!(matches!(next_event(&rx), events::Event::Reconnected));
+
+    client.send(initialize())?;
+
+    client.execute(requests::RequestBody::ConfigurationDone)?;
+    adapter.join().unwrap();
+    Ok(())
+}
+
+#[test]
+fn fails_requests_when_connection_lost() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    let adapter = thread::spawn(move || {
+        let (conn, _) = listener.accept().unwrap();
+        receive_request(&conn);
+    });
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let client = transport::Client::new(TcpStream::connect(addr)?, tx)?;
+    client.set_timeout(None);
+
+    // without a reconnect policy the client gives up straight away
+    let err = client.send(initialize()).unwrap_err();
+    assert!(
+        matches!(err, SendError::ConnectionLost(_)),
+        "unexpected error {err:?}"
+    );
+    assert!(matches!(next_event(&rx), events::Event::Disconnected));
+    assert!(rx.recv_timeout(TIMEOUT).is_err());
+
+    let err = client.send(initialize()).unwrap_err();
+    assert!(
+        matches!(err, SendError::ConnectionLost(_)),
+        "unexpected error {err:?}"
+    );
+
+    adapter.join().unwrap();
+    Ok(())
+}
+
+#[test]
+fn backoff_doubles_up_to_maximum() {
+    let policy = ReconnectPolicy {
+        max_attempts: 10,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+    };
+    assert_eq!(policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(policy.backoff(3), Duration::from_millis(400));
+    assert_eq!(policy.backoff(10), Duration::from_secs(1));
+}
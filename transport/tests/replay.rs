@@ -0,0 +1,205 @@
+//! Replaying recorded sessions in place of an adapter
+use std::{sync::Arc, time::Duration};
+
+use eyre::Result;
+use serde_json::json;
+use transport::{
+    events,
+    replay::{Recording, Timing},
+    requests::{self, Initialize, PathFormat},
+    responses,
+    tap::{Direction, JsonlTap, MessageTap, TappedMessage},
+    SendError,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn message(direction: Direction, millis: u64, payload: serde_json::Value) -> TappedMessage {
+    TappedMessage {
+        direction,
+        timestamp: Duration::from_millis(millis),
+        body: serde_json::to_vec(&payload).unwrap().into(),
+    }
+}
+
+/// Session recorded by another client, with different sequence numbers
+fn recording() -> Vec<TappedMessage> {
+    use Direction::*;
+    vec![
+        message(
+            ClientToServer,
+            0,
+            json!({ "seq": 10, "type": "request", "command": "initialize", "arguments": {} }),
+        ),
+        message(
+            ServerToClient,
+            10,
+            json!({
+                "seq": 1,
+                "type": "response",
+                "request_seq": 10,
+                "command": "initialize",
+                "success": true,
+                "body": { "supportsConfigurationDoneRequest": true },
+            }),
+        ),
+        message(
+            ServerToClient,
+            20,
+            json!({ "seq": 2, "type": "event", "event": "initialized" }),
+        ),
+        message(
+            ServerToClient,
+            30,
+            json!({
+                "seq": 3,
+                "type": "event",
+                "event": "output",
+                "body": { "category": "stdout", "output": "hello\n" },
+            }),
+        ),
+    ]
+}
+
+fn initialize() -> requests::RequestBody {
+    requests::RequestBody::Initialize(Initialize {
+        adapter_id: "dap gui".to_string(),
+        lines_start_at_one: false,
+        path_format: PathFormat::Path,
+        supports_start_debugging_request: false,
+        supports_variable_type: false,
+        supports_variable_paging: false,
+        supports_progress_reporting: false,
+        supports_memory_event: false,
+        supports_invalidated_event: false,
+    })
+}
+
+#[test]
+fn answers_requests_with_recorded_responses() -> Result<()> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let client = Recording::new(recording()).replay(Timing::Immediate, tx, None)?;
+
+    // nothing is sent before the client repeats the recorded request
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+    let response = client.send(initialize())?;
+    let responses::ResponseBody::Initialize(capabilities) = response else {
+        panic!("unexpected response body {response:?}");
+    };
+    assert_eq!(capabilities.supports_configuration_done_request, Some(true));
+
+    assert!(matches!(
+        rx.recv_timeout(TIMEOUT)?,
+        events::Event::Initialized
+    ));
+    let events::Event::Output(output) = rx.recv_timeout(TIMEOUT)? else {
+        panic!("expected output event");
+    };
+    assert_eq!(output.output, "hello\n");
+
+    // requests missing from the recording fail rather than hang
+    let err = client.send(requests::RequestBody::Threads).unwrap_err();
+    let SendError::Adapter(err) = err else {
+        panic!("expected adapter error, got {err:?}");
+    };
+    assert_eq!(err.command, "threads");
+    assert_eq!(
+        err.message.as_deref(),
+        Some("threads request is not in the recording")
+    );
+    Ok(())
+}
+
+#[test]
+fn replays_jsonl_recording() -> Result<()> {
+    let mut output = Vec::new();
+    {
+        let tap = JsonlTap::new(&mut output);
+        for message in recording() {
+            tap.on_message(&message);
+        }
+    }
+    let recording = Recording::from_jsonl(output.as_slice())?;
+    assert_eq!(recording.messages().len(), 4);
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let client = recording.replay(Timing::Accelerated(10.0), tx, None)?;
+    let log = Arc::new(transport::tap::MessageLog::new(8));
+    client.set_tap(Some(log.clone()));
+
+    client.send(initialize())?;
+    assert!(matches!(
+        rx.recv_timeout(TIMEOUT)?,
+        events::Event::Initialized
+    ));
+    rx.recv_timeout(TIMEOUT)?;
+
+    // the adapter's sequence numbers follow on from each other
+    let seqs: Vec<i64> = log
+        .messages()
+        .iter()
+        .filter(|m| m.direction == Direction::ServerToClient)
+        .map(|m| {
+            serde_json::from_slice::<serde_json::Value>(&m.body).unwrap()["seq"]
+                .as_i64()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(seqs, vec![1, 2, 3]);
+    Ok(())
+}
+
+#[test]
+fn drops_requests_the_client_passes_over() -> Result<()> {
+    use Direction::*;
+    let recording = Recording::new(vec![
+        message(
+            ClientToServer,
+            0,
+            json!({ "seq": 1, "type": "request", "command": "setBreakpoints", "arguments": {} }),
+        ),
+        message(
+            ClientToServer,
+            0,
+            json!({ "seq": 2, "type": "request", "command": "configurationDone" }),
+        ),
+        message(
+            ServerToClient,
+            0,
+            json!({
+                "seq": 1,
+                "type": "response",
+                "request_seq": 1,
+                "command": "setBreakpoints",
+                "success": true,
+                "body": { "breakpoints": [] },
+            }),
+        ),
+        message(
+            ServerToClient,
+            0,
+            json!({
+                "seq": 2,
+                "type": "response",
+                "request_seq": 2,
+                "command": "configurationDone",
+                "success": true,
+            }),
+        ),
+        message(
+            ServerToClient,
+            0,
+            json!({ "seq": 3, "type": "event", "event": "initialized" }),
+        ),
+    ]);
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let client = recording.replay(Timing::Immediate, tx, None)?;
+    client.send(requests::RequestBody::ConfigurationDone)?;
+    assert!(matches!(
+        rx.recv_timeout(TIMEOUT)?,
+        events::Event::Initialized
+    ));
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Responses and failures of requests sent with `Client::send`
+use std::{
+    io::{BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use eyre::Result;
+use serde_json::json;
+use transport::{requests, responses::ResponseBody, Message, Reader, SendError};
+
+/// Wait for the next request sent over `conn`
+fn receive_request(conn: &TcpStream) -> requests::Request {
+    let mut reader = transport::reader::get(BufReader::new(conn.try_clone().unwrap()));
+    match reader.poll_message() {
+        Ok(Some(Message::Request(request))) => request,
+        other => panic!("expected request, got {other:?}"),
+    }
+}
+
+fn respond(conn: &mut TcpStream, request: &requests::Request, success: bool) {
+    let body = json!({
+        "seq": 1,
+        "type": "response",
+        "request_seq": request.seq,
+        "command": request.body.command(),
+        "success": success,
+        "message": (!success).then_some("not now"),
+    })
+    .to_string();
+    write!(conn, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+}
+
+/// Ignore everything else sent over `conn` until the client closes it
+fn wait_for_close(mut conn: TcpStream) {
+    let _ = std::io::copy(&mut conn, &mut std::io::sink());
+}
+
+/// Connect a client to an adapter that handles each connection with `adapter`
+fn connect(adapter: impl FnOnce(TcpStream) + Send + 'static) -> Result<transport::Client> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    thread::spawn(move || {
+        let (conn, _) = listener.accept().unwrap();
+        adapter(conn);
+    });
+    let (tx, _rx) = crossbeam_channel::unbounded();
+    transport::Client::new(TcpStream::connect(addr)?, tx)
+}
+
+#[test]
+fn returns_other_for_commands_without_a_modelled_response() -> Result<()> {
+    let client = connect(|mut conn| {
+        let request = receive_request(&conn);
+        respond(&mut conn, &request, true);
+        wait_for_close(conn);
+    })?;
+
+    let body = client.send(requests::RequestBody::Next(requests::Next {
+        thread_id: 1,
+        single_thread: None,
+    }))?;
+    assert!(
+        matches!(body, ResponseBody::Other),
+        "unexpected body {body:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn distinguishes_adapter_errors_and_timeouts() -> Result<()> {
+    let client = connect(|mut conn| {
+        let request = receive_request(&conn);
+        respond(&mut conn, &request, false);
+        // never respond to the second request
+        wait_for_close(conn);
+    })?;
+
+    let err = client
+        .send(requests::RequestBody::ConfigurationDone)
+        .unwrap_err();
+    let SendError::Adapter(err) = err else {
+        panic!("expected adapter error, got {err:?}");
+    };
+    assert_eq!(err.command, "configurationDone");
+    assert_eq!(err.message.as_deref(), Some("not now"));
+
+    let err = client
+        .send_with_timeout(
+            requests::RequestBody::Threads,
+            Some(Duration::from_millis(100)),
+        )
+        .unwrap_err();
+    assert!(
+        matches!(&err, SendError::Timeout(timeout) if timeout.command == "threads"),
+        "unexpected error {err:?}"
+    );
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Talking to an adapter process over its stdin and stdout
+#![cfg(unix)]
+use std::{process::Command, sync::Arc, time::Duration};
+
+use eyre::Result;
+use serde_json::json;
+use transport::{
+    events,
+    requests::{self, Initialize, PathFormat},
+    responses,
+    tap::{Direction, MessageLog},
+    StdioTransport,
+};
+
+/// Frame `messages` with `Content-Length` headers as an adapter would write them
+fn framed(messages: &[serde_json::Value]) -> String {
+    messages
+        .iter()
+        .map(|message| {
+            let body = message.to_string();
+            format!("Content-Length: {}\r\n\r\n{body}", body.len())
+        })
+        .collect()
+}
+
+/// Adapter that answers `initialize` once the first request arrives
+fn adapter() -> Command {
+    let output = framed(&[
+        json!({
+            "seq": 1,
+            "type": "response",
+            "request_seq": 1,
+            "command": "initialize",
+            "success": true,
+            "body": { "supportsConfigurationDoneRequest": true },
+        }),
+        json!({ "seq": 2, "type": "event", "event": "initialized" }),
+    ]);
+    // answer once the first request arrives, then keep reading until the transport kills us
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("head -c 1 > /dev/null; printf '%s' \"$ADAPTER_OUTPUT\"; echo starting >&2; cat > /dev/null")
+        .env("ADAPTER_OUTPUT", output);
+    command
+}
+
+fn initialize() -> requests::RequestBody {
+    requests::RequestBody::Initialize(Initialize {
+        adapter_id: "dap gui".to_string(),
+        lines_start_at_one: false,
+        path_format: PathFormat::Path,
+        supports_start_debugging_request: false,
+        supports_variable_type: false,
+        supports_variable_paging: false,
+        supports_progress_reporting: false,
+        supports_memory_event: false,
+        supports_invalidated_event: false,
+    })
+}
+
+#[test]
+fn exchanges_messages_over_pipes() -> Result<()> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let (mut transport, client) = StdioTransport::spawn(adapter(), tx, None)?;
+    let stderr = transport.take_stderr().expect("stderr is piped");
+    assert!(transport.take_stderr().is_none());
+
+    let response = client.send(initialize())?;
+    let responses::ResponseBody::Initialize(capabilities) = response else {
+        panic!("unexpected response body {response:?}");
+    };
+    assert_eq!(capabilities.supports_configuration_done_request, Some(true));
+
+    let event = rx.recv_timeout(Duration::from_secs(5))?;
+    assert!(matches!(event, events::Event::Initialized));
+
+    let mut line = String::new();
+    std::io::BufRead::read_line(&mut std::io::BufReader::new(stderr), &mut line)?;
+    assert_eq!(line.trim(), "starting");
+
+    drop(client);
+    drop(transport);
+    Ok(())
+}
+
+#[test]
+fn mirrors_messages_to_tap() -> Result<()> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let (transport, client) = StdioTransport::spawn(adapter(), tx, None)?;
+    let log = Arc::new(MessageLog::new(16));
+    client.set_tap(Some(log.clone()));
+
+    client.send(initialize())?;
+    rx.recv_timeout(Duration::from_secs(5))?;
+
+    let messages: Vec<(Direction, serde_json::Value)> = log
+        .messages()
+        .into_iter()
+        .map(|m| (m.direction, serde_json::from_slice(&m.body).unwrap()))
+        .collect();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].0, Direction::ClientToServer);
+    assert_eq!(messages[0].1["command"], "initialize");
+    assert_eq!(messages[1].0, Direction::ServerToClient);
+    assert_eq!(messages[1].1["type"], "response");
+    assert_eq!(messages[2].1["event"], "initialized");
+
+    drop(client);
+    drop(transport);
+    Ok(())
+}
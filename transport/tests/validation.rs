@@ -0,0 +1,64 @@
+//! Reporting adapter messages that break the protocol
+use std::{io::Write, time::Duration};
+
+use eyre::Result;
+use serde_json::json;
+use transport::{events, validation::Violation, Client};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn framed(message: serde_json::Value) -> String {
+    let body = message.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+#[test]
+fn reports_violations_in_strict_mode() -> Result<()> {
+    let (input, mut adapter) = std::io::pipe()?;
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let client = Client::from_streams(input, std::io::sink(), tx, None)?;
+
+    // not reported until strict mode is enabled
+    write!(
+        adapter,
+        "{}",
+        framed(json!({ "seq": 5, "type": "event", "event": "initialized" }))
+    )?;
+    assert!(matches!(
+        rx.recv_timeout(TIMEOUT)?,
+        events::Event::Initialized
+    ));
+    write!(
+        adapter,
+        "{}",
+        framed(json!({ "seq": 1, "type": "event", "event": "initialized" }))
+    )?;
+    assert!(matches!(
+        rx.recv_timeout(TIMEOUT)?,
+        events::Event::Initialized
+    ));
+
+    client.set_strict(true);
+    for seq in [3, 3] {
+        write!(
+            adapter,
+            "{}",
+            framed(json!({ "seq": seq, "type": "event", "event": "initialized" }))
+        )?;
+    }
+    let received: Vec<events::Event> = rx.iter().take(3).collect();
+    assert!(matches!(received[0], events::Event::Initialized));
+    let events::Event::ProtocolViolation(violation) = &received[1] else {
+        panic!("expected protocol violation, got {:?}", received[1]);
+    };
+    assert_eq!(
+        *violation,
+        Violation::SequenceNotIncreasing {
+            seq: 3,
+            previous: 3
+        }
+    );
+    // the message is still handled as usual
+    assert!(matches!(received[2], events::Event::Initialized));
+    Ok(())
+}